@@ -0,0 +1,94 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use std::env;
+use std::sync::{Arc, OnceLock};
+
+use chrono::prelude::*;
+
+use crate::warning;
+
+/// Set to a date, formatted as YYYY-MM-DD, to use it as today instead of
+/// the system date. This keeps snapshot tests and reproducible builds
+/// from changing at year boundaries.
+pub const FAKE_TODAY_VAR: &str = "LICENSURE_FAKE_TODAY";
+
+/// The source of the current date used when rendering headers.
+pub trait Clock: Send + Sync {
+    fn today(&self) -> NaiveDate;
+
+    fn year(&self) -> String {
+        format!("{}", self.today().year())
+    }
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn today(&self) -> NaiveDate {
+        Local::now().date_naive()
+    }
+}
+
+pub struct FixedClock(pub NaiveDate);
+
+impl Clock for FixedClock {
+    fn today(&self) -> NaiveDate {
+        self.0
+    }
+}
+
+static CURRENT: OnceLock<Arc<dyn Clock>> = OnceLock::new();
+
+/// The clock for this run, fixed to LICENSURE_FAKE_TODAY if it's set and
+/// the system clock otherwise.
+pub fn current() -> Arc<dyn Clock> {
+    CURRENT
+        .get_or_init(|| from_fake_today(env::var(FAKE_TODAY_VAR).ok().as_deref()))
+        .clone()
+}
+
+fn from_fake_today(fake_today: Option<&str>) -> Arc<dyn Clock> {
+    let fake_today = match fake_today {
+        Some(date) if !date.is_empty() => date,
+        _ => return Arc::new(SystemClock),
+    };
+
+    match NaiveDate::parse_from_str(fake_today, "%Y-%m-%d") {
+        Ok(date) => Arc::new(FixedClock(date)),
+        Err(e) => {
+            warning!(
+                "ignoring {}={} because it isn't a YYYY-MM-DD date: {}",
+                FAKE_TODAY_VAR,
+                fake_today,
+                e
+            );
+            Arc::new(SystemClock)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_fake_today() {
+        assert_eq!("1999", from_fake_today(Some("1999-12-31")).year());
+        assert_eq!(
+            Local::now().date_naive(),
+            from_fake_today(Some("31/12/1999")).today()
+        );
+        assert_eq!(Local::now().date_naive(), from_fake_today(None).today());
+    }
+}
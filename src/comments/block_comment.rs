@@ -1,13 +1,19 @@
 use crate::comments::line_comment::LineComment;
 
+use super::wrap::{fill, WrapOptions};
 use super::Comment;
 
 pub struct BlockComment {
     start: String,
     end: String,
-    per_line: Option<Box<dyn Comment>>,
+    per_line: Option<String>,
+    align_per_line: bool,
+    per_line_prefix: Option<String>,
+    end_prefix: Option<String>,
+    padding: usize,
     trailing_lines: usize,
     cols: Option<usize>,
+    wrap: WrapOptions,
 }
 
 impl BlockComment {
@@ -16,8 +22,13 @@ impl BlockComment {
             start: String::from(start),
             end: String::from(end),
             per_line: None,
+            align_per_line: false,
+            per_line_prefix: None,
+            end_prefix: None,
+            padding: 1,
             trailing_lines: 0,
             cols,
+            wrap: WrapOptions::default(),
         }
     }
 
@@ -27,32 +38,110 @@ impl BlockComment {
     }
 
     pub fn with_per_line(mut self, per_line: &str) -> BlockComment {
-        self.per_line = Some(Box::new(
-            LineComment::new(per_line, self.cols).skip_trailing_lines(),
-        ));
+        self.per_line = Some(per_line.to_string());
         self
     }
+
+    /// Indent the per line character, and the end block character when
+    /// it starts with the same character, so that it lines up under the
+    /// first matching character of the start block. For example with a
+    /// start of `/**` and a per line char of `*` this produces Javadoc
+    /// style comments.
+    pub fn align_per_line_with_start(mut self, yes_or_no: bool) -> BlockComment {
+        self.align_per_line = yes_or_no;
+        self
+    }
+
+    /// Put prefix before the per line character of every line, e.g. " "
+    /// for clang-format style " * " lines. It takes precedence over
+    /// aligning the per line character with the start block.
+    pub fn with_per_line_prefix(mut self, prefix: &str) -> BlockComment {
+        self.per_line_prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// Put prefix before the end block character, e.g. " " for " */".
+    /// It takes precedence over aligning the end with the start block.
+    pub fn with_end_prefix(mut self, prefix: &str) -> BlockComment {
+        self.end_prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// Set the number of spaces between the per line character and the
+    /// text, 1 by default.
+    pub fn set_padding(mut self, padding: usize) -> BlockComment {
+        self.padding = padding;
+        self
+    }
+
+    /// Set how the text is wrapped at the column width.
+    pub fn set_wrap_options(mut self, wrap: WrapOptions) -> BlockComment {
+        self.wrap = wrap;
+        self
+    }
+
+    fn alignment(&self) -> usize {
+        if !self.align_per_line {
+            return 0;
+        }
+
+        let first_char = self.per_line_first_char();
+        if first_char.is_empty() {
+            return 0;
+        }
+
+        let first_line = self.start.lines().next().unwrap_or_default();
+        first_line
+            .find(first_char)
+            .map_or(0, |idx| first_line[..idx].chars().count())
+    }
+
+    fn per_line_first_char(&self) -> &str {
+        let per_line = self.per_line.as_deref().unwrap_or_default().trim_start();
+        match per_line.chars().next() {
+            Some(c) => &per_line[..c.len_utf8()],
+            None => "",
+        }
+    }
 }
 
 impl Comment for BlockComment {
     fn comment(&self, text: &str) -> String {
         let mut new_text = self.start.clone();
         let wrapped_text;
+        let alignment = self.alignment();
 
         match self.per_line {
-            Some(ref commenter) => {
-                let commented_text = commenter.comment(text);
+            Some(ref per_line) => {
+                let prefix = match &self.per_line_prefix {
+                    Some(prefix) => prefix.clone(),
+                    None => " ".repeat(alignment),
+                };
+                let commented_text = LineComment::new(per_line, self.cols)
+                    .with_prefix(&prefix)
+                    .set_padding(self.padding)
+                    .set_wrap_options(self.wrap)
+                    .skip_trailing_lines()
+                    .comment(text);
                 new_text.push_str(&commented_text);
             }
             None => new_text.push_str(match self.cols {
                 Some(cols) => {
-                    wrapped_text = textwrap::fill(text, cols);
+                    wrapped_text = fill(text, cols, self.wrap);
                     wrapped_text.as_str()
                 }
                 None => text,
             }),
         };
 
+        match &self.end_prefix {
+            Some(prefix) => new_text.push_str(prefix),
+            None if alignment > 0 && self.end.starts_with(self.per_line_first_char()) => {
+                new_text.push_str(&" ".repeat(alignment));
+            }
+            None => {}
+        }
+
         new_text.push_str(&self.end);
 
         for _ in 0..self.trailing_lines {
@@ -61,4 +150,8 @@ impl Comment for BlockComment {
 
         new_text
     }
+
+    fn line_marker(&self) -> Option<&str> {
+        self.per_line.as_deref().map(str::trim)
+    }
 }
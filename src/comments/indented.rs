@@ -0,0 +1,52 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use super::Comment;
+
+/// A commenter which indents every non-blank line of the comment of
+/// another, for headers embedded in indented content such as templates
+/// inside YAML.
+pub struct Indented {
+    commenter: Box<dyn Comment>,
+    indent: String,
+}
+
+impl Indented {
+    pub fn new(commenter: Box<dyn Comment>, indent: &str) -> Indented {
+        Indented {
+            commenter,
+            indent: indent.to_string(),
+        }
+    }
+}
+
+impl Comment for Indented {
+    fn comment(&self, text: &str) -> String {
+        self.commenter
+            .comment(text)
+            .split_inclusive('\n')
+            .map(|line| match line.trim().is_empty() {
+                true => line.to_string(),
+                false => format!("{}{}", self.indent, line),
+            })
+            .collect()
+    }
+
+    fn line_marker(&self) -> Option<&str> {
+        self.commenter.line_marker()
+    }
+
+    fn case_insensitive_marker(&self) -> Option<&str> {
+        self.commenter.case_insensitive_marker()
+    }
+}
@@ -11,20 +11,31 @@
 // You should have received a copy of the GNU General Public License along with
 // this program. If not, see <https://www.gnu.org/licenses/>.
 //
+use super::wrap::{fill, WrapOptions};
 use super::Comment;
 
 pub struct LineComment {
     character: String,
+    first_line: Option<String>,
     trailing_lines: usize,
     cols: Option<usize>,
+    prefix: String,
+    padding: usize,
+    case_insensitive: bool,
+    wrap: WrapOptions,
 }
 
 impl LineComment {
     pub fn new(character: &str, cols: Option<usize>) -> LineComment {
         LineComment {
             character: String::from(character),
+            first_line: None,
             trailing_lines: 0,
             cols,
+            prefix: String::new(),
+            padding: 1,
+            case_insensitive: false,
+            wrap: WrapOptions::default(),
         }
     }
 
@@ -37,6 +48,40 @@ impl LineComment {
         self.trailing_lines = 0;
         self
     }
+
+    /// Detect existing headers regardless of the case of the comment
+    /// character, for case insensitive markers like batch file's REM.
+    pub fn set_case_insensitive(mut self, yes_or_no: bool) -> LineComment {
+        self.case_insensitive = yes_or_no;
+        self
+    }
+
+    /// Start the first line with character instead of the comment
+    /// character, for decorated comments such as //! followed by //.
+    pub fn with_first_line(mut self, character: &str) -> LineComment {
+        self.first_line = Some(character.to_string());
+        self
+    }
+
+    /// Set how the text is wrapped at the column width.
+    pub fn set_wrap_options(mut self, wrap: WrapOptions) -> LineComment {
+        self.wrap = wrap;
+        self
+    }
+
+    /// Put prefix before the comment character of every line, e.g.
+    /// spaces to indent it. The prefix counts against the column width.
+    pub fn with_prefix(mut self, prefix: &str) -> LineComment {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// Set the number of spaces between the comment character and the
+    /// text, 1 by default. Extra spaces count against the column width.
+    pub fn set_padding(mut self, padding: usize) -> LineComment {
+        self.padding = padding;
+        self
+    }
 }
 
 impl Comment for LineComment {
@@ -45,7 +90,18 @@ impl Comment for LineComment {
             Some(cols) => {
                 // Subtract two columns to account for the comment
                 // character and space we will add later.
-                textwrap::fill(text, if cols > 2 { cols - 2 } else { cols })
+                // A longer first line character counts against every
+                // line so that the text is wrapped the same throughout.
+                let first_line_extra = self.first_line.as_ref().map_or(0, |first| {
+                    first
+                        .chars()
+                        .count()
+                        .saturating_sub(self.character.chars().count())
+                });
+                let extra =
+                    self.prefix.chars().count() + self.padding.saturating_sub(1) + first_line_extra;
+                let cols = cols.saturating_sub(extra).max(1);
+                fill(text, if cols > 2 { cols - 2 } else { cols }, self.wrap)
             }
             None => text.to_string(),
         };
@@ -57,11 +113,16 @@ impl Comment for LineComment {
             lines.pop();
         }
 
+        let padding = " ".repeat(self.padding);
         let mut new_text = "".to_string();
-        for line in lines {
+        for (i, line) in lines.into_iter().enumerate() {
+            let character = match (i, &self.first_line) {
+                (0, Some(first)) => first,
+                _ => &self.character,
+            };
             let new_line = match line {
-                "" => format!("{}\n", self.character),
-                _ => format!("{} {}\n", self.character, line),
+                "" => format!("{}{}\n", self.prefix, character),
+                _ => format!("{}{}{}{}\n", self.prefix, character, padding, line),
             };
 
             new_text.push_str(&new_line);
@@ -73,4 +134,15 @@ impl Comment for LineComment {
 
         new_text
     }
+
+    fn line_marker(&self) -> Option<&str> {
+        Some(&self.character)
+    }
+
+    fn case_insensitive_marker(&self) -> Option<&str> {
+        match self.case_insensitive {
+            true => Some(&self.character),
+            false => None,
+        }
+    }
 }
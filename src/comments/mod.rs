@@ -13,16 +13,37 @@
 //
 
 pub use block_comment::BlockComment;
+pub use indented::Indented;
 pub use line_comment::LineComment;
+pub use plain_text::PlainText;
+pub use trimmed::{trim_line_ends, Trimmed};
+pub use wrap::WrapOptions;
 
 mod block_comment;
+mod indented;
 mod line_comment;
+mod plain_text;
+mod trimmed;
+mod wrap;
 
 pub trait Comment {
     fn comment(&self, text: &str) -> String;
+
+    /// The characters placed at the start of every commented line, if
+    /// this commenter has any.
+    fn line_marker(&self) -> Option<&str> {
+        None
+    }
+
+    /// The line marker, if existing headers should be detected no
+    /// matter the case it was written in (e.g. REM vs rem).
+    fn case_insensitive_marker(&self) -> Option<&str> {
+        None
+    }
 }
 
 #[cfg(test)]
+#[allow(clippy::redundant_static_lifetimes)]
 mod tests {
     use super::*;
 
@@ -60,6 +81,20 @@ it looked super dapper
         )
     }
 
+    #[test]
+    fn test_comment_first_line() {
+        assert_eq!(
+            "//! There once was a man
+// with a very nice cat
+// the cat wore a top hat
+// it looked super dapper
+",
+            LineComment::new("//", None)
+                .with_first_line("//!")
+                .comment(EX_TEXT)
+        )
+    }
+
     #[test]
     fn test_comment_cpp() {
         assert_eq!(
@@ -105,4 +140,110 @@ it looked super dapper
             BlockComment::new("<!--\n", "-->", None).comment(EX_TEXT)
         )
     }
+
+    #[test]
+    fn test_comment_indented() {
+        assert_eq!(
+            "    # There once was a man
+    # with a very nice cat
+    # the cat wore a top hat
+    # it looked super dapper
+
+",
+            Indented::new(
+                Box::new(LineComment::new("#", None).set_trailing_lines(1)),
+                "    "
+            )
+            .comment(EX_TEXT)
+        )
+    }
+
+    #[test]
+    fn test_comment_trimmed() {
+        assert_eq!(
+            "# There once was a man\n#\n# with a very nice cat\n",
+            Trimmed::new(Box::new(LineComment::new("#", None)))
+                .comment("There once was a man  \n\nwith a very nice cat\t\n")
+        )
+    }
+
+    #[test]
+    fn test_comment_javadoc_aligned() {
+        assert_eq!(
+            "/**
+ * There once was a man
+ * with a very nice cat
+ * the cat wore a top hat
+ * it looked super dapper
+ */",
+            BlockComment::new("/**\n", "*/", None)
+                .with_per_line("*")
+                .align_per_line_with_start(true)
+                .comment(EX_TEXT)
+        )
+    }
+
+    #[test]
+    fn test_comment_multi_char_per_line_aligned() {
+        assert_eq!(
+            "(***
+ ** There once was a man
+ ** with a very nice cat
+ ** the cat wore a top hat
+ ** it looked super dapper
+ ***)",
+            BlockComment::new("(***\n", "***)", None)
+                .with_per_line("**")
+                .align_per_line_with_start(true)
+                .comment(EX_TEXT)
+        )
+    }
+
+    #[test]
+    fn test_comment_per_line_and_end_prefix() {
+        assert_eq!(
+            "/*
+ * There once was a man
+ * with a very nice cat
+ * the cat wore a top hat
+ * it looked super dapper
+ */",
+            BlockComment::new("/*\n", "*/", None)
+                .with_per_line("*")
+                .with_per_line_prefix(" ")
+                .with_end_prefix(" ")
+                .comment(EX_TEXT)
+        )
+    }
+
+    #[test]
+    fn test_comment_prefix_and_padding_count_against_columns() {
+        assert_eq!(
+            "/*
+ *   There
+ *   once was
+ *   a man
+ */",
+            BlockComment::new("/*\n", "*/", Some(14))
+                .with_per_line("*")
+                .with_per_line_prefix(" ")
+                .with_end_prefix(" ")
+                .set_padding(3)
+                .comment("There once was a man")
+        )
+    }
+
+    #[test]
+    fn test_comment_aligned_counts_against_columns() {
+        assert_eq!(
+            "/**
+ * There once
+ * was a man
+ */",
+            BlockComment::new("/**\n", "*/", Some(14))
+                .with_per_line("*")
+                .align_per_line_with_start(true)
+                .comment("There once was a man")
+        )
+    }
 }
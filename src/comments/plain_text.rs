@@ -0,0 +1,26 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use super::Comment;
+
+/// A commenter which leaves the text as is, used for headers which are
+/// written to sidecar files instead of the file itself.
+pub struct PlainText;
+
+impl Comment for PlainText {
+    fn comment(&self, text: &str) -> String {
+        let mut new_text = text.trim_end_matches('\n').to_string();
+        new_text.push('\n');
+        new_text
+    }
+}
@@ -0,0 +1,49 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use super::Comment;
+
+/// A commenter which removes any spaces and tabs from the end of every
+/// line of the comment of another, e.g. the "# " of blank lines or the
+/// trailing whitespace of license texts.
+pub struct Trimmed {
+    commenter: Box<dyn Comment>,
+}
+
+impl Trimmed {
+    pub fn new(commenter: Box<dyn Comment>) -> Trimmed {
+        Trimmed { commenter }
+    }
+}
+
+impl Comment for Trimmed {
+    fn comment(&self, text: &str) -> String {
+        trim_line_ends(&self.commenter.comment(text))
+    }
+
+    fn line_marker(&self) -> Option<&str> {
+        self.commenter.line_marker()
+    }
+
+    fn case_insensitive_marker(&self) -> Option<&str> {
+        self.commenter.case_insensitive_marker()
+    }
+}
+
+/// Text without spaces or tabs at the end of any of its lines.
+pub fn trim_line_ends(text: &str) -> String {
+    text.split('\n')
+        .map(|line| line.trim_end_matches([' ', '\t']))
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
@@ -0,0 +1,125 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use textwrap::word_separators::AsciiSpace;
+use textwrap::word_splitters::{HyphenSplitter, WordSplitter};
+use textwrap::Options;
+
+/// How comment text is wrapped at the column width.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WrapOptions {
+    /// Break words longer than a line, otherwise they overflow it.
+    pub break_long_words: bool,
+    /// Never split URLs, lines are then only broken at spaces and URLs
+    /// longer than a line overflow it.
+    pub preserve_urls: bool,
+}
+
+impl Default for WrapOptions {
+    fn default() -> WrapOptions {
+        WrapOptions {
+            break_long_words: true,
+            preserve_urls: false,
+        }
+    }
+}
+
+/// Wrap text at width columns.
+pub fn fill(text: &str, width: usize, options: WrapOptions) -> String {
+    let wrapping = Options::new(width).break_words(options.break_long_words);
+    if !options.preserve_urls {
+        return textwrap::fill(text, wrapping);
+    }
+
+    // textwrap only knows to break long words or not, so a URL which
+    // doesn't fit on a line keeps every long word whole.
+    let long_url = text
+        .split_whitespace()
+        .any(|word| is_url(word) && word.chars().count() > width);
+    textwrap::fill(
+        text,
+        wrapping
+            .break_words(options.break_long_words && !long_url)
+            .word_separator(AsciiSpace)
+            .word_splitter(UrlPreserving),
+    )
+}
+
+fn is_url(word: &str) -> bool {
+    word.contains("://") || word.starts_with("www.")
+}
+
+/// Splits words at hyphens like textwrap does by default, except for
+/// URLs which aren't split at all.
+#[derive(Clone, Debug)]
+struct UrlPreserving;
+
+impl WordSplitter for UrlPreserving {
+    fn split_points(&self, word: &str) -> Vec<usize> {
+        match is_url(word) {
+            true => Vec::new(),
+            false => HyphenSplitter.split_points(word),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "see https://opensource.org/licenses/BSD-3-Clause and more";
+
+    #[test]
+    fn test_fill_default() {
+        assert_eq!(
+            textwrap::fill(TEXT, 20),
+            fill(TEXT, 20, WrapOptions::default())
+        );
+    }
+
+    #[test]
+    fn test_fill_preserve_urls() {
+        let options = WrapOptions {
+            preserve_urls: true,
+            ..WrapOptions::default()
+        };
+        assert_eq!(
+            "see\nhttps://opensource.org/licenses/BSD-3-Clause\nand more",
+            fill(TEXT, 20, options)
+        );
+        assert_eq!(
+            "see the license\nat www.gnu.org for\nnon-commercial use",
+            fill(
+                "see the license at www.gnu.org for non-commercial use",
+                18,
+                options
+            )
+        );
+    }
+
+    #[test]
+    fn test_fill_no_break_long_words() {
+        let options = WrapOptions {
+            break_long_words: false,
+            ..WrapOptions::default()
+        };
+        assert_eq!(
+            "a\nsupercalifragilistic\nword",
+            fill("a supercalifragilistic word", 8, options)
+        );
+        assert_eq!(
+            "a\nsupercal\nifragili\nstic\nword",
+            fill("a supercalifragilistic word", 8, WrapOptions::default())
+        );
+    }
+}
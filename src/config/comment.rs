@@ -11,22 +11,85 @@
 // You should have received a copy of the GNU General Public License along with
 // this program. If not, see <https://www.gnu.org/licenses/>.
 //
+use std::path::Path;
+
+use regex::Regex;
 use serde::Deserialize;
 
 use crate::comments::BlockComment;
 use crate::comments::Comment;
 use crate::comments::LineComment;
+use crate::comments::PlainText;
+use crate::comments::{Indented, Trimmed, WrapOptions};
+use crate::config::VerifyCommand;
+use crate::errors;
+use crate::fail;
 
 fn def_trailing_lines() -> usize {
     0
 }
 
+fn def_per_line_padding() -> usize {
+    1
+}
+
+fn def_break_long_words() -> bool {
+    true
+}
+
+// Commenters for common file types, used for files which no comments
+// config matches unless use_builtin_commenters is false.
+const BUILTIN_LINE_COMMENTERS: &[(&str, &[&str])] = &[
+    (
+        "//",
+        &[
+            "rs", "js", "jsx", "mjs", "cjs", "ts", "tsx", "go", "c", "h", "cpp", "cc", "cxx",
+            "hpp", "hh", "java", "kt", "kts", "swift", "scala", "cs", "dart", "groovy", "gradle",
+            "proto", "zig", "scss", "less",
+        ],
+    ),
+    (
+        "#",
+        &[
+            "py",
+            "rb",
+            "sh",
+            "bash",
+            "zsh",
+            "fish",
+            "pl",
+            "pm",
+            "r",
+            "yaml",
+            "yml",
+            "toml",
+            "ps1",
+            "psm1",
+            "nix",
+            "tf",
+            "cmake",
+            "ex",
+            "exs",
+            "jl",
+            "coffee",
+            "Makefile",
+            "Dockerfile",
+        ],
+    ),
+    ("--", &["sql", "hs", "lua", "elm", "ada"]),
+    (";;", &["el", "lisp", "clj", "cljs", "scm"]),
+    ("%", &["erl", "hrl", "tex"]),
+    ("\"", &["vim"]),
+];
+
+const BUILTIN_BLOCK_COMMENTERS: &[(&str, &str, Option<&str>, &[&str])] = &[
+    ("/*\n", "*/", Some("*"), &["css"]),
+    ("<!--\n", "-->", None, &["html", "htm", "xml", "svg"]),
+    ("(*\n", "*)", None, &["ml", "mli"]),
+];
+
 pub fn get_filetype(filename: &str) -> &str {
-    let iter = filename.split('.');
-    match iter.last() {
-        Some(s) => s,
-        None => "",
-    }
+    filename.rsplit('.').next().unwrap_or_default()
 }
 
 #[derive(Clone, Deserialize, Debug)]
@@ -37,6 +100,12 @@ pub enum Commenter {
         start_block_char: String,
         end_block_char: String,
         per_line_char: Option<String>,
+        #[serde(default)]
+        align_per_line_with_start: bool,
+        per_line_prefix: Option<String>,
+        end_prefix: Option<String>,
+        #[serde(default = "def_per_line_padding")]
+        per_line_padding: usize,
         #[serde(default = "def_trailing_lines")]
         trailing_lines: usize,
     },
@@ -45,7 +114,16 @@ pub enum Commenter {
         comment_char: String,
         #[serde(default = "def_trailing_lines")]
         trailing_lines: usize,
+        #[serde(default)]
+        case_insensitive: bool,
+        /// The comment character of the first line, if it differs from
+        /// the rest, e.g. //! or -- |.
+        first_line_char: Option<String>,
     },
+    /// For formats which can't contain comments the header is written,
+    /// uncommented, to a separate <file>.license file.
+    #[serde(alias = "sidecar")]
+    Sidecar,
 }
 
 #[derive(Clone, Deserialize, Debug)]
@@ -56,10 +134,234 @@ enum FileType {
 }
 
 impl FileType {
-    fn matches(&self, ft: &str) -> bool {
+    /// Extensions match either the file type or the full file name, so
+    /// that files like tsconfig.json can be configured separately from
+    /// other files with the same extension.
+    fn matches(&self, ft: &str, file_name: &str) -> bool {
+        match self {
+            FileType::Single(ext) => ext == "any" || ext == ft || ext == file_name,
+            FileType::List(ref extensions) => {
+                extensions.iter().any(|ext| ext == ft || ext == file_name)
+            }
+        }
+    }
+}
+
+/// A list of regexes matched against individual lines of a file.
+#[derive(Clone, Deserialize, Debug, Default)]
+#[serde(from = "Vec<String>")]
+pub struct LinePatterns {
+    patterns: Vec<Regex>,
+}
+
+impl LinePatterns {
+    pub fn is_match(&self, line: &str) -> bool {
+        self.patterns.iter().any(|p| p.is_match(line))
+    }
+
+    /// If one of the patterns matches at the start of content return the
+    /// length of the match. Unlike is_match patterns can span multiple
+    /// lines, ^ and $ match at line boundaries.
+    pub fn match_at_start(&self, content: &str) -> Option<usize> {
+        self.patterns.iter().find_map(|p| {
+            p.find(content)
+                .filter(|m| m.start() == 0 && m.end() > 0)
+                .map(|m| m.end())
+        })
+    }
+
+    fn push(&mut self, pattern: &str) {
+        self.patterns
+            .push(Regex::new(pattern).expect("preamble preset regex didn't compile!"));
+    }
+}
+
+/// Well known lines which have to stay at the top of a file, selected
+/// by name in preserve_preamble instead of writing the regex by hand.
+#[derive(Clone, Copy, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PreamblePreset {
+    /// <?xml version="1.0"?>
+    XmlDeclaration,
+    /// <?xml-stylesheet href="style.xsl"?>
+    XmlStylesheet,
+    /// <!DOCTYPE html>
+    Doctype,
+    /// <?php
+    Php,
+    /// <template> in Vue single file components
+    VueTemplate,
+    /// Python style encoding declarations, # -*- coding: utf-8 -*-
+    Coding,
+    /// YAML front matter between --- lines, or TOML front matter between
+    /// +++ lines, at the start of Markdown and other docs.
+    FrontMatter,
+    /// load() statements at the start of Bazel and Buck BUILD files and
+    /// other Starlark files.
+    BazelLoad,
+}
+
+impl PreamblePreset {
+    fn pattern(&self) -> &'static str {
+        match self {
+            PreamblePreset::XmlDeclaration => r"^<\?xml\s.*\?>\s*$",
+            PreamblePreset::XmlStylesheet => r"^<\?xml-stylesheet\s.*\?>\s*$",
+            PreamblePreset::Doctype => r"(?i)^<!DOCTYPE\s[^>]*>\s*$",
+            PreamblePreset::Php => r"^<\?php\s*$",
+            PreamblePreset::VueTemplate => r"^<template(\s[^>]*)?>\s*$",
+            PreamblePreset::Coding => r"^#.*(coding[:=]|fileencoding=)",
+            PreamblePreset::FrontMatter => {
+                r"(?m)\A(---|\+\+\+)[ \t]*\n(?s:.*?)^(---|\+\+\+|\.\.\.)[ \t]*$"
+            }
+            PreamblePreset::BazelLoad => r"\Aload\([^)]*\)",
+        }
+    }
+}
+
+impl From<Vec<String>> for LinePatterns {
+    fn from(pats: Vec<String>) -> LinePatterns {
+        let patterns = pats
+            .iter()
+            // Multi line mode only matters for match_at_start, is_match
+            // is always given a single line.
+            .map(|p| match Regex::new(&format!("(?m){}", p)) {
+                Ok(r) => r,
+                Err(e) => fail!(
+                    errors::INVALID_PATTERN,
+                    "Failed to compile line pattern: {}",
+                    e
+                ),
+            })
+            .collect();
+        LinePatterns { patterns }
+    }
+}
+
+/// How the license header is spaced from the surrounding content.
+#[derive(Clone, Copy, Deserialize, Debug, Default, PartialEq)]
+pub struct Spacing {
+    /// Blank lines between a preamble, such as a shebang, and the header.
+    #[serde(default)]
+    pub leading_lines: usize,
+    /// When true blank lines already following the header are removed so
+    /// that exactly trailing_lines blank lines separate it from the code.
+    #[serde(default)]
+    pub exact_trailing_lines: bool,
+}
+
+/// The number of blank lines between the header and the line following
+/// it by the kind of that line, overriding the trailing_lines of the
+/// commenter.
+#[derive(Clone, Copy, Deserialize, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TrailingLinesAfter {
+    /// Before code, any line which isn't a preprocessor line.
+    pub code: Option<usize>,
+    /// Before preprocessor directives and imports, such as #include or
+    /// CSS's @import.
+    pub preprocessor: Option<usize>,
+}
+
+impl TrailingLinesAfter {
+    /// The blank lines after a header followed by line, if a rule applies
+    /// to it.
+    pub fn for_line(&self, line: &str) -> Option<usize> {
+        match is_preprocessor_line(line) {
+            true => self.preprocessor,
+            false => self.code,
+        }
+    }
+}
+
+fn is_preprocessor_line(line: &str) -> bool {
+    let line = line.trim_start();
+    match line.strip_prefix('#') {
+        Some(rest) => rest
+            .trim_start()
+            .starts_with(|c: char| c.is_ascii_alphabetic()),
+        None => ["@import", "@use", "@forward", "@charset", "@namespace"]
+            .iter()
+            .any(|directive| line.starts_with(directive)),
+    }
+}
+
+/// Where in a file the license header goes.
+#[derive(Clone, Copy, Deserialize, Debug, Default, PartialEq)]
+#[serde(try_from = "String")]
+pub enum Position {
+    /// At the top, after any preamble such as a shebang.
+    #[default]
+    Top,
+    /// At the end, before any emacs Local Variables block.
+    Bottom,
+    /// After the first N lines of the file.
+    AfterLine(usize),
+}
+
+impl TryFrom<String> for Position {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Position, String> {
+        match s.as_str() {
+            "top" => return Ok(Position::Top),
+            "bottom" => return Ok(Position::Bottom),
+            _ => (),
+        }
+
+        s.strip_prefix("after_line(")
+            .and_then(|rest| rest.strip_suffix(')'))
+            .and_then(|n| n.trim().parse().ok())
+            .map(Position::AfterLine)
+            .ok_or_else(|| {
+                format!(
+                    "invalid placement {}, expected top, bottom, or after_line(N)",
+                    s
+                )
+            })
+    }
+}
+
+/// The line ending of files which are changed.
+#[derive(Clone, Copy, Deserialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LineEnding {
+    /// Whichever line ending most lines of the file already use.
+    #[default]
+    Auto,
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    /// Whether a file with content should be written with CRLF line
+    /// endings.
+    pub fn is_crlf(self, content: &[u8]) -> bool {
+        match self {
+            LineEnding::Lf => false,
+            LineEnding::Crlf => true,
+            LineEnding::Auto => {
+                let lines = content.iter().filter(|b| **b == b'\n').count();
+                let crlf = content.windows(2).filter(|w| w == b"\r\n").count();
+                crlf * 2 > lines
+            }
+        }
+    }
+}
+
+/// The indentation of every line of a header, a number of spaces or
+/// the text to indent with.
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Indent {
+    Spaces(usize),
+    Text(String),
+}
+
+impl Indent {
+    fn text(&self) -> String {
         match self {
-            FileType::Single(ext) => ext == "any" || ext == ft,
-            FileType::List(ref extensions) => extensions.iter().any(|ext| ext == ft),
+            Indent::Spaces(n) => " ".repeat(*n),
+            Indent::Text(text) => text.clone(),
         }
     }
 }
@@ -69,7 +371,26 @@ pub struct Config {
     #[serde(alias = "extensions")]
     extension: FileType,
     columns: Option<usize>,
+    #[serde(default = "def_break_long_words")]
+    break_long_words: bool,
+    #[serde(default)]
+    preserve_urls: bool,
+    indent: Option<Indent>,
+    #[serde(default)]
+    strip_trailing_whitespace: bool,
     commenter: Commenter,
+    verify_with: Option<VerifyCommand>,
+    #[serde(default)]
+    magic_comments: LinePatterns,
+    #[serde(default)]
+    preserve_preamble: Vec<PreamblePreset>,
+    #[serde(default)]
+    insert_after: LinePatterns,
+    #[serde(default)]
+    spacing: Spacing,
+    #[serde(default)]
+    trailing_lines_after: TrailingLinesAfter,
+    placement: Option<Position>,
 }
 
 impl Config {
@@ -77,59 +398,447 @@ impl Config {
         Config {
             extension: FileType::Single("any".to_string()),
             columns: None,
+            break_long_words: true,
+            preserve_urls: false,
+            indent: None,
+            strip_trailing_whitespace: false,
             commenter: Commenter::Line {
                 comment_char: "#".to_string(),
                 trailing_lines: 0,
+                case_insensitive: false,
+                first_line_char: None,
             },
+            verify_with: None,
+            magic_comments: LinePatterns::default(),
+            preserve_preamble: Vec::new(),
+            insert_after: LinePatterns::default(),
+            spacing: Spacing::default(),
+            trailing_lines_after: TrailingLinesAfter::default(),
+            placement: None,
         }
     }
 
-    pub fn matches(&self, file_type: &str) -> bool {
-        self.extension.matches(file_type)
+    /// The built in comment configs for common file types.
+    pub fn builtins() -> Vec<Config> {
+        let with_commenter = |extensions: &[&str], commenter| Config {
+            extension: FileType::List(extensions.iter().map(|e| e.to_string()).collect()),
+            commenter,
+            ..Config::default()
+        };
+
+        let line = BUILTIN_LINE_COMMENTERS
+            .iter()
+            .map(|(comment_char, extensions)| {
+                with_commenter(
+                    extensions,
+                    Commenter::Line {
+                        comment_char: comment_char.to_string(),
+                        trailing_lines: 0,
+                        case_insensitive: false,
+                        first_line_char: None,
+                    },
+                )
+            });
+        let block = BUILTIN_BLOCK_COMMENTERS.iter().map(
+            |(start_block_char, end_block_char, per_line_char, extensions)| {
+                with_commenter(
+                    extensions,
+                    Commenter::Block {
+                        start_block_char: start_block_char.to_string(),
+                        end_block_char: end_block_char.to_string(),
+                        per_line_char: per_line_char.map(str::to_string),
+                        align_per_line_with_start: false,
+                        per_line_prefix: None,
+                        end_prefix: None,
+                        per_line_padding: 1,
+                        trailing_lines: 0,
+                    },
+                )
+            },
+        );
+
+        // Docs may start with front matter, for static site generators,
+        // which has to stay first.
+        let docs = [
+            with_commenter(
+                &["md", "markdown"],
+                Commenter::Block {
+                    start_block_char: "<!--\n".to_string(),
+                    end_block_char: "-->".to_string(),
+                    per_line_char: None,
+                    align_per_line_with_start: false,
+                    per_line_prefix: None,
+                    end_prefix: None,
+                    per_line_padding: 1,
+                    trailing_lines: 1,
+                },
+            ),
+            with_commenter(
+                &["rst"],
+                Commenter::Line {
+                    comment_char: "..".to_string(),
+                    trailing_lines: 0,
+                    case_insensitive: false,
+                    first_line_char: None,
+                },
+            ),
+        ]
+        .into_iter()
+        .map(|mut cfg| {
+            cfg.preserve_preamble = vec![PreamblePreset::FrontMatter];
+            cfg.merge_preamble_presets();
+            cfg
+        });
+
+        // Starlark files start with their load statements, which style
+        // checkers like buildifier require above everything else, set off
+        // from the rest of the file by a blank line.
+        let mut starlark = with_commenter(
+            &["bzl", "bazel", "star", "BUILD", "WORKSPACE", "BUCK"],
+            Commenter::Line {
+                comment_char: "#".to_string(),
+                trailing_lines: 1,
+                case_insensitive: false,
+                first_line_char: None,
+            },
+        );
+        starlark.preserve_preamble = vec![PreamblePreset::BazelLoad];
+        starlark.spacing = Spacing {
+            leading_lines: 1,
+            exact_trailing_lines: true,
+        };
+        starlark.merge_preamble_presets();
+
+        line.chain(block)
+            .chain(docs)
+            .chain(std::iter::once(starlark))
+            .collect()
+    }
+
+    /// Whether this config applies to filename, whose file type is
+    /// detected by the caller so that it's only done once when checking
+    /// many configs.
+    pub fn matches(&self, filename: &str, filetype: &str) -> bool {
+        let file_name = Path::new(filename)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        self.extension.matches(filetype, file_name)
+    }
+
+    /// The extensions the config applies to, separated by commas.
+    pub fn extensions(&self) -> String {
+        match &self.extension {
+            FileType::Single(ext) => ext.clone(),
+            FileType::List(extensions) => extensions.join(", "),
+        }
+    }
+
+    pub fn is_sidecar(&self) -> bool {
+        matches!(self.commenter, Commenter::Sidecar)
     }
 
     pub fn commenter(&self) -> Box<dyn Comment> {
+        let commenter = self.indented_commenter();
+        match self.strip_trailing_whitespace && !self.is_sidecar() {
+            true => Box::new(Trimmed::new(commenter)),
+            false => commenter,
+        }
+    }
+
+    fn indented_commenter(&self) -> Box<dyn Comment> {
+        let indent = self.indent.as_ref().map(Indent::text).unwrap_or_default();
+        if indent.is_empty() || self.is_sidecar() {
+            return self.unindented_commenter(self.get_columns());
+        }
+
+        // The indentation counts against the column width.
+        let columns = self
+            .get_columns()
+            .map(|cols| cols.saturating_sub(indent.chars().count()).max(1));
+        Box::new(Indented::new(self.unindented_commenter(columns), &indent))
+    }
+
+    fn unindented_commenter(&self, columns: Option<usize>) -> Box<dyn Comment> {
         match &self.commenter {
             Commenter::Line {
                 comment_char,
                 trailing_lines,
-            } => Box::new(
-                LineComment::new(comment_char.as_str(), self.get_columns())
-                    .set_trailing_lines(*trailing_lines),
-            ),
+                case_insensitive,
+                first_line_char,
+            } => {
+                let mut lc = LineComment::new(comment_char.as_str(), columns)
+                    .set_wrap_options(self.wrap_options())
+                    .set_trailing_lines(*trailing_lines)
+                    .set_case_insensitive(*case_insensitive);
+
+                if let Some(ch) = first_line_char {
+                    lc = lc.with_first_line(ch);
+                }
+
+                Box::new(lc)
+            }
             Commenter::Block {
                 start_block_char,
                 end_block_char,
                 per_line_char,
+                align_per_line_with_start,
+                per_line_prefix,
+                end_prefix,
+                per_line_padding,
                 trailing_lines,
             } => {
-                let mut bc = BlockComment::new(
-                    start_block_char.as_str(),
-                    end_block_char.as_str(),
-                    self.get_columns(),
-                )
-                .set_trailing_lines(*trailing_lines);
+                let mut bc =
+                    BlockComment::new(start_block_char.as_str(), end_block_char.as_str(), columns)
+                        .set_wrap_options(self.wrap_options())
+                        .set_trailing_lines(*trailing_lines)
+                        .align_per_line_with_start(*align_per_line_with_start)
+                        .set_padding(*per_line_padding);
 
                 if let Some(ch) = per_line_char {
                     bc = bc.with_per_line(ch.as_str());
                 }
 
+                if let Some(prefix) = per_line_prefix {
+                    bc = bc.with_per_line_prefix(prefix);
+                }
+
+                if let Some(prefix) = end_prefix {
+                    bc = bc.with_end_prefix(prefix);
+                }
+
                 Box::new(bc)
             }
+            Commenter::Sidecar => Box::new(PlainText),
+        }
+    }
+
+    fn wrap_options(&self) -> WrapOptions {
+        WrapOptions {
+            break_long_words: self.break_long_words,
+            preserve_urls: self.preserve_urls,
         }
     }
 
     pub fn get_columns(&self) -> Option<usize> {
         self.columns
     }
+
+    pub fn set_columns(&mut self, columns: usize) {
+        self.columns = Some(columns);
+    }
+
+    pub fn trailing_lines_after(&self) -> TrailingLinesAfter {
+        self.trailing_lines_after
+    }
+
+    /// Set the trailing_lines of the commenter so there are blank_lines
+    /// empty lines after the header.
+    pub fn set_blank_lines_after(&mut self, blank_lines: usize) {
+        match &mut self.commenter {
+            Commenter::Line { trailing_lines, .. } => *trailing_lines = blank_lines,
+            // The newline ending end_block_char is one of the trailing lines.
+            Commenter::Block { trailing_lines, .. } => *trailing_lines = blank_lines + 1,
+            Commenter::Sidecar => (),
+        }
+    }
+
+    pub fn verify_command(&self) -> Option<&VerifyCommand> {
+        self.verify_with.as_ref()
+    }
+
+    pub fn magic_comments(&self) -> &LinePatterns {
+        &self.magic_comments
+    }
+
+    pub fn insert_after(&self) -> &LinePatterns {
+        &self.insert_after
+    }
+
+    pub fn spacing(&self) -> Spacing {
+        self.spacing
+    }
+
+    pub fn placement(&self) -> Option<Position> {
+        self.placement
+    }
+
+    /// Add the patterns of the preserve_preamble presets to the magic
+    /// comments, or insert_after for presets spanning several lines,
+    /// they are handled the same way.
+    pub fn merge_preamble_presets(&mut self) {
+        for preset in &self.preserve_preamble {
+            match preset {
+                // Front matter and load statements span several lines so
+                // they're handled like insert_after.
+                PreamblePreset::FrontMatter | PreamblePreset::BazelLoad => {
+                    self.insert_after.push(preset.pattern())
+                }
+                _ => self.magic_comments.push(preset.pattern()),
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 pub mod tests {
     use super::*;
 
+    #[test]
+    fn test_line_ending() {
+        assert!(!LineEnding::Auto.is_crlf(b"a\nb\n"));
+        assert!(LineEnding::Auto.is_crlf(b"a\r\nb\r\nc\n"));
+        assert!(!LineEnding::Auto.is_crlf(b"a\r\nb\nc\n"));
+        assert!(!LineEnding::Auto.is_crlf(b""));
+        assert!(LineEnding::Crlf.is_crlf(b"a\nb\n"));
+        assert!(!LineEnding::Lf.is_crlf(b"a\r\nb\r\n"));
+    }
+
+    #[test]
+    fn test_front_matter_preset() {
+        let mut cfg: Config = serde_yaml::from_str(
+            "extension: md\npreserve_preamble: [front_matter]\ncommenter:\n  type: block\n  start_block_char: \"<!--\\n\"\n  end_block_char: \"-->\"\n",
+        )
+        .unwrap();
+        cfg.merge_preamble_presets();
+        assert!(!cfg.magic_comments().is_match("---"));
+        assert_eq!(
+            Some(18),
+            cfg.insert_after()
+                .match_at_start("---\ntitle: A\n---  \n\n# A\n")
+        );
+        assert_eq!(
+            None,
+            cfg.insert_after().match_at_start("# A\n---\nb\n---\n")
+        );
+    }
+
+    #[test]
+    fn test_indent() {
+        let parse = |indent: &str| -> Config {
+            serde_yaml::from_str(&format!(
+                "extension: yml\ncolumns: 16\nindent: {}\ncommenter:\n  type: line\n  comment_char: \"#\"\n",
+                indent
+            ))
+            .unwrap()
+        };
+        assert_eq!(
+            "    # Copyright\n    # 2024\n",
+            parse("4").commenter().comment("Copyright 2024")
+        );
+        assert_eq!(
+            "\t# Copyright\n\t# 2024\n",
+            parse("\"\\t\"").commenter().comment("Copyright 2024")
+        );
+    }
+
+    #[test]
+    fn test_strip_trailing_whitespace() {
+        let cfg: Config = serde_yaml::from_str(
+            "extension: py\nstrip_trailing_whitespace: true\ncommenter:\n  type: line\n  comment_char: \"#\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            "# Copyright 2024\n#\n# All rights reserved.\n",
+            cfg.commenter()
+                .comment("Copyright 2024  \n\nAll rights reserved.\n")
+        );
+    }
+
+    #[test]
+    fn test_wrap_options() {
+        let cfg: Config = serde_yaml::from_str(
+            "extension: c\ncolumns: 24\npreserve_urls: true\ncommenter:\n  type: block\n  start_block_char: \"/*\\n\"\n  end_block_char: \"*/\"\n  per_line_char: \"*\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            "/*\n* See\n* https://www.gnu.org/licenses/\n* for details.\n*/",
+            cfg.commenter()
+                .comment("See https://www.gnu.org/licenses/ for details.")
+        );
+    }
+
+    #[test]
+    fn test_trailing_lines_after() {
+        let rules = TrailingLinesAfter {
+            code: Some(1),
+            preprocessor: Some(2),
+        };
+        assert_eq!(Some(2), rules.for_line("#include <stdio.h>"));
+        assert_eq!(Some(2), rules.for_line("# define A 1"));
+        assert_eq!(Some(2), rules.for_line("@import url(a.css);"));
+        assert_eq!(Some(1), rules.for_line("int main() {}"));
+        assert_eq!(Some(1), rules.for_line("#[derive(Debug)]"));
+        assert_eq!(
+            None,
+            TrailingLinesAfter::default().for_line("#include <stdio.h>")
+        );
+    }
+
+    #[test]
+    fn test_first_line_char() {
+        let cfg: Config = serde_yaml::from_str(
+            "extension: hs\ncommenter:\n  type: line\n  comment_char: \"--\"\n  first_line_char: \"-- |\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            "-- | Copyright 2024\n--\n-- All rights reserved.\n",
+            cfg.commenter()
+                .comment("Copyright 2024\n\nAll rights reserved.\n")
+        );
+    }
+
+    #[test]
+    fn test_position() {
+        let parse = |s: &str| Position::try_from(s.to_string());
+        assert_eq!(Ok(Position::Top), parse("top"));
+        assert_eq!(Ok(Position::Bottom), parse("bottom"));
+        assert_eq!(Ok(Position::AfterLine(3)), parse("after_line(3)"));
+        assert!(parse("after_line(x)").is_err());
+        assert!(parse("middle").is_err());
+    }
+
     #[test]
     fn test_get_filetype() {
         assert_eq!("py", get_filetype("test.py"))
     }
+
+    #[test]
+    fn test_preamble_presets() {
+        let mut cfg: Config = serde_yaml::from_str(
+            "extension: xml\npreserve_preamble: [xml_declaration, xml_stylesheet, doctype]\ncommenter:\n  type: sidecar\n",
+        )
+        .unwrap();
+        cfg.merge_preamble_presets();
+        let patterns = cfg.magic_comments();
+        assert!(patterns.is_match("<?xml version=\"1.0\" encoding=\"UTF-8\"?>"));
+        assert!(patterns.is_match("<?xml-stylesheet type=\"text/xsl\" href=\"a.xsl\"?>"));
+        assert!(patterns.is_match("<!doctype html>"));
+        assert!(!patterns.is_match("<html>"));
+
+        assert!(Regex::new(PreamblePreset::Php.pattern())
+            .unwrap()
+            .is_match("<?php"));
+        assert!(Regex::new(PreamblePreset::VueTemplate.pattern())
+            .unwrap()
+            .is_match("<template lang=\"pug\">"));
+        assert!(Regex::new(PreamblePreset::Coding.pattern())
+            .unwrap()
+            .is_match("# -*- coding: utf-8 -*-"));
+
+        let load = Regex::new(PreamblePreset::BazelLoad.pattern()).unwrap();
+        assert!(load.is_match("load(\n    \"//a:b.bzl\",\n    \"c\",\n)\n"));
+        assert!(!load.is_match("cc_library(name = \"load\")\nload(\"//a:b.bzl\", \"c\")"));
+    }
+
+    #[test]
+    fn test_matches_file_name() {
+        let cfg: Config = serde_yaml::from_str(
+            "extensions: [jsonc, tsconfig.json]\ncommenter:\n  type: line\n  comment_char: '//'",
+        )
+        .unwrap();
+        assert!(cfg.matches("src/tsconfig.json", "json"));
+        assert!(cfg.matches("settings.jsonc", "jsonc"));
+        assert!(!cfg.matches("package.json", "json"));
+    }
 }
@@ -13,16 +13,60 @@
 //
 // Simply contains the default YAML config for generation and consumption
 pub const DEFAULT_CONFIG: &str = r#"
-# Regexes which if matched by a file path will always be excluded from
-# getting a license header
+# Regexes which if matched by the whole path of a file will always be
+# excluded from getting a license header. Patterns which should match any
+# part of a path are given as {pattern: <regex>, unanchored: true}.
 excludes:
-  - \.gitignore
+  - (.*/)?\.gitignore
   - .*lock
-  - \.git/.*
+  - (.*/)?\.git/.*
   - \.licensure\.yml
-  - README.*
-  - LICENSE.*
+  - (.*/)?README.*
+  - (.*/)?LICENSE.*
   - .*\.(md|rst|txt)
+  - .*\.license
+# Rules excluding files by path depth, size, or modification time.
+# exclude_rules:
+#   - max_depth: 6
+#   - larger_than: 1MB
+#   - older_than: 2015-01-01
+# Variables which can be used in any license template as [name], for
+# example [project] would be substituted with MyApp below.
+# variables:
+#   project: MyApp
+#   homepage: https://example.com
+# Files which no entry in comments matches use a built in commenter for
+# common file types, set this to false to only use the configured ones.
+# use_builtin_commenters: true
+# Exclude files which .gitattributes marks as linguist-generated or
+# linguist-vendored.
+# use_gitattributes: false
+# What to do with files which can't contain a comment, those with a
+# sidecar commenter and binary files. The policy is one of sidecar,
+# dep5, which records them in the DEP-5 file dep5_file, or skip.
+# uncommentable:
+#   policy: dep5
+#   dep5_file: .reuse/dep5
+# Files containing any of these near their top are generated and are
+# never changed.
+# skip_if_contains:
+#   - DO NOT EDIT
+#   - "@generated"
+#   - Code generated by
+# The line ending of changed files, lf, crlf, or auto to use the one most
+# lines of the file already have.
+# line_ending: auto
+# Whether changed files should end with exactly one newline.
+# ensure_trailing_newline: true
+# The conditions which fail a --check run, the others are reported as
+# warnings. By default all of them fail it:
+#   missing: files have no license header
+#   outdated: files have a header which needs updating
+#   unconfigured: files matched no license or comment config
+#   format: files have the correct header but it needs reformatting
+#   mixed_licenses: directories contain files with different licenses
+#   unknown_license: files have the header of an unconfigured license
+# check_fail_on: [missing, outdated, unconfigured, format, mixed_licenses, unknown_license]
 # Definition of the licenses used on this project and to what files
 # they should apply.
 #
@@ -52,6 +96,20 @@ licenses: []
   #     - name: Your Name Here
   #       Optionally provide email for copyright purposes
   #       email: you@yourdomain.com
+  #       Optionally provide the years this author holds copyright
+  #       for, they are rendered before the name e.g. 2019-2021 Name
+  #       start_year: 2019
+  #       end_year: 2021
+  #
+  #   How [year] is rendered when it's a range of years, [start] and
+  #   [end] are replaced by the first and last year.
+  #   year_range_format: "[start], [end]"
+  #
+  #   How the years of existing headers are updated, current replaces
+  #   them with the rendered years, preserve never changes them, and
+  #   range_extend keeps their start year and extends them to the
+  #   rendered end year.
+  #   year_mode: current
   #
   #   The template that will be rendered to generate the header before
   #   comment characters are applied. Available variables are:
@@ -60,6 +118,21 @@ licenses: []
   #      if provided. If email is provided the output appears as Full
   #      Name <email@example.com>. If multiple authors are provided the
   #      list is concatenated together with commas.
+  #    - [path]: substituted with the path of the file being licensed.
+  #    - [filename]: substituted with the file name, e.g. main.rs
+  #    - [basename]: substituted with the file name without its
+  #      extension, e.g. main
+  #    - [date]: substituted with the current date, e.g. 2024-06-01,
+  #      the date of an existing header is kept when it's updated.
+  #    - [month]: substituted with the current month, e.g. June
+  #    - [date:<format>]: substituted with the current date in a
+  #      strftime format, e.g. [date:%d.%m.%Y]
+  #    - [version]: substituted with the release given to licensure
+  #      stamp-release, existing headers keep theirs until the next.
+  #    - [<name>]: substituted with the value of <name> from the
+  #      top level variables option.
+  #   Text between [if <name>] and [end] is only rendered when the
+  #   variable <name> is set and not empty, e.g. [if email] <[email]>[end]
   #   template: |
   #     Copyright [year] [name of author]. All rights reserved. Use of
   #     this source code is governed by the [ident] license that can be
@@ -80,8 +153,95 @@ licenses: []
   #   Important Note: this means the ident must be a valid SPDX identifier
   #   auto_template: true
   #
+  #   Transforms applied to the template, including one fetched with
+  #   auto_template, in order before it is rendered. strip_paragraph
+  #   removes the nth paragraph, counting from 1.
+  #   transforms:
+  #     - replace:
+  #         from: the authors
+  #         to: Your Company Inc.
+  #     - strip_paragraph: 3
+  #
+  #   Text placed before and after the template, separated from it by a
+  #   blank line. They use the same variables as the template.
+  #   prefix: "SPDX-License-Identifier: [ident]"
+  #   suffix: All rights reserved.
+  #
   #   If true try to detect the text wrapping of the template, and unwrap it
   #   unwrap_text: false
+  #
+  #   If true the authors are everyone who has committed to the file
+  #   according to git, ordered by number of commits. Aliases map other
+  #   names or emails to a single author and authors with fewer than
+  #   min_author_commits commits are left out.
+  #   use_dynamic_authors: true
+  #   min_author_commits: 1
+  #   author_aliases:
+  #     old@yourdomain.com: Your Name Here <you@yourdomain.com>
+  #
+  #   When a header is added to a file in place its authors are added
+  #   to this file, which is kept sorted and free of duplicates.
+  #   contributors_file: CONTRIBUTORS
+  #
+  #   Variables whose value is the trimmed output of a command, run
+  #   once per run without a shell. Like verify_with a command is a
+  #   command line or a list of arguments. Commands running longer than variable_command_timeout
+  #   seconds are killed.
+  #   variable_commands:
+  #     revision: git rev-parse --short HEAD
+  #   variable_command_timeout: 10
+  #
+  #   Test cases run by licensure validate-config, each renders the
+  #   commented header for file on the date today and compares it to
+  #   expected, or only its first line to first_line.
+  #   tests:
+  #     - file: src/main.rs
+  #       today: 2024-06-01
+  #       first_line: // Copyright 2024 Your Name Here
+  #
+  #   Classes of files, like tests, whose template is used instead of
+  #   the template above for the files matching their regex.
+  #   classes:
+  #     - class: tests
+  #       files: tests/.*|.*_test\.go
+  #       template: Copyright [year] [name of author]. Licensed under [ident].
+  #
+  #   Match existing headers with any authors so that they're
+  #   updated when the authors above change, e.g. after a rename.
+  #   authors_varying: true
+  #
+  #   Templates, by license ident or as text, whose headers are
+  #   replaced by the header of this license, e.g. after relicensing.
+  #   replaces_templates:
+  #     - ident: Apache-2.0
+  #     - template: Copyright [year] Old Corp. All rights reserved.
+  #
+  #   Files with fewer lines, or for which the header would have more
+  #   than max_header_ratio times their lines, are too small for the
+  #   header. They get a header with only an SPDX tag instead, or are
+  #   skipped with a warning if small_files is skip.
+  #   min_file_lines_for_full_header: 10
+  #   max_header_ratio: 1.5
+  #   small_files: spdx_tag
+  #
+  #   Only accept headers which are byte for byte identical to the
+  #   rendered header, differences in whitespace or wrapping make the
+  #   file need an update.
+  #   strict_bytes: true
+  #
+  #   Regexes for banner lines, like rows of dashes, which are treated
+  #   as part of an existing header when directly above or below it.
+  #   They're matched without the comment characters.
+  #   banners:
+  #     - "^[-=]{4,}$"
+  #
+  #   Where the header goes for files of this license, overriding the
+  #   placement of their comment config.
+  #   placement: bottom
+  #
+  #   A command checking the formatting of files of this license after
+  #   they're changed, overriding the verify_with of their comment config.
+  #   verify_with: [rustfmt, --check, "{file}"]
 
 # Define type of comment characters to apply based on file extensions.
 comments:
@@ -104,6 +264,52 @@ comments:
       type: line
       comment_char: "//"
       trailing_lines: 0
+    # insert_after is a list of regexes, which may span multiple lines,
+    # for leading constructs the header should be inserted below. For
+    # example Rust's inner attributes and module docs:
+    # insert_after:
+    #   - '#!\[(?s:.*?)\]$'
+    #   - "//!.*"
+    #
+    # spacing controls the blank lines around the header. leading_lines
+    # is the number of blank lines after a preamble, like a shebang, and
+    # when exact_trailing_lines is true blank lines already at the start
+    # of the code are removed so exactly trailing_lines separate it from
+    # the header.
+    # spacing:
+    #   leading_lines: 0
+    #   exact_trailing_lines: false
+    #
+    # trailing_lines_after sets the number of blank lines after the
+    # header by the first line following it, preprocessor for lines like
+    # #include or CSS's @import and code for anything else. An existing
+    # license comment is skipped to find that line, omitted kinds use the
+    # commenter's trailing_lines.
+    # trailing_lines_after:
+    #   code: 0
+    #   preprocessor: 1
+    #
+    # placement is where the header goes, top, bottom, or after_line(N)
+    # to go after the first N lines. A license can set it too, which
+    # takes precedence.
+    # placement: top
+    #
+    # indent prefixes every line of the header with a number of spaces
+    # or the given text, for headers embedded in indented content.
+    # Indented headers are detected and updated like any other.
+    # indent: 2
+    #
+    # strip_trailing_whitespace removes spaces and tabs from the end of
+    # every line of the header. Headers with or without them are
+    # considered the same when checking.
+    # strip_trailing_whitespace: false
+    #
+    # When columns is set the header is wrapped at that width. Words
+    # longer than a line are broken unless break_long_words is false,
+    # and with preserve_urls URLs are never split across lines.
+    # columns: 80
+    # break_long_words: true
+    # preserve_urls: false
   - extensions:
       - css
       - cpp
@@ -117,6 +323,11 @@ comments:
     #
     # trailing_lines works the same for both block and line commenter
     # types
+    #
+    # per_line_prefix is put before per_line_char on every line and
+    # end_prefix before end_block_char, " " for both produces " * "
+    # lines closed by " */". per_line_padding is the number of spaces
+    # between per_line_char and the text, 1 if omitted.
     commenter:
       type: block
       start_block_char: "/*\n"
@@ -124,11 +335,45 @@ comments:
       per_line_char: "*"
       trailing_lines: 0
   # In this case extension is singular and a single string extension is provided.
+  #
+  # preserve_preamble is a list of presets for well known lines which
+  # must stay above the license header: xml_declaration, xml_stylesheet,
+  # doctype, php, vue_template, coding, front_matter for the YAML or
+  # TOML front matter of Markdown docs, and bazel_load for the load()
+  # statements of Bazel and Buck BUILD files.
   - extension: html
+    preserve_preamble:
+      - doctype
+    commenter:
+      type: block
+      start_block_char: "<!--\n"
+      end_block_char: "-->"
+  - extensions:
+      - xml
+      - svg
+      - xsl
+    preserve_preamble:
+      - xml_declaration
+      - xml_stylesheet
+      - doctype
+    commenter:
+      type: block
+      start_block_char: "<!--\n"
+      end_block_char: "-->"
+  - extension: vue
+    preserve_preamble:
+      - vue_template
     commenter:
       type: block
       start_block_char: "<!--\n"
       end_block_char: "-->"
+  - extension: php
+    preserve_preamble:
+      - php
+    commenter:
+      type: line
+      comment_char: "//"
+      trailing_lines: 0
   - extensions:
       - el
       - lisp
@@ -136,6 +381,80 @@ comments:
       type: line
       comment_char: ";;;"
       trailing_lines: 0
+  - extensions:
+      - asm
+      - s
+      - ini
+    commenter:
+      type: line
+      comment_char: ";"
+      trailing_lines: 0
+  - extensions:
+      - sql
+      - hs
+      - lua
+    commenter:
+      type: line
+      comment_char: "--"
+      trailing_lines: 0
+  - extensions:
+      - vb
+      - vbs
+      - bas
+    commenter:
+      type: line
+      comment_char: "'"
+      trailing_lines: 0
+  # Some comment characters are case insensitive, when case_insensitive
+  # is true existing headers using any case (e.g. rem instead of REM)
+  # are detected instead of being duplicated. They will be rewritten
+  # to use the configured comment_char.
+  - extensions:
+      - bat
+      - cmd
+    commenter:
+      type: line
+      comment_char: "REM"
+      case_insensitive: true
+      trailing_lines: 0
+  # first_line_char replaces comment_char on the first line of the
+  # header only, for decorated comments such as Haskell's "-- |".
+  # - extension: hs
+  #   commenter:
+  #     type: line
+  #     comment_char: "--"
+  #     first_line_char: "-- |"
+  # Entries in extensions can also be full file names. Some JSON
+  # variants allow comments so they get a line commenter.
+  - extensions:
+      - jsonc
+      - json5
+      - tsconfig.json
+    commenter:
+      type: line
+      comment_char: "//"
+      trailing_lines: 0
+  # Plain JSON can't contain comments. The sidecar commenter writes the
+  # uncommented header to a separate file named after the original with
+  # a .license suffix, e.g. package.json.license
+  - extension: json
+    commenter:
+      type: sidecar
+  # magic_comments is a list of regexes matching lines which must stay
+  # at the top of the file, such as encoding declarations. The license
+  # header will be inserted after any leading lines which match. Shebangs
+  # and emacs file variable lines are always kept at the top.
+  - extensions:
+      - rb
+      - py
+    magic_comments:
+      - "^# frozen_string_literal:"
+      - "^# (en)?coding[:=]"
+      - "^# typed:"
+    commenter:
+      type: line
+      comment_char: '#'
+      trailing_lines: 0
   # The extension string "any" is special and so will match any file
   # extensions. Commenter configurations are always checked in the
   # order they are defined, so if any is used it should be the last
@@ -0,0 +1,227 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use std::fmt;
+
+use regex::Regex;
+use serde_yaml::Value;
+
+/// A config key which has been renamed. The old key is still accepted
+/// but will produce a warning until it is removed.
+pub struct Deprecation {
+    /// The top level section containing the key, the section is
+    /// expected to be a list of objects.
+    pub section: &'static str,
+    pub old_key: &'static str,
+    pub new_key: &'static str,
+}
+
+pub const DEPRECATIONS: &[Deprecation] = &[Deprecation {
+    section: "licenses",
+    old_key: "year",
+    new_key: "end_year",
+}];
+
+#[derive(Debug, PartialEq)]
+pub struct DeprecationWarning {
+    pub section: &'static str,
+    pub index: usize,
+    pub old_key: &'static str,
+    pub new_key: &'static str,
+}
+
+impl fmt::Display for DeprecationWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}[{}]: `{}` is deprecated, use `{}` instead (run licensure --fix-config to update)",
+            self.section, self.index, self.old_key, self.new_key
+        )
+    }
+}
+
+/// Find all uses of deprecated keys in the raw YAML of a config file.
+pub fn find_deprecated_keys(config: &Value) -> Vec<DeprecationWarning> {
+    let mut warnings = Vec::new();
+
+    for dep in DEPRECATIONS {
+        let entries = match config.get(dep.section).and_then(Value::as_sequence) {
+            Some(entries) => entries,
+            None => continue,
+        };
+
+        for (index, entry) in entries.iter().enumerate() {
+            if entry.get(dep.old_key).is_some() {
+                warnings.push(DeprecationWarning {
+                    section: dep.section,
+                    index,
+                    old_key: dep.old_key,
+                    new_key: dep.new_key,
+                });
+            }
+        }
+    }
+
+    warnings
+}
+
+/// Rewrite deprecated keys in the text of a config file to their
+/// replacements. This works line by line instead of round tripping
+/// through serde so that comments and formatting are preserved.
+pub fn fix_deprecated_keys(content: &str) -> (String, usize) {
+    let mut fixed = content.to_string();
+    let mut count = 0;
+
+    for dep in DEPRECATIONS {
+        let (rewritten, n) = fix_deprecated_key(&fixed, dep);
+        fixed = rewritten;
+        count += n;
+    }
+
+    (fixed, count)
+}
+
+/// Rewrite the old key of dep where it's a key of an entry in its
+/// section, the same keys find_deprecated_keys warns about. Keys of
+/// other sections, nested values, and the text of block scalars are
+/// left alone.
+fn fix_deprecated_key(content: &str, dep: &Deprecation) -> (String, usize) {
+    let key = Regex::new(&format!(r"^{}[ \t]*:", regex::escape(dep.old_key)))
+        .expect("deprecation regex didn't compile!");
+
+    let mut fixed = String::with_capacity(content.len());
+    let mut count = 0;
+    let mut in_section = false;
+    // The indent of the dashes of the section's entries and the column
+    // their keys start at.
+    let mut entry_indent = None;
+    let mut key_column = None;
+
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim_start_matches([' ', '\t']);
+        let indent = line.len() - trimmed.len();
+        if trimmed.trim().is_empty() || trimmed.starts_with('#') {
+            fixed.push_str(line);
+            continue;
+        }
+
+        if indent == 0 && !trimmed.starts_with('-') {
+            in_section = trimmed
+                .strip_prefix(dep.section)
+                .is_some_and(|rest| rest.trim_start().starts_with(':'));
+            entry_indent = None;
+            key_column = None;
+            fixed.push_str(line);
+            continue;
+        }
+
+        if !in_section {
+            fixed.push_str(line);
+            continue;
+        }
+
+        let is_entry = trimmed.starts_with('-')
+            && trimmed[1..].starts_with([' ', '\t'])
+            && *entry_indent.get_or_insert(indent) == indent;
+        let column = if is_entry {
+            let after_dash = trimmed[1..].trim_start_matches([' ', '\t']);
+            let column = line.len() - after_dash.len();
+            key_column = Some(column);
+            Some(column)
+        } else if key_column == Some(indent) {
+            Some(indent)
+        } else {
+            None
+        };
+
+        match column {
+            Some(column) if key.is_match(&line[column..]) => {
+                fixed.push_str(&line[..column]);
+                fixed.push_str(dep.new_key);
+                fixed.push_str(&line[column + dep.old_key.len()..]);
+                count += 1;
+            }
+            _ => fixed.push_str(line),
+        }
+    }
+
+    (fixed, count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD_CONFIG: &str = "licenses:
+  # The year key is old
+  - files: any
+    ident: MIT
+    year: 2020
+  - year: 2021
+    ident: MIT
+";
+
+    #[test]
+    fn test_find_deprecated_keys() {
+        let value: Value = serde_yaml::from_str(OLD_CONFIG).unwrap();
+        let warnings = find_deprecated_keys(&value);
+        assert_eq!(2, warnings.len());
+        assert_eq!(0, warnings[0].index);
+        assert_eq!(1, warnings[1].index);
+        assert_eq!("end_year", warnings[0].new_key);
+    }
+
+    #[test]
+    fn test_fix_deprecated_keys() {
+        let (fixed, count) = fix_deprecated_keys(OLD_CONFIG);
+        assert_eq!(2, count);
+        assert_eq!(
+            "licenses:
+  # The year key is old
+  - files: any
+    ident: MIT
+    end_year: 2020
+  - end_year: 2021
+    ident: MIT
+",
+            fixed
+        );
+    }
+
+    #[test]
+    fn test_fix_deprecated_keys_only_in_licenses() {
+        let config = "excludes: []
+variables:
+  year: 2020
+licenses:
+- files: any
+  template: |
+    year: [year]
+  authors:
+    - name: Bob
+      year: 2020
+- ident: MIT
+  year: 2020
+comments:
+  - year: 2020
+";
+        let (fixed, count) = fix_deprecated_keys(config);
+        assert_eq!(1, count);
+        assert_eq!(
+            config.replacen("  year: 2020\ncomments", "  end_year: 2020\ncomments", 1),
+            fixed
+        );
+        let value: Value = serde_yaml::from_str(config).unwrap();
+        assert_eq!(1, find_deprecated_keys(&value).len());
+    }
+}
@@ -0,0 +1,278 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use std::path::Path;
+
+use regex::Regex;
+
+use crate::config::comment::get_filetype;
+use crate::utils::cache;
+
+// File types of common script interpreters, used for files without an
+// extension which start with a shebang.
+const INTERPRETER_FILETYPES: &[(&str, &str)] = &[
+    ("python", "py"),
+    ("sh", "sh"),
+    ("bash", "sh"),
+    ("dash", "sh"),
+    ("ksh", "sh"),
+    ("zsh", "sh"),
+    ("fish", "fish"),
+    ("node", "js"),
+    ("nodejs", "js"),
+    ("deno", "ts"),
+    ("perl", "pl"),
+    ("ruby", "rb"),
+    ("php", "php"),
+    ("lua", "lua"),
+    ("Rscript", "r"),
+    ("pwsh", "ps1"),
+    ("runghc", "hs"),
+    ("runhaskell", "hs"),
+    ("elixir", "exs"),
+    ("julia", "jl"),
+    ("swift", "swift"),
+    ("groovy", "groovy"),
+];
+
+// File types of vim filetypes and emacs major modes, matched case
+// insensitively, used for files without an extension which have a
+// modeline.
+const MODE_FILETYPES: &[(&str, &str)] = &[
+    ("python", "py"),
+    ("ruby", "rb"),
+    ("sh", "sh"),
+    ("bash", "sh"),
+    ("zsh", "sh"),
+    ("shell-script", "sh"),
+    ("javascript", "js"),
+    ("js", "js"),
+    ("typescript", "ts"),
+    ("perl", "pl"),
+    ("cperl", "pl"),
+    ("php", "php"),
+    ("lua", "lua"),
+    ("yaml", "yaml"),
+    ("toml", "toml"),
+    ("make", "Makefile"),
+    ("makefile", "Makefile"),
+    ("dockerfile", "Dockerfile"),
+    ("c", "c"),
+    ("cpp", "cpp"),
+    ("c++", "cpp"),
+    ("rust", "rs"),
+    ("go", "go"),
+    ("java", "java"),
+    ("haskell", "hs"),
+    ("sql", "sql"),
+    ("html", "html"),
+    ("xml", "xml"),
+    ("nxml", "xml"),
+    ("css", "css"),
+    ("vim", "vim"),
+    ("emacs-lisp", "el"),
+    ("lisp", "el"),
+    ("erlang", "erl"),
+    ("tex", "tex"),
+    ("latex", "tex"),
+];
+
+// How much of the start and end of a file is searched for a shebang or
+// modeline.
+const SNIFF_LEN: usize = 4096;
+
+// Like vim only this many lines at the start and end of a file are
+// checked for a modeline.
+const MODELINE_LINES: usize = 5;
+
+/// The file type of filename. For files without an extension this falls
+/// back to the interpreter of a shebang, e.g. #!/usr/bin/env python3, and
+/// then to a vim modeline, e.g. # vim: ft=python, or an emacs mode line,
+/// e.g. # -*- mode: ruby -*-.
+pub fn detect_filetype(filename: &str) -> &str {
+    if Path::new(filename).extension().is_some() {
+        return get_filetype(filename);
+    }
+
+    let detected = read_head_and_tail(filename).and_then(|(head, tail)| {
+        head.lines()
+            .next()
+            .and_then(shebang_filetype)
+            .or_else(|| modeline_filetype(&head, &tail))
+    });
+    detected.unwrap_or_else(|| get_filetype(filename))
+}
+
+/// Read the start and, if the file is longer than that, the end of
+/// filename.
+fn read_head_and_tail(filename: &str) -> Option<(String, String)> {
+    // The whole file is read, through the cache, since it will be read
+    // again to license it.
+    let content = cache::read(filename).ok()?;
+    let len = content.len();
+    let head = &content[..len.min(SNIFF_LEN)];
+    let tail = if len > SNIFF_LEN {
+        &content[len - SNIFF_LEN..]
+    } else {
+        &[]
+    };
+
+    Some((
+        String::from_utf8_lossy(head).to_string(),
+        String::from_utf8_lossy(tail).to_string(),
+    ))
+}
+
+/// Map a shebang line to the file type of its interpreter. Arguments to
+/// env and version suffixes such as python3.12 are ignored.
+fn shebang_filetype(shebang: &str) -> Option<&'static str> {
+    let mut words = shebang.strip_prefix("#!")?.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = words.find(|w| !w.starts_with('-') && !w.contains('='))?;
+    }
+
+    let interpreter = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    INTERPRETER_FILETYPES
+        .iter()
+        .find(|(name, _)| *name == interpreter)
+        .map(|(_, ft)| *ft)
+}
+
+/// Find the file type named by an emacs mode line, which must be one of
+/// the first two lines, or a vim modeline in the first or last lines.
+fn modeline_filetype(head: &str, tail: &str) -> Option<&'static str> {
+    let emacs = head.lines().take(2).find_map(emacs_mode);
+    let vim = || {
+        let mut lines: Vec<&str> = head.lines().take(MODELINE_LINES).collect();
+        let source = if tail.is_empty() { head } else { tail };
+        let last: Vec<&str> = source.lines().rev().take(MODELINE_LINES).collect();
+        lines.extend(last);
+        lines.into_iter().find_map(vim_filetype)
+    };
+
+    emacs.or_else(vim).and_then(|mode| {
+        MODE_FILETYPES
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(&mode))
+            .map(|(_, ft)| *ft)
+    })
+}
+
+/// The major mode of an emacs mode line, either -*- mode: ruby -*- or
+/// the short form -*- ruby -*-.
+fn emacs_mode(line: &str) -> Option<String> {
+    let vars = Regex::new(r"-\*-(.*?)-\*-")
+        .expect("emacs mode line regex didn't compile!")
+        .captures(line)?
+        .get(1)?
+        .as_str()
+        .trim();
+
+    if !vars.contains(':') {
+        return Some(vars.to_string()).filter(|m| !m.is_empty());
+    }
+
+    vars.split(';').find_map(|var| {
+        let (name, value) = var.split_once(':')?;
+        if name.trim().eq_ignore_ascii_case("mode") {
+            Some(value.trim().to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// The filetype set by a vim modeline, e.g. vim: ft=python or
+/// vim: set filetype=ruby :
+fn vim_filetype(line: &str) -> Option<String> {
+    let options = Regex::new(r"(?:^|\s)(?:vi|vim|ex):\s*(.*)")
+        .expect("vim modeline regex didn't compile!")
+        .captures(line)?
+        .get(1)?
+        .as_str();
+
+    Regex::new(r"(?:^|[\s:])(?:ft|filetype)=([\w+-]+)")
+        .expect("vim filetype regex didn't compile!")
+        .captures(options)
+        .map(|caps| caps[1].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    #[test]
+    fn test_shebang_filetype() {
+        assert_eq!(Some("py"), shebang_filetype("#!/usr/bin/env python3"));
+        assert_eq!(Some("py"), shebang_filetype("#!/usr/bin/python3.12 -u"));
+        assert_eq!(Some("sh"), shebang_filetype("#!/bin/bash -e"));
+        assert_eq!(
+            Some("js"),
+            shebang_filetype("#!/usr/bin/env -S node --harmony")
+        );
+        assert_eq!(Some("pl"), shebang_filetype("#! /usr/bin/perl"));
+        assert_eq!(None, shebang_filetype("#!/usr/bin/env unknown"));
+        assert_eq!(None, shebang_filetype("#!"));
+    }
+
+    #[test]
+    fn test_modeline_filetype() {
+        assert_eq!(
+            Some("py"),
+            modeline_filetype("# vim: ft=python\nx = 1\n", "")
+        );
+        assert_eq!(
+            Some("rb"),
+            modeline_filetype("puts 1\n", "a\n# vim: set filetype=ruby :\n")
+        );
+        assert_eq!(
+            Some("rb"),
+            modeline_filetype("# -*- mode: Ruby; coding: utf-8 -*-\n", "")
+        );
+        assert_eq!(
+            Some("sh"),
+            modeline_filetype("#!/bin/unknown\n# -*- sh -*-\n", "")
+        );
+        assert_eq!(None, modeline_filetype("# mode: ruby\n# ft=python\n", ""));
+        assert_eq!(None, modeline_filetype("# vim: ft=unknown\n", ""));
+    }
+
+    #[test]
+    fn test_detect_filetype() {
+        let dir = env::temp_dir().join(format!("licensure-detect-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let script = dir.join("deploy");
+        fs::write(&script, "#!/usr/bin/env ruby\nputs 1\n").unwrap();
+        assert_eq!("rb", detect_filetype(script.to_str().unwrap()));
+
+        // The shebang takes precedence over a modeline.
+        let tool = dir.join("tool");
+        fs::write(&tool, "#!/bin/sh\n# vim: ft=python\n").unwrap();
+        assert_eq!("sh", detect_filetype(tool.to_str().unwrap()));
+
+        let long = dir.join("long");
+        let content = format!("{}# vim: ft=lua\n", "x = 1\n".repeat(2000));
+        fs::write(&long, content).unwrap();
+        assert_eq!("lua", detect_filetype(long.to_str().unwrap()));
+
+        // The extension always takes precedence.
+        let lib = dir.join("lib.py");
+        fs::write(&lib, "#!/bin/sh\n").unwrap();
+        assert_eq!("py", detect_filetype(lib.to_str().unwrap()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
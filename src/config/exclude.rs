@@ -0,0 +1,189 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path};
+use std::time::SystemTime;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Deserialize;
+
+/// A criterion for excluding files which is awkward to express as a
+/// regex.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    /// Files with more path components than this, so src/main.rs has
+    /// a depth of 2.
+    MaxDepth(usize),
+    /// Files larger than this.
+    LargerThan(Size),
+    /// Files last modified before this date.
+    OlderThan(Date),
+}
+
+impl Rule {
+    pub fn matches(&self, path: &str) -> bool {
+        match self {
+            Rule::MaxDepth(max) => depth(path) > *max,
+            Rule::LargerThan(Size(size)) => fs::metadata(path).is_ok_and(|m| m.len() > *size),
+            Rule::OlderThan(Date(date)) => fs::metadata(path)
+                .and_then(|m| m.modified())
+                .is_ok_and(|modified| modified_date(modified) < *date),
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rule::MaxDepth(max) => write!(f, "max_depth: {}", max),
+            Rule::LargerThan(Size(size)) => write!(f, "larger_than: {} bytes", size),
+            Rule::OlderThan(Date(date)) => write!(f, "older_than: {}", date),
+        }
+    }
+}
+
+/// The exclude_rules of the config, a file matching any of them is
+/// excluded.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(transparent)]
+pub struct Rules(Vec<Rule>);
+
+impl Rules {
+    /// The first rule which path matches.
+    pub fn matching(&self, path: &str) -> Option<&Rule> {
+        self.0.iter().find(|rule| rule.matches(path))
+    }
+}
+
+/// A size in bytes, given as a number or with a B, KB, MB, or GB suffix
+/// where a KB is 1024 bytes.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "SizeValue")]
+pub struct Size(u64);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SizeValue {
+    Bytes(u64),
+    Text(String),
+}
+
+impl TryFrom<SizeValue> for Size {
+    type Error = String;
+
+    fn try_from(value: SizeValue) -> Result<Size, String> {
+        let s = match value {
+            SizeValue::Bytes(n) => return Ok(Size(n)),
+            SizeValue::Text(s) => s,
+        };
+
+        let upper = s.trim().to_uppercase();
+        let digits = upper.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+        let multiplier = match upper[digits.len()..]
+            .trim_end_matches("IB")
+            .trim_end_matches('B')
+        {
+            "" => 1,
+            "K" => 1024,
+            "M" => 1024 * 1024,
+            "G" => 1024 * 1024 * 1024,
+            _ => return Err(format!("invalid size {}, expected e.g. 512KB or 1MB", s)),
+        };
+
+        digits
+            .trim()
+            .parse::<u64>()
+            .map(|n| Size(n * multiplier))
+            .map_err(|_| format!("invalid size {}, expected e.g. 512KB or 1MB", s))
+    }
+}
+
+/// A date given as YYYY-MM-DD.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "String")]
+pub struct Date(NaiveDate);
+
+impl TryFrom<String> for Date {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Date, String> {
+        NaiveDate::parse_from_str(&s, "%Y-%m-%d")
+            .map(Date)
+            .map_err(|e| format!("invalid date {}, expected YYYY-MM-DD: {}", s, e))
+    }
+}
+
+fn depth(path: &str) -> usize {
+    Path::new(path)
+        .components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .count()
+}
+
+fn modified_date(modified: SystemTime) -> NaiveDate {
+    DateTime::<Utc>::from(modified).date_naive()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_rules() {
+        let rules: Rules = serde_yaml::from_str(
+            "- max_depth: 6\n- larger_than: 1MB\n- larger_than: 2048\n- older_than: 2015-01-01\n",
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                Rule::MaxDepth(6),
+                Rule::LargerThan(Size(1024 * 1024)),
+                Rule::LargerThan(Size(2048)),
+                Rule::OlderThan(Date(NaiveDate::from_ymd_opt(2015, 1, 1).unwrap())),
+            ],
+            rules.0
+        );
+
+        assert!(serde_yaml::from_str::<Rules>("- larger_than: 1TB\n").is_err());
+        assert!(serde_yaml::from_str::<Rules>("- older_than: last year\n").is_err());
+    }
+
+    #[test]
+    fn test_size() {
+        let size = |s: &str| Size::try_from(SizeValue::Text(s.to_string()));
+        assert_eq!(Ok(Size(512 * 1024)), size("512KB"));
+        assert_eq!(Ok(Size(1024 * 1024)), size("1 MiB"));
+        assert_eq!(Ok(Size(3 * 1024 * 1024 * 1024)), size("3gb"));
+        assert_eq!(Ok(Size(100)), size("100B"));
+        assert!(size("MB").is_err());
+    }
+
+    #[test]
+    fn test_rule_matches() {
+        assert!(Rule::MaxDepth(1).matches("src/main.rs"));
+        assert!(!Rule::MaxDepth(2).matches("./src/main.rs"));
+        assert!(Rule::LargerThan(Size(10)).matches("Cargo.toml"));
+        assert!(!Rule::LargerThan(Size(u64::MAX)).matches("Cargo.toml"));
+        assert!(!Rule::LargerThan(Size(0)).matches("does/not/exist"));
+
+        let today = Utc::now().date_naive();
+        assert!(
+            !Rule::OlderThan(Date(NaiveDate::from_ymd_opt(1990, 1, 1).unwrap()))
+                .matches("Cargo.toml")
+        );
+        assert!(Rule::OlderThan(Date(today.succ_opt().unwrap())).matches("Cargo.toml"));
+    }
+}
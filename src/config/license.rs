@@ -11,14 +11,114 @@
 // You should have received a copy of the GNU General Public License along with
 // this program. If not, see <https://www.gnu.org/licenses/>.
 //
-use std::process::{self, Command};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
+use chrono::{DateTime, SecondsFormat, Utc};
 use regex::Regex;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use crate::template::{Authors, Context, Template};
+use crate::clock;
+use crate::config::transform::{self, Transform};
+use crate::config::{xdg_cache_dir, LinePatterns, Position, VerifyCommand};
+use crate::errors;
+use crate::fail;
+use crate::metrics;
+use crate::template::{
+    Authors, Context, SmallFileLimits, SmallFiles, Template, YearMode, YearRangeFormat,
+};
+use crate::utils::git;
+use crate::utils::sha256;
+use crate::warning;
 
-#[derive(Deserialize, Debug)]
+// How long a cached SPDX template is considered fresh. Stale entries
+// are still used if SPDX can't be reached.
+const SPDX_CACHE_TTL: Duration = Duration::from_secs(60 * 60 * 24 * 7);
+
+// How long, in seconds, a variable command may run for by default.
+const DEFAULT_VARIABLE_COMMAND_TIMEOUT: u64 = 10;
+
+// The output of a variable command, set once it has run.
+type CommandOutput = Arc<OnceLock<Option<String>>>;
+
+// The output of every variable command which has been run, they're only
+// run once per process.
+static VARIABLE_COMMAND_OUTPUT: OnceLock<Mutex<HashMap<VerifyCommand, CommandOutput>>> =
+    OnceLock::new();
+
+// Whether cached SPDX templates are fetched again even if they're
+// fresh, set by --refresh-spdx-cache.
+static REFRESH_SPDX_CACHE: AtomicBool = AtomicBool::new(false);
+
+// Where the fetched template of every license stamped in this process
+// came from, by ident.
+static TEMPLATE_PROVENANCE: OnceLock<Mutex<BTreeMap<String, Provenance>>> = OnceLock::new();
+
+/// Where the SPDX template of a license was had from, so that a
+/// reviewer can reproduce the exact header text of a run later.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Provenance {
+    pub ident: String,
+    pub origin: Origin,
+    pub url: String,
+    /// The SHA-256 of the template text, before any transforms.
+    pub sha256: String,
+    /// When the template was downloaded, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fetched_at: Option<String>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Origin {
+    Downloaded,
+    Cached,
+    // A cached template used because SPDX couldn't be reached.
+    StaleCache,
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let origin = match self.origin {
+            Origin::Downloaded => "downloaded",
+            Origin::Cached => "cached",
+            Origin::StaleCache => "stale cache",
+        };
+        write!(f, "{} template from {} ({}", self.ident, self.url, origin)?;
+        if let Some(fetched_at) = &self.fetched_at {
+            write!(f, ", fetched {}", fetched_at)?;
+        }
+        write!(f, "), sha256 {}", self.sha256)
+    }
+}
+
+/// The provenance of every SPDX template used to stamp files so far, in
+/// order of ident.
+pub fn template_provenance() -> Vec<Provenance> {
+    TEMPLATE_PROVENANCE
+        .get()
+        .map(|recorded| recorded.lock().unwrap().values().cloned().collect())
+        .unwrap_or_default()
+}
+
+fn record_provenance(provenance: Provenance) {
+    let recorded = TEMPLATE_PROVENANCE.get_or_init(|| Mutex::new(BTreeMap::new()));
+    recorded
+        .lock()
+        .unwrap()
+        .entry(provenance.ident.clone())
+        .or_insert(provenance);
+}
+
+#[derive(Deserialize, Debug, Clone)]
 #[serde(from = "String")]
 struct FileMatcher {
     any: bool,
@@ -26,6 +126,13 @@ struct FileMatcher {
 }
 
 impl FileMatcher {
+    pub fn as_str(&self) -> &str {
+        match &self.regex {
+            Some(r) => r.as_str(),
+            None => "any",
+        }
+    }
+
     pub fn is_match(&self, s: &str) -> bool {
         if self.any {
             return true;
@@ -49,10 +156,11 @@ impl From<String> for FileMatcher {
 
         let r = match Regex::new(&s) {
             Ok(r) => r,
-            Err(e) => {
-                println!("Failed to compile file matcher regex: {}", e);
-                process::exit(1);
-            }
+            Err(e) => fail!(
+                errors::INVALID_PATTERN,
+                "Failed to compile file matcher regex: {}",
+                e
+            ),
         };
 
         FileMatcher {
@@ -70,7 +178,7 @@ struct SPDXLicenseInfo {
     license_header: Option<String>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Config {
     files: FileMatcher,
 
@@ -79,14 +187,116 @@ pub struct Config {
     #[serde(alias = "year")]
     end_year: Option<String>,
     start_year: Option<String>,
+    // How [year] is rendered when it's a range, e.g. "[start]-[end]".
+    #[serde(default)]
+    year_range_format: YearRangeFormat,
+    // How the years of existing headers are updated.
+    #[serde(default)]
+    year_mode: YearMode,
     #[serde(default = "default_dynamic_year_ranges")]
     use_dynamic_year_ranges: bool,
 
+    #[serde(default)]
+    use_dynamic_authors: bool,
+    #[serde(default)]
+    author_aliases: HashMap<String, String>,
+    #[serde(default = "default_min_author_commits")]
+    min_author_commits: usize,
+
     template: Option<String>,
     auto_template: Option<bool>,
+    // Templates, by the ident of a license or as text, whose headers
+    // are replaced by this license's header.
+    #[serde(default)]
+    replaces_templates: Vec<ReplacedTemplate>,
+    // Named classes of files, like tests, which get a different template
+    // than the other files of this license.
+    #[serde(default)]
+    classes: Vec<FileClass>,
+    // Text placed before and after the template, separated from it by
+    // a blank line, e.g. an SPDX tag or company boilerplate.
+    prefix: Option<String>,
+    suffix: Option<String>,
+    // Overrides the placement of the comment config.
+    placement: Option<Position>,
+    // Overrides the formatter command of the comment config.
+    verify_with: Option<VerifyCommand>,
+    // Separator lines, e.g. of dashes, which existing headers may be
+    // wrapped in.
+    #[serde(default)]
+    banners: LinePatterns,
 
     #[serde(default = "default_unwrap_text")]
     unwrap_text: bool,
+
+    #[serde(default)]
+    auto_strip_comments: bool,
+
+    // Only headers identical to the rendered header, byte for byte, are
+    // accepted.
+    #[serde(default)]
+    strict_bytes: bool,
+
+    // Existing headers with other authors are updated instead of being
+    // considered a different header.
+    #[serde(default)]
+    authors_varying: bool,
+
+    // Files with fewer lines, or for which the header would have more
+    // than this ratio of their lines, are too small for the header and
+    // are handled as small_files says instead.
+    min_file_lines_for_full_header: Option<usize>,
+    max_header_ratio: Option<f64>,
+    #[serde(default)]
+    small_files: SmallFiles,
+
+    #[serde(default)]
+    transforms: Vec<Transform>,
+
+    contributors_file: Option<String>,
+
+    // Variables whose values are the output of a command, e.g. revision:
+    // git rev-parse --short HEAD.
+    #[serde(default)]
+    variable_commands: BTreeMap<String, VerifyCommand>,
+    #[serde(default = "default_variable_command_timeout")]
+    variable_command_timeout: u64,
+
+    // Expectations about the rendered header, checked by licensure
+    // validate-config.
+    #[serde(default)]
+    tests: Vec<TemplateTest>,
+}
+
+/// A class of the files of a license entry, such as tests, whose
+/// template is used for its files instead of the license's template.
+#[derive(Deserialize, Debug, Clone)]
+struct FileClass {
+    class: String,
+    files: FileMatcher,
+    template: String,
+}
+
+/// A template whose headers are replaced by those of a license entry,
+/// either the template of a license by ident or template text.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+enum ReplacedTemplate {
+    Ident { ident: String },
+    Text { template: String },
+}
+
+/// A test case of a license entry, run by licensure validate-config.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct TemplateTest {
+    /// The path to render the header for, which doesn't need to exist.
+    pub file: String,
+    /// The date to render the header on, as YYYY-MM-DD, instead of today.
+    pub today: Option<String>,
+    /// The expected first line of the commented header.
+    pub first_line: Option<String>,
+    /// The expected commented header.
+    pub expected: Option<String>,
 }
 
 fn default_unwrap_text() -> bool {
@@ -97,122 +307,327 @@ fn default_dynamic_year_ranges() -> bool {
     false
 }
 
+fn default_min_author_commits() -> usize {
+    1
+}
+
+fn default_variable_command_timeout() -> u64 {
+    DEFAULT_VARIABLE_COMMAND_TIMEOUT
+}
+
 impl Config {
     pub fn file_is_match(&self, s: &str) -> bool {
         self.files.is_match(s)
     }
 
-    fn fetch_template(&self) -> String {
-        let url = format!("https://spdx.org/licenses/{}.json", &self.ident);
-        let response = match ureq::get(&url).call() {
-            Ok(r) => r,
-            Err(e) => {
-                println!("Failed to fetch license template from SPDX: {}", e);
-                process::exit(1);
+    pub fn ident(&self) -> &str {
+        &self.ident
+    }
+
+    pub fn files(&self) -> &str {
+        self.files.as_str()
+    }
+
+    pub fn contributors_file(&self) -> Option<&str> {
+        self.contributors_file.as_deref()
+    }
+
+    pub fn placement(&self) -> Option<Position> {
+        self.placement
+    }
+
+    pub fn verify_command(&self) -> Option<&VerifyCommand> {
+        self.verify_with.as_ref()
+    }
+
+    /// The fingerprint of the header this license entry would produce,
+    /// see Template::fingerprint.
+    pub fn fingerprint(&self, variables: &BTreeMap<String, String>) -> String {
+        self.static_template()
+            .set_variables(variables.clone())
+            .fingerprint()
+    }
+
+    /// The template with the configured years and authors, ignoring
+    /// dynamic years and authors, for uses which aren't about a single
+    /// file.
+    pub fn static_template(&self) -> Template {
+        self.build_template(self.end_year.clone(), self.start_year.clone(), "")
+    }
+
+    /// The templates whose headers templ, the template of this license
+    /// for a file, replaces. configured is the template for the file of
+    /// the configured license with an ident, licenses which aren't
+    /// configured use their SPDX template.
+    pub fn replaced_templates(
+        &self,
+        templ: &Template,
+        configured: impl Fn(&str) -> Option<Template>,
+    ) -> Vec<Template> {
+        self.replaces_templates
+            .iter()
+            .map(|replaced| match replaced {
+                ReplacedTemplate::Ident { ident } => configured(ident).unwrap_or_else(|| {
+                    templ.with_content(&fetch_spdx_template(ident), ident, true)
+                }),
+                ReplacedTemplate::Text { template } => {
+                    templ.with_content(template, &self.ident, false)
+                }
+            })
+            .collect()
+    }
+
+    pub fn tests(&self) -> &[TemplateTest] {
+        &self.tests
+    }
+
+    pub fn get_template(&self, filename: &str) -> Template {
+        let (end_year, start_year) = if self.use_dynamic_year_ranges {
+            match &get_git_dates_for_file(filename)[..] {
+                // Files without any history, e.g. those not yet committed
+                // or outside of a git repository, use the current year.
+                [] => (None, None),
+                dates => {
+                    let (last_updated_date, created_date) = (&dates[0], &dates[dates.len() - 1]);
+
+                    // Git formats the dates such that we get "Wed May 29 04:54:58 2024 +0100" we only care
+                    // about the 4th "field" which is the year.
+                    let created_year = created_date
+                        .split(' ')
+                        .nth(4)
+                        .expect("Unable to parse created year!");
+                    let last_updated_year = last_updated_date
+                        .split(' ')
+                        .nth(4)
+                        .expect("Unable to parse last updated year!");
+
+                    (
+                        Some(last_updated_year.to_string()),
+                        Some(created_year.to_string()),
+                    )
+                }
             }
+        } else {
+            (self.end_year.clone(), self.start_year.clone())
         };
 
-        match response.status() {
-            404 => {
-                println!(
-                    "{} does not appear to be a valid SPDX identifier, go to https://spdx.org/licenses/ to view a list of valid identifiers",
-                    &self.ident
-                );
-                process::exit(1)
-            }
-            200 => (),
-            _ => {
-                println!(
-                    "Failed to fetch license template from SPDX for {}: {:?}",
-                    &self.ident,
-                    response.status()
-                );
-                process::exit(1);
-            }
+        let t = self.build_template(end_year, start_year, filename);
+        if !self.use_dynamic_authors {
+            return t;
         }
 
-        let license_info: SPDXLicenseInfo = match response.into_json() {
-            Ok(json) => json,
-            Err(err) => {
-                println!("Failed to deserialize SPDX JSON: {}", err);
-                process::exit(1);
-            }
-        };
+        let authors = count_authors(
+            &get_git_authors_for_file(filename),
+            &self.author_aliases,
+            self.min_author_commits,
+        );
+        if authors.is_empty() {
+            // Files without any history, e.g. those not yet committed,
+            // use the configured authors.
+            return t;
+        }
 
-        match license_info.license_header {
-            Some(header) => header,
-            None => license_info.license_text,
+        t.set_authors(Authors::from_names(authors))
+    }
+
+    /// Surround the template text with the configured prefix and suffix.
+    /// They become part of the template so the header, including them,
+    /// is detected the same as the template alone would be.
+    fn add_prefix_and_suffix(&self, template: String) -> String {
+        let mut t = template;
+        if let Some(prefix) = &self.prefix {
+            t = format!(
+                "{}\n\n{}",
+                prefix.trim_end_matches('\n'),
+                t.trim_start_matches('\n')
+            );
+        }
+
+        if let Some(suffix) = &self.suffix {
+            t = format!(
+                "{}\n\n{}",
+                t.trim_end_matches('\n'),
+                suffix.trim_start_matches('\n')
+            );
         }
+
+        t
     }
 
-    pub fn get_template(&self, filename: &str) -> Template {
+    fn small_file_limits(&self) -> Option<SmallFileLimits> {
+        if self.min_file_lines_for_full_header.is_none() && self.max_header_ratio.is_none() {
+            return None;
+        }
+
+        Some(SmallFileLimits {
+            min_lines: self.min_file_lines_for_full_header,
+            max_ratio: self.max_header_ratio,
+            action: self.small_files,
+        })
+    }
+
+    /// The class of filename, the first class whose files match it.
+    fn class_of(&self, filename: &str) -> Option<&FileClass> {
+        self.classes.iter().find(|c| c.files.is_match(filename))
+    }
+
+    fn build_template(
+        &self,
+        end_year: Option<String>,
+        start_year: Option<String>,
+        filename: &str,
+    ) -> Template {
         let auto_templ;
-        let t = match &self.template {
-            Some(ref t) => t,
-            None => {
+        let class = self.class_of(filename);
+        if let Some(class) = class {
+            debug!(
+                "{} is in the {} class of {}",
+                filename, class.class, self.ident
+            );
+        }
+
+        let t = match (class, &self.template) {
+            (Some(class), _) => &class.template,
+            (None, Some(ref t)) => t,
+            (None, None) => {
                 if self.auto_template.unwrap_or(false) {
-                    auto_templ = self.fetch_template();
+                    let provenance;
+                    (auto_templ, provenance) = fetch_spdx_template_with_provenance(&self.ident);
+                    record_provenance(provenance);
                     &auto_templ
                 } else {
-                    println!("auto_template not enabled and no template provided, please add a template option to the license definition for {}. Exitting", self.ident);
-                    process::exit(1);
+                    fail!(errors::MISSING_TEMPLATE, "auto_template not enabled and no template provided, please add a template option to the license definition for {}", self.ident);
                 }
             }
         };
 
-        let (end_year, start_year) = if self.use_dynamic_year_ranges {
-            let dates = get_git_dates_for_file(filename);
-            let (last_updated_date, created_date) = match &dates[..] {
-                [first_date, .., last_date] => (first_date, last_date),
-                [first_date] => (first_date, first_date),
-                _ => panic!("Did not get any dates from git!"),
-            };
-
-            // Git formats the dates such that we get "Wed May 29 04:54:58 2024 +0100" we only care
-            // about the 4th "field" which is the year.
-            let created_year = created_date
-                .split(' ')
-                .nth(4)
-                .expect("Unable to parse created year!");
-            let last_updated_year = last_updated_date
-                .split(' ')
-                .nth(4)
-                .expect("Unable to parse last updated year!");
-
-            (
-                Some(last_updated_year.to_string()),
-                Some(created_year.to_string()),
-            )
-        } else {
-            (self.end_year.clone(), self.start_year.clone())
-        };
-
+        let t = transform::apply_all(&self.transforms, t);
+        let t = self.add_prefix_and_suffix(t);
         let t = Template::new(
-            t,
+            &t,
             Context {
                 end_year,
                 start_year,
                 ident: self.ident.clone(),
                 authors: self.authors.clone(),
                 unwrap_text: self.unwrap_text,
+                path: filename.to_string(),
+                variables: self.command_variables(),
+                clock: clock::current(),
+                year_range_format: self.year_range_format.clone(),
+                date: None,
+                version: None,
             },
-        );
+        )
+        .set_auto_strip_comments(self.auto_strip_comments)
+        .set_strict_bytes(self.strict_bytes)
+        .set_authors_varying(self.authors_varying)
+        .set_small_file_limits(self.small_file_limits())
+        .set_year_mode(self.year_mode)
+        .set_banners(self.banners.clone());
 
-        if self.auto_template.unwrap_or(false) {
+        if class.is_none() && self.auto_template.unwrap_or(false) {
             return t.set_spdx_template(true);
         }
 
         t
     }
+
+    // The values of variable_commands, leaving out those whose command
+    // failed.
+    fn command_variables(&self) -> BTreeMap<String, String> {
+        let timeout = Duration::from_secs(self.variable_command_timeout);
+        self.variable_commands
+            .iter()
+            .filter_map(|(name, command)| {
+                run_variable_command(command, timeout).map(|value| (name.clone(), value))
+            })
+            .collect()
+    }
+}
+
+// The trimmed stdout of command, which is only run the first time it's
+// needed. Warns and returns None if it fails or runs for longer than
+// timeout.
+fn run_variable_command(command: &VerifyCommand, timeout: Duration) -> Option<String> {
+    // Only the lookup holds the lock, threads needing the same command
+    // wait on its cell for the output instead of running it again.
+    let output = VARIABLE_COMMAND_OUTPUT
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(command.clone())
+        .or_default()
+        .clone();
+
+    output
+        .get_or_init(|| match run_with_timeout(command, timeout) {
+            Ok(output) => Some(output),
+            Err(e) => {
+                warning!("variable command `{}` failed: {}", command, e);
+                None
+            }
+        })
+        .clone()
+}
+
+fn run_with_timeout(command: &VerifyCommand, timeout: Duration) -> Result<String, io::Error> {
+    let (program, args) = command.program();
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    // Read on another thread so a command with a lot of output doesn't
+    // block on a full pipe while it's being waited for.
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let reader = thread::spawn(move || {
+        let mut out = Vec::new();
+        stdout.read_to_end(&mut out).map(|_| out)
+    });
+
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+
+        if start.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("timed out after {}s", timeout.as_secs_f32()),
+            ));
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    let out = reader.join().expect("stdout reader panicked!")?;
+    if !status.success() {
+        return Err(io::Error::other(format!("it exited with {}", status)));
+    }
+
+    Ok(String::from_utf8_lossy(&out).trim().to_string())
 }
 
 fn get_git_dates_for_file(filename: &str) -> Vec<String> {
-    match Command::new("git")
+    let repo = match git::repo() {
+        Some(repo) => repo,
+        None => return Vec::new(),
+    };
+
+    match repo
+        .command()
         .arg("log")
         .arg("--follow")
         .arg("--format=%ad")
         .args(["--date", "default"])
-        .arg(filename)
+        .arg("--")
+        .arg(repo.root_relative(filename))
         .output()
     {
         Ok(proc) => String::from_utf8(proc.stdout)
@@ -222,9 +637,490 @@ fn get_git_dates_for_file(filename: &str) -> Vec<String> {
             .filter(|s| !s.is_empty())
             .collect(),
         Err(e) => {
-            println!("Failed to run git log to get file dates. Make sure you're in a git repo.");
-            println!("{}", e);
-            process::exit(1)
+            warning!(
+                "failed to run git log to get the dates of {}: {}",
+                filename,
+                e
+            );
+            Vec::new()
+        }
+    }
+}
+
+fn get_git_authors_for_file(filename: &str) -> Vec<String> {
+    let repo = match git::repo() {
+        Some(repo) => repo,
+        None => return Vec::new(),
+    };
+
+    match repo
+        .command()
+        .arg("log")
+        .arg("--follow")
+        .arg("--format=%an <%ae>")
+        .arg("--")
+        .arg(repo.root_relative(filename))
+        .output()
+    {
+        Ok(proc) => String::from_utf8_lossy(&proc.stdout)
+            .lines()
+            .map(str::to_string)
+            .filter(|s| !s.is_empty())
+            .collect(),
+        Err(e) => {
+            warning!(
+                "failed to run git log to get the authors of {}: {}",
+                filename,
+                e
+            );
+            Vec::new()
+        }
+    }
+}
+
+/// Count the commits of every author in the "Name <email>" lines output
+/// by git log, resolving aliases, and return those with at least
+/// min_commits ordered by number of commits. Aliases may be keyed by
+/// "Name <email>", email, or name and map to "Name <email>" or "Name".
+fn count_authors(
+    log: &[String],
+    aliases: &HashMap<String, String>,
+    min_commits: usize,
+) -> Vec<(String, Option<String>)> {
+    let ident_re =
+        Regex::new(r"^(?P<name>.*?)\s*<(?P<email>[^>]*)>$").expect("ident regex didn't compile!");
+    let parse = |ident: &str| match ident_re.captures(ident) {
+        Some(caps) if !caps["email"].is_empty() => {
+            (caps["name"].to_string(), Some(caps["email"].to_string()))
+        }
+        Some(caps) => (caps["name"].to_string(), None),
+        None => (ident.trim().to_string(), None),
+    };
+
+    let mut counts: Vec<((String, Option<String>), usize)> = Vec::new();
+    for line in log {
+        let (name, email) = parse(line);
+        let alias = aliases
+            .get(line.as_str())
+            .or_else(|| email.as_ref().and_then(|e| aliases.get(e)))
+            .or_else(|| aliases.get(&name));
+        let author = match alias {
+            Some(alias) => parse(alias),
+            None => (name, email),
+        };
+
+        match counts.iter_mut().find(|(a, _)| *a == author) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((author, 1)),
+        }
+    }
+
+    // sort_by_key is stable so authors with the same number of commits
+    // stay in order of their most recent commit.
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    counts
+        .into_iter()
+        .filter(|(_, count)| *count >= min_commits)
+        .map(|(author, _)| author)
+        .collect()
+}
+
+/// The SPDX template of the license with ident, from the cache if it's
+/// fresh, otherwise downloaded. Exits if it can't be had at all.
+fn fetch_spdx_template(ident: &str) -> String {
+    fetch_spdx_template_with_provenance(ident).0
+}
+
+fn fetch_spdx_template_with_provenance(ident: &str) -> (String, Provenance) {
+    let refresh = REFRESH_SPDX_CACHE.load(Ordering::Relaxed);
+    match fetch_spdx_template_from(
+        ident,
+        spdx_cache_dir().as_deref(),
+        refresh,
+        download_spdx_template,
+    ) {
+        Ok(fetched) => fetched,
+        Err(msg) => fail!(errors::SPDX_FETCH_FAILED, "{}", msg),
+    }
+}
+
+/// The template of ident from the cache in cache_dir if it's fresh and
+/// refresh isn't set, otherwise from download which is cached. A stale
+/// cache entry is used if download fails.
+fn fetch_spdx_template_from(
+    ident: &str,
+    cache_dir: Option<&Path>,
+    refresh: bool,
+    download: impl Fn(&str) -> Result<String, String>,
+) -> Result<(String, Provenance), String> {
+    let provenance = |template: &str, origin, age: Option<Duration>| Provenance {
+        ident: ident.to_string(),
+        origin,
+        url: spdx_url(ident),
+        sha256: sha256::hex_digest(template.as_bytes()),
+        fetched_at: age
+            .and_then(|age| SystemTime::now().checked_sub(age))
+            .map(|at| DateTime::<Utc>::from(at).to_rfc3339_opts(SecondsFormat::Secs, true)),
+    };
+
+    let cache_file = cache_dir.map(|dir| spdx_cache_file(dir, ident));
+    let cached = cache_file.as_deref().and_then(read_cached_spdx_template);
+    if let Some((ref template, age)) = cached {
+        if age < SPDX_CACHE_TTL && !refresh {
+            debug!("using cached SPDX template for {}", ident);
+            let provenance = provenance(template, Origin::Cached, Some(age));
+            return Ok((template.clone(), provenance));
+        }
+    }
+
+    match download(ident) {
+        Ok(template) => {
+            if let Some(path) = &cache_file {
+                if let Err(e) = write_cached_spdx_template(path, &template) {
+                    info!("unable to cache SPDX template for {}: {}", ident, e);
+                }
+            }
+
+            let provenance = provenance(&template, Origin::Downloaded, Some(Duration::ZERO));
+            Ok((template, provenance))
+        }
+        Err(msg) => match cached {
+            Some((template, age)) => {
+                warning!("{}, using stale cached template", msg);
+                let provenance = provenance(&template, Origin::StaleCache, Some(age));
+                Ok((template, provenance))
+            }
+            None => Err(msg),
+        },
+    }
+}
+
+fn spdx_url(ident: &str) -> String {
+    format!("https://spdx.org/licenses/{}.json", ident)
+}
+
+fn download_spdx_template(ident: &str) -> Result<String, String> {
+    let url = spdx_url(ident);
+    metrics::SPDX_FETCHES.inc();
+    let response = match ureq::get(&url).call() {
+        Ok(r) => r,
+        Err(ureq::Error::Status(404, _)) => {
+            return Err(format!(
+                "{} does not appear to be a valid SPDX identifier, go to https://spdx.org/licenses/ to view a list of valid identifiers",
+                ident
+            ))
+        }
+        Err(e) => return Err(format!("Failed to fetch license template from SPDX: {}", e)),
+    };
+
+    if response.status() != 200 {
+        return Err(format!(
+            "Failed to fetch license template from SPDX for {}: {:?}",
+            ident,
+            response.status()
+        ));
+    }
+
+    let license_info: SPDXLicenseInfo = match response.into_json() {
+        Ok(json) => json,
+        Err(err) => return Err(format!("Failed to deserialize SPDX JSON: {}", err)),
+    };
+
+    Ok(match license_info.license_header {
+        Some(header) => header,
+        None => license_info.license_text,
+    })
+}
+
+fn spdx_cache_dir() -> Option<PathBuf> {
+    xdg_cache_dir().map(|mut dir| {
+        dir.push("licensure");
+        dir.push("spdx");
+        dir
+    })
+}
+
+// The file in dir caching the template of ident. Characters which can't
+// be in an SPDX ident are replaced so that the file is always directly in
+// dir, whatever the ident in the config is.
+fn spdx_cache_file(dir: &Path, ident: &str) -> PathBuf {
+    let name: String = ident
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '+' | '.' => c,
+            _ => '_',
+        })
+        .collect();
+    dir.join(format!("{}.txt", name))
+}
+
+/// Returns the template cached in path, if any, along with how long
+/// ago it was cached.
+fn read_cached_spdx_template(path: &Path) -> Option<(String, Duration)> {
+    let template = fs::read_to_string(path).ok()?;
+    let age = fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .unwrap_or(Duration::MAX);
+    Some((template, age))
+}
+
+fn write_cached_spdx_template(path: &Path, template: &str) -> Result<(), io::Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(path, template)
+}
+
+/// Fetch SPDX templates again even if their cached copy is fresh. The
+/// cached copies are kept so they can still be used if SPDX can't be
+/// reached.
+pub fn set_refresh_spdx_cache(refresh: bool) {
+    REFRESH_SPDX_CACHE.store(refresh, Ordering::Relaxed);
+}
+
+/// Fetch every cached SPDX template again, returning how many were
+/// updated. Templates which can't be fetched keep their cached copy.
+pub fn refresh_cached_spdx_templates() -> Result<usize, io::Error> {
+    let dir = match spdx_cache_dir() {
+        Some(dir) if dir.exists() => dir,
+        _ => return Ok(0),
+    };
+
+    let mut refreshed = 0;
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        let ident = match (path.file_stem(), path.extension()) {
+            (Some(stem), Some(ext)) if ext == "txt" => stem.to_string_lossy().to_string(),
+            _ => continue,
+        };
+
+        match download_spdx_template(&ident) {
+            Ok(template) => {
+                write_cached_spdx_template(&path, &template)?;
+                refreshed += 1;
+            }
+            Err(msg) => warning!("{}, keeping the cached template", msg),
         }
     }
+
+    Ok(refreshed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::fs::File;
+
+    #[test]
+    fn test_spdx_cache() {
+        let dir = std::env::temp_dir().join(format!("licensure-spdx-cache-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let downloads = Cell::new(0);
+        let online = |ident: &str| {
+            downloads.set(downloads.get() + 1);
+            Ok(format!("{} template", ident))
+        };
+        let offline = |_: &str| Err("SPDX is unreachable".to_string());
+
+        // A miss is downloaded and cached, then hit.
+        let (template, provenance) =
+            fetch_spdx_template_from("MIT", Some(&dir), false, online).unwrap();
+        assert_eq!("MIT template", template);
+        assert_eq!(Origin::Downloaded, provenance.origin);
+        let (template, provenance) =
+            fetch_spdx_template_from("MIT", Some(&dir), false, online).unwrap();
+        assert_eq!("MIT template", template);
+        assert_eq!(Origin::Cached, provenance.origin);
+        assert_eq!(1, downloads.get());
+
+        // Refreshing downloads even a fresh entry, but keeps it when
+        // SPDX can't be reached.
+        fetch_spdx_template_from("MIT", Some(&dir), true, online).unwrap();
+        assert_eq!(2, downloads.get());
+        let (template, provenance) =
+            fetch_spdx_template_from("MIT", Some(&dir), true, offline).unwrap();
+        assert_eq!("MIT template", template);
+        assert_eq!(Origin::StaleCache, provenance.origin);
+
+        // Entries older than the TTL are downloaded again, or used if
+        // that fails.
+        let expired = SystemTime::now() - SPDX_CACHE_TTL - Duration::from_secs(60);
+        File::options()
+            .write(true)
+            .open(spdx_cache_file(&dir, "MIT"))
+            .unwrap()
+            .set_modified(expired)
+            .unwrap();
+        let (_, provenance) = fetch_spdx_template_from("MIT", Some(&dir), false, offline).unwrap();
+        assert_eq!(Origin::StaleCache, provenance.origin);
+        let (_, provenance) = fetch_spdx_template_from("MIT", Some(&dir), false, online).unwrap();
+        assert_eq!(Origin::Downloaded, provenance.origin);
+        assert_eq!(3, downloads.get());
+
+        assert!(fetch_spdx_template_from("Apache-2.0", Some(&dir), false, offline).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_spdx_cache_file() {
+        let dir = Path::new("cache");
+        assert_eq!(dir.join("GPL-2.0+.txt"), spdx_cache_file(dir, "GPL-2.0+"));
+        assert_eq!(dir.join(".._.._x.txt"), spdx_cache_file(dir, "../../x"));
+        assert_eq!(dir.join("a_b.txt"), spdx_cache_file(dir, "a\\b"));
+    }
+
+    #[test]
+    fn test_prefix_and_suffix() {
+        let cfg: Config = serde_yaml::from_str(
+            "files: any
+ident: MIT
+authors: [{name: Bob}]
+end_year: \"2024\"
+template: \"Copyright [year] [name of author].\\n\"
+prefix: \"SPDX-License-Identifier: [ident]\"
+suffix: All rights reserved.
+",
+        )
+        .unwrap();
+        assert_eq!(
+            "SPDX-License-Identifier: MIT\n\nCopyright 2024 Bob.\n\nAll rights reserved.",
+            cfg.static_template().render()
+        );
+    }
+
+    #[test]
+    fn test_classes() {
+        let cfg: Config = serde_yaml::from_str(
+            "files: any
+ident: MIT
+authors: [{name: Bob}]
+end_year: \"2024\"
+template: Copyright [year] [name of author]. Use of this file is governed by the [ident] license.
+classes:
+  - class: tests
+    files: tests/.*|.*_test\\.go
+    template: \"[filename] is [ident] licensed\"
+",
+        )
+        .unwrap();
+        let render = |file: &str| cfg.get_template(file).render();
+        assert_eq!(
+            "Copyright 2024 Bob. Use of this file is governed by the MIT license.",
+            render("src/main.go")
+        );
+        assert_eq!("main_test.go is MIT licensed", render("src/main_test.go"));
+        assert_eq!("cli.rs is MIT licensed", render("tests/cli.rs"));
+    }
+
+    #[test]
+    fn test_variable_commands() {
+        let cfg: Config = serde_yaml::from_str(
+            "files: any
+ident: MIT
+authors: [{name: Bob}]
+end_year: \"2024\"
+template: \"Copyright [year] [name of author], [project] at [revision] [missing]\"
+variable_commands:
+  revision: echo abc123
+  missing: \"false\"
+",
+        )
+        .unwrap();
+        let mut variables = BTreeMap::new();
+        variables.insert("project".to_string(), "Demo".to_string());
+        variables.insert("revision".to_string(), "overridden".to_string());
+        assert_eq!(
+            "Copyright 2024 Bob, Demo at abc123 [missing]",
+            cfg.static_template().set_variables(variables).render()
+        );
+    }
+
+    #[test]
+    fn test_run_with_timeout() {
+        let command = |line| serde_yaml::from_str::<VerifyCommand>(line).unwrap();
+        let second = Duration::from_secs(1);
+        assert_eq!(
+            "a b",
+            run_with_timeout(&command("echo  a b "), second).unwrap()
+        );
+        assert_eq!(
+            "a  b",
+            run_with_timeout(&command("\"echo 'a  b'\""), second).unwrap()
+        );
+        assert_eq!(
+            "a  b",
+            run_with_timeout(&command("[echo, a  b]"), second).unwrap()
+        );
+        assert!(run_with_timeout(&command("\"false\""), second).is_err());
+
+        let start = Instant::now();
+        let err = run_with_timeout(&command("sleep 5"), Duration::from_millis(100)).unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_template_provenance() {
+        let provenance = |origin| Provenance {
+            ident: "LicenseRef-provenance-test".to_string(),
+            origin,
+            url: spdx_url("LicenseRef-provenance-test"),
+            sha256: sha256::hex_digest(b"abc"),
+            fetched_at: Some("2024-06-01T00:00:00Z".to_string()),
+        };
+        record_provenance(provenance(Origin::Downloaded));
+        // The first template used for an ident is the one reported.
+        record_provenance(provenance(Origin::Cached));
+
+        let recorded = template_provenance();
+        let recorded: Vec<&Provenance> = recorded
+            .iter()
+            .filter(|p| p.ident == "LicenseRef-provenance-test")
+            .collect();
+        assert_eq!(vec![&provenance(Origin::Downloaded)], recorded);
+        assert_eq!(
+            "LicenseRef-provenance-test template from https://spdx.org/licenses/LicenseRef-provenance-test.json (downloaded, fetched 2024-06-01T00:00:00Z), sha256 ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            recorded[0].to_string()
+        );
+    }
+
+    #[test]
+    fn test_count_authors() {
+        let log: Vec<String> = vec![
+            "Alice <alice@example.com>",
+            "Bob <bob@example.com>",
+            "Bob <bob@example.com>",
+            "alice <alice@old.example.com>",
+            "Carol <carol@example.com>",
+        ]
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+
+        let mut aliases = HashMap::new();
+        aliases.insert(
+            "alice@old.example.com".to_string(),
+            "Alice <alice@example.com>".to_string(),
+        );
+
+        assert_eq!(
+            vec![
+                ("Alice".to_string(), Some("alice@example.com".to_string())),
+                ("Bob".to_string(), Some("bob@example.com".to_string())),
+                ("Carol".to_string(), Some("carol@example.com".to_string())),
+            ],
+            count_authors(&log, &aliases, 1)
+        );
+        assert_eq!(
+            vec![
+                ("Alice".to_string(), Some("alice@example.com".to_string())),
+                ("Bob".to_string(), Some("bob@example.com".to_string())),
+            ],
+            count_authors(&log, &aliases, 2)
+        );
+    }
 }
@@ -11,45 +11,205 @@
 // You should have received a copy of the GNU General Public License along with
 // this program. If not, see <https://www.gnu.org/licenses/>.
 //
+use std::collections::{BTreeMap, HashSet};
 use std::env;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io;
-use std::path::PathBuf;
-use std::process;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
 use regex::RegexSet;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
 
+pub use comment::{LineEnding, LinePatterns, Position, Spacing};
 pub use default::DEFAULT_CONFIG;
+pub use exclude::Rules as ExcludeRules;
+pub use license::{
+    refresh_cached_spdx_templates, set_refresh_spdx_cache, template_provenance, Provenance,
+    TemplateTest,
+};
+pub use verify::VerifyCommand;
 
 use crate::comments::Comment;
-use crate::config::comment::get_filetype;
 use crate::config::comment::Config as CommentConfig;
 use crate::config::license::Config as LicenseConfig;
+use crate::errors;
+use crate::exit;
+use crate::fail;
+use crate::import::first_line_after_header;
 use crate::template::Template;
+use crate::uncommentable::Config as UncommentableConfig;
+use crate::warning;
 
 mod comment;
 mod default;
+mod deprecation;
+mod detect;
+mod exclude;
 mod license;
+mod platform;
+mod transform;
+mod verify;
 
 fn default_off() -> bool {
     false
 }
 
-#[derive(Deserialize, Debug)]
+fn default_on() -> bool {
+    true
+}
+
+fn default_skip_if_contains() -> Vec<String> {
+    vec![
+        "DO NOT EDIT".to_string(),
+        "@generated".to_string(),
+        "Code generated by".to_string(),
+    ]
+}
+
+#[derive(Deserialize, Debug, Clone)]
 pub struct Config {
     #[serde(default = "default_off")]
     pub change_in_place: bool,
 
     pub excludes: RegexList,
+    #[serde(default)]
+    pub exclude_rules: ExcludeRules,
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
     pub licenses: LicenseConfigList,
     pub comments: CommentConfigList,
+    #[serde(default = "default_on")]
+    pub use_builtin_commenters: bool,
+    #[serde(default = "default_off")]
+    pub use_gitattributes: bool,
+    #[serde(default)]
+    pub uncommentable: Option<UncommentableConfig>,
+    // Markers of machine generated files, which are never changed.
+    #[serde(default = "default_skip_if_contains")]
+    pub skip_if_contains: Vec<String>,
+    #[serde(default)]
+    pub line_ending: LineEnding,
+    // Changed files end with exactly one newline.
+    #[serde(default = "default_on")]
+    pub ensure_trailing_newline: bool,
+    // The conditions which fail --check, the rest are only warned about.
+    #[serde(default = "default_check_fail_on")]
+    pub check_fail_on: Vec<CheckCondition>,
+}
+
+/// A class of problem found by --check.
+#[derive(Clone, Copy, Deserialize, Serialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckCondition {
+    /// Files have no license header.
+    Missing,
+    /// Files have a header which needs updating.
+    Outdated,
+    /// Files matched no license or comment config.
+    Unconfigured,
+    /// Files have the correct header text but it needs reformatting.
+    Format,
+    /// Directories contain files with different licenses.
+    MixedLicenses,
+    /// Files have the header of a license which isn't configured.
+    UnknownLicense,
+}
+
+impl CheckCondition {
+    /// Every condition, in the order of precedence of their exit codes.
+    pub const ALL: [CheckCondition; 6] = [
+        CheckCondition::Missing,
+        CheckCondition::Outdated,
+        CheckCondition::Unconfigured,
+        CheckCondition::Format,
+        CheckCondition::MixedLicenses,
+        CheckCondition::UnknownLicense,
+    ];
+
+    pub fn exit_code(self) -> i32 {
+        match self {
+            CheckCondition::Missing | CheckCondition::Outdated => exit::NEEDS_UPDATE,
+            CheckCondition::Unconfigured => exit::NOT_LICENSED,
+            CheckCondition::Format => exit::NEEDS_FORMAT_FIX,
+            CheckCondition::MixedLicenses => exit::MIXED_LICENSES,
+            CheckCondition::UnknownLicense => exit::UNKNOWN_LICENSE,
+        }
+    }
+
+    /// What count of this condition were found, e.g. "3 files missing a
+    /// license header".
+    pub fn describe(self, count: usize) -> String {
+        let (noun, description) = match self {
+            CheckCondition::Missing => ("file", "missing a license header"),
+            CheckCondition::Outdated => ("file", "with an outdated license header"),
+            CheckCondition::Unconfigured => ("file", "with no matching license or comment config"),
+            CheckCondition::Format => ("file", "whose header needs reformatting"),
+            CheckCondition::MixedLicenses => ("directory", "with mixed licenses"),
+            CheckCondition::UnknownLicense => ("file", "with an unknown license"),
+        };
+        let noun = match (count, noun) {
+            (1, noun) => noun.to_string(),
+            (_, "directory") => "directories".to_string(),
+            (_, noun) => format!("{}s", noun),
+        };
+        format!("{} {} {}", count, noun, description)
+    }
+}
+
+fn default_check_fail_on() -> Vec<CheckCondition> {
+    CheckCondition::ALL.to_vec()
+}
+
+/// The license which applies to a path according to the config.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ResolvedLicense {
+    pub ident: String,
+    /// Every author as "Name <email>", or just their name.
+    pub authors: Vec<String>,
+    /// The rendered template, without comment characters.
+    pub template: String,
 }
 
 impl Config {
     pub fn add_exclude(&mut self, pat: &str) {
         self.excludes.add_exclude(pat);
     }
+
+    /// The license licensure would apply to path, which doesn't need to
+    /// exist, or None if it's excluded or no license matches it.
+    pub fn license_for(&self, path: &str) -> Option<ResolvedLicense> {
+        if self.excludes.is_match(path) || self.exclude_rules.matching(path).is_some() {
+            return None;
+        }
+
+        let templ = self
+            .licenses
+            .get_template(path)?
+            .set_variables(self.variables.clone());
+        Some(ResolvedLicense {
+            ident: templ.ident().to_string(),
+            authors: templ.authors().identities(),
+            template: templ.render(),
+        })
+    }
+
+    /// The files which are excluded even though they match the files
+    /// pattern of a license, with the ident and pattern of that license.
+    /// Licenses matching any file are left out since they are expected
+    /// to match excluded files.
+    pub fn excluded_license_matches<'a>(&self, files: &'a [String]) -> Vec<(&'a str, &str, &str)> {
+        files
+            .iter()
+            .filter(|file| self.excludes.is_match(file))
+            .filter_map(|file| {
+                self.licenses
+                    .explicit_match(file)
+                    .map(|(ident, pattern)| (file.as_str(), ident, pattern))
+            })
+            .collect()
+    }
 }
 
 impl Default for Config {
@@ -58,72 +218,240 @@ impl Default for Config {
     }
 }
 
+/// An exclude pattern, either a regex which must match whole paths or
+/// one which can opt into matching any part of a path.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+enum ExcludePattern {
+    Anchored(String),
+    Options {
+        pattern: String,
+        #[serde(default)]
+        unanchored: bool,
+    },
+}
+
+impl ExcludePattern {
+    fn pattern(&self) -> &str {
+        match self {
+            ExcludePattern::Anchored(pattern) => pattern,
+            ExcludePattern::Options { pattern, .. } => pattern,
+        }
+    }
+
+    /// The regex matching what the pattern excludes.
+    fn regex(&self) -> String {
+        match self {
+            ExcludePattern::Options {
+                pattern,
+                unanchored: true,
+            } => pattern.clone(),
+            _ => format!("^(?:{})$", self.pattern()),
+        }
+    }
+}
+
 #[derive(Deserialize, Debug)]
-#[serde(from = "Vec<String>")]
+#[serde(from = "Vec<ExcludePattern>")]
 pub struct RegexList {
+    patterns: Vec<ExcludePattern>,
     regex: RegexSet,
+    // The patterns matching any part of a path, as all of them used to,
+    // to warn about paths which are no longer excluded.
+    unanchored: RegexSet,
+    // The patterns which have been warned about.
+    warned: Mutex<HashSet<usize>>,
 }
 
 impl RegexList {
+    /// Whether s, a path relative to the current directory, is matched
+    /// by any of the patterns.
     pub fn is_match(&self, s: &str) -> bool {
-        self.regex.is_match(s)
+        self.matching(s).is_some()
     }
 
-    pub fn add_exclude(&mut self, pat: &str) {
-        let mut old_pats = Vec::from(self.regex.patterns());
-        let mut new_pats = vec![pat.to_string()];
-        new_pats.append(&mut old_pats);
-        self.regex = match RegexSet::new(&new_pats) {
-            Ok(r) => r,
-            Err(e) => {
-                println!("Failed to compile exclude pattern: {}", e);
-                process::exit(1);
+    /// The first pattern matching s, a path relative to the current
+    /// directory.
+    pub fn matching(&self, s: &str) -> Option<&str> {
+        let s = s.strip_prefix("./").unwrap_or(s);
+        if let Some(idx) = self.regex.matches(s).iter().next() {
+            return Some(self.patterns[idx].pattern());
+        }
+
+        for idx in self.unanchored.matches(s).iter() {
+            if self
+                .warned
+                .lock()
+                .expect("warned lock was poisoned!")
+                .insert(idx)
+            {
+                warning!(
+                    "exclude pattern {} no longer excludes {} because excludes match whole paths, use {{pattern: '{}', unanchored: true}} to match part of a path",
+                    self.patterns[idx].pattern(),
+                    s,
+                    self.patterns[idx].pattern()
+                );
             }
-        };
+        }
+
+        None
+    }
+
+    pub fn add_exclude(&mut self, pat: &str) {
+        let mut patterns = vec![ExcludePattern::Anchored(pat.to_string())];
+        patterns.append(&mut self.patterns);
+        *self = RegexList::from(patterns);
     }
 }
 
-impl From<Vec<String>> for RegexList {
-    fn from(rgxs: Vec<String>) -> RegexList {
+// The patterns warned about aren't cloned, so a clone warns about them
+// again.
+impl Clone for RegexList {
+    fn clone(&self) -> RegexList {
+        RegexList::from(self.patterns.clone())
+    }
+}
+
+impl From<Vec<ExcludePattern>> for RegexList {
+    fn from(patterns: Vec<ExcludePattern>) -> RegexList {
+        let compile = |rgxs: Vec<String>| match RegexSet::new(rgxs) {
+            Ok(r) => r,
+            Err(e) => fail!(
+                errors::INVALID_PATTERN,
+                "Failed to compile exclude pattern: {}",
+                e
+            ),
+        };
+
         RegexList {
-            regex: match RegexSet::new(rgxs) {
-                Ok(r) => r,
-                Err(e) => {
-                    println!("Failed to compile exclude pattern: {}", e);
-                    process::exit(1);
-                }
-            },
+            regex: compile(patterns.iter().map(ExcludePattern::regex).collect()),
+            unanchored: compile(patterns.iter().map(|p| p.pattern().to_string()).collect()),
+            patterns,
+            warned: Mutex::new(HashSet::new()),
         }
     }
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 #[serde(from = "Vec<CommentConfig>")]
 pub struct CommentConfigList {
     cfgs: Vec<CommentConfig>,
+    // Checked after cfgs so configured commenters always take
+    // precedence.
+    builtins: Vec<CommentConfig>,
 }
 
 impl From<Vec<CommentConfig>> for CommentConfigList {
-    fn from(cfgs: Vec<CommentConfig>) -> CommentConfigList {
-        CommentConfigList { cfgs }
+    fn from(mut cfgs: Vec<CommentConfig>) -> CommentConfigList {
+        for cfg in &mut cfgs {
+            cfg.merge_preamble_presets();
+        }
+
+        CommentConfigList {
+            cfgs,
+            builtins: CommentConfig::builtins(),
+        }
     }
 }
 
 impl CommentConfigList {
-    pub fn get_commenter(&self, filename: &str) -> Box<dyn Comment> {
-        let file_type = get_filetype(filename);
+    fn get_config(&self, filename: &str) -> Option<&CommentConfig> {
+        let filetype = detect::detect_filetype(filename);
+        self.cfgs
+            .iter()
+            .chain(self.builtins.iter())
+            .find(|c| c.matches(filename, filetype))
+    }
 
-        for c in &self.cfgs {
-            if c.matches(file_type) {
-                return c.commenter();
-            }
+    /// Only use the configured commenters, files which none match get
+    /// the default commenter.
+    pub fn disable_builtins(&mut self) {
+        self.builtins.clear();
+    }
+
+    /// Which comment config applies to filename, by its index in the
+    /// comments section and its extensions, or the built in one for its
+    /// file type.
+    pub fn describe_match(&self, filename: &str) -> String {
+        match self.get_config(filename) {
+            Some(c) => match self.cfgs.iter().position(|cfg| std::ptr::eq(cfg, c)) {
+                Some(idx) => format!("comments[{}] ({})", idx, c.extensions()),
+                None => format!("built in for {}", detect::detect_filetype(filename)),
+            },
+            None => "default".to_string(),
+        }
+    }
+
+    pub fn get_commenter(&self, filename: &str) -> Box<dyn Comment> {
+        match self.get_config(filename) {
+            Some(c) => c.commenter(),
+            None => CommentConfig::default().commenter(),
         }
+    }
+
+    /// Like get_commenter but with the trailing_lines_after rule of the
+    /// config for the first line of content after any existing header, if
+    /// one applies.
+    pub fn get_commenter_for_content(
+        &self,
+        filename: &str,
+        content: &str,
+    ) -> Option<Box<dyn Comment>> {
+        let cfg = self.get_config(filename)?;
+        let line = first_line_after_header(content)?;
+        let lines = cfg.trailing_lines_after().for_line(line)?;
+        let mut cfg = cfg.clone();
+        cfg.set_blank_lines_after(lines);
+        Some(cfg.commenter())
+    }
+
+    /// Like get_commenter but wrapping at columns instead of the
+    /// configured width.
+    pub fn get_commenter_with_columns(&self, filename: &str, columns: usize) -> Box<dyn Comment> {
+        let mut cfg = self
+            .get_config(filename)
+            .cloned()
+            .unwrap_or_else(CommentConfig::default);
+        cfg.set_columns(columns);
+        cfg.commenter()
+    }
+
+    pub fn get_verify_command(&self, filename: &str) -> Option<&VerifyCommand> {
+        self.get_config(filename)
+            .and_then(CommentConfig::verify_command)
+    }
 
-        CommentConfig::default().commenter()
+    /// Whether the header for filename should be written to a separate
+    /// <filename>.license file.
+    pub fn is_sidecar(&self, filename: &str) -> bool {
+        self.get_config(filename)
+            .is_some_and(CommentConfig::is_sidecar)
+    }
+
+    /// Lines which must stay at the top of the file, the license header
+    /// is inserted after them.
+    pub fn get_magic_comments(&self, filename: &str) -> Option<&LinePatterns> {
+        self.get_config(filename).map(CommentConfig::magic_comments)
+    }
+
+    /// Leading constructs, which may span multiple lines, that the
+    /// license header is inserted after.
+    pub fn get_insert_after(&self, filename: &str) -> Option<&LinePatterns> {
+        self.get_config(filename).map(CommentConfig::insert_after)
+    }
+
+    pub fn get_spacing(&self, filename: &str) -> Spacing {
+        self.get_config(filename)
+            .map(CommentConfig::spacing)
+            .unwrap_or_default()
+    }
+
+    pub fn get_placement(&self, filename: &str) -> Option<Position> {
+        self.get_config(filename).and_then(CommentConfig::placement)
     }
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 #[serde(from = "Vec<LicenseConfig>")]
 pub struct LicenseConfigList {
     cfgs: Vec<LicenseConfig>,
@@ -133,12 +461,133 @@ impl LicenseConfigList {
     pub fn get_template(&self, filename: &str) -> Option<Template> {
         for cfg in &self.cfgs {
             if cfg.file_is_match(filename) {
-                return Some(cfg.get_template(filename));
+                return Some(self.template_of(cfg, filename));
             }
         }
 
         None
     }
+
+    /// The file the authors of newly licensed files should be added to,
+    /// if the license matching filename has one.
+    pub fn get_contributors_file(&self, filename: &str) -> Option<&str> {
+        self.cfgs
+            .iter()
+            .find(|cfg| cfg.file_is_match(filename))
+            .and_then(LicenseConfig::contributors_file)
+    }
+
+    /// The placement of the license matching filename, if it sets one.
+    pub fn get_placement(&self, filename: &str) -> Option<Position> {
+        self.cfgs
+            .iter()
+            .find(|cfg| cfg.file_is_match(filename))
+            .and_then(LicenseConfig::placement)
+    }
+
+    /// The formatter command of the license matching filename, if it
+    /// sets one.
+    pub fn get_verify_command(&self, filename: &str) -> Option<&VerifyCommand> {
+        self.cfgs
+            .iter()
+            .find(|cfg| cfg.file_is_match(filename))
+            .and_then(LicenseConfig::verify_command)
+    }
+
+    /// The ident and files pattern of the license matching filename,
+    /// unless it's one matching any file.
+    pub fn explicit_match(&self, filename: &str) -> Option<(&str, &str)> {
+        self.cfgs
+            .iter()
+            .find(|cfg| cfg.file_is_match(filename))
+            .filter(|cfg| cfg.files() != "any")
+            .map(|cfg| (cfg.ident(), cfg.files()))
+    }
+
+    /// The index and ident of the license entry matching filename.
+    pub fn matching_entry(&self, filename: &str) -> Option<(usize, &str)> {
+        self.cfgs
+            .iter()
+            .position(|cfg| cfg.file_is_match(filename))
+            .map(|idx| (idx, self.cfgs[idx].ident()))
+    }
+
+    /// The ident of the license matching filename.
+    pub fn matching_ident(&self, filename: &str) -> Option<&str> {
+        self.cfgs
+            .iter()
+            .find(|cfg| cfg.file_is_match(filename))
+            .map(|cfg| cfg.ident())
+    }
+
+    /// Every configured ident once, in config order.
+    pub fn idents(&self) -> Vec<&str> {
+        let mut idents: Vec<&str> = Vec::new();
+        for cfg in &self.cfgs {
+            if !idents.contains(&cfg.ident()) {
+                idents.push(cfg.ident());
+            }
+        }
+
+        idents
+    }
+
+    pub fn has_ident(&self, ident: &str) -> bool {
+        self.cfgs.iter().any(|cfg| cfg.ident() == ident)
+    }
+
+    /// Every test of every license entry, with the ident of the entry and
+    /// its template for the file of the test.
+    pub fn template_tests(&self) -> Vec<(&str, &TemplateTest, Template)> {
+        self.cfgs
+            .iter()
+            .flat_map(|cfg| {
+                cfg.tests()
+                    .iter()
+                    .map(move |test| (cfg.ident(), test, cfg.get_template(&test.file)))
+            })
+            .collect()
+    }
+
+    /// The template of the first license with ident, regardless of its
+    /// files pattern.
+    pub fn get_template_by_ident(&self, ident: &str, filename: &str) -> Option<Template> {
+        self.cfgs
+            .iter()
+            .find(|cfg| cfg.ident() == ident)
+            .map(|cfg| self.template_of(cfg, filename))
+    }
+
+    /// The template of cfg for filename, with the templates it replaces.
+    fn template_of(&self, cfg: &LicenseConfig, filename: &str) -> Template {
+        let templ = cfg.get_template(filename);
+        let replaces = cfg.replaced_templates(&templ, |ident| {
+            self.cfgs
+                .iter()
+                .find(|other| other.ident() == ident && other.ident() != cfg.ident())
+                .map(|other| other.get_template(filename))
+        });
+        templ.set_replaces(replaces)
+    }
+
+    /// The ident, files pattern, and header fingerprint of every
+    /// configured license in the order they are matched.
+    pub fn fingerprints(&self, variables: &BTreeMap<String, String>) -> Vec<(&str, &str, String)> {
+        self.cfgs
+            .iter()
+            .map(|cfg| (cfg.ident(), cfg.files(), cfg.fingerprint(variables)))
+            .collect()
+    }
+
+    /// The files pattern and the template, with its configured years and
+    /// authors, of every configured license in the order they are
+    /// matched.
+    pub fn static_templates(&self) -> Vec<(&str, Template)> {
+        self.cfgs
+            .iter()
+            .map(|cfg| (cfg.files(), cfg.static_template()))
+            .collect()
+    }
 }
 
 impl From<Vec<LicenseConfig>> for LicenseConfigList {
@@ -147,13 +596,13 @@ impl From<Vec<LicenseConfig>> for LicenseConfigList {
     }
 }
 
-pub fn xdg_config_dir() -> Option<PathBuf> {
-    match env::var("XDG_CONFIG_HOME") {
+pub fn xdg_cache_dir() -> Option<PathBuf> {
+    match env::var("XDG_CACHE_HOME") {
         Ok(d) => Some(PathBuf::from(d)),
         Err(_) => match env::var("HOME") {
             Ok(home) => {
                 let mut home_dir = PathBuf::from(home);
-                home_dir.push(".config");
+                home_dir.push(".cache");
                 Some(home_dir)
             }
             Err(_) => None,
@@ -161,53 +610,292 @@ pub fn xdg_config_dir() -> Option<PathBuf> {
     }
 }
 
-/// Walk up from the current working directory searching for
-/// the first .licensure.yml config file available else find the
-/// global config file.
-fn find_config_file() -> Option<PathBuf> {
-    if let Ok(mut cwd) = env::current_dir() {
-        loop {
-            cwd.push(".licensure.yml");
-            if cwd.exists() {
-                return Some(cwd);
-            }
+/// All the locations a config file is searched for in order of
+/// precedence. Starting at the current working directory every parent
+/// directory is checked for a .licensure.yml file, followed by the
+/// global config file locations for this platform.
+pub fn config_search_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
 
-            // Pop the .licensure.yml file we added
-            cwd.pop();
+    if let Ok(cwd) = env::current_dir() {
+        paths.extend(cwd.ancestors().map(|dir| dir.join(".licensure.yml")));
+    }
 
-            // Move up a directory checking if we have hit root yet
-            if !cwd.pop() {
-                break;
-            }
-        }
+    paths.extend(platform::global_config_files());
+    paths
+}
+
+/// Find the first config file which exists, see config_search_paths.
+/// A global config location is also used if it only has fragments.
+pub fn find_config_file() -> Option<PathBuf> {
+    config_search_paths()
+        .into_iter()
+        .find(|p| p.exists() || !config_fragments(p).is_empty())
+}
+
+/// The directory of the global config file may contain a config.d
+/// directory of YAML fragments which are merged on top of it in lexical
+/// order. Returns the fragments for path, which is empty if path is not
+/// a global config file.
+pub fn config_fragments(path: &Path) -> Vec<PathBuf> {
+    if !platform::global_config_files().iter().any(|p| p == path) {
+        return Vec::new();
     }
 
-    if let Some(mut global) = xdg_config_dir() {
-        global.push(".licensure");
-        global.push("config.yml");
-        if global.exists() {
-            return Some(global);
+    let dir = match path.parent() {
+        Some(dir) => dir.join("config.d"),
+        None => return Vec::new(),
+    };
+
+    let mut fragments: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .map(|e| e.path())
+            .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "yml"))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    fragments.sort();
+    fragments
+}
+
+fn read_config_value(path: &Path) -> Result<Value, io::Error> {
+    let f = File::open(path)?;
+    let raw: Value = match serde_yaml::from_reader(f) {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(io::Error::other(format!(
+                "Invalid YAML in {}: {}",
+                path.display(),
+                e
+            )))
         }
+    };
+
+    for warning in deprecation::find_deprecated_keys(&raw) {
+        warning!("{}: {}", path.display(), warning);
     }
 
-    None
+    Ok(raw)
+}
+
+/// Merge other into base. Mappings are merged key by key, lists are
+/// appended, and any other value in other replaces the one in base.
+fn merge_values(base: &mut Value, other: Value) {
+    match (base, other) {
+        (Value::Mapping(base), Value::Mapping(other)) => {
+            for (key, value) in other {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Sequence(base), Value::Sequence(other)) => base.extend(other),
+        (base, other) => *base = other,
+    }
 }
 
 pub fn load_config() -> Result<Config, io::Error> {
-    match find_config_file() {
-        Some(path) => {
-            let f = File::open(path.clone())?;
-            match serde_yaml::from_reader(f) {
-                Ok(c) => Ok(c),
-                Err(e) => Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("Invalid YAML in {}: {}", path.display(), e),
-                )),
+    let path = match find_config_file() {
+        Some(path) => path,
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "Config file not found",
+            ))
+        }
+    };
+
+    let mut raw = if path.exists() {
+        read_config_value(&path)?
+    } else {
+        Value::Mapping(Mapping::new())
+    };
+
+    for fragment in config_fragments(&path) {
+        merge_values(&mut raw, read_config_value(&fragment)?);
+    }
+
+    match serde_yaml::from_value::<Config>(raw) {
+        Ok(mut c) => {
+            if !c.use_builtin_commenters {
+                c.comments.disable_builtins();
             }
+
+            Ok(c)
+        }
+        Err(e) => Err(io::Error::other(format!(
+            "Invalid YAML in {}: {}",
+            path.display(),
+            e
+        ))),
+    }
+}
+
+/// Rewrite any deprecated keys in the config file that would be loaded
+/// to their replacements. Returns the path of the config file and the
+/// number of keys which were rewritten.
+pub fn fix_config() -> Result<(PathBuf, usize), io::Error> {
+    let path = match find_config_file() {
+        Some(path) => path,
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "Config file not found",
+            ))
+        }
+    };
+
+    let mut files = config_fragments(&path);
+    if path.exists() {
+        files.insert(0, path.clone());
+    }
+
+    let mut total = 0;
+    for file in files {
+        let content = fs::read_to_string(&file)?;
+        let (fixed, count) = deprecation::fix_deprecated_keys(&content);
+        if count > 0 {
+            fs::write(&file, fixed)?;
+            total += count;
         }
-        None => Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            "Config file not found",
-        )),
+    }
+
+    Ok((path, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_values() {
+        let mut base: Value = serde_yaml::from_str(
+            "change_in_place: false\nexcludes:\n  - a\nvariables:\n  project: A\n",
+        )
+        .unwrap();
+        let other: Value = serde_yaml::from_str(
+            "change_in_place: true\nexcludes:\n  - b\nvariables:\n  homepage: B\n",
+        )
+        .unwrap();
+        merge_values(&mut base, other);
+
+        let expected: Value = serde_yaml::from_str(
+            "change_in_place: true\nexcludes:\n  - a\n  - b\nvariables:\n  project: A\n  homepage: B\n",
+        )
+        .unwrap();
+        assert_eq!(expected, base);
+    }
+
+    #[test]
+    fn test_builtin_commenters() {
+        let mut comments = CommentConfigList::from(Vec::new());
+        assert_eq!(
+            "// text\n",
+            comments.get_commenter("src/main.rs").comment("text")
+        );
+        assert_eq!(
+            "-- text\n",
+            comments.get_commenter("schema.sql").comment("text")
+        );
+        assert_eq!(
+            "# text\n",
+            comments.get_commenter("Makefile").comment("text")
+        );
+        assert_eq!(
+            "# text\n\n",
+            comments.get_commenter("pkg/BUILD").comment("text")
+        );
+        assert_eq!(
+            Some(28),
+            comments
+                .get_insert_after("defs.bzl")
+                .and_then(|p| p.match_at_start("load(\"//a:b.bzl\",\n    \"c\",\n) # c\n\nd()\n"))
+        );
+
+        comments.disable_builtins();
+        assert_eq!(
+            "# text\n",
+            comments.get_commenter("src/main.rs").comment("text")
+        );
+    }
+    #[test]
+    fn test_license_for() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+excludes: ['.*\.json']
+variables:
+  project: Demo
+licenses:
+  - files: '\.png$'
+    ident: CC-BY-4.0
+    authors: [{name: Bob, email: bob@example.com}]
+    end_year: "2024"
+    template: "[year] [name of author], part of [project]"
+comments: []
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Some(ResolvedLicense {
+                ident: "CC-BY-4.0".to_string(),
+                authors: vec!["Bob <bob@example.com>".to_string()],
+                template: "2024 Bob <bob@example.com>, part of Demo".to_string(),
+            }),
+            config.license_for("assets/logo.png")
+        );
+        assert_eq!(None, config.license_for("data.json"));
+        assert_eq!(None, config.license_for("main.rs"));
+    }
+
+    #[test]
+    fn test_excludes_match_whole_paths() {
+        let excludes: RegexList = serde_yaml::from_str(
+            r#"
+- test
+- docs/.*
+- pattern: \.snap$
+  unanchored: true
+"#,
+        )
+        .unwrap();
+        assert!(excludes.is_match("test"));
+        assert!(excludes.is_match("./test"));
+        assert!(!excludes.is_match("src/latest.rs"));
+        assert!(excludes.is_match("docs/index.rs"));
+        assert!(!excludes.is_match("src/docs/index.rs"));
+        assert!(excludes.is_match("src/snapshots/main.snap"));
+        assert!(!excludes.is_match("src/main.snap.rs"));
+    }
+
+    #[test]
+    fn test_excluded_license_matches() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+excludes: ['.*\.json$', 'README.*']
+licenses:
+  - files: 'assets/.*'
+    ident: CC-BY-4.0
+    authors: [{name: Bob}]
+    template: "[year]"
+  - files: any
+    ident: MIT
+    authors: [{name: Bob}]
+    template: "[year]"
+comments: []
+"#,
+        )
+        .unwrap();
+        let files: Vec<String> = ["assets/a.json", "assets/b.png", "README.md", "c.json"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        assert_eq!(
+            vec![("assets/a.json", "CC-BY-4.0", "assets/.*")],
+            config.excluded_license_matches(&files)
+        );
     }
 }
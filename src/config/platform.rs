@@ -0,0 +1,109 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use std::env;
+use std::path::PathBuf;
+
+/// The locations of the global config file in the order they are
+/// searched on the current platform.
+pub fn global_config_files() -> Vec<PathBuf> {
+    global_config_files_for(env::consts::OS, |var| env::var(var).ok())
+}
+
+fn global_config_files_for<F>(os: &str, var: F) -> Vec<PathBuf>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut files = Vec::new();
+
+    if os == "windows" {
+        if let Some(appdata) = var("APPDATA") {
+            files.push(PathBuf::from(appdata).join("licensure").join("config.yml"));
+        }
+    }
+
+    let xdg_config_dir = match var("XDG_CONFIG_HOME") {
+        Some(d) => Some(PathBuf::from(d)),
+        None => var("HOME").map(|home| PathBuf::from(home).join(".config")),
+    };
+    if let Some(dir) = xdg_config_dir {
+        files.push(dir.join(".licensure").join("config.yml"));
+    }
+
+    if os == "macos" {
+        if let Some(home) = var("HOME") {
+            files.push(
+                PathBuf::from(home)
+                    .join("Library")
+                    .join("Application Support")
+                    .join("licensure")
+                    .join("config.yml"),
+            );
+        }
+    }
+
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        move |name| {
+            pairs
+                .iter()
+                .find(|(k, _)| *k == name)
+                .map(|(_, v)| v.to_string())
+        }
+    }
+
+    #[test]
+    fn test_global_config_files_linux() {
+        assert_eq!(
+            vec![PathBuf::from("/home/me/.config/.licensure/config.yml")],
+            global_config_files_for("linux", vars(&[("HOME", "/home/me")]))
+        );
+        assert_eq!(
+            vec![PathBuf::from("/xdg/.licensure/config.yml")],
+            global_config_files_for(
+                "linux",
+                vars(&[("HOME", "/home/me"), ("XDG_CONFIG_HOME", "/xdg")])
+            )
+        );
+    }
+
+    #[test]
+    fn test_global_config_files_macos() {
+        assert_eq!(
+            vec![
+                PathBuf::from("/Users/me/.config/.licensure/config.yml"),
+                PathBuf::from("/Users/me/Library/Application Support/licensure/config.yml"),
+            ],
+            global_config_files_for("macos", vars(&[("HOME", "/Users/me")]))
+        );
+    }
+
+    #[test]
+    fn test_global_config_files_windows() {
+        assert_eq!(
+            vec![PathBuf::from("C:\\Users\\me\\AppData\\Roaming")
+                .join("licensure")
+                .join("config.yml")],
+            global_config_files_for(
+                "windows",
+                vars(&[("APPDATA", "C:\\Users\\me\\AppData\\Roaming")])
+            )
+        );
+    }
+}
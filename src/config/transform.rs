@@ -0,0 +1,113 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::warning;
+
+/// A change applied to the text of a license template before it is
+/// rendered, mostly useful for tweaking templates fetched with
+/// auto_template.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Transform {
+    /// Replace every occurrence of from with to.
+    Replace { from: String, to: String },
+    /// Remove the nth (starting at 1) paragraph, paragraphs are separated
+    /// by blank lines.
+    StripParagraph(usize),
+}
+
+impl Transform {
+    pub fn apply(&self, template: &str) -> String {
+        match self {
+            Transform::Replace { from, to } => template.replace(from, to),
+            Transform::StripParagraph(n) => strip_paragraph(template, *n),
+        }
+    }
+}
+
+pub fn apply_all(transforms: &[Transform], template: &str) -> String {
+    transforms
+        .iter()
+        .fold(template.to_string(), |templ, t| t.apply(&templ))
+}
+
+fn strip_paragraph(template: &str, n: usize) -> String {
+    let paragraph_re = Regex::new(r"\n[ \t]*\n\s*").expect("paragraph regex didn't compile!");
+    let trimmed = template.trim();
+    let mut paragraphs: Vec<&str> = paragraph_re.split(trimmed).collect();
+    if n == 0 || n > paragraphs.len() {
+        warning!(
+            "strip_paragraph: {} is out of range, the template has {} paragraphs",
+            n,
+            paragraphs.len()
+        );
+        return template.to_string();
+    }
+
+    paragraphs.remove(n - 1);
+    let mut out = paragraphs.join("\n\n");
+    if template.ends_with('\n') {
+        out.push('\n');
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_transforms() {
+        let transforms: Vec<Transform> = serde_yaml::from_str(
+            "- replace:\n    from: the authors\n    to: Acme Inc.\n- strip_paragraph: 3\n",
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                Transform::Replace {
+                    from: "the authors".to_string(),
+                    to: "Acme Inc.".to_string()
+                },
+                Transform::StripParagraph(3),
+            ],
+            transforms
+        );
+    }
+
+    #[test]
+    fn test_apply_all() {
+        let transforms = vec![
+            Transform::Replace {
+                from: "the authors".to_string(),
+                to: "Acme Inc.".to_string(),
+            },
+            Transform::StripParagraph(2),
+        ];
+        assert_eq!(
+            "Copyright Acme Inc.\n\nThird.\n",
+            apply_all(
+                &transforms,
+                "Copyright the authors\n\nSecond\nparagraph.\n  \nThird.\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_strip_paragraph_out_of_range() {
+        assert_eq!("One.\n", strip_paragraph("One.\n", 2));
+    }
+}
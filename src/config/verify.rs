@@ -0,0 +1,182 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use std::fmt;
+
+use serde::Deserialize;
+
+/// A command run by licensure, like verify_with or variable_commands,
+/// given either as a list of arguments or as a command line which is
+/// split into arguments the way a shell would, without running a shell.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(try_from = "CommandLine")]
+pub struct VerifyCommand(Vec<String>);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CommandLine {
+    Line(String),
+    Args(Vec<String>),
+}
+
+impl TryFrom<CommandLine> for VerifyCommand {
+    type Error = String;
+
+    fn try_from(command: CommandLine) -> Result<VerifyCommand, String> {
+        let args = match command {
+            CommandLine::Line(line) => split(&line)?,
+            CommandLine::Args(args) => args,
+        };
+        if args.is_empty() {
+            return Err("the command is empty".to_string());
+        }
+
+        Ok(VerifyCommand(args))
+    }
+}
+
+impl VerifyCommand {
+    /// The program and its arguments for file, with every `{file}`
+    /// replaced by its path.
+    pub fn args(&self, file: &str) -> (String, Vec<String>) {
+        let mut args = self.0.iter().map(|arg| arg.replace("{file}", file));
+        let program = args.next().expect("verify command is empty!");
+        (program, args.collect())
+    }
+
+    /// The program and its arguments as configured.
+    pub fn program(&self) -> (&str, &[String]) {
+        let (program, args) = self.0.split_first().expect("command is empty!");
+        (program, args)
+    }
+}
+
+impl fmt::Display for VerifyCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let quoted: Vec<String> = self.0.iter().map(|arg| quote(arg)).collect();
+        write!(f, "{}", quoted.join(" "))
+    }
+}
+
+/// Split line into arguments like a POSIX shell, supporting single and
+/// double quotes and backslash escapes but no expansions.
+fn split(line: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut arg: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                args.extend(arg.take());
+                continue;
+            }
+            '\'' => {
+                let current = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => current.push(c),
+                        None => return Err(format!("unterminated quote in `{}`", line)),
+                    }
+                }
+            }
+            '"' => {
+                let current = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        // In double quotes a backslash only escapes the
+                        // characters which are special there.
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => current.push(c),
+                            Some('\n') => (),
+                            Some(c) => {
+                                current.push('\\');
+                                current.push(c);
+                            }
+                            None => return Err(format!("unterminated quote in `{}`", line)),
+                        },
+                        Some(c) => current.push(c),
+                        None => return Err(format!("unterminated quote in `{}`", line)),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') => (),
+                Some(c) => arg.get_or_insert_with(String::new).push(c),
+                None => return Err(format!("trailing backslash in `{}`", line)),
+            },
+            c => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    args.extend(arg);
+    Ok(args)
+}
+
+// Quote arg for display if a shell would split or expand it.
+fn quote(arg: &str) -> String {
+    let plain = |c: char| c.is_alphanumeric() || "-_./=:,+@%{}".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        return arg.to_string();
+    }
+
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        assert_eq!(
+            vec!["rustfmt", "--check", "{file}"],
+            split("rustfmt  --check\t{file}").unwrap()
+        );
+        assert_eq!(
+            vec!["sh", "-c", "test -f \"$1\"", "sh", "{file}"],
+            split(r#"sh -c 'test -f "$1"' sh {file}"#).unwrap()
+        );
+        assert_eq!(
+            vec!["a b", "c\"d", "e\\f", "", "g h"],
+            split(r#""a b" "c\"d" "e\f" '' g\ h"#).unwrap()
+        );
+        assert!(split("sh -c 'test").is_err());
+        assert!(split("sh -c \"test").is_err());
+        assert!(split("sh \\").is_err());
+    }
+
+    #[test]
+    fn test_deserialize_verify_command() {
+        let line: VerifyCommand = serde_yaml::from_str("\"black --check '{file}'\"").unwrap();
+        let args: VerifyCommand = serde_yaml::from_str("[black, --check, '{file}']").unwrap();
+        assert_eq!(line, args);
+        assert_eq!(
+            (
+                "black".to_string(),
+                vec!["--check".to_string(), "a b.py".to_string()]
+            ),
+            args.args("a b.py")
+        );
+        assert_eq!("black --check {file}", args.to_string());
+        assert!(serde_yaml::from_str::<VerifyCommand>("[]").is_err());
+        assert!(serde_yaml::from_str::<VerifyCommand>("\"black 'x\"").is_err());
+    }
+
+    #[test]
+    fn test_display_quotes_args() {
+        let cmd = VerifyCommand(vec!["sh".into(), "-c".into(), "it's $1".into()]);
+        assert_eq!(r"sh -c 'it'\''s $1'", cmd.to_string());
+    }
+}
@@ -0,0 +1,240 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use std::fmt;
+use std::process;
+
+use crate::exit;
+
+/// A class of failure which stops licensure, the code is printed with
+/// the failure so it can be looked up with licensure explain.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ErrorCode {
+    pub code: &'static str,
+    pub exit: i32,
+    pub summary: &'static str,
+    pub explanation: &'static str,
+}
+
+pub const CONFIG_NOT_FOUND: ErrorCode = ErrorCode {
+    code: "E001",
+    exit: exit::CONFIG_ERROR,
+    summary: "No config file found",
+    explanation: "licensure looks for .licensure.yml in the current directory and its \
+parents, then in the user config directory. Run licensure config path to see \
+every location searched, or licensure --generate-config to create a config in \
+the current directory.",
+};
+
+pub const INVALID_CONFIG: ErrorCode = ErrorCode {
+    code: "E002",
+    exit: exit::CONFIG_ERROR,
+    summary: "The config file could not be loaded",
+    explanation: "The config file is not valid YAML, has a key of the wrong type, or \
+could not be read. The message includes the position of the problem. Run \
+licensure --fix-config to rename deprecated keys and compare the config with \
+the output of licensure --generate-config.",
+};
+
+pub const INVALID_PATTERN: ErrorCode = ErrorCode {
+    code: "E003",
+    exit: exit::CONFIG_ERROR,
+    summary: "A pattern in the config is not a valid regex",
+    explanation: "An excludes pattern, a files pattern of a license or a line pattern \
+of a commenter failed to compile. Patterns are regexes rather than globs, so \
+*.rs must be written as .*\\.rs, and characters like ( and [ must be escaped \
+with a backslash.",
+};
+
+pub const MISSING_TEMPLATE: ErrorCode = ErrorCode {
+    code: "E004",
+    exit: exit::CONFIG_ERROR,
+    summary: "A license has no template",
+    explanation: "A license matched a file but has neither a template nor a template \
+for the file's class, and auto_template is not enabled. Add a template to the \
+license, or set auto_template: true to download the SPDX template for its \
+ident.",
+};
+
+pub const SPDX_FETCH_FAILED: ErrorCode = ErrorCode {
+    code: "E005",
+    exit: exit::CONFIG_ERROR,
+    summary: "An SPDX template could not be downloaded",
+    explanation: "auto_template is enabled for a license but its template could not be \
+downloaded from spdx.org and there is no cached copy. Check the ident is a \
+valid SPDX identifier and that spdx.org is reachable, or add a template to the \
+license so nothing needs to be downloaded.",
+};
+
+pub const UNKNOWN_IDENT: ErrorCode = ErrorCode {
+    code: "E006",
+    exit: exit::CONFIG_ERROR,
+    summary: "No license with the given ident is configured",
+    explanation: "A command was given a license ident which is not in the licenses of \
+the config. Idents are compared exactly, so check its case and version suffix \
+against the config.",
+};
+
+pub const TEMPLATE_TESTS_FAILED: ErrorCode = ErrorCode {
+    code: "E007",
+    exit: exit::CONFIG_ERROR,
+    summary: "Template tests failed",
+    explanation: "The header rendered for one of the tests of a license did not match \
+its expected header. Each failure is printed with the rendered header, update \
+the template or the expected header so they agree.",
+};
+
+pub const LISTING_FILES_FAILED: ErrorCode = ErrorCode {
+    code: "E008",
+    exit: exit::LISTING_ERROR,
+    summary: "The files to license could not be listed",
+    explanation: "git failed to list the project or changed files, the project \
+directory could not be walked, or file names could not be read from stdin. \
+Check that git is installed and the current directory is inside the \
+repository, that a --changed-since ref exists, or pass --no-git to walk the \
+directory instead.",
+};
+
+pub const INVALID_ARGUMENT: ErrorCode = ErrorCode {
+    code: "E009",
+    exit: exit::USAGE_ERROR,
+    summary: "A command line argument is invalid",
+    explanation: "An option was given a value of the wrong type, such as a --jobs, \
+--width or --interval which is not a number. See licensure --help for the \
+value each option expects.",
+};
+
+pub const IO_FAILED: ErrorCode = ErrorCode {
+    code: "E010",
+    exit: exit::IO_ERROR,
+    summary: "Reading or writing files failed",
+    explanation: "A file could not be read or written while licensing, reporting or \
+relicensing, usually because of permissions or a full disk. Files changed in \
+place are journaled, run licensure recover to finish or roll back an \
+interrupted run.",
+};
+
+pub const GENERATE_CONFIG_FAILED: ErrorCode = ErrorCode {
+    code: "E011",
+    exit: exit::IO_ERROR,
+    summary: "The default config could not be written",
+    explanation: "licensure --generate-config could not create .licensure.yml in the \
+current directory. Check that the directory is writable.",
+};
+
+pub const IMPORT_FAILED: ErrorCode = ErrorCode {
+    code: "E012",
+    exit: exit::USAGE_ERROR,
+    summary: "A template could not be imported",
+    explanation: "The file given to import-template could not be read or does not \
+start with a comment block. Pass a file whose first lines are the header to \
+import.",
+};
+
+pub const NO_LICENSE: ErrorCode = ErrorCode {
+    code: "E013",
+    exit: exit::NOT_LICENSED,
+    summary: "No license applies to the file",
+    explanation: "The file is excluded, or it matches the files pattern of no license. \
+Run licensure -v on the file to see which excludes and licenses it matches.",
+};
+
+pub const RENDER_FAILED: ErrorCode = ErrorCode {
+    code: "E014",
+    exit: exit::CONFIG_ERROR,
+    summary: "A header could not be rendered",
+    explanation: "No license matched the path or file type given to render, or the \
+--license ident is not configured. Pass --license to choose a license \
+explicitly.",
+};
+
+pub const CACHE_FAILED: ErrorCode = ErrorCode {
+    code: "E015",
+    exit: exit::IO_ERROR,
+    summary: "The SPDX template cache could not be refreshed",
+    explanation: "--refresh-spdx-cache could not read or write the cached SPDX \
+templates. Check the permissions of the cache directory, or remove it by hand.",
+};
+
+pub const CATALOG: &[ErrorCode] = &[
+    CONFIG_NOT_FOUND,
+    INVALID_CONFIG,
+    INVALID_PATTERN,
+    MISSING_TEMPLATE,
+    SPDX_FETCH_FAILED,
+    UNKNOWN_IDENT,
+    TEMPLATE_TESTS_FAILED,
+    LISTING_FILES_FAILED,
+    INVALID_ARGUMENT,
+    IO_FAILED,
+    GENERATE_CONFIG_FAILED,
+    IMPORT_FAILED,
+    NO_LICENSE,
+    RENDER_FAILED,
+    CACHE_FAILED,
+];
+
+/// Find the error with the given code, which may be given without its
+/// leading E or zero padding.
+pub fn lookup(code: &str) -> Option<&'static ErrorCode> {
+    let digits = code.trim().trim_start_matches(['E', 'e']);
+    let number = digits.parse::<u32>().ok()?;
+    CATALOG
+        .iter()
+        .find(|error| error.code[1..].parse::<u32>() == Ok(number))
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}: {}", self.code, self.summary)?;
+        writeln!(f)?;
+        write!(f, "{}", self.explanation)
+    }
+}
+
+/// Print message with the code of error and exit with its exit code.
+pub fn fail(error: &ErrorCode, message: fmt::Arguments) -> ! {
+    eprintln!("error[{}]: {}", error.code, message);
+    eprintln!("Run licensure explain {} for details", error.code);
+    process::exit(error.exit)
+}
+
+/// Print a failure with its error code and exit.
+#[macro_export]
+macro_rules! fail {
+    ($error:expr, $($arg:tt)*) => {
+        $crate::errors::fail(&$error, format_args!($($arg)*))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_codes_are_unique() {
+        let codes: HashSet<&str> = CATALOG.iter().map(|e| e.code).collect();
+        assert_eq!(codes.len(), CATALOG.len());
+    }
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(lookup("E004"), Some(&MISSING_TEMPLATE));
+        assert_eq!(lookup("e4"), Some(&MISSING_TEMPLATE));
+        assert_eq!(lookup("13"), Some(&NO_LICENSE));
+        assert_eq!(lookup("E999"), None);
+        assert_eq!(lookup("nope"), None);
+    }
+}
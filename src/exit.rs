@@ -0,0 +1,40 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+
+// Exit codes used by licensure. These are part of the command line
+// interface so scripts can tell failure classes apart, don't change the
+// value of an existing code.
+
+/// Files need a license header added or updated. No error exits with
+/// this code, so it always means the files need changing.
+pub const NEEDS_UPDATE: i32 = 1;
+/// The config file is missing or invalid.
+pub const CONFIG_ERROR: i32 = 2;
+/// Files matched no license or comment config.
+pub const NOT_LICENSED: i32 = 3;
+/// Files have the correct header text but it needs reformatting.
+pub const NEEDS_FORMAT_FIX: i32 = 4;
+/// Reading or writing files failed.
+pub const IO_ERROR: i32 = 5;
+/// Directories contain files with different licenses, only checked with
+/// --enforce-directory-consistency.
+pub const MIXED_LICENSES: i32 = 6;
+/// Files have a license header which doesn't match any configured
+/// license, such as one of third party code.
+pub const UNKNOWN_LICENSE: i32 = 7;
+/// The command line is invalid, such as an unknown option or an option
+/// value of the wrong type, or a file given to it can't be used.
+pub const USAGE_ERROR: i32 = 8;
+/// The files to license could not be listed, e.g. because git failed.
+pub const LISTING_ERROR: i32 = 9;
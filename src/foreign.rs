@@ -0,0 +1,150 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::import::extract_leading_comment;
+
+// Phrases from the headers of common licenses, as lowercase words. The
+// LGPL and AGPL come before the GPL since their headers mention it too.
+const KNOWN_HEADERS: &[(&str, &str)] = &[
+    ("Apache-2.0", "licensed under the apache license version 2 0"),
+    (
+        "MIT",
+        "permission is hereby granted free of charge to any person obtaining a copy",
+    ),
+    (
+        "BSD",
+        "redistribution and use in source and binary forms with or without modification are permitted",
+    ),
+    ("MPL-2.0", "mozilla public license v 2 0"),
+    ("AGPL", "gnu affero general public license"),
+    ("LGPL", "gnu lesser general public license"),
+    ("LGPL", "gnu library general public license"),
+    ("GPL", "gnu general public license"),
+];
+
+static SPDX_RE: OnceLock<Regex> = OnceLock::new();
+
+/// The license of the header at the top of content, an SPDX tag's
+/// ident or the name of a common license whose boilerplate it has.
+/// Callers are expected to have ruled out the configured headers.
+pub fn detect(content: &str) -> Option<String> {
+    if let Some(ident) = spdx_ident(content) {
+        return Some(ident);
+    }
+
+    let comment = extract_leading_comment(content)?;
+    let words = format!(" {} ", words(&comment).collect::<Vec<_>>().join(" "));
+    KNOWN_HEADERS
+        .iter()
+        .find(|(_, phrase)| words.contains(&format!(" {} ", phrase)))
+        .map(|(ident, _)| ident.to_string())
+}
+
+/// The ident of the SPDX tag in the header at the top of content.
+pub fn spdx_ident(content: &str) -> Option<String> {
+    let comment = extract_leading_comment(content)?;
+    let spdx_re = SPDX_RE.get_or_init(|| {
+        Regex::new(r"SPDX-License-Identifier:\s*(?P<ident>\S+)")
+            .expect("SPDX regex didn't compile!")
+    });
+    spdx_re
+        .captures(&comment)
+        .map(|caps| caps["ident"].to_string())
+}
+
+/// Whether content contains text when only their words are compared, so
+/// a header is found regardless of its comment characters, punctuation,
+/// wrapping, and years.
+pub fn contains_text(content: &str, text: &str) -> bool {
+    let without_years = |text| {
+        words(text)
+            .filter(|word| !is_year(word))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let text = without_years(text);
+    !text.is_empty() && format!(" {} ", without_years(content)).contains(&format!(" {} ", text))
+}
+
+// The lowercase words of text, so phrases match regardless of comment
+// characters, punctuation, and wrapping.
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+fn is_year(word: &str) -> bool {
+    word.len() == 4 && word.bytes().all(|b| b.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            Some("Apache-2.0".to_string()),
+            detect("// Copyright 2020 Acme\n//\n// Licensed under the Apache License,\n// Version 2.0 (the \"License\");\npackage main\n")
+        );
+        assert_eq!(
+            Some("MIT".to_string()),
+            detect("#!/usr/bin/env python\n# Permission is hereby granted, free of charge, to any\n# person obtaining a copy\nimport os\n")
+        );
+        assert_eq!(
+            Some("LGPL".to_string()),
+            detect("/*\n * under the terms of the GNU Lesser General Public License\n * as published by the GNU General Public License\n */\n")
+        );
+        assert_eq!(
+            Some("BSD-3-Clause".to_string()),
+            detect("// SPDX-License-Identifier: BSD-3-Clause\nfn main() {}\n")
+        );
+        assert_eq!(None, detect("// Helpers for parsing.\nfn main() {}\n"));
+        assert_eq!(
+            None,
+            spdx_ident("// Licensed under the Apache License, Version 2.0\npackage main\n")
+        );
+        assert_eq!(
+            None,
+            detect("fn main() {}\n// Licensed under the Apache License, Version 2.0\n")
+        );
+    }
+
+    #[test]
+    fn test_contains_text() {
+        let header = "Copyright 2024 Jane Doe. Licensed under the\nMIT license, see LICENSE.";
+        assert!(contains_text(
+            "/*\n * Copyright 2019, 2023 Jane Doe.\n * Licensed under the MIT license,\n * see LICENSE.\n */\nint x;\n",
+            header
+        ));
+        assert!(contains_text(
+            "# copyright 2024 jane doe. licensed under the mit\n# license, see license.\n",
+            header
+        ));
+        assert!(!contains_text(
+            "// Copyright 2024 Jane Doe. Licensed under the\n// Apache license, see LICENSE.\n",
+            header
+        ));
+        assert!(!contains_text(
+            "// Copyright 2024 Jane Doest\n",
+            "Copyright 2024 Jane Doe"
+        ));
+        assert!(!contains_text("fn main() {}\n", "2024"));
+    }
+}
@@ -0,0 +1,392 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use std::ops::Range;
+
+use regex::Regex;
+
+// Line comment characters we know how to strip, longest first so that
+// ";;;" isn't detected as ";".
+const LINE_COMMENT_CHARS: &[&str] = &[";;;", "//", "--", ";;", "#", ";", "%"];
+
+// Start and end markers of block comments we know how to strip.
+const BLOCK_COMMENT_CHARS: &[(&str, &str)] =
+    &[("/*", "*/"), ("<!--", "-->"), ("(*", "*)"), ("{-", "-}")];
+
+/// A license template inferred from the header of an existing file.
+#[derive(Debug, PartialEq)]
+pub struct ImportedTemplate {
+    pub template: String,
+    pub ident: Option<String>,
+    pub authors: Vec<(String, Option<String>)>,
+}
+
+impl ImportedTemplate {
+    /// Render as a license config entry suitable for the licenses
+    /// section of .licensure.yml.
+    pub fn to_license_config(&self, default_ident: &str) -> String {
+        let mut out = String::from("  - files: any\n");
+        out.push_str(&format!(
+            "    ident: {}\n",
+            yaml_scalar(self.ident.as_deref().unwrap_or(default_ident))
+        ));
+
+        out.push_str("    authors:\n");
+        if self.authors.is_empty() {
+            out.push_str("      - name: Your Name Here\n");
+        }
+
+        for (name, email) in &self.authors {
+            out.push_str(&format!("      - name: {}\n", yaml_scalar(name)));
+            if let Some(email) = email {
+                out.push_str(&format!("        email: {}\n", yaml_scalar(email)));
+            }
+        }
+
+        // The imported template keeps the line breaks of the original
+        // header so it should not be unwrapped. The indentation is given
+        // since the first line may be indented.
+        out.push_str("    unwrap_text: false\n");
+        out.push_str("    template: |2\n");
+        for line in self.template.lines() {
+            if line.is_empty() {
+                out.push('\n');
+            } else {
+                out.push_str(&format!("      {}\n", line));
+            }
+        }
+
+        out
+    }
+}
+
+/// s as a YAML scalar, quoted unless it reads back as the same string,
+/// e.g. names with a colon or which look like a number.
+fn yaml_scalar(s: &str) -> String {
+    match serde_yaml::from_str::<serde_yaml::Value>(s) {
+        Ok(serde_yaml::Value::String(plain)) if plain == s => s.to_string(),
+        // JSON strings are valid double quoted YAML scalars.
+        _ => serde_json::to_string(s).expect("strings always serialize!"),
+    }
+}
+
+/// Extract the first comment block of content with the comment
+/// characters removed. A leading shebang is skipped.
+pub fn extract_leading_comment(content: &str) -> Option<String> {
+    let mut lines: Vec<&str> = content.lines().collect();
+    if lines.first().is_some_and(|l| l.starts_with("#!")) {
+        lines.remove(0);
+    }
+
+    while lines.first().is_some_and(|l| l.trim().is_empty()) {
+        lines.remove(0);
+    }
+
+    let first = lines.first()?.trim_start();
+
+    for (start, end) in BLOCK_COMMENT_CHARS {
+        if first.starts_with(start) {
+            return extract_block_comment(&lines, start, end);
+        }
+    }
+
+    let comment_char = LINE_COMMENT_CHARS.iter().find(|c| first.starts_with(*c))?;
+    let body: Vec<&str> = lines
+        .iter()
+        .map(|l| l.trim_start())
+        .take_while(|l| l.starts_with(comment_char))
+        .map(|l| strip_marker(l, comment_char))
+        .collect();
+
+    Some(body.join("\n").trim_end().to_string())
+}
+
+/// The byte range of the first comment block of content, including the
+/// newline ending its last line. A leading shebang and blank lines are
+/// skipped the same as extract_leading_comment.
+pub fn leading_comment_range(content: &str) -> Option<Range<usize>> {
+    let mut lines = content.split_inclusive('\n').peekable();
+    let mut start = 0;
+    if lines.peek().is_some_and(|l| l.starts_with("#!")) {
+        start += lines.next().map_or(0, str::len);
+    }
+
+    while lines.peek().is_some_and(|l| l.trim().is_empty()) {
+        start += lines.next().map_or(0, str::len);
+    }
+
+    let first = lines.peek()?.trim_start();
+    let mut end = start;
+
+    if let Some((open, close)) = BLOCK_COMMENT_CHARS
+        .iter()
+        .find(|(s, _)| first.starts_with(s))
+    {
+        for (idx, line) in lines.enumerate() {
+            end += line.len();
+            let searched = if idx == 0 {
+                &line.trim_start()[open.len()..]
+            } else {
+                line
+            };
+            if searched.contains(close) {
+                return Some(start..end);
+            }
+        }
+
+        // An unterminated block comment isn't a header.
+        return None;
+    }
+
+    let comment_char = LINE_COMMENT_CHARS.iter().find(|c| first.starts_with(*c))?;
+    for line in lines.take_while(|l| l.trim_start().starts_with(comment_char)) {
+        end += line.len();
+    }
+
+    Some(start..end)
+}
+
+/// The first non-blank line of content after its shebang and leading
+/// comment, if that comment looks like a license header.
+pub fn first_line_after_header(content: &str) -> Option<&str> {
+    let rest = match leading_comment_range(content) {
+        Some(range) if looks_like_license(&content[range.clone()]) => &content[range.end..],
+        _ => content.strip_prefix("#!").map_or(content, |rest| {
+            rest.split_once('\n').map_or("", |(_, rest)| rest)
+        }),
+    };
+
+    rest.lines().find(|line| !line.trim().is_empty())
+}
+
+fn looks_like_license(comment: &str) -> bool {
+    let comment = comment.to_lowercase();
+    ["copyright", "license", "spdx-license-identifier"]
+        .iter()
+        .any(|word| comment.contains(word))
+}
+
+fn extract_block_comment(lines: &[&str], start: &str, end: &str) -> Option<String> {
+    let mut body = Vec::new();
+    for (idx, line) in lines.iter().enumerate() {
+        let mut line = line.trim();
+        if idx == 0 {
+            line = line[start.len()..].trim_start_matches(start.chars().last().unwrap_or(' '));
+        }
+
+        let finished = match line.find(end) {
+            Some(pos) => {
+                line = &line[..pos];
+                true
+            }
+            None => false,
+        };
+
+        // Strip per line decoration such as the " * " used in C style
+        // block comments.
+        let line = match start {
+            "/*" | "(*" => strip_marker(line, "*"),
+            _ => line.trim(),
+        };
+        body.push(line);
+
+        if finished {
+            break;
+        }
+    }
+
+    while body.first().is_some_and(|l| l.is_empty()) {
+        body.remove(0);
+    }
+
+    Some(body.join("\n").trim_end().to_string())
+}
+
+fn strip_marker<'a>(line: &'a str, marker: &str) -> &'a str {
+    let line = line.trim_start();
+    let stripped = line.strip_prefix(marker).unwrap_or(line);
+    stripped.strip_prefix(' ').unwrap_or(stripped).trim_end()
+}
+
+/// Replace the years, copyright holders, and SPDX identifier found in
+/// header with the equivalent template tokens.
+pub fn infer_template(header: &str) -> ImportedTemplate {
+    let year_re = Regex::new(r"\b(19|20)[0-9]{2}(\s*(,|-|–)\s*(19|20)[0-9]{2})*\b")
+        .expect("year regex didn't compile!");
+    let mut template = year_re.replace_all(header, "[year]").to_string();
+
+    let mut ident = None;
+    let spdx_re = Regex::new(r"SPDX-License-Identifier:\s*(?P<ident>\S+)")
+        .expect("SPDX regex didn't compile!");
+    if let Some(caps) = spdx_re.captures(&template) {
+        ident = Some(caps["ident"].to_string());
+        template = spdx_re
+            .replace(&template, "SPDX-License-Identifier: [ident]")
+            .to_string();
+    }
+
+    let mut authors = Vec::new();
+    let author_re = Regex::new(
+        r"(?im)^(?P<pre>.*copyright.*?\[year\],?\s+)(?P<author>.+?)(?P<post>\.?(\s*all rights reserved\.?)?)$",
+    )
+    .expect("author regex didn't compile!");
+    if let Some(caps) = author_re.captures(&template) {
+        authors = parse_authors(&caps["author"]);
+        template = author_re
+            .replace(&template, "${pre}[name of author]${post}")
+            .to_string();
+    }
+
+    ImportedTemplate {
+        template,
+        ident,
+        authors,
+    }
+}
+
+fn parse_authors(authors: &str) -> Vec<(String, Option<String>)> {
+    let email_re =
+        Regex::new(r"^(?P<name>.*?)\s*<(?P<email>[^>]+)>$").expect("email regex didn't compile!");
+    authors
+        .split(',')
+        .map(str::trim)
+        .filter(|a| !a.is_empty())
+        .map(|a| match email_re.captures(a) {
+            Some(caps) => (caps["name"].to_string(), Some(caps["email"].to_string())),
+            None => (a.to_string(), None),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_line_comment() {
+        let content = "#!/usr/bin/env python3
+# Copyright 2020 Jane Doe
+#
+# Some license text.
+import os
+";
+        assert_eq!(
+            Some("Copyright 2020 Jane Doe\n\nSome license text.".to_string()),
+            extract_leading_comment(content)
+        );
+    }
+
+    #[test]
+    fn test_extract_block_comment() {
+        let content = "/*
+ * Copyright 2020 Jane Doe
+ *
+ * Some license text.
+ */
+int main() {}
+";
+        assert_eq!(
+            Some("Copyright 2020 Jane Doe\n\nSome license text.".to_string()),
+            extract_leading_comment(content)
+        );
+    }
+
+    #[test]
+    fn test_extract_no_comment() {
+        assert_eq!(None, extract_leading_comment("fn main() {}\n"));
+    }
+
+    #[test]
+    fn test_first_line_after_header() {
+        let content = "/* Copyright 2020 A */\n\n#include <a.h>\n";
+        assert_eq!(Some("#include <a.h>"), first_line_after_header(content));
+        let content = "#!/bin/sh\n# setup\necho hi\n";
+        assert_eq!(Some("# setup"), first_line_after_header(content));
+        assert_eq!(None, first_line_after_header("// License: MIT\n"));
+    }
+
+    #[test]
+    fn test_leading_comment_range() {
+        let content = "#!/bin/sh\n\n# a\n# b\necho hi\n";
+        assert_eq!(Some(11..19), leading_comment_range(content));
+
+        let content = "/* a\n * b */\nint x;\n";
+        assert_eq!(Some(0..13), leading_comment_range(content));
+
+        assert_eq!(None, leading_comment_range("/* a\n * b\n"));
+        assert_eq!(None, leading_comment_range("fn main() {}\n"));
+    }
+
+    #[test]
+    fn test_infer_template() {
+        let imported = infer_template(
+            "Copyright (C) 2019, 2024 Mathew Robinson <chasinglogic@gmail.com>. All rights reserved.
+SPDX-License-Identifier: MIT",
+        );
+        assert_eq!(
+            "Copyright (C) [year] [name of author]. All rights reserved.
+SPDX-License-Identifier: [ident]",
+            imported.template
+        );
+        assert_eq!(Some("MIT".to_string()), imported.ident);
+        assert_eq!(
+            vec![(
+                "Mathew Robinson".to_string(),
+                Some("chasinglogic@gmail.com".to_string())
+            )],
+            imported.authors
+        );
+    }
+
+    #[test]
+    fn test_to_license_config() {
+        let imported = infer_template("Copyright 2020 Jane Doe\n\nSome license text.");
+        let yaml = format!("licenses:\n{}", imported.to_license_config("MIT"));
+        let value: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        let license = &value["licenses"][0];
+        assert_eq!("MIT", license["ident"].as_str().unwrap());
+        assert_eq!("Jane Doe", license["authors"][0]["name"].as_str().unwrap());
+        assert_eq!(
+            "Copyright [year] [name of author]\n\nSome license text.\n",
+            license["template"].as_str().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_to_license_config_quotes() {
+        let imported = ImportedTemplate {
+            template: "  Indented\n\n# Not a comment\n".to_string(),
+            ident: Some("Acme: 1.0".to_string()),
+            authors: vec![
+                ("Acme: Inc".to_string(), Some("#legal@acme.com".to_string())),
+                ("2024".to_string(), None),
+                ("Jane Doe".to_string(), None),
+            ],
+        };
+        let yaml = format!("licenses:\n{}", imported.to_license_config("MIT"));
+        assert!(yaml.contains("- name: Jane Doe\n"));
+        let value: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        let license = &value["licenses"][0];
+        assert_eq!("Acme: 1.0", license["ident"].as_str().unwrap());
+        assert_eq!("Acme: Inc", license["authors"][0]["name"].as_str().unwrap());
+        assert_eq!(
+            "#legal@acme.com",
+            license["authors"][0]["email"].as_str().unwrap()
+        );
+        assert_eq!("2024", license["authors"][1]["name"].as_str().unwrap());
+        assert_eq!(
+            "  Indented\n\n# Not a comment\n",
+            license["template"].as_str().unwrap()
+        );
+    }
+}
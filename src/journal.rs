@@ -0,0 +1,251 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::atomic;
+
+/// Where the journal of an in place run is kept, relative to the
+/// directory licensure is run in.
+pub const DEFAULT_DIR: &str = ".licensure-journal";
+
+/// A file licensure is about to change. The original and updated content
+/// are stored next to the entry as <n>.orig and <n>.new.
+#[derive(Serialize, Deserialize, Debug)]
+struct Entry {
+    path: String,
+    /// Whether the file existed before, if it didn't rolling back
+    /// removes it.
+    existed: bool,
+}
+
+/// An intent log of the changes made by an in place run. Every change is
+/// recorded before the file is written, so if licensure is killed part
+/// way through a run the changes can be rolled back or completed with
+/// recover. The journal is removed once the run finishes. It's only
+/// written once the first change is recorded, so a run which fails
+/// before changing anything doesn't block the next one.
+pub struct Journal {
+    dir: PathBuf,
+    next: AtomicUsize,
+}
+
+impl Journal {
+    /// Start a journal in dir, failing if it holds the journal of an
+    /// interrupted run. The directory is created by the first record.
+    pub fn create(dir: impl AsRef<Path>) -> io::Result<Journal> {
+        let dir = dir.as_ref();
+        if dir.exists() {
+            return Err(io::Error::other(format!(
+                "{} holds the journal of an interrupted run, run licensure recover to roll back or complete its changes",
+                dir.display()
+            )));
+        }
+
+        Ok(Journal {
+            dir: dir.to_path_buf(),
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// Record that path is about to be changed from original to updated.
+    /// The entry is written last so entries are only seen once their
+    /// content is complete.
+    pub fn record(&self, path: &str, original: Option<&[u8]>, updated: &[u8]) -> io::Result<()> {
+        let n = self.next.fetch_add(1, Ordering::SeqCst);
+        fs::create_dir_all(&self.dir)?;
+        fs::write(
+            self.dir.join(format!("{}.orig", n)),
+            original.unwrap_or_default(),
+        )?;
+        fs::write(self.dir.join(format!("{}.new", n)), updated)?;
+        let entry = Entry {
+            path: path.to_string(),
+            existed: original.is_some(),
+        };
+        atomic::write(
+            self.dir.join(format!("{}.json", n)),
+            &serde_json::to_vec(&entry)?,
+            &atomic::Options::default(),
+        )
+    }
+
+    /// Remove the journal after every change was made.
+    pub fn finish(self) -> io::Result<()> {
+        match fs::remove_dir_all(&self.dir) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
+
+/// How recover deals with the changes of an interrupted run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Recovery {
+    /// Restore every changed file to its original content.
+    Rollback,
+    /// Make every recorded change which wasn't made yet.
+    Complete,
+}
+
+/// Roll back or complete the changes recorded in the journal in dir,
+/// then remove it. Returns the files which were changed, or None if
+/// there is no journal.
+pub fn recover(dir: impl AsRef<Path>, recovery: Recovery) -> io::Result<Option<Vec<String>>> {
+    let dir = dir.as_ref();
+    if !dir.exists() {
+        return Ok(None);
+    }
+
+    let mut entries = Vec::new();
+    for file in fs::read_dir(dir)? {
+        let path = file?.path();
+        let n = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(".json"))
+            .and_then(|n| n.parse::<usize>().ok());
+        if let Some(n) = n {
+            let entry: Entry = serde_json::from_slice(&fs::read(&path)?)?;
+            entries.push((n, entry));
+        }
+    }
+    entries.sort_by_key(|(n, _)| *n);
+
+    let mut changed = Vec::new();
+    for (n, entry) in entries {
+        for stale in atomic::stale_temp_files(&entry.path)? {
+            fs::remove_file(stale)?;
+        }
+
+        let current = match fs::read(&entry.path) {
+            Ok(content) => Some(content),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        let wanted = match (recovery, entry.existed) {
+            (Recovery::Rollback, false) => None,
+            (Recovery::Rollback, true) => Some(fs::read(dir.join(format!("{}.orig", n)))?),
+            (Recovery::Complete, _) => Some(fs::read(dir.join(format!("{}.new", n)))?),
+        };
+        if current == wanted {
+            continue;
+        }
+
+        match wanted {
+            Some(content) => atomic::write(&entry.path, &content, &atomic::Options::default())?,
+            None => fs::remove_file(&entry.path)?,
+        }
+        changed.push(entry.path);
+    }
+
+    fs::remove_dir_all(dir)?;
+    Ok(Some(changed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    // Record and write changes to a.rs and b.rs, crashing while writing
+    // b.rs after its temporary file was created but before it replaced
+    // b.rs. Returns the project directory.
+    fn interrupted_run(name: &str) -> PathBuf {
+        let dir =
+            env::temp_dir().join(format!("licensure-journal-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let a = dir.join("a.rs").to_string_lossy().to_string();
+        let b = dir.join("b.rs").to_string_lossy().to_string();
+        let c = dir.join("c.rs").to_string_lossy().to_string();
+        fs::write(&a, "fn a() {}\n").unwrap();
+        fs::write(&b, "fn b() {}\n").unwrap();
+
+        let journal = Journal::create(dir.join(DEFAULT_DIR)).unwrap();
+        let opts = atomic::Options::default();
+        journal
+            .record(&a, Some(b"fn a() {}\n"), b"// License\nfn a() {}\n")
+            .unwrap();
+        atomic::write(&a, b"// License\nfn a() {}\n", &opts).unwrap();
+        journal.record(&c, None, b"// License\n").unwrap();
+        atomic::write(&c, b"// License\n", &opts).unwrap();
+
+        journal
+            .record(&b, Some(b"fn b() {}\n"), b"// License\nfn b() {}\n")
+            .unwrap();
+        atomic::crash_before_rename(true);
+        assert!(atomic::write(&b, b"// License\nfn b() {}\n", &opts).is_err());
+        atomic::crash_before_rename(false);
+        assert_eq!(1, atomic::stale_temp_files(&b).unwrap().len());
+
+        // A crash while recording leaves content without an entry.
+        fs::write(dir.join(DEFAULT_DIR).join("3.orig"), "partial").unwrap();
+
+        // The journal of the interrupted run blocks new runs.
+        assert!(Journal::create(dir.join(DEFAULT_DIR)).is_err());
+        dir
+    }
+
+    fn read(dir: &Path, file: &str) -> Option<String> {
+        fs::read_to_string(dir.join(file)).ok()
+    }
+
+    #[test]
+    fn test_recover_complete() {
+        let dir = interrupted_run("complete");
+        let changed = recover(dir.join(DEFAULT_DIR), Recovery::Complete)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            vec![dir.join("b.rs").to_string_lossy().to_string()],
+            changed
+        );
+        assert_eq!(
+            Some("// License\nfn a() {}\n".to_string()),
+            read(&dir, "a.rs")
+        );
+        assert_eq!(
+            Some("// License\nfn b() {}\n".to_string()),
+            read(&dir, "b.rs")
+        );
+        assert_eq!(Some("// License\n".to_string()), read(&dir, "c.rs"));
+        // Only the project files are left.
+        assert_eq!(3, fs::read_dir(&dir).unwrap().count());
+
+        assert_eq!(
+            None,
+            recover(dir.join(DEFAULT_DIR), Recovery::Complete).unwrap()
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_recover_rollback() {
+        let dir = interrupted_run("rollback");
+        let changed = recover(dir.join(DEFAULT_DIR), Recovery::Rollback)
+            .unwrap()
+            .unwrap();
+        assert_eq!(2, changed.len());
+        assert_eq!(Some("fn a() {}\n".to_string()), read(&dir, "a.rs"));
+        assert_eq!(Some("fn b() {}\n".to_string()), read(&dir, "b.rs"));
+        assert_eq!(None, read(&dir, "c.rs"));
+        assert_eq!(2, fs::read_dir(&dir).unwrap().count());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -11,33 +11,206 @@
 // You should have received a copy of the GNU General Public License along with
 // this program. If not, see <https://www.gnu.org/licenses/>.
 //
-use std::fs::File;
-use std::io::{self, prelude::*};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
 
+use chrono::NaiveDate;
 use regex::Regex;
+use serde::Serialize;
 
-use crate::comments::Comment;
-use crate::config::Config;
-use crate::template::Template;
+use crate::clock::FixedClock;
+use crate::comments::{trim_line_ends, Comment, PlainText};
+use crate::config::{
+    template_provenance, CheckCondition, Config, LinePatterns, Position, Provenance, Spacing,
+    VerifyCommand,
+};
+use crate::foreign;
+use crate::import::{extract_leading_comment, leading_comment_range};
+use crate::journal::Journal;
+use crate::metrics;
+use crate::progress::Progress;
+use crate::release::Release;
+use crate::source::{FileSource, OsSource};
+use crate::template::{SmallFiles, Template, YearMode};
+use crate::uncommentable::{Dep5, Policy};
+use crate::utils::diff::{changed_lines, unified_diff, word_diff};
+use crate::utils::git::{self, Repo};
+use crate::utils::utf8_paths;
+use crate::warning;
 
 pub struct Licensure {
     config: Config,
-    stats: LicenseStats,
     check_mode: bool,
+    diff_mode: bool,
+    remove_mode: bool,
+    explain_diff: bool,
+    fix_format: bool,
+    // Headers are added even to files which seem to have one already.
+    force: bool,
+    // Files on disk, which are used unless another source is set, and
+    // how they're written.
+    os: OsSource,
+    source: Option<Box<dyn FileSource>>,
+    // The work tree used instead of the one licensure is running in.
+    repo: Option<Repo>,
+    // Whether a count of processed files is drawn on stderr.
+    progress: bool,
+    // Whether directories with files of different licenses are reported.
+    enforce_directory_consistency: bool,
+    jobs: usize,
+    // Authors of newly licensed files by the contributors file they
+    // should be added to.
+    signoffs: Mutex<BTreeMap<String, BTreeSet<String>>>,
+    // Files marked as generated or vendored in .gitattributes, only
+    // populated when use_gitattributes is enabled.
+    linguist_excluded: HashSet<String>,
+    // The DEP-5 file being maintained, only loaded when the
+    // uncommentable policy is dep5.
+    dep5: Option<Dep5>,
+    // Changes to make to the DEP-5 file by file, a copyright and license
+    // to add or None to remove the file. These are applied once all
+    // files are processed, in path order so the result doesn't depend
+    // on the order files were processed in.
+    dep5_changes: Mutex<BTreeMap<String, Option<(String, String)>>>,
+    // Records changes before they're made when writing in place.
+    journal: Option<Journal>,
+    // The release headers are stamped for by stamp-release.
+    release: Option<Release>,
+    // Whether check mode writes updates which only change years.
+    fix_years: bool,
+    // The lines replaced in each file whose header is added or updated,
+    // as the old and new lines.
+    changes: Mutex<BTreeMap<String, (String, String)>>,
+    // What's printed for each file being processed, which is held until
+    // the file is done so the output of files processed concurrently
+    // isn't interleaved and is printed in the order of the files.
+    output: Mutex<BTreeMap<String, String>>,
 }
 
+// The template and commenter that apply to a file.
+type Resolved = (Template, Box<dyn Comment>);
+
 enum LicenseStatus {
+    // The file has no license header and needs one added.
+    NeedsHeader(String),
     NeedsUpdate(String),
+    // The header text is correct but its wrapping or spacing isn't.
+    NeedsFormatFix(String),
     AlreadyLicensed,
     NoConfigMatched,
+    NoHeaderFound,
+    Excluded,
+    Binary,
+    // The file can't contain a comment and the uncommentable policy is
+    // skip.
+    Uncommentable,
+    // The file contains one of the skip_if_contains markers.
+    Generated,
+    // The file isn't binary but isn't valid UTF-8 either.
+    BadEncoding,
+    // The path of the file isn't valid UTF-8, which paths are matched
+    // and reported as.
+    BadPath,
+    // The file needs changing in place but it or its directory is
+    // read-only.
+    ReadOnly,
+    // The file is too small for its header and its license skips such
+    // files.
+    TooSmall,
+    // The file has the header of a license which isn't configured.
+    UnknownLicense,
+    // The file seems to have the header of a configured license, which
+    // only differs in formatting that couldn't be fixed.
+    SimilarHeader,
+    // The header only needed its years updated, which was written in
+    // check mode because of fix_years.
+    YearsFixed,
+}
+
+impl LicenseStatus {
+    // The action for a file with this status and the reason for it,
+    // where the action alone doesn't explain it.
+    fn action(&self) -> (Action, Option<&'static str>) {
+        match self {
+            LicenseStatus::NeedsHeader(_) => (Action::Added, None),
+            LicenseStatus::NeedsUpdate(_) => (Action::Updated, None),
+            LicenseStatus::NeedsFormatFix(_) => (Action::Updated, Some("format_fix")),
+            LicenseStatus::YearsFixed => (Action::Updated, Some("years_fixed")),
+            LicenseStatus::AlreadyLicensed => (Action::AlreadyLicensed, None),
+            LicenseStatus::Excluded => (Action::Excluded, None),
+            LicenseStatus::NoConfigMatched => (Action::Skipped, Some("no_config_matched")),
+            LicenseStatus::NoHeaderFound => (Action::Skipped, Some("no_header_found")),
+            LicenseStatus::Binary => (Action::Skipped, Some("binary")),
+            LicenseStatus::Uncommentable => (Action::Skipped, Some("uncommentable")),
+            LicenseStatus::Generated => (Action::Skipped, Some("generated")),
+            LicenseStatus::BadEncoding => (Action::Skipped, Some("bad_encoding")),
+            LicenseStatus::BadPath => (Action::Skipped, Some("bad_path")),
+            LicenseStatus::ReadOnly => (Action::Skipped, Some("read_only")),
+            LicenseStatus::TooSmall => (Action::Skipped, Some("too_small")),
+            LicenseStatus::UnknownLicense => (Action::Skipped, Some("unknown_license")),
+            LicenseStatus::SimilarHeader => (Action::Skipped, Some("similar_header")),
+        }
+    }
+}
+
+// The content of a file as read by read_target.
+enum Content {
+    Text(String),
+    Binary,
+    // Text which isn't UTF-8, with a description of why.
+    BadEncoding(&'static str),
+}
+
+/// Where the header goes relative to the existing content of a file
+/// and how it is spaced from it.
+#[derive(Default, Clone, Copy)]
+struct Placement<'a> {
+    magic_comments: Option<&'a LinePatterns>,
+    insert_after: Option<&'a LinePatterns>,
+    spacing: Spacing,
+    position: Position,
 }
 
+// How many bytes from the start of a file are checked for null bytes
+// when detecting binary files, this is the same heuristic git uses.
+const BINARY_SNIFF_LEN: usize = 8000;
+
+// The UTF-8 byte order mark.
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
 impl Licensure {
     pub fn new(config: Config) -> Licensure {
         Licensure {
             config,
             check_mode: false,
-            stats: LicenseStats::new(),
+            diff_mode: false,
+            remove_mode: false,
+            explain_diff: false,
+            fix_format: false,
+            force: false,
+            os: OsSource::default(),
+            source: None,
+            repo: None,
+            progress: false,
+            enforce_directory_consistency: false,
+            jobs: 1,
+            signoffs: Mutex::new(BTreeMap::new()),
+            linguist_excluded: HashSet::new(),
+            dep5: None,
+            dep5_changes: Mutex::new(BTreeMap::new()),
+            journal: None,
+            release: None,
+            fix_years: false,
+            changes: Mutex::new(BTreeMap::new()),
+            output: Mutex::new(BTreeMap::new()),
         }
     }
 
@@ -46,58 +219,807 @@ impl Licensure {
         self
     }
 
-    pub fn license_files(mut self, files: &[String]) -> Result<LicenseStats, io::Error> {
-        self.stats = LicenseStats::new();
+    /// When enabled, and not changing files in place, print a unified
+    /// diff of the changes instead of the full updated file content.
+    pub fn with_diff_mode(mut self, diff_mode: bool) -> Licensure {
+        self.diff_mode = diff_mode;
+        self
+    }
+
+    /// When enabled the configured license header is removed from
+    /// files instead of added.
+    pub fn with_remove_mode(mut self, remove_mode: bool) -> Licensure {
+        self.remove_mode = remove_mode;
+        self
+    }
+
+    /// When enabled files needing an update print a word level diff
+    /// between their existing header and the expected one.
+    pub fn with_explain_diff(mut self, explain_diff: bool) -> Licensure {
+        self.explain_diff = explain_diff;
+        self
+    }
+
+    /// When enabled headers whose text is correct but whose formatting
+    /// differs from the current config are rewritten in place of the
+    /// existing header.
+    pub fn with_fix_format(mut self, fix_format: bool) -> Licensure {
+        self.fix_format = fix_format;
+        self
+    }
+
+    /// When enabled headers are added to files which appear to already
+    /// have a header, one with different formatting or of an unknown
+    /// license, instead of those files being left alone.
+    pub fn with_force(mut self, force: bool) -> Licensure {
+        self.force = force;
+        self
+    }
+
+    /// When set, files changed in place have their original content
+    /// copied to the file name with backup_ext appended first.
+    pub fn with_backup_ext(mut self, backup_ext: Option<String>) -> Licensure {
+        self.os.options.backup_ext = backup_ext;
+        self
+    }
+
+    /// When enabled files changed in place keep their modification time.
+    pub fn with_preserve_mtime(mut self, preserve_mtime: bool) -> Licensure {
+        self.os.options.preserve_mtime = preserve_mtime;
+        self
+    }
+
+    /// When enabled read-only files which need changing in place are
+    /// made writable while they're written, restoring their mode after.
+    pub fn with_chmod_if_needed(mut self, chmod_if_needed: bool) -> Licensure {
+        self.os.chmod_if_needed = chmod_if_needed;
+        self
+    }
+
+    /// Read and write files from source instead of the disk. The options
+    /// for writing files on disk don't apply to it.
+    #[cfg(test)]
+    pub fn with_source(mut self, source: Box<dyn FileSource>) -> Licensure {
+        self.source = Some(source);
+        self
+    }
+
+    /// Use repo instead of the work tree licensure is running in.
+    #[cfg(test)]
+    pub fn with_repo(mut self, repo: Repo) -> Licensure {
+        self.repo = Some(repo);
+        self
+    }
+
+    fn source(&self) -> &dyn FileSource {
+        match &self.source {
+            Some(source) => source.as_ref(),
+            None => &self.os,
+        }
+    }
+
+    /// When enabled a count of processed files is drawn on stderr while
+    /// licensing files.
+    pub fn with_progress(mut self, progress: bool) -> Licensure {
+        self.progress = progress;
+        self
+    }
+
+    /// When enabled directories containing files with different
+    /// licenses are reported as a failure.
+    pub fn with_enforce_directory_consistency(mut self, enforce: bool) -> Licensure {
+        self.enforce_directory_consistency = enforce;
+        self
+    }
+
+    /// When enabled, check mode writes the updates of headers whose only
+    /// difference is their years instead of failing on them, so that a
+    /// scheduled job can keep years up to date without other rewrites.
+    pub fn with_fix_years(mut self, fix_years: bool) -> Licensure {
+        self.fix_years = fix_years;
+        self
+    }
+
+    /// Record every change in journal before making it, so that the
+    /// changes of an interrupted run can be recovered.
+    pub fn with_journal(mut self, journal: Option<Journal>) -> Licensure {
+        self.journal = journal;
+        self
+    }
+
+    /// When set, headers are rendered with the version of the release
+    /// and their end years are bumped to its year.
+    pub fn with_release(mut self, release: Option<Release>) -> Licensure {
+        self.release = release;
+        self
+    }
+
+    /// Set the number of files to process concurrently. A value of 0
+    /// will use the available parallelism of the machine.
+    pub fn with_jobs(mut self, jobs: usize) -> Licensure {
+        self.jobs = match jobs {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+        self
+    }
+
+    pub fn license_files<P: AsRef<Path>>(mut self, paths: &[P]) -> Result<LicenseStats, io::Error> {
+        let mut stats = LicenseStats::new();
+        stats.fail_on = self.config.check_fail_on.clone();
+        let (files, not_utf8) = utf8_paths(paths);
+        for file in &not_utf8 {
+            warning!("skipping {} because its path isn't valid UTF-8", file);
+            stats.record(file, LicenseStatus::BadPath);
+        }
+        let files = files.as_slice();
+
+        if self.config.use_gitattributes {
+            if let Some(repo) = self.repo.as_ref().or_else(|| git::repo()) {
+                self.linguist_excluded = repo.linguist_excluded(files)?;
+            }
+        }
+
+        if let Some(cfg) = &self.config.uncommentable {
+            if cfg.policy == Policy::Dep5 {
+                self.dep5 = Some(Dep5::load(&cfg.dep5_file)?);
+            }
+        }
+
+        let progress = Progress::new(files.len(), self.progress);
+        if self.jobs <= 1 || files.len() <= 1 {
+            for file in files {
+                let status = self.process_file(file);
+                self.flush_output(file);
+                progress.tick();
+                stats.record(file, status?);
+            }
+        } else {
+            for (file, status) in files
+                .iter()
+                .zip(self.license_files_parallel(files, &progress)?)
+            {
+                stats.record(file, status);
+            }
+        }
+        drop(progress);
 
+        let mut changes = self.changes.lock().expect("changes lock was poisoned!");
         for file in files {
-            if self.config.excludes.is_match(file) {
-                info!("skipping {} because it is excluded.", file);
-                continue;
+            let rules = self.matched_rules(file);
+            if let Some(report) = stats.files.get_mut(file) {
+                report.license = rules.license.as_ref().map(|entry| entry.ident.clone());
+                report.comment = Some(rules.comment.clone());
+                if let Some((old, new)) = changes.remove(file) {
+                    report.old = Some(old);
+                    report.new = Some(new);
+                }
+            }
+            stats.matched_rules.insert(file.clone(), rules);
+        }
+        drop(changes);
+
+        if self.enforce_directory_consistency {
+            let mut idents = Vec::new();
+            for file in &stats.files_checked {
+                if let Some(ident) = self.license_ident(file)? {
+                    idents.push((file.as_str(), ident));
+                }
+            }
+            stats.mixed_license_directories = mixed_license_directories(&idents);
+        }
+
+        stats.templates = template_provenance();
+        self.write_dep5()?;
+        self.write_signoffs()?;
+        if let Some(journal) = self.journal.take() {
+            journal.finish()?;
+        }
+        Ok(stats)
+    }
+
+    /// Process files on a pool of worker threads, returning the results
+    /// in the same order as the given files so that the produced stats
+    /// are deterministic. Like processing them one at a time no more
+    /// files are started once one fails, and the first error in the
+    /// order of the files is returned.
+    fn license_files_parallel(
+        &self,
+        files: &[String],
+        progress: &Progress,
+    ) -> Result<Vec<LicenseStatus>, io::Error> {
+        let next = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let results = Mutex::new(Vec::with_capacity(files.len()));
+
+        thread::scope(|s| {
+            for _ in 0..self.jobs.min(files.len()) {
+                s.spawn(|| loop {
+                    if failed.load(Ordering::SeqCst) {
+                        break;
+                    }
+
+                    let idx = next.fetch_add(1, Ordering::SeqCst);
+                    if idx >= files.len() {
+                        break;
+                    }
+
+                    let result = self.process_file(&files[idx]);
+                    if result.is_err() {
+                        failed.store(true, Ordering::SeqCst);
+                    }
+                    progress.tick();
+                    results
+                        .lock()
+                        .expect("results lock was poisoned!")
+                        .push((idx, result));
+                });
+            }
+        });
+
+        // Files are started in order, so the results are those of the
+        // first files without any gaps.
+        let mut results = results.into_inner().expect("results lock was poisoned!");
+        results.sort_by_key(|(idx, _)| *idx);
+        results
+            .into_iter()
+            .map(|(idx, result)| {
+                self.flush_output(&files[idx]);
+                result
+            })
+            .collect()
+    }
+
+    /// Print text as output of file once it's done.
+    fn print(&self, file: &str, text: &str) {
+        self.output
+            .lock()
+            .expect("output lock was poisoned!")
+            .entry(file.to_string())
+            .or_default()
+            .push_str(text);
+    }
+
+    /// Print the output of file.
+    fn flush_output(&self, file: &str) {
+        let output = self
+            .output
+            .lock()
+            .expect("output lock was poisoned!")
+            .remove(file);
+        if let Some(output) = output {
+            print!("{}", output);
+        }
+    }
+
+    // License file, counting it in the metrics.
+    fn process_file(&self, file: &String) -> Result<LicenseStatus, io::Error> {
+        metrics::FILES_PROCESSED.inc();
+        let result = self.license_file(file);
+        if result.is_err() {
+            metrics::ERRORS.inc();
+        }
+
+        result
+    }
+
+    /// The config entries which apply to file.
+    fn matched_rules(&self, file: &str) -> MatchedRules {
+        MatchedRules {
+            exclude: self.config.excludes.matching(file).map(str::to_string),
+            exclude_rule: self
+                .config
+                .exclude_rules
+                .matching(file)
+                .map(|rule| rule.to_string()),
+            license: self
+                .config
+                .licenses
+                .matching_entry(file)
+                .map(|(index, ident)| LicenseEntry {
+                    index,
+                    ident: ident.to_string(),
+                }),
+            comment: self.config.comments.describe_match(file),
+        }
+    }
+
+    fn license_file(&self, file: &String) -> Result<LicenseStatus, io::Error> {
+        info!("{} matched {}", file, self.matched_rules(file));
+        if let Some(pattern) = self.config.excludes.matching(file) {
+            info!(
+                "skipping {} because it matches the exclude pattern {}.",
+                file, pattern
+            );
+            return Ok(LicenseStatus::Excluded);
+        }
+
+        if let Some(rule) = self.config.exclude_rules.matching(file) {
+            info!(
+                "skipping {} because it matches the exclude rule {}.",
+                file, rule
+            );
+            return Ok(LicenseStatus::Excluded);
+        }
+
+        if self.linguist_excluded.contains(file) {
+            info!(
+                "skipping {} because it is generated or vendored according to .gitattributes.",
+                file
+            );
+            return Ok(LicenseStatus::Excluded);
+        }
+
+        if self.is_dep5_file(file) {
+            info!("skipping {} because it is the dep5 file.", file);
+            return Ok(LicenseStatus::Excluded);
+        }
+
+        match self.config.uncommentable.as_ref().map(|cfg| cfg.policy) {
+            Some(Policy::Skip) if self.is_uncommentable(file)? => {
+                info!("skipping {} because it can't contain a comment.", file);
+                return Ok(LicenseStatus::Uncommentable);
+            }
+            Some(Policy::Dep5) if self.is_uncommentable(file)? => return self.license_dep5(file),
+            _ => (),
+        }
+
+        let (target, mut content) = match self.read_target(file)? {
+            (target, Content::Text(content)) => (target, content),
+            (_, Content::Binary) => {
+                info!("skipping {} because it is a binary file.", file);
+                return Ok(LicenseStatus::Binary);
+            }
+            (_, Content::BadEncoding(reason)) => {
+                warning!("skipping {} because {}", file, reason);
+                return Ok(LicenseStatus::BadEncoding);
+            }
+        };
+
+        if let Some(marker) = find_generated_marker(&content, &self.config.skip_if_contains) {
+            info!(
+                "skipping {} because it contains the generated marker {:?}.",
+                file, marker
+            );
+            return Ok(LicenseStatus::Generated);
+        }
+
+        let license = match find_pragma(&content) {
+            Some(Pragma::Ignore) => {
+                info!("skipping {} because of its licensure: ignore pragma.", file);
+                return Ok(LicenseStatus::Excluded);
             }
+            Some(Pragma::License(ident)) => Some(ident),
+            None => None,
+        };
 
-            let mut content = String::new();
+        let original = content.clone();
+        let status = if self.remove_mode {
+            self.remove_license_header(file, &content, license.as_deref())?
+        } else {
+            self.add_license_header(file, &mut content, license.as_deref())?
+        };
+        if let LicenseStatus::NeedsHeader(ref update)
+        | LicenseStatus::NeedsUpdate(ref update)
+        | LicenseStatus::NeedsFormatFix(ref update) = status
+        {
+            if self.config.change_in_place
+                && !self.check_mode
+                && !self.source().is_writable(&target)
             {
-                let mut f = File::open(file)?;
-                f.read_to_string(&mut content)?;
+                info!("skipping {} because it is read-only.", file);
+                return Ok(LicenseStatus::ReadOnly);
+            }
+
+            self.changes
+                .lock()
+                .expect("changes lock was poisoned!")
+                .insert(file.clone(), changed_lines(&original, update));
+
+            if let LicenseStatus::NeedsUpdate(_) = status {
+                if self.check_mode
+                    && self.fix_years
+                    && is_year_only_change(&original, update)
+                    && self.source().is_writable(&target)
+                {
+                    info!("{} only needs its years updated, updating it", file);
+                    self.write_target(&target, &self.finish_content(update))?;
+                    return Ok(LicenseStatus::YearsFixed);
+                }
+            }
+
+            self.handle_update(&target, &original, update)?;
+            if let LicenseStatus::NeedsHeader(_) = status {
+                if self.config.change_in_place && !self.check_mode {
+                    self.record_signoff(file, license.as_deref())?;
+                }
+            }
+        }
+
+        Ok(status)
+    }
+
+    /// Whether file is the DEP-5 file maintained by the dep5 policy.
+    fn is_dep5_file(&self, file: &str) -> bool {
+        self.config.uncommentable.as_ref().is_some_and(|cfg| {
+            cfg.policy == Policy::Dep5 && Path::new(&cfg.dep5_file) == Path::new(file)
+        })
+    }
+
+    /// Whether file can't contain a comment, either because it has a
+    /// sidecar commenter or because it's binary.
+    fn is_uncommentable(&self, file: &str) -> Result<bool, io::Error> {
+        if self.config.comments.is_sidecar(file) {
+            return Ok(true);
+        }
+
+        Ok(is_binary(&self.source().read(file)?))
+    }
+
+    /// Whether the header of file is written to a <file>.license file
+    /// instead of file itself. With the sidecar uncommentable policy this
+    /// includes binary files.
+    fn uses_sidecar(&self, file: &str) -> Result<bool, io::Error> {
+        match self.config.uncommentable.as_ref().map(|cfg| cfg.policy) {
+            Some(Policy::Sidecar) => self.is_uncommentable(file),
+            _ => Ok(self.config.comments.is_sidecar(file)),
+        }
+    }
+
+    /// Record the copyright and license of file, which can't contain a
+    /// comment, in the DEP-5 file. The file itself is never changed.
+    fn license_dep5(&self, file: &String) -> Result<LicenseStatus, io::Error> {
+        let dep5 = self
+            .dep5
+            .as_ref()
+            .expect("dep5 file wasn't loaded for the dep5 policy!");
+        let mut changes = self
+            .dep5_changes
+            .lock()
+            .expect("dep5 changes lock was poisoned!");
+
+        if self.remove_mode {
+            if !dep5.lists(file) {
+                return Ok(LicenseStatus::NoHeaderFound);
+            }
+
+            info!("removing {} from the dep5 file", file);
+            changes.insert(file.clone(), None);
+            return Ok(LicenseStatus::NeedsUpdate(String::new()));
+        }
+
+        let templ = match self.config.licenses.get_template(file) {
+            Some(t) => t,
+            None => {
+                info!("skipping {} because no license config matched.", file);
+                return Ok(LicenseStatus::NoConfigMatched);
+            }
+        };
+
+        let copyright = templ.copyright();
+        if dep5.contains(file, &copyright, templ.ident()) {
+            info!("{} already licensed in the dep5 file", file);
+            return Ok(LicenseStatus::AlreadyLicensed);
+        }
+
+        changes.insert(file.clone(), Some((copyright, templ.ident().to_string())));
+        Ok(LicenseStatus::NeedsHeader(String::new()))
+    }
+
+    /// Write the DEP-5 file if any files were added to or removed from
+    /// it, this follows the same check, diff, and in place modes as
+    /// licensed files.
+    fn write_dep5(&self) -> Result<(), io::Error> {
+        let (cfg, mut dep5) = match (&self.config.uncommentable, self.dep5.clone()) {
+            (Some(cfg), Some(dep5)) => (cfg, dep5),
+            _ => return Ok(()),
+        };
+        let changes = self
+            .dep5_changes
+            .lock()
+            .expect("dep5 changes lock was poisoned!");
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        for (file, change) in changes.iter() {
+            match change {
+                Some((copyright, license)) => dep5.add(file, copyright, license),
+                None => {
+                    dep5.remove(file);
+                }
+            }
+        }
+
+        let path = &cfg.dep5_file;
+        let before = match self.source().read(path) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Err(_) => String::new(),
+        };
+        self.handle_update(path, &before, &dep5.render())
+    }
+
+    /// The file the header of file is written to, which is file itself
+    /// unless it can't contain comments, and its content. The content is
+    /// None for binary files.
+    fn read_target(&self, file: &String) -> Result<(String, Content), io::Error> {
+        // Files which can't contain comments have their header written
+        // to a sidecar file instead.
+        let target = if self.uses_sidecar(file)? {
+            format!("{}.license", file)
+        } else {
+            file.clone()
+        };
+
+        let bytes = if target != *file && !self.source().exists(&target) {
+            Arc::new(Vec::new())
+        } else {
+            self.source().read(&target)?
+        };
+        // UTF-16 text is full of null bytes so it has to be recognized
+        // before binary files.
+        if bytes.starts_with(b"\xFF\xFE") || bytes.starts_with(b"\xFE\xFF") {
+            return Ok((target, Content::BadEncoding("it is UTF-16 encoded")));
+        }
+        if is_binary(&bytes) {
+            return Ok((target, Content::Binary));
+        }
+
+        // A byte order mark has to stay first in the file so it's left
+        // out of the content, and headers are always rendered with LF
+        // line endings so CRLF ones are converted. write_target restores
+        // both.
+        let bytes = bytes.strip_prefix(BOM).unwrap_or(&bytes);
+        let content = match String::from_utf8(bytes.to_vec()) {
+            Ok(content) => content,
+            Err(_) => return Ok((target, Content::BadEncoding("it isn't valid UTF-8"))),
+        };
+        let content = if content.contains("\r\n") {
+            content.replace("\r\n", "\n")
+        } else {
+            content
+        };
+        Ok((target, Content::Text(content)))
+    }
+
+    // Write content read with read_target back to target, restoring its
+    // byte order mark and line endings.
+    fn write_target(&self, target: &str, content: &str) -> Result<(), io::Error> {
+        let exists = self.source().exists(target);
+        let existing = if exists {
+            self.source().read(target)?
+        } else {
+            Arc::new(Vec::new())
+        };
+
+        let mut bytes = Vec::with_capacity(content.len());
+        if existing.starts_with(BOM) {
+            bytes.extend_from_slice(BOM);
+        }
+        if self.config.line_ending.is_crlf(&existing) {
+            bytes.extend_from_slice(content.replace('\n', "\r\n").as_bytes());
+        } else {
+            bytes.extend_from_slice(content.as_bytes());
+        }
+
+        if let Some(journal) = &self.journal {
+            journal.record(target, exists.then_some(existing.as_slice()), &bytes)?;
+        }
+
+        self.source().write(target, &bytes)
+    }
+
+    // The ident of the configured license whose header file has, or of
+    // the license matching its path if it has none.
+    fn license_ident<'a>(&'a self, file: &String) -> Result<Option<&'a str>, io::Error> {
+        for ident in self.config.licenses.idents() {
+            if self.has_license(file, ident)? {
+                return Ok(Some(ident));
+            }
+        }
+
+        Ok(self.config.licenses.matching_ident(file))
+    }
+
+    /// Whether file is excluded by the excludes or exclude rules.
+    pub fn is_excluded(&self, file: &str) -> bool {
+        self.config.excludes.is_match(file) || self.config.exclude_rules.matching(file).is_some()
+    }
+
+    /// The ident of the license whose files pattern matches file.
+    pub fn matching_license(&self, file: &str) -> Option<&str> {
+        self.config.licenses.matching_ident(file)
+    }
+
+    /// The idents of every configured license whose header file has.
+    pub fn detected_licenses(&self, file: &String) -> Result<Vec<&str>, io::Error> {
+        let mut detected = Vec::new();
+        for ident in self.config.licenses.idents() {
+            if self.has_license(file, ident)? {
+                detected.push(ident);
             }
+        }
+
+        Ok(detected)
+    }
+
+    /// Whether file has the header of the license with ident, in any
+    /// year. Excluded and binary files never do.
+    pub fn has_license(&self, file: &String, ident: &str) -> Result<bool, io::Error> {
+        if self.is_excluded(file) {
+            return Ok(false);
+        }
+
+        let content = match self.read_target(file)? {
+            (_, Content::Text(content)) => content,
+            _ => return Ok(false),
+        };
 
-            match self.add_license_header(file, &mut content) {
-                LicenseStatus::NeedsUpdate(update) => self.handle_update(file, &update)?,
-                LicenseStatus::NoConfigMatched => self.stats.files_not_licensed.push(file.clone()),
-                LicenseStatus::AlreadyLicensed => continue,
+        Ok(match self.resolve(file, Some(ident))? {
+            Some((templ, commenter)) => {
+                Self::strip_header(&templ, commenter.as_ref(), &content).is_some()
             }
+            None => false,
+        })
+    }
+
+    /// Replace the header of the license with ident from in file with the
+    /// header of the license with ident to, writing the file in place.
+    /// Returns false, without changing the file, if it doesn't have the
+    /// header of from.
+    pub fn relicense_file(&self, file: &String, from: &str, to: &str) -> Result<bool, io::Error> {
+        let (target, content) = match self.read_target(file)? {
+            (target, Content::Text(content)) => (target, content),
+            _ => return Ok(false),
+        };
+
+        if find_generated_marker(&content, &self.config.skip_if_contains).is_some() {
+            return Ok(false);
         }
 
-        Ok(self.stats)
+        let (from_templ, from_commenter) = match self.resolve(file, Some(from))? {
+            Some(resolved) => resolved,
+            None => return Ok(false),
+        };
+        let mut stripped = match Self::strip_header(&from_templ, from_commenter.as_ref(), &content)
+        {
+            Some(stripped) => stripped,
+            None => return Ok(false),
+        };
+
+        let (to_templ, to_commenter) = match self.resolve(file, Some(to))? {
+            Some(resolved) => resolved,
+            None => return Ok(false),
+        };
+        let header = to_commenter.comment(&to_templ.render());
+        let relicensed = self.add_header(header, &mut stripped, &self.placement(file));
+        self.write_target(&target, &self.finish_content(&relicensed))?;
+        Ok(true)
+    }
+
+    /// Apply the policies for the end of changed files to content.
+    fn finish_content<'a>(&self, content: &'a str) -> Cow<'a, str> {
+        if self.config.ensure_trailing_newline {
+            ensure_trailing_newline(content)
+        } else {
+            Cow::Borrowed(content)
+        }
     }
 
-    fn handle_update(&self, file: &String, content: &str) -> Result<(), io::Error> {
+    fn handle_update(&self, file: &String, original: &str, content: &str) -> Result<(), io::Error> {
         if self.check_mode {
             return Result::Ok(());
         }
 
+        let content = &self.finish_content(content);
+
         if self.config.change_in_place {
-            let mut f = File::create(file)?;
-            return f.write_all(content.as_bytes());
+            self.write_target(file, content)?;
+
+            let verify_command = self
+                .config
+                .licenses
+                .get_verify_command(file)
+                .or_else(|| self.config.comments.get_verify_command(file));
+            if let Some(cmd) = verify_command {
+                match verify_formatting(cmd, file) {
+                    Ok(true) => (),
+                    Ok(false) => warning!(
+                        "{} would be reformatted by `{}`, the license header may be reflowed",
+                        file,
+                        cmd
+                    ),
+                    Err(e) => warning!("failed to run `{}` for {}: {}", cmd, file, e),
+                }
+            }
+
+            return Ok(());
         }
 
-        println!("{}", content);
+        if self.diff_mode {
+            self.print(
+                file,
+                &unified_diff(
+                    original,
+                    content,
+                    &format!("a/{}", file),
+                    &format!("b/{}", file),
+                    3,
+                ),
+            );
+            return Ok(());
+        }
+
+        self.print(file, &format!("{}\n", content));
         Result::Ok(())
     }
 
-    fn strip_shebang_if_found(content: &mut String) -> Option<String> {
-        // Can't use Option::map because of double borrow.
-        #[allow(clippy::manual_map)]
-        match Regex::new(r"^#!.*\n")
-            .expect("shebang regex didn't compile!")
+    fn strip_preamble_if_found(content: &mut String, placement: &Placement) -> Option<String> {
+        // Shebangs and emacs file variable lines (-*- mode: python -*-)
+        // have to stay at the top of the file. Emacs allows the file
+        // variable line to be the second line when the first is a
+        // shebang. Rust inner attributes (#![...]) aren't shebangs.
+        let mut end = Regex::new(r"^(#!(?:[^\[\n].*)?\n)?(.*-\*-.*-\*-.*\n)?")
+            .expect("preamble regex didn't compile!")
             .find(content)
-        {
-            // If we idenfied a shebang, strip it from content (we'll add it back at the end)
-            Some(shebang_match) => Some(content.drain(..shebang_match.end()).collect()),
-            None => None,
+            .map_or(0, |m| m.end());
+
+        // Magic comments such as Ruby's frozen_string_literal also have
+        // to stay within the first lines of the file.
+        if let Some(magic_comments) = placement.magic_comments {
+            for line in content[end..].split_inclusive('\n') {
+                if !magic_comments.is_match(line.trim_end()) {
+                    break;
+                }
+
+                end += line.len();
+            }
+        }
+
+        // Constructs such as Rust's inner attributes and module docs can
+        // be kept above the header with insert_after. Blank lines between
+        // them are skipped but blank lines after the last one stay below
+        // the header.
+        if let Some(insert_after) = placement.insert_after {
+            let mut pos = end;
+            loop {
+                let rest = &content[pos..];
+                let blank = rest.len() - rest.trim_start_matches(['\n', '\r', ' ', '\t']).len();
+                let blank = rest[..blank].rfind('\n').map_or(0, |i| i + 1);
+                match insert_after.match_at_start(&rest[blank..]) {
+                    Some(len) => {
+                        pos += blank + len;
+                        // Always consume through the end of the line.
+                        pos += content[pos..]
+                            .find('\n')
+                            .map_or(content.len() - pos, |i| i + 1);
+                        end = pos;
+                    }
+                    None => break,
+                }
+            }
+        }
+
+        // If we idenfied a preamble, strip it from content (we'll add it back at the end)
+        match end {
+            0 => None,
+            _ => Some(content.drain(..end).collect()),
         }
     }
 
+    /// Split content before a trailing emacs "Local Variables:" block,
+    /// which must never be touched by header replacements. Emacs only
+    /// looks for the block in the last 3000 characters of a file.
+    fn split_local_variables(content: &str) -> (&str, &str) {
+        let idx = match content.rfind("Local Variables:") {
+            Some(idx) if idx + 3000 >= content.len() => idx,
+            _ => return (content, ""),
+        };
+
+        let line_start = content[..idx].rfind('\n').map_or(0, |i| i + 1);
+        content.split_at(line_start)
+    }
+
     fn check_if_outdated(
         &self,
         templ: &Template,
@@ -105,133 +1027,2275 @@ impl Licensure {
         content: &str,
         header: &str,
     ) -> Option<String> {
-        let outdated_re = templ.outdated_license_pattern(commenter);
-        println!("{}", content);
-        println!("{:?}", outdated_re);
-        if outdated_re.is_match(content) {
-            return Some(outdated_re.replace(content, header).to_string());
-        }
+        let (content, local_variables) = Self::split_local_variables(content);
+        let (range, trimmed) = Self::find_header(templ, commenter, content)?;
+        Some(Self::replace_header(
+            content,
+            range,
+            trimmed,
+            header,
+            local_variables,
+        ))
+    }
+
+    /// Replace the header of templ in content which is wrapped
+    /// differently than header, e.g. because the columns of its comment
+    /// config changed, with header.
+    fn rewrap_header(
+        &self,
+        templ: &Template,
+        commenter: &dyn Comment,
+        content: &str,
+        header: &str,
+    ) -> Option<String> {
+        let (content, local_variables) = Self::split_local_variables(content);
+        let m = templ.rewrapped_license_pattern(commenter).find(content)?;
+        let range = Self::include_banners(templ, commenter, content, m.range());
+        Some(Self::replace_header(
+            content,
+            range,
+            true,
+            header,
+            local_variables,
+        ))
+    }
+
+    // Content with range replaced by header. The blank lines following a
+    // header only found ignoring whitespace, when trimmed is true, are
+    // replaced by those of header.
+    fn replace_header(
+        content: &str,
+        range: Range<usize>,
+        trimmed: bool,
+        header: &str,
+        local_variables: &str,
+    ) -> String {
+        let rest = match trimmed {
+            true => trim_leading_blank_lines(&content[range.end..]),
+            false => &content[range.end..],
+        };
+        format!(
+            "{}{}{}{}",
+            &content[..range.start],
+            header,
+            rest,
+            local_variables
+        )
+    }
+
+    /// Find the header of templ in content, in any year, including any
+    /// banner lines directly around it. The second value is true when the
+    /// header was only found ignoring whitespace changes, in which case
+    /// the range doesn't include its final newline.
+    fn find_header(
+        templ: &Template,
+        commenter: &dyn Comment,
+        content: &str,
+    ) -> Option<(Range<usize>, bool)> {
+        if let Some(m) = templ.outdated_license_pattern(commenter).find(content) {
+            let range = Self::include_banners(templ, commenter, content, m.range());
+            return Some((range, false));
+        }
+
+        // Account for possible whitespace changes
+        let m = templ
+            .outdated_license_trimmed_pattern(commenter)
+            .find(content)?;
+        Some((
+            Self::include_banners(templ, commenter, content, m.range()),
+            true,
+        ))
+    }
+
+    /// Widen range to include the banner lines of templ directly above
+    /// and below it, keeping whether it ends with a newline.
+    fn include_banners(
+        templ: &Template,
+        commenter: &dyn Comment,
+        content: &str,
+        range: Range<usize>,
+    ) -> Range<usize> {
+        let Range { mut start, mut end } = range;
+        while start > 0 && content[..start].ends_with('\n') {
+            let line_start = content[..start - 1].rfind('\n').map_or(0, |i| i + 1);
+            if !templ.is_banner(commenter, &content[line_start..start]) {
+                break;
+            }
+
+            start = line_start;
+        }
+
+        let with_newline = content[..end].ends_with('\n');
+        loop {
+            // Trailing whitespace left out of a trimmed match.
+            let rest = content[end..].trim_start_matches([' ', '\t']);
+            let line_start = if content[..end].ends_with('\n') {
+                end
+            } else if rest.starts_with('\n') {
+                content.len() - rest.len() + 1
+            } else {
+                break;
+            };
+            let line_end = content[line_start..]
+                .find('\n')
+                .map_or(content.len(), |i| line_start + i + 1);
+            if line_start == line_end || !templ.is_banner(commenter, &content[line_start..line_end])
+            {
+                break;
+            }
+
+            end = match content[..line_end].strip_suffix('\n') {
+                Some(line) if !with_newline => line.len(),
+                _ => line_end,
+            };
+        }
+
+        start..end
+    }
+
+    fn add_header(
+        &self,
+        mut header: String,
+        content: &mut String,
+        placement: &Placement,
+    ) -> String {
+        match placement.position {
+            Position::Top => (),
+            Position::Bottom => return Self::append_header(&header, content),
+            Position::AfterLine(n) => return Self::insert_header_after_line(&header, content, n),
+        }
+
+        if let Some(value) = Self::strip_preamble_if_found(content, placement) {
+            header.insert_str(0, &"\n".repeat(placement.spacing.leading_lines));
+            header.insert_str(0, &value);
+        }
+
+        if placement.spacing.exact_trailing_lines {
+            header.push_str(trim_leading_blank_lines(content));
+        } else {
+            header.push_str(content);
+        }
+
+        header
+    }
+
+    /// Add header at the end of content separated by a blank line. An
+    /// emacs Local Variables block stays at the very end.
+    fn append_header(header: &str, content: &str) -> String {
+        let (body, _) = Self::split_local_variables(content);
+        // The block starts at the beginning of the line containing
+        // "Local Variables:", after its comment characters.
+        let split = if body.len() == content.len() {
+            body.len()
+        } else {
+            body.rfind('\n').map_or(0, |i| i + 1)
+        };
+        let (body, local_variables) = content.split_at(split);
+
+        let mut out = body.trim_end_matches(['\n', '\r']).to_string();
+        if !out.is_empty() {
+            out.push_str("\n\n");
+        }
+        out.push_str(header.trim_end_matches('\n'));
+        out.push('\n');
+        if !local_variables.is_empty() {
+            out.push('\n');
+            out.push_str(local_variables);
+        }
+
+        out
+    }
+
+    /// Add header after the first n lines of content.
+    fn insert_header_after_line(header: &str, content: &str, n: usize) -> String {
+        let end: usize = content.split_inclusive('\n').take(n).map(str::len).sum();
+        let mut out = content[..end].to_string();
+        if !out.is_empty() && !out.ends_with('\n') {
+            out.push('\n');
+        }
+        out.push_str(header);
+        out.push_str(&content[end..]);
+        out
+    }
+
+    fn placement(&self, file: &str) -> Placement<'_> {
+        Placement {
+            magic_comments: self.config.comments.get_magic_comments(file),
+            insert_after: self.config.comments.get_insert_after(file),
+            spacing: self.config.comments.get_spacing(file),
+            position: self
+                .config
+                .licenses
+                .get_placement(file)
+                .or_else(|| self.config.comments.get_placement(file))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Find the license template and commenter to use for file. The
+    /// license with ident license is used instead of the one matching
+    /// file if it's given and configured.
+    fn resolve(&self, file: &String, license: Option<&str>) -> Result<Option<Resolved>, io::Error> {
+        let templ = match license {
+            Some(ident) => self
+                .config
+                .licenses
+                .get_template_by_ident(ident, file)
+                .or_else(|| {
+                    warning!(
+                        "{} has a pragma for license {} which isn't configured, using the license matching its path",
+                        file, ident
+                    );
+                    self.config.licenses.get_template(file)
+                }),
+            None => self.config.licenses.get_template(file),
+        };
+        let templ = match templ {
+            Some(t) => t,
+            None => {
+                info!("skipping {} because no license config matched.", file);
+                return Ok(None);
+            }
+        };
+
+        let commenter: Box<dyn Comment> = if self.uses_sidecar(file)? {
+            Box::new(PlainText)
+        } else {
+            self.config.comments.get_commenter(file)
+        };
+        self.prepare(file, templ, commenter).map(Some)
+    }
+
+    /// Render the commented header licensure expects for file, which
+    /// doesn't need to exist. The license can be chosen by its ident
+    /// instead of the files patterns and the configured width of the
+    /// comment can be overridden.
+    pub fn render_header(
+        &self,
+        file: &str,
+        ident: Option<&str>,
+        columns: Option<usize>,
+    ) -> Result<String, io::Error> {
+        let templ = match ident {
+            Some(ident) => self.config.licenses.get_template_by_ident(ident, file),
+            None => self.config.licenses.get_template(file),
+        };
+        let templ = templ.ok_or_else(|| match ident {
+            Some(ident) => {
+                io::Error::other(format!("no license with ident {} is configured", ident))
+            }
+            None => io::Error::other(format!("no license config matched {}", file)),
+        })?;
+
+        let commenter = match columns {
+            Some(columns) => self
+                .config
+                .comments
+                .get_commenter_with_columns(file, columns),
+            None => self.config.comments.get_commenter(file),
+        };
+
+        let (templ, commenter) = self.prepare(file, templ, commenter)?;
+        Ok(commenter.comment(&templ.render()))
+    }
+
+    /// Run the tests of every license entry, returning a description of
+    /// each failure.
+    pub fn run_template_tests(&self) -> Result<Vec<String>, io::Error> {
+        let mut failures = Vec::new();
+        for (ident, test, templ) in self.config.licenses.template_tests() {
+            let templ = match test.today.as_deref().map(parse_date) {
+                Some(Ok(today)) => templ.set_clock(Arc::new(FixedClock(today))),
+                Some(Err(e)) => {
+                    failures.push(format!("{} test for {}: {}", ident, test.file, e));
+                    continue;
+                }
+                None => templ,
+            };
+
+            let commenter = self.config.comments.get_commenter(&test.file);
+            let (templ, commenter) = self.prepare(&test.file, templ, commenter)?;
+            let rendered = commenter.comment(&templ.render());
+
+            if let Some(first_line) = &test.first_line {
+                let actual = rendered.lines().next().unwrap_or_default();
+                if actual != first_line {
+                    failures.push(format!(
+                        "{} test for {}: expected the first line {:?} but got {:?}",
+                        ident, test.file, first_line, actual
+                    ));
+                }
+            }
+
+            if let Some(expected) = &test.expected {
+                let expected = format!("{}\n", expected.trim_end_matches('\n'));
+                let actual = format!("{}\n", rendered.trim_end_matches('\n'));
+                if actual != expected {
+                    failures.push(format!(
+                        "{} test for {}: the header differs from expected\n{}",
+                        ident,
+                        test.file,
+                        unified_diff(&expected, &actual, "expected", "rendered", 3).trim_end()
+                    ));
+                }
+            }
+        }
+
+        Ok(failures)
+    }
+
+    fn prepare(
+        &self,
+        file: &str,
+        templ: Template,
+        commenter: Box<dyn Comment>,
+    ) -> Result<Resolved, io::Error> {
+        let templ = templ.set_variables(self.config.variables.clone());
+        let templ = match &self.release {
+            Some(release) => release.stamp(templ),
+            None => templ,
+        };
+        let templ = match commenter.line_marker() {
+            Some(marker) => templ
+                .strip_comment_markers(marker)
+                .map_err(|e| io::Error::other(format!("{}: {}", file, e)))?,
+            None => templ,
+        };
+
+        Ok((templ, commenter))
+    }
+
+    fn add_license_header(
+        &self,
+        file: &String,
+        content: &mut String,
+        license: Option<&str>,
+    ) -> Result<LicenseStatus, io::Error> {
+        let (templ, commenter) = match self.resolve(file, license)? {
+            Some(resolved) => resolved,
+            None => return Ok(LicenseStatus::NoConfigMatched),
+        };
+        // The trailing lines can depend on what follows the header, which
+        // doesn't change the markers prepare stripped from the template.
+        let commenter = match self.uses_sidecar(file)? {
+            true => commenter,
+            false => self
+                .config
+                .comments
+                .get_commenter_for_content(file, content)
+                .unwrap_or(commenter),
+        };
+        self.add_header_of(file, content, templ, commenter.as_ref())
+    }
+
+    fn add_header_of(
+        &self,
+        file: &String,
+        content: &mut String,
+        templ: Template,
+        commenter: &dyn Comment,
+    ) -> Result<LicenseStatus, io::Error> {
+        let templ = match templ.existing_date(commenter, content) {
+            Some(date) => templ.set_date(date),
+            None => templ,
+        };
+        // Headers keep the release they were stamped for until the next.
+        let templ = match (&self.release, templ.existing_version(commenter, content)) {
+            (None, Some(version)) => templ.set_version(version),
+            _ => templ,
+        };
+        let templ = match templ.year_mode() {
+            YearMode::Current => templ,
+            _ => match templ.existing_years(commenter, content) {
+                Some(years) => templ.apply_year_mode(&years),
+                None => templ,
+            },
+        };
+
+        let uncommented = templ.render();
+        let header = commenter.comment(&uncommented);
+        let strict = templ.strict_bytes();
+        // Headers are the same with or without trailing whitespace.
+        if content.contains(&header)
+            || (!strict && trim_line_ends(content).contains(trim_line_ends(&header).trim_end()))
+        {
+            info!("{} already licensed", file);
+            return Ok(LicenseStatus::AlreadyLicensed);
+        }
+
+        if self.explain_diff {
+            self.print(
+                file,
+                &format!("{}\n", Self::explain_difference(file, content, &header)),
+            );
+        }
+
+        if let Some(update) = self.check_if_outdated(&templ, commenter, content, &header) {
+            info!("{} licensed, but year is outdated", file);
+            if strict {
+                warning!("{}", describe_byte_difference(file, content, &update));
+            }
+            return Ok(LicenseStatus::NeedsUpdate(update));
+        }
+
+        for replaced in templ.replaces() {
+            if let Some(update) = self.check_if_outdated(replaced, commenter, content, &header) {
+                info!("{} has a replaced {} header", file, replaced.ident());
+                return Ok(LicenseStatus::NeedsUpdate(update));
+            }
+        }
+
+        if let Some(update) = self.rewrap_header(&templ, commenter, content, &header) {
+            info!("{} licensed, but its header is wrapped differently", file);
+            if strict {
+                warning!("{}", describe_byte_difference(file, content, &update));
+                return Ok(LicenseStatus::NeedsUpdate(update));
+            }
+            return Ok(LicenseStatus::NeedsFormatFix(update));
+        }
+
+        let placement = self.placement(file);
+        if self.fix_format || strict {
+            if let Some(update) = Self::fix_header_format(content, &header, &placement) {
+                info!("{} licensed, but header formatting differs", file);
+                if strict {
+                    warning!("{}", describe_byte_difference(file, content, &update));
+                    return Ok(LicenseStatus::NeedsUpdate(update));
+                }
+                return Ok(LicenseStatus::NeedsFormatFix(update));
+            }
+        }
+
+        if !self.force && self.has_similar_header(&uncommented, content) {
+            warning!(
+                "{} seems to already have its license header formatted differently, not adding another, reformat it or use --force",
+                file
+            );
+            return Ok(LicenseStatus::SimilarHeader);
+        }
+
+        if let Some(ident) = foreign::detect(content).filter(|_| !self.force) {
+            let configured = self.config.licenses.idents().contains(&ident.as_str());
+            if configured && foreign::spdx_ident(content).is_some() {
+                warning!(
+                    "{} has only an SPDX tag for {} instead of its configured header, not adding another",
+                    file,
+                    ident
+                );
+            } else {
+                warning!(
+                    "{} has a {} header which doesn't match any configured license, not adding another",
+                    file,
+                    ident
+                );
+            }
+            return Ok(LicenseStatus::UnknownLicense);
+        }
+
+        let (content_lines, header_lines) = (content.lines().count(), header.lines().count());
+        match templ.small_file_action(content_lines, header_lines) {
+            Some(SmallFiles::SpdxTag) => {
+                info!(
+                    "{} is too small for its full header, using an SPDX tag",
+                    file
+                );
+                return self.add_header_of(file, content, templ.spdx_tag_only(), commenter);
+            }
+            Some(SmallFiles::Skip) => {
+                warning!(
+                    "skipping {} because its {} line header would be too large for its {} line(s)",
+                    file,
+                    header_lines,
+                    content_lines
+                );
+                return Ok(LicenseStatus::TooSmall);
+            }
+            None => {}
+        }
+
+        Ok(LicenseStatus::NeedsHeader(
+            self.add_header(header, content, &placement),
+        ))
+    }
+
+    /// Whether content has the text of header, the uncommented header of
+    /// its license, or of any configured license when only the words of
+    /// the headers are compared.
+    fn has_similar_header(&self, header: &str, content: &str) -> bool {
+        foreign::contains_text(content, header)
+            || self
+                .config
+                .licenses
+                .static_templates()
+                .iter()
+                .any(|(_, templ)| {
+                    let rendered = templ
+                        .clone()
+                        .set_variables(self.config.variables.clone())
+                        .render();
+                    foreign::contains_text(content, &rendered)
+                })
+    }
+
+    /// Remember the authors of a file which had its header written so
+    /// they can be added to the contributors file of its license, if it
+    /// has one, once all files are processed.
+    fn record_signoff(&self, file: &String, license: Option<&str>) -> Result<(), io::Error> {
+        let contributors_file = match self.config.licenses.get_contributors_file(file) {
+            Some(f) => f,
+            None => return Ok(()),
+        };
+        let templ = match self.resolve(file, license)? {
+            Some((templ, _)) => templ,
+            None => return Ok(()),
+        };
+
+        self.signoffs
+            .lock()
+            .expect("signoffs lock was poisoned!")
+            .entry(contributors_file.to_string())
+            .or_default()
+            .extend(templ.authors().identities());
+        Ok(())
+    }
+
+    /// Add the recorded authors to their contributors files, keeping the
+    /// files sorted and free of duplicates.
+    fn write_signoffs(&self) -> Result<(), io::Error> {
+        let signoffs = self.signoffs.lock().expect("signoffs lock was poisoned!");
+        for (path, authors) in signoffs.iter() {
+            let existing = match self.source().read(path) {
+                Ok(content) => String::from_utf8_lossy(&content).into_owned(),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+                Err(e) => return Err(e),
+            };
+
+            let merged = merge_contributors(&existing, authors);
+            if merged != existing {
+                info!("updating contributors file {}", path);
+                self.source().write(path, merged.as_bytes())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// If the leading comment of content, after any preamble, has the same
+    /// text as header but is formatted differently return content with
+    /// that comment replaced by header.
+    fn fix_header_format(content: &str, header: &str, placement: &Placement) -> Option<String> {
+        // Only headers at the top are a leading comment.
+        if placement.position != Position::Top {
+            return None;
+        }
+
+        let mut rest = content.to_string();
+        let preamble = Self::strip_preamble_if_found(&mut rest, placement).unwrap_or_default();
+
+        let range = leading_comment_range(&rest)?;
+        let existing = extract_leading_comment(&rest[range.clone()])?;
+        let expected = extract_leading_comment(header)?;
+        if existing.is_empty() || !existing.split_whitespace().eq(expected.split_whitespace()) {
+            return None;
+        }
+
+        // The blank lines following the existing header are replaced by
+        // those of the configured header.
+        let after = trim_leading_blank_lines(&rest[range.end..]);
+        let leading = if !preamble.is_empty() && placement.spacing.leading_lines > 0 {
+            "\n".repeat(placement.spacing.leading_lines)
+        } else {
+            rest[..range.start].to_string()
+        };
+        Some(format!("{}{}{}{}", preamble, leading, header, after))
+    }
+
+    /// Describe how the existing header of a file differs from the
+    /// expected header. Comment characters are stripped from both before
+    /// comparing so the comparison is between the header text.
+    fn explain_difference(file: &str, content: &str, header: &str) -> String {
+        let existing = match extract_leading_comment(content) {
+            Some(existing) if !existing.is_empty() => existing,
+            _ => return format!("{}: no existing license header found", file),
+        };
+
+        let expected = extract_leading_comment(header).unwrap_or_else(|| header.to_string());
+        let diff = word_diff(&existing, &expected);
+        if !diff.contains("[-") && !diff.contains("{+") {
+            return format!(
+                "{}: header text matches but its wrapping or whitespace differs",
+                file
+            );
+        }
+
+        format!("{}: header differs from expected:\n{}", file, diff)
+    }
+
+    fn remove_license_header(
+        &self,
+        file: &String,
+        content: &str,
+        license: Option<&str>,
+    ) -> Result<LicenseStatus, io::Error> {
+        let (templ, commenter) = match self.resolve(file, license)? {
+            Some(resolved) => resolved,
+            None => return Ok(LicenseStatus::NoConfigMatched),
+        };
+
+        match Self::strip_header(&templ, commenter.as_ref(), content) {
+            Some(stripped) => {
+                info!("removing license header from {}", file);
+                Ok(LicenseStatus::NeedsUpdate(stripped))
+            }
+            None => {
+                info!("{} has no license header to remove", file);
+                Ok(LicenseStatus::NoHeaderFound)
+            }
+        }
+    }
+
+    fn strip_header(templ: &Template, commenter: &dyn Comment, content: &str) -> Option<String> {
+        let (range, trimmed) = Self::find_header(templ, commenter, content)?;
+        let rest = &content[range.end..];
+        // The trimmed pattern doesn't include the final newline of the
+        // header so remove it as well.
+        let rest = match trimmed {
+            true => rest.strip_prefix('\n').unwrap_or(rest),
+            false => rest,
+        };
+        Some(format!("{}{}", &content[..range.start], rest))
+    }
+}
+
+/// An inline override of the config for a single file, written in a
+/// comment near the top of it, e.g. `# licensure: ignore` or
+/// `// licensure: license=MIT`.
+#[derive(Debug, PartialEq)]
+enum Pragma {
+    Ignore,
+    License(String),
+}
+
+// Pragmas and generated markers are looked for in this many lines after
+// the leading comment, which is searched as a whole since the pragma may
+// have been merged into it when the header was added above.
+const PRAGMA_LINES: usize = 10;
+
+// The lines pragmas and generated markers are looked for in.
+fn head_lines(content: &str) -> impl Iterator<Item = &str> {
+    let header_end = leading_comment_range(content).map_or(0, |r| r.end);
+    content[..header_end]
+        .lines()
+        .chain(content[header_end..].lines().take(PRAGMA_LINES))
+}
+
+fn find_pragma(content: &str) -> Option<Pragma> {
+    let pragma_re = Regex::new(r"licensure:\s*(?:license=(?P<ident>[\w.+-]+)|ignore\b)")
+        .expect("pragma regex didn't compile!");
+    head_lines(content).find_map(|line| {
+        let caps = pragma_re.captures(line)?;
+        Some(match caps.name("ident") {
+            Some(ident) => Pragma::License(ident.as_str().to_string()),
+            None => Pragma::Ignore,
+        })
+    })
+}
+
+/// The first of markers found near the top of content, whose presence
+/// means the file is generated.
+fn find_generated_marker<'a>(content: &str, markers: &'a [String]) -> Option<&'a str> {
+    if markers.is_empty() {
+        return None;
+    }
+
+    head_lines(content).find_map(|line| {
+        markers
+            .iter()
+            .find(|marker| line.contains(marker.as_str()))
+            .map(String::as_str)
+    })
+}
+
+/// Make content end with exactly one newline, unless it's empty.
+fn ensure_trailing_newline(content: &str) -> Cow<'_, str> {
+    let trimmed = content.trim_end_matches('\n');
+    if content.is_empty() || trimmed.len() + 1 == content.len() {
+        return Cow::Borrowed(content);
+    }
+
+    Cow::Owned(format!("{}\n", trimmed))
+}
+
+/// Remove any blank lines from the start of content, whitespace on the
+/// first non-blank line is kept.
+fn trim_leading_blank_lines(content: &str) -> &str {
+    let trimmed = content.trim_start();
+    let line_start = content[..content.len() - trimmed.len()]
+        .rfind('\n')
+        .map_or(0, |i| i + 1);
+    &content[line_start..]
+}
+
+/// Add authors to the one per line contributors list in existing,
+/// returning the sorted and deduplicated result.
+fn merge_contributors(existing: &str, authors: &BTreeSet<String>) -> String {
+    let mut all: BTreeSet<&str> = existing
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect();
+    all.extend(authors.iter().map(String::as_str));
+
+    let mut out = all.into_iter().collect::<Vec<_>>().join("\n");
+    out.push('\n');
+    out
+}
+
+// How many bytes around the first difference describe_byte_difference
+// shows.
+const BYTE_DIFFERENCE_CONTEXT: usize = 20;
+
+/// Whether updated only differs from existing in its years, e.g. a year
+/// bumped or turned into a range, so that it's safe to apply unreviewed.
+fn is_year_only_change(existing: &str, updated: &str) -> bool {
+    let years =
+        Regex::new(r"\b[0-9]{4}(?:(?:, ?| ?- ?)[0-9]{4})*\b").expect("years regex didn't compile!");
+    existing != updated
+        && years.replace_all(existing, "[year]") == years.replace_all(updated, "[year]")
+}
+
+/// Describe the first byte at which the existing content of file differs
+/// from its expected content.
+fn describe_byte_difference(file: &str, existing: &str, expected: &str) -> String {
+    let (existing, expected) = (existing.as_bytes(), expected.as_bytes());
+    let offset = existing
+        .iter()
+        .zip(expected)
+        .position(|(a, b)| a != b)
+        .unwrap_or_else(|| existing.len().min(expected.len()));
+    let excerpt = |bytes: &[u8]| {
+        let end = bytes.len().min(offset + BYTE_DIFFERENCE_CONTEXT);
+        String::from_utf8_lossy(&bytes[offset..end]).into_owned()
+    };
+
+    format!(
+        "{}: header differs from the expected header at byte {}, expected {:?} but found {:?}",
+        file,
+        offset,
+        excerpt(expected),
+        excerpt(existing)
+    )
+}
+
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(BINARY_SNIFF_LEN)].contains(&0)
+}
+
+/// Parse the YYYY-MM-DD date used in place of today's date.
+fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|e| format!("invalid today {}, expected YYYY-MM-DD: {}", date, e))
+}
+
+/// Run a formatter in check mode against file. Every `{file}` in the
+/// command is replaced with the file path. Returns whether the formatter
+/// considers the file correctly formatted.
+fn verify_formatting(command: &VerifyCommand, file: &str) -> Result<bool, io::Error> {
+    let (program, args) = command.args(file);
+    let status = Command::new(program)
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    Ok(status.success())
+}
+
+// The directories whose files don't all have the same license, with
+// the files of each license in them.
+fn mixed_license_directories(
+    idents: &[(&str, &str)],
+) -> BTreeMap<String, BTreeMap<String, Vec<String>>> {
+    let mut dirs: BTreeMap<String, BTreeMap<String, Vec<String>>> = BTreeMap::new();
+    for (file, ident) in idents {
+        let dir = match Path::new(file).parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.display().to_string(),
+            _ => ".".to_string(),
+        };
+        dirs.entry(dir)
+            .or_default()
+            .entry(ident.to_string())
+            .or_default()
+            .push(file.to_string());
+    }
+
+    dirs.retain(|_, licenses| licenses.len() > 1);
+    dirs
+}
+
+#[derive(Serialize)]
+pub struct LicenseStats {
+    pub files_not_licensed: Vec<String>,
+    pub files_needing_license_update: Vec<String>,
+    pub files_skipped_binary: Vec<String>,
+    pub files_needing_format_fix: Vec<String>,
+    pub files_skipped_generated: Vec<String>,
+    pub files_skipped_encoding: Vec<String>,
+    // Files whose path isn't valid UTF-8, by their lossy names.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub files_skipped_bad_path: Vec<String>,
+    pub files_skipped_read_only: Vec<String>,
+    pub files_skipped_small: Vec<String>,
+    pub files_with_unknown_license: Vec<String>,
+    pub files_with_similar_header: Vec<String>,
+    // Files whose years were updated by a check run with fix_years.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub files_with_years_fixed: Vec<String>,
+    // What happened to each file and why.
+    pub files: BTreeMap<String, FileReport>,
+    // The config entries which applied to each file.
+    pub matched_rules: BTreeMap<String, MatchedRules>,
+    // Only filled in with --enforce-directory-consistency.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub mixed_license_directories: BTreeMap<String, BTreeMap<String, Vec<String>>>,
+    // Where the SPDX templates used in this run came from.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub templates: Vec<Provenance>,
+    // Every file that was in scope, i.e. not excluded, in the order
+    // they were given.
+    #[serde(skip)]
+    pub files_checked: Vec<String>,
+    #[serde(skip)]
+    pub summary: Summary,
+    // The conditions which fail the check, from check_fail_on.
+    #[serde(skip)]
+    pub fail_on: Vec<CheckCondition>,
+}
+
+/// What was done to a file, in check mode what would be done.
+#[derive(Clone, Copy, Serialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Added,
+    Updated,
+    Skipped,
+    Excluded,
+    AlreadyLicensed,
+}
+
+/// The action taken for a file and the config which led to it. Files
+/// whose header is added or updated include the lines replaced.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct FileReport {
+    pub action: Action,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<String>,
+}
+
+impl FileReport {
+    pub(crate) fn new(action: Action, reason: Option<&'static str>) -> FileReport {
+        FileReport {
+            action,
+            reason,
+            license: None,
+            comment: None,
+            old: None,
+            new: None,
+        }
+    }
+}
+
+/// The config entries which applied to a file, to debug which of
+/// several overlapping entries won.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct MatchedRules {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude_rule: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<LicenseEntry>,
+    pub comment: String,
+}
+
+/// A license entry by its index in the licenses section.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct LicenseEntry {
+    pub index: usize,
+    pub ident: String,
+}
+
+impl fmt::Display for MatchedRules {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(pattern) = &self.exclude {
+            write!(f, "exclude pattern {}, ", pattern)?;
+        }
+
+        if let Some(rule) = &self.exclude_rule {
+            write!(f, "exclude rule {}, ", rule)?;
+        }
+
+        match &self.license {
+            Some(entry) => write!(f, "licenses[{}] ({}), ", entry.index, entry.ident)?,
+            None => write!(f, "no license, ")?,
+        }
+
+        write!(f, "comment config {}", self.comment)
+    }
+}
+
+/// Counts of what happened to each file, in check mode these are what
+/// would happen.
+#[derive(Default, Serialize)]
+pub struct Summary {
+    pub scanned: usize,
+    pub excluded: usize,
+    pub already_licensed: usize,
+    pub added: usize,
+    pub updated: usize,
+    // Files with no matching config, binary files, generated files, files
+    // or paths which aren't UTF-8, read-only files, files which can't contain a
+    // comment with the skip policy, files with no header to remove, and
+    // files with the header of an unknown license or a header which
+    // seems to be theirs.
+    pub skipped: usize,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} scanned, {} excluded, {} already licensed, {} added, {} updated, {} skipped",
+            self.scanned,
+            self.excluded,
+            self.already_licensed,
+            self.added,
+            self.updated,
+            self.skipped
+        )
+    }
+}
+
+impl LicenseStats {
+    pub fn new() -> Self {
+        Self {
+            files_not_licensed: Vec::new(),
+            files_needing_license_update: Vec::new(),
+            files_skipped_binary: Vec::new(),
+            files_needing_format_fix: Vec::new(),
+            files_skipped_generated: Vec::new(),
+            files_skipped_encoding: Vec::new(),
+            files_skipped_bad_path: Vec::new(),
+            files_skipped_read_only: Vec::new(),
+            files_skipped_small: Vec::new(),
+            files_with_unknown_license: Vec::new(),
+            files_with_similar_header: Vec::new(),
+            files_with_years_fixed: Vec::new(),
+            files: BTreeMap::new(),
+            matched_rules: BTreeMap::new(),
+            mixed_license_directories: BTreeMap::new(),
+            templates: Vec::new(),
+            files_checked: Vec::new(),
+            summary: Summary::default(),
+            fail_on: CheckCondition::ALL.to_vec(),
+        }
+    }
+
+    /// Whether any problem was found, including those which don't fail
+    /// the check because of check_fail_on.
+    pub fn has_failures(&self) -> bool {
+        !self.conditions().is_empty()
+    }
+
+    /// Whether condition fails the check.
+    pub fn fails_on(&self, condition: CheckCondition) -> bool {
+        self.fail_on.contains(&condition)
+    }
+
+    /// Whether file, which needs a license update, is missing its header
+    /// or has an outdated one.
+    pub fn license_condition(&self, file: &str) -> CheckCondition {
+        match self.files.get(file).map(|report| report.action) {
+            Some(Action::Added) => CheckCondition::Missing,
+            _ => CheckCondition::Outdated,
+        }
+    }
+
+    /// The conditions found and how many files, or directories, have
+    /// each in the order of precedence of their exit codes.
+    pub fn conditions(&self) -> Vec<(CheckCondition, usize)> {
+        let missing = self
+            .files
+            .values()
+            .filter(|report| report.action == Action::Added)
+            .count();
+        let counts = [
+            missing,
+            self.files_needing_license_update.len() - missing,
+            self.files_not_licensed.len(),
+            self.files_needing_format_fix.len() + self.files_with_similar_header.len(),
+            self.mixed_license_directories.len(),
+            self.files_with_unknown_license.len(),
+        ];
+
+        CheckCondition::ALL
+            .into_iter()
+            .zip(counts)
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+
+    /// The exit code for the most important class of failure found among
+    /// the conditions which fail the check, files needing an update take
+    /// precedence over files with no matching config which take
+    /// precedence over formatting fixes, then directories with mixed
+    /// licenses, and files with unknown licenses come last.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.conditions()
+            .into_iter()
+            .find(|(condition, _)| self.fails_on(*condition))
+            .map(|(condition, _)| condition.exit_code())
+    }
+
+    fn record(&mut self, file: &str, status: LicenseStatus) {
+        self.summary.scanned += 1;
+        if !matches!(status, LicenseStatus::Excluded) {
+            self.files_checked.push(file.to_string());
+        }
+
+        let (action, reason) = status.action();
+        self.files
+            .insert(file.to_string(), FileReport::new(action, reason));
+
+        match status {
+            LicenseStatus::NeedsHeader(_) => {
+                self.summary.added += 1;
+                self.files_needing_license_update.push(file.to_string())
+            }
+            LicenseStatus::NeedsUpdate(_) => {
+                self.summary.updated += 1;
+                self.files_needing_license_update.push(file.to_string())
+            }
+            LicenseStatus::NeedsFormatFix(_) => {
+                self.summary.updated += 1;
+                self.files_needing_format_fix.push(file.to_string())
+            }
+            LicenseStatus::NoConfigMatched => {
+                self.summary.skipped += 1;
+                self.files_not_licensed.push(file.to_string())
+            }
+            LicenseStatus::Binary => {
+                self.summary.skipped += 1;
+                self.files_skipped_binary.push(file.to_string())
+            }
+            LicenseStatus::NoHeaderFound => self.summary.skipped += 1,
+            LicenseStatus::AlreadyLicensed => self.summary.already_licensed += 1,
+            LicenseStatus::Excluded => self.summary.excluded += 1,
+            LicenseStatus::Uncommentable => self.summary.skipped += 1,
+            LicenseStatus::Generated => {
+                self.summary.skipped += 1;
+                self.files_skipped_generated.push(file.to_string())
+            }
+            LicenseStatus::BadEncoding => {
+                self.summary.skipped += 1;
+                self.files_skipped_encoding.push(file.to_string())
+            }
+            LicenseStatus::BadPath => {
+                self.summary.skipped += 1;
+                self.files_skipped_bad_path.push(file.to_string())
+            }
+            LicenseStatus::ReadOnly => {
+                self.summary.skipped += 1;
+                self.files_skipped_read_only.push(file.to_string())
+            }
+            LicenseStatus::TooSmall => {
+                self.summary.skipped += 1;
+                self.files_skipped_small.push(file.to_string())
+            }
+            LicenseStatus::UnknownLicense => {
+                self.summary.skipped += 1;
+                self.files_with_unknown_license.push(file.to_string())
+            }
+            LicenseStatus::SimilarHeader => {
+                self.summary.skipped += 1;
+                self.files_with_similar_header.push(file.to_string())
+            }
+            LicenseStatus::YearsFixed => {
+                self.summary.updated += 1;
+                self.files_with_years_fixed.push(file.to_string())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    use crate::config::{Config, LineEnding, LinePatterns};
+    use crate::exit;
+    use crate::source::MemorySource;
+    use crate::template::test_context_with_range;
+    use crate::{
+        comments::LineComment,
+        template::{test_context, Template},
+    };
+
+    #[test]
+    fn test_detects_outdated_year() {
+        let l = Licensure::new(Config::default());
+        let templ = Template::new("License [year]\n\ntext", test_context("2024"));
+        let commenter = LineComment::new("#", None);
+        let header = commenter.comment(&templ.render());
+        let content = "# License 2020\n#\n# text";
+        let result = l.check_if_outdated(&templ, &commenter, content, &header);
+        assert!(result.is_some());
+    }
+
+    #[test]
+    fn test_detects_outdated_year_range() {
+        let l = Licensure::new(Config::default());
+        let templ = Template::new(
+            "License [year]\n\ntext",
+            test_context_with_range("2020", "2024"),
+        );
+        let commenter = LineComment::new("#", None);
+        let header = commenter.comment(&templ.render());
+        let content = "# License 2020, 2023\n#\n# text";
+        let result = l.check_if_outdated(&templ, &commenter, content, &header);
+        assert!(result.is_some());
+    }
+
+    #[test]
+    fn test_detects_outdated_year_range_when_previous_header_wasnt_a_range() {
+        let l = Licensure::new(Config::default());
+        let templ = Template::new(
+            "License [year]\n\ntext",
+            test_context_with_range("2020", "2024"),
+        );
+        let commenter = LineComment::new("#", None);
+        let header = commenter.comment(&templ.render());
+        let content = "# License 2020\n#\n# text";
+        let result = l.check_if_outdated(&templ, &commenter, content, &header);
+        assert!(result.is_some());
+    }
+
+    #[test]
+    fn test_detects_outdated_year_trailing_whitespace() {
+        let l = Licensure::new(Config::default());
+        let templ = Template::new("License [year]\n\ntext", test_context("2024"));
+        let commenter = LineComment::new("#", None);
+        let header = commenter.comment(&templ.render());
+        let content = "# License 2020\n#\n# text\n";
+        let result = l.check_if_outdated(&templ, &commenter, content, &header);
+        assert!(result.is_some());
+    }
+
+    #[test]
+    fn test_keeps_stamped_version() {
+        let templ = Template::new(
+            "License [year][if version]\n\nVersion [version][end]",
+            test_context("2024"),
+        );
+        let commenter = LineComment::new("#", None);
+        let file = "test.py".to_string();
+
+        let mut content = "# License 2024\n#\n# Version v1.0.0\n".to_string();
+        let status = Licensure::new(Config::default())
+            .add_header_of(&file, &mut content, templ.clone(), &commenter)
+            .unwrap();
+        assert!(matches!(status, LicenseStatus::AlreadyLicensed));
+
+        let stamped = Licensure::new(Config::default())
+            .with_release(Some(Release::new("v2.0.0", "2025")))
+            .add_header_of(
+                &file,
+                &mut content,
+                Release::new("v2.0.0", "2025").stamp(templ),
+                &commenter,
+            )
+            .unwrap();
+        match stamped {
+            LicenseStatus::NeedsUpdate(updated) => {
+                assert_eq!("# License 2025\n#\n# Version v2.0.0\n", updated)
+            }
+            _ => panic!("expected the header to be updated"),
+        }
+    }
+
+    #[test]
+    fn test_strict_bytes() {
+        let status = |strict_bytes: bool, content: &str| {
+            let config = test_config(
+                &format!(
+                    r#"strict_bytes: {}, template: "Copyright [year] [name of author]. All rights reserved.""#,
+                    strict_bytes
+                ),
+                RS_COMMENT,
+            );
+            Licensure::new(config)
+                .add_license_header(&"main.rs".to_string(), &mut content.to_string(), None)
+                .unwrap()
+        };
+
+        let exact = "// Copyright 2024 Bob. All rights reserved.\n\nfn main() {}\n";
+        assert!(matches!(
+            status(true, exact),
+            LicenseStatus::AlreadyLicensed
+        ));
+
+        let trailing_space = "// Copyright 2024 Bob. All rights reserved. \n\nfn main() {}\n";
+        assert!(matches!(
+            status(true, trailing_space),
+            LicenseStatus::NeedsUpdate(ref update) if update == exact
+        ));
+
+        let rewrapped = "// Copyright 2024 Bob.\n// All rights reserved.\n\nfn main() {}\n";
+        assert!(matches!(
+            status(false, rewrapped),
+            LicenseStatus::NeedsFormatFix(ref update) if update == exact
+        ));
+        assert!(matches!(
+            status(true, rewrapped),
+            LicenseStatus::NeedsUpdate(ref update) if update == exact
+        ));
+
+        let no_newline = "fn main() {}\n// Copyright 2024 Bob. All rights reserved.";
+        assert!(matches!(
+            status(false, no_newline),
+            LicenseStatus::AlreadyLicensed
+        ));
+        assert!(matches!(
+            status(true, no_newline),
+            LicenseStatus::NeedsUpdate(_)
+        ));
+    }
+
+    #[test]
+    fn test_strip_trailing_whitespace() {
+        let config = test_config(
+            r#"template: "Copyright [year] [name of author].  \n\nAll rights reserved.\n""#,
+            r##"{extension: py, strip_trailing_whitespace: true, commenter: {type: line, comment_char: "#", trailing_lines: 1}}"##,
+        );
+        let licensure = Licensure::new(config);
+
+        let mut content = "print()\n".to_string();
+        let status = licensure
+            .add_license_header(&"a.py".to_string(), &mut content, None)
+            .unwrap();
+        assert!(matches!(
+            status,
+            LicenseStatus::NeedsHeader(ref update) if update == "# Copyright 2024 Bob.\n#\n# All rights reserved.\n\nprint()\n"
+        ));
+
+        // Headers written with trailing whitespace are left as they are.
+        let mut content =
+            "# Copyright 2024 Bob.  \n# \n# All rights reserved.\n\nprint()\n".to_string();
+        let status = licensure
+            .add_license_header(&"a.py".to_string(), &mut content, None)
+            .unwrap();
+        assert!(matches!(status, LicenseStatus::AlreadyLicensed));
+    }
+
+    #[test]
+    fn test_trailing_lines_after() {
+        let config = test_config(
+            r#"template: "Copyright [year] [name of author].""#,
+            r#"{extension: c, trailing_lines_after: {code: 2, preprocessor: 1}, commenter: {type: block, start_block_char: "/*", end_block_char: "*/", trailing_lines: 0}}"#,
+        );
+        let licensure = Licensure::new(config);
+        let header = "/*Copyright 2024 Bob.*/\n";
+
+        for (content, expected) in [
+            ("#include <a.h>\n", "\n#include <a.h>\n"),
+            ("int a;\n", "\n\nint a;\n"),
+        ] {
+            let mut content = content.to_string();
+            let status = licensure
+                .add_license_header(&"a.c".to_string(), &mut content, None)
+                .unwrap();
+            let expected = format!("{}{}", header, expected);
+            assert!(matches!(
+                status,
+                LicenseStatus::NeedsHeader(ref update) if *update == expected
+            ));
+        }
+
+        // Existing headers followed by a different number of blank lines
+        // are still recognised.
+        let mut content = format!("{}\n\n\n#include <a.h>\n", header);
+        let status = licensure
+            .add_license_header(&"a.c".to_string(), &mut content, None)
+            .unwrap();
+        assert!(matches!(status, LicenseStatus::AlreadyLicensed));
+    }
+
+    #[test]
+    fn test_rewraps_header_when_columns_change() {
+        let config = test_config(
+            r#"template: "Copyright [year] [name of author]. Use of this source code is governed by the MIT license.""#,
+            r#"{extension: rs, columns: 40, commenter: {type: line, comment_char: "//", trailing_lines: 1}}"#,
+        );
+        let mut content = "// Copyright 2019 Bob. Use of this source code is governed by the MIT license.\n\nfn main() {}\n".to_string();
+        let status = Licensure::new(config)
+            .add_license_header(&"main.rs".to_string(), &mut content, None)
+            .unwrap();
+        assert!(matches!(
+            status,
+            LicenseStatus::NeedsFormatFix(ref update) if update == "// Copyright 2024 Bob. Use of this source\n// code is governed by the MIT license.\n\nfn main() {}\n"
+        ));
+    }
+
+    #[test]
+    fn test_license_files_similar_header() {
+        let content = b"/*\n * Copyright 2019 Bob\n */\n\nfn main() {}\n";
+        let (summary, licensed) = license_in_place("similar", content, |_| ());
+        assert_eq!(content.to_vec(), licensed);
+        assert!(
+            summary.ends_with("0 added, 0 updated, 1 skipped"),
+            "{}",
+            summary
+        );
+    }
+
+    #[test]
+    fn test_license_files_unknown_license() {
+        let content = b"// Licensed under the Apache License, Version 2.0\n\nfn main() {}\n";
+        let (summary, licensed) = license_in_place("unknown", content, |_| ());
+        assert_eq!(content.to_vec(), licensed);
+        assert!(
+            summary.ends_with("0 added, 0 updated, 1 skipped"),
+            "{}",
+            summary
+        );
+    }
+
+    #[test]
+    fn test_license_files_configured_spdx_tag() {
+        let content = b"// SPDX-License-Identifier: MIT\n\nfn main() {}\n";
+        let (summary, licensed) = license_in_place("spdx-tag", content, |_| ());
+        assert_eq!(content.to_vec(), licensed);
+        assert!(
+            summary.ends_with("0 added, 0 updated, 1 skipped"),
+            "{}",
+            summary
+        );
+    }
+
+    #[test]
+    fn test_ensure_trailing_newline() {
+        assert_eq!("", ensure_trailing_newline(""));
+        assert_eq!("a\n", ensure_trailing_newline("a"));
+        assert_eq!("a\n", ensure_trailing_newline("a\n"));
+        assert_eq!("a\n\nb\n", ensure_trailing_newline("a\n\nb\n\n\n"));
+        assert!(matches!(ensure_trailing_newline("a\n"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_license_files_trailing_newline() {
+        let (_, licensed) = license_in_place("eof", b"fn main() {}", |_| ());
+        assert_eq!(
+            b"// Copyright 2024 Bob\n\nfn main() {}\n".to_vec(),
+            licensed
+        );
+
+        let (_, licensed) = license_in_place("eof-crlf", b"fn main() {\r\n}\r\n\r\n", |_| ());
+        assert_eq!(
+            b"// Copyright 2024 Bob\r\n\r\nfn main() {\r\n}\r\n".to_vec(),
+            licensed
+        );
+
+        let (_, licensed) = license_in_place("eof-off", b"fn main() {}", |c| {
+            c.ensure_trailing_newline = false
+        });
+        assert_eq!(b"// Copyright 2024 Bob\n\nfn main() {}".to_vec(), licensed);
+    }
+
+    #[test]
+    fn test_describe_byte_difference() {
+        assert_eq!(
+            "a.rs: header differs from the expected header at byte 10, expected \"4\" but found \"0\"",
+            describe_byte_difference("a.rs", "// (C) 2020", "// (C) 2024")
+        );
+        assert_eq!(
+            "a.rs: header differs from the expected header at byte 2, expected \"\\n\" but found \"\"",
+            describe_byte_difference("a.rs", "//", "//\n")
+        );
+    }
+
+    #[test]
+    fn test_license_files_parallel_preserves_order() {
+        let files: Vec<String> = vec![
+            "src/main.rs",
+            "src/licensure.rs",
+            "src/template.rs",
+            "src/utils/mod.rs",
+            "README.md",
+        ]
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+
+        let stats = Licensure::new(Config::default())
+            .with_check_mode(true)
+            .with_jobs(3)
+            .license_files(&files)
+            .unwrap();
+
+        // README.md is excluded by the default config and no licenses are
+        // configured so every other file is reported as not licensed.
+        assert_eq!(&files[..4], stats.files_not_licensed.as_slice());
+        assert_eq!(
+            "5 scanned, 1 excluded, 0 already licensed, 0 added, 0 updated, 4 skipped",
+            stats.summary.to_string()
+        );
+    }
+
+    #[test]
+    fn test_license_files_parallel_fails_like_sequential() {
+        for jobs in [1, 3] {
+            let mut config = test_config(TEMPLATE, RS_COMMENT);
+            config.change_in_place = true;
+
+            let source = Arc::new(MemorySource::new(&[
+                ("a.rs", "fn a() {}\n"),
+                ("b.rs", "fn b() {}\n"),
+            ]));
+            let result = Licensure::new(config)
+                .with_source(Box::new(source.clone()))
+                .with_jobs(jobs)
+                .license_files(&["a.rs", "missing.rs", "b.rs", "gone.rs"]);
+
+            assert_eq!(
+                Some("missing.rs doesn't exist".to_string()),
+                result.err().map(|e| e.to_string())
+            );
+            assert_eq!(
+                Some("// Copyright 2024 Bob\n\nfn a() {}\n".to_string()),
+                source.content("a.rs")
+            );
+        }
+    }
+
+    #[test]
+    fn test_failed_run_leaves_no_journal() {
+        let dir =
+            std::env::temp_dir().join(format!("licensure-journal-run-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let source = Arc::new(MemorySource::new(&[("a.rs", "fn a() {}\n")]));
+        let license = |files: &[&str]| {
+            let mut config = test_config(TEMPLATE, RS_COMMENT);
+            config.change_in_place = true;
+            Licensure::new(config)
+                .with_source(Box::new(source.clone()))
+                .with_journal(Some(Journal::create(&dir).unwrap()))
+                .license_files(files)
+        };
+
+        assert!(license(&["missing.rs", "a.rs"]).is_err());
+        assert!(!dir.exists());
+        assert_eq!(1, license(&["a.rs"]).unwrap().summary.added);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_license_files_from_source() {
+        let mut config = test_config(TEMPLATE, RS_COMMENT);
+        config.change_in_place = true;
+
+        let source = Arc::new(MemorySource::new(&[
+            ("virtual/a.rs", "fn a() {}\n"),
+            ("virtual/b.rs", "// Copyright 2024 Bob\n\nfn b() {}\n"),
+        ]));
+        let files = source.list().unwrap();
+        let stats = Licensure::new(config)
+            .with_source(Box::new(source.clone()))
+            .license_files(&files)
+            .unwrap();
+
+        assert_eq!(1, stats.summary.added);
+        assert_eq!(1, stats.summary.already_licensed);
+        assert_eq!(
+            Some("// Copyright 2024 Bob\n\nfn a() {}\n".to_string()),
+            source.content("virtual/a.rs")
+        );
+        assert!(!Path::new("virtual").exists());
+    }
+
+    #[test]
+    fn test_license_files_skips_linguist_excluded() {
+        let mut config = test_config(TEMPLATE, RS_COMMENT);
+        config.use_gitattributes = true;
+        config.change_in_place = true;
+
+        let dir = std::env::temp_dir().join(format!("licensure-linguist-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join(".gitattributes"),
+            "gen/* linguist-generated\nvendor/** linguist-vendored\n",
+        )
+        .unwrap();
+        assert!(Command::new("git")
+            .arg("-C")
+            .arg(&dir)
+            .args(["init", "-q"])
+            .status()
+            .unwrap()
+            .success());
+
+        let source = Arc::new(MemorySource::new(&[
+            ("gen/a.rs", "fn a() {}\n"),
+            ("vendor/lib/b.rs", "fn b() {}\n"),
+            ("c.rs", "fn c() {}\n"),
+        ]));
+        let files = source.list().unwrap();
+        let stats = Licensure::new(config)
+            .with_source(Box::new(source.clone()))
+            .with_repo(Repo::discover(&dir).unwrap())
+            .license_files(&files)
+            .unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            "3 scanned, 2 excluded, 0 already licensed, 1 added, 0 updated, 0 skipped",
+            stats.summary.to_string()
+        );
+        assert_eq!(Some("fn a() {}\n".to_string()), source.content("gen/a.rs"));
+        assert_eq!(
+            Some("fn b() {}\n".to_string()),
+            source.content("vendor/lib/b.rs")
+        );
+        assert_eq!(
+            Some("// Copyright 2024 Bob\n\nfn c() {}\n".to_string()),
+            source.content("c.rs")
+        );
+    }
+
+    #[test]
+    fn test_license_files_below_bazel_loads() {
+        // BUILD files use the built in Starlark commenter.
+        let mut config = test_config(TEMPLATE, RS_COMMENT);
+        config.change_in_place = true;
+
+        let build = r#"load("@rules_cc//cc:defs.bzl", "cc_library")
+load(
+    "//tools:defs.bzl",
+    "lint",
+    "test_suite",
+)
+
+load("//:version.bzl", "VERSION")
+cc_library(name = "a")
+"#;
+        let source = Arc::new(MemorySource::new(&[("pkg/BUILD", build)]));
+        let files = source.list().unwrap();
+        let stats = Licensure::new(config)
+            .with_source(Box::new(source.clone()))
+            .license_files(&files)
+            .unwrap();
+
+        assert_eq!(1, stats.summary.added);
+        assert_eq!(
+            Some(
+                r#"load("@rules_cc//cc:defs.bzl", "cc_library")
+load(
+    "//tools:defs.bzl",
+    "lint",
+    "test_suite",
+)
+
+load("//:version.bzl", "VERSION")
+
+# Copyright 2024 Bob
+
+cc_library(name = "a")
+"#
+                .to_string()
+            ),
+            source.content("pkg/BUILD")
+        );
+
+        let stats = Licensure::new(test_config(TEMPLATE, RS_COMMENT))
+            .with_source(Box::new(source.clone()))
+            .with_check_mode(true)
+            .license_files(&files)
+            .unwrap();
+        assert_eq!(1, stats.summary.already_licensed);
+    }
+
+    #[test]
+    fn test_fix_years_in_check_mode() {
+        let config = test_config(TEMPLATE, RS_COMMENT);
+
+        let source = Arc::new(MemorySource::new(&[
+            ("a.rs", "fn a() {}\n"),
+            ("b.rs", "// Copyright 2020 Bob\n\nfn b() {}\n"),
+        ]));
+        let files = source.list().unwrap();
+        let stats = Licensure::new(config)
+            .with_source(Box::new(source.clone()))
+            .with_check_mode(true)
+            .with_fix_years(true)
+            .license_files(&files)
+            .unwrap();
+
+        assert_eq!(vec!["b.rs".to_string()], stats.files_with_years_fixed);
+        assert_eq!(vec!["a.rs".to_string()], stats.files_needing_license_update);
+        assert_eq!(
+            Some("// Copyright 2024 Bob\n\nfn b() {}\n".to_string()),
+            source.content("b.rs")
+        );
+        assert_eq!(Some("fn a() {}\n".to_string()), source.content("a.rs"));
+    }
+
+    #[test]
+    fn test_file_reports() {
+        let mut config = test_config(TEMPLATE, RS_COMMENT);
+        config.excludes.add_exclude(r"c\.rs");
+
+        let source = Arc::new(MemorySource::new(&[
+            ("a.rs", "fn a() {}\n"),
+            ("b.rs", "// Copyright 2020 Bob\n\nfn b() {}\n"),
+            ("c.rs", "fn c() {}\n"),
+            ("d.rs", "\0\u{1}\u{2}"),
+        ]));
+        let files = source.list().unwrap();
+        let stats = Licensure::new(config)
+            .with_source(Box::new(source))
+            .with_check_mode(true)
+            .license_files(&files)
+            .unwrap();
+
+        let added = &stats.files["a.rs"];
+        assert_eq!(Action::Added, added.action);
+        assert_eq!(Some("MIT"), added.license.as_deref());
+        assert_eq!(Some("comments[0] (rs)"), added.comment.as_deref());
+        assert_eq!(Some(""), added.old.as_deref());
+        assert_eq!(Some("// Copyright 2024 Bob\n\n"), added.new.as_deref());
+
+        let updated = &stats.files["b.rs"];
+        assert_eq!(Action::Updated, updated.action);
+        assert_eq!(Some("// Copyright 2020 Bob\n"), updated.old.as_deref());
+        assert_eq!(Some("// Copyright 2024 Bob\n"), updated.new.as_deref());
+
+        assert_eq!(Action::Excluded, stats.files["c.rs"].action);
+        assert_eq!(None, stats.files["c.rs"].old);
+        let skipped = &stats.files["d.rs"];
+        assert_eq!(Action::Skipped, skipped.action);
+        assert_eq!(Some("binary"), skipped.reason);
+    }
+
+    #[test]
+    fn test_is_year_only_change() {
+        assert!(is_year_only_change(
+            "// Copyright 2020 Bob\n",
+            "// Copyright 2020-2024 Bob\n"
+        ));
+        assert!(is_year_only_change(
+            "// Copyright 2020, 2022 Bob\n",
+            "// Copyright 2024 Bob\n"
+        ));
+        assert!(!is_year_only_change(
+            "// Copyright 2020 Bob\n",
+            "// Copyright 2024 Alice\n"
+        ));
+        assert!(!is_year_only_change(
+            "// Copyright 2024 Bob\n",
+            "// Copyright 2024 Bob\n"
+        ));
+    }
+
+    // The template of most tests.
+    const TEMPLATE: &str = r#"template: "Copyright [year] [name of author]""#;
+
+    // The comment config of most tests, line comments for .rs files.
+    const RS_COMMENT: &str =
+        r#"{extension: rs, commenter: {type: line, comment_char: "//", trailing_lines: 1}}"#;
+
+    // A config licensing every file as MIT for Bob in 2024. license is
+    // the rest of the license entry, at least its template, as YAML
+    // mapping entries and comment is the only comment config.
+    fn test_config(license: &str, comment: &str) -> Config {
+        serde_yaml::from_str(&format!(
+            r#"
+excludes: []
+licenses:
+  - {{files: any, ident: MIT, authors: [{{name: Bob}}], end_year: "2024", {}}}
+comments:
+  - {}
+"#,
+            license, comment
+        ))
+        .unwrap()
+    }
+
+    // License a file with content in place using the config changed by
+    // configure, returning the summary of the run and the new content of
+    // the file.
+    fn license_in_place(
+        name: &str,
+        content: &[u8],
+        configure: fn(&mut Config),
+    ) -> (String, Vec<u8>) {
+        let dir = std::env::temp_dir().join(format!("licensure-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("main.rs").to_string_lossy().to_string();
+        fs::write(&file, content).unwrap();
+
+        let mut config = test_config(TEMPLATE, RS_COMMENT);
+        config.change_in_place = true;
+        configure(&mut config);
+        let stats = Licensure::new(config)
+            .license_files(std::slice::from_ref(&file))
+            .unwrap();
+        let licensed = fs::read(&file).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+        (stats.summary.to_string(), licensed)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_license_files_through_symlink() {
+        let dir = std::env::temp_dir().join(format!("licensure-symlink-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("real")).unwrap();
+        fs::write(dir.join("real/a.rs"), "fn main() {}\n").unwrap();
+        let link = dir.join("link.rs");
+        std::os::unix::fs::symlink("real/a.rs", &link).unwrap();
+
+        let mut config = test_config(TEMPLATE, RS_COMMENT);
+        config.change_in_place = true;
+        Licensure::new(config)
+            .license_files(std::slice::from_ref(&link))
+            .unwrap();
+
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(
+            "// Copyright 2024 Bob\n\nfn main() {}\n",
+            fs::read_to_string(dir.join("real/a.rs")).unwrap()
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_signoffs_skip_read_only_files() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("licensure-signoff-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let contributors = dir.join("CONTRIBUTORS");
+        let file = dir.join("a.rs");
+        fs::write(&file, "fn main() {}\n").unwrap();
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o444)).unwrap();
+
+        let license = |config: Config| {
+            let mut config = config;
+            config.change_in_place = true;
+            Licensure::new(config)
+                .license_files(std::slice::from_ref(&file))
+                .unwrap()
+        };
+        let config = || {
+            test_config(
+                &format!(
+                    "{}, contributors_file: {}",
+                    TEMPLATE,
+                    contributors.display()
+                ),
+                RS_COMMENT,
+            )
+        };
+
+        let stats = license(config());
+        assert_eq!(1, stats.files_skipped_read_only.len());
+        assert!(!contributors.exists());
+
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o644)).unwrap();
+        license(config());
+        assert_eq!("Bob\n", fs::read_to_string(&contributors).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_license_files_keeps_bom() {
+        let (_, licensed) = license_in_place("bom", b"\xEF\xBB\xBFfn main() {}\n", |_| ());
+        assert_eq!(
+            b"\xEF\xBB\xBF// Copyright 2024 Bob\n\nfn main() {}\n".to_vec(),
+            licensed
+        );
+    }
+
+    #[test]
+    fn test_license_files_skips_bad_encoding() {
+        let latin1 = b"// caf\xE9\nfn main() {}\n";
+        let (summary, licensed) = license_in_place("latin1", latin1, |_| ());
+        assert!(summary.ends_with("1 skipped"), "{}", summary);
+        assert_eq!(latin1.to_vec(), licensed);
+
+        let utf16 = b"\xFF\xFEf\0n\0\n\0";
+        let (summary, licensed) = license_in_place("utf16", utf16, |_| ());
+        assert!(summary.ends_with("1 skipped"), "{}", summary);
+        assert_eq!(utf16.to_vec(), licensed);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_license_files_skips_bad_path() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        use std::path::PathBuf;
+
+        let stats = Licensure::new(Config::default())
+            .with_check_mode(true)
+            .license_files(&[PathBuf::from(OsStr::from_bytes(b"caf\xE9.rs"))])
+            .unwrap();
+        assert_eq!(
+            vec!["caf\u{FFFD}.rs".to_string()],
+            stats.files_skipped_bad_path
+        );
+        assert!(stats.files_skipped_encoding.is_empty());
+        assert_eq!(1, stats.summary.skipped);
+    }
+
+    #[test]
+    fn test_license_files_line_endings() {
+        let (_, licensed) = license_in_place("crlf", b"fn main() {\r\n}\r\n", |_| ());
+        assert_eq!(
+            b"// Copyright 2024 Bob\r\n\r\nfn main() {\r\n}\r\n".to_vec(),
+            licensed
+        );
 
-        // Account for possible whitespace changes
-        let trimmed_outdated_re = templ.outdated_license_trimmed_pattern(commenter);
-        if trimmed_outdated_re.is_match(content) {
-            Some(trimmed_outdated_re.replace(content, header).to_string())
-        } else {
-            None
-        }
+        // A CRLF header is detected.
+        let (summary, _) = license_in_place("crlf-licensed", &licensed, |_| ());
+        assert!(summary.contains("1 already licensed"), "{}", summary);
+
+        let (_, licensed) = license_in_place("lf", b"fn main() {\n}\n", |c| {
+            c.line_ending = LineEnding::Crlf
+        });
+        assert_eq!(
+            b"// Copyright 2024 Bob\r\n\r\nfn main() {\r\n}\r\n".to_vec(),
+            licensed
+        );
     }
 
-    fn add_header(&self, mut header: String, content: &mut String) -> String {
-        if let Some(value) = Self::strip_shebang_if_found(content) {
-            println!("Shebang: {}", value);
-            header.insert_str(0, &value);
-        }
+    #[test]
+    fn test_run_template_tests() {
+        let config: Config = serde_yaml::from_str(
+            r##"
+excludes: []
+licenses:
+  - files: any
+    ident: MIT
+    authors: [{name: Bob}]
+    template: "Copyright [year] [name of author]\nAll rights reserved.\n"
+    unwrap_text: false
+    tests:
+      - file: src/main.rs
+        today: 2021-06-01
+        first_line: // Copyright 2021 Bob
+        expected: |
+          // Copyright 2021 Bob
+          // All rights reserved.
+      - file: run.sh
+        today: 2021-06-01
+        first_line: "# Copyright 2020 Bob"
+      - file: src/lib.rs
+        today: June
+comments:
+  - extension: rs
+    commenter: {type: line, comment_char: "//", trailing_lines: 0}
+"##,
+        )
+        .unwrap();
 
-        header.push_str(content);
-        header
+        let failures = Licensure::new(config).run_template_tests().unwrap();
+        assert_eq!(
+            vec![
+                "MIT test for run.sh: expected the first line \"# Copyright 2020 Bob\" but got \"# Copyright 2021 Bob\"",
+                "MIT test for src/lib.rs: invalid today June, expected YYYY-MM-DD: input contains invalid characters",
+            ],
+            failures
+        );
     }
 
-    fn add_license_header(&mut self, file: &String, content: &mut String) -> LicenseStatus {
-        let templ = match self.config.licenses.get_template(file) {
-            Some(t) => t,
-            None => {
-                info!("skipping {} because no license config matched.", file);
-                return LicenseStatus::NoConfigMatched;
-            }
-        };
+    #[test]
+    fn test_mixed_license_directories() {
+        let idents = [
+            ("main.rs", "MIT"),
+            ("src/a.rs", "MIT"),
+            ("src/b.rs", "Apache-2.0"),
+            ("src/c.rs", "MIT"),
+            ("src/vendor/d.rs", "BSD-3-Clause"),
+        ];
+        let mixed = mixed_license_directories(&idents);
+        assert_eq!(vec!["src"], mixed.keys().collect::<Vec<_>>());
+        assert_eq!(
+            vec!["src/a.rs".to_string(), "src/c.rs".to_string()],
+            mixed["src"]["MIT"]
+        );
+        assert_eq!(vec!["src/b.rs".to_string()], mixed["src"]["Apache-2.0"]);
+    }
 
-        let commenter = self.config.comments.get_commenter(file);
+    #[test]
+    fn test_exit_code() {
+        let mut stats = LicenseStats::new();
+        assert_eq!(None, stats.exit_code());
+        stats.record("a.rs", LicenseStatus::NeedsFormatFix(String::new()));
+        assert_eq!(Some(exit::NEEDS_FORMAT_FIX), stats.exit_code());
+        stats.record("b.rs", LicenseStatus::NoConfigMatched);
+        assert_eq!(Some(exit::NOT_LICENSED), stats.exit_code());
+        stats.record("c.rs", LicenseStatus::NeedsHeader(String::new()));
+        assert_eq!(Some(exit::NEEDS_UPDATE), stats.exit_code());
+        assert_eq!(1, stats.summary.added);
+        assert_eq!(1, stats.summary.updated);
+    }
 
-        let uncommented = templ.render();
-        let header = commenter.comment(&uncommented);
-        if content.contains(&header) || content.contains(header.trim_end()) {
-            info!("{} already licensed", file);
-            return LicenseStatus::AlreadyLicensed;
-        }
+    #[test]
+    fn test_check_fail_on() {
+        let mut stats = LicenseStats::new();
+        stats.fail_on = vec![CheckCondition::Outdated, CheckCondition::Unconfigured];
+        stats.record("a.rs", LicenseStatus::NeedsHeader(String::new()));
+        stats.record("b.rs", LicenseStatus::NeedsFormatFix(String::new()));
+        assert!(stats.has_failures());
+        assert_eq!(None, stats.exit_code());
+        assert_eq!(
+            vec![(CheckCondition::Missing, 1), (CheckCondition::Format, 1)],
+            stats.conditions()
+        );
 
-        if let Some(update) = self.check_if_outdated(&templ, commenter.as_ref(), content, &header) {
-            info!("{} licensed, but year is outdated", file);
-            self.stats.files_needing_license_update.push(file.clone());
-            return LicenseStatus::NeedsUpdate(update);
-        }
+        stats.record("c.py", LicenseStatus::NoConfigMatched);
+        assert_eq!(Some(exit::NOT_LICENSED), stats.exit_code());
+        stats.record("d.rs", LicenseStatus::NeedsUpdate(String::new()));
+        assert_eq!(Some(exit::NEEDS_UPDATE), stats.exit_code());
+        assert_eq!(CheckCondition::Missing, stats.license_condition("a.rs"));
+        assert_eq!(CheckCondition::Outdated, stats.license_condition("d.rs"));
+    }
 
-        self.stats.files_needing_license_update.push(file.clone());
-        LicenseStatus::NeedsUpdate(self.add_header(header, content))
+    #[test]
+    fn test_is_binary() {
+        assert!(is_binary(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"));
+        assert!(!is_binary("fn main() {}\n".as_bytes()));
+        assert!(!is_binary(b""));
     }
-}
 
-pub struct LicenseStats {
-    pub files_not_licensed: Vec<String>,
-    pub files_needing_license_update: Vec<String>,
-}
+    #[test]
+    fn test_verify_formatting() {
+        let file = std::env::temp_dir().join(format!("licensure verify {}", std::process::id()));
+        fs::write(&file, "formatted\n").unwrap();
+        let file = file.to_str().unwrap();
+        let command = |yaml: &str| serde_yaml::from_str::<VerifyCommand>(yaml).unwrap();
 
-impl LicenseStats {
-    fn new() -> Self {
-        Self {
-            files_not_licensed: Vec::new(),
-            files_needing_license_update: Vec::new(),
-        }
-    }
-}
+        let formatted = r#"sh -c 'read line < "$1" && [ "$line" = formatted ]' sh {file}"#;
+        assert!(verify_formatting(&command(&format!("{:?}", formatted)), file).unwrap());
+        assert!(
+            verify_formatting(&command(r#"[sh, -c, '[ -f "$1" ]', sh, "{file}"]"#), file).unwrap()
+        );
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use crate::config::Config;
-    use crate::template::test_context_with_range;
-    use crate::{
-        comments::LineComment,
-        template::{test_context, Template},
-    };
+        fs::write(file, "unformatted\n").unwrap();
+        assert!(!verify_formatting(&command(&format!("{:?}", formatted)), file).unwrap());
+        assert!(verify_formatting(&command("this-command-does-not-exist {file}"), file).is_err());
+        fs::remove_file(file).unwrap();
+    }
 
     #[test]
-    fn test_detects_outdated_year() {
-        let l = Licensure::new(Config::default());
+    fn test_strip_header() {
         let templ = Template::new("License [year]\n\ntext", test_context("2024"));
         let commenter = LineComment::new("#", None);
-        let header = commenter.comment(&templ.render());
-        let content = "# License 2020\n#\n# text";
-        let result = l.check_if_outdated(&templ, &commenter, content, &header);
-        assert!(result.is_some());
+        let content = "#!/bin/sh\n# License 2020\n#\n# text\necho hello\n";
+        assert_eq!(
+            Some("#!/bin/sh\necho hello\n".to_string()),
+            Licensure::strip_header(&templ, &commenter, content)
+        );
     }
 
     #[test]
-    fn test_detects_outdated_year_range() {
+    fn test_header_banners() {
         let l = Licensure::new(Config::default());
-        let templ = Template::new(
-            "License [year]\n\ntext",
-            test_context_with_range("2020", "2024"),
-        );
+        let templ = Template::new("License [year]\n\ntext", test_context("2024"))
+            .set_banners(LinePatterns::from(vec!["^-{4,}$".to_string()]));
         let commenter = LineComment::new("#", None);
         let header = commenter.comment(&templ.render());
-        let content = "# License 2020, 2023\n#\n# text";
-        let result = l.check_if_outdated(&templ, &commenter, content, &header);
-        assert!(result.is_some());
+        let content = "#!/bin/sh\n# ----\n# License 2020\n#\n# text\n# --------\necho hello\n";
+        assert_eq!(
+            Some("#!/bin/sh\necho hello\n".to_string()),
+            Licensure::strip_header(&templ, &commenter, content)
+        );
+        assert_eq!(
+            Some("#!/bin/sh\n# License 2024\n#\n# text\necho hello\n".to_string()),
+            l.check_if_outdated(&templ, &commenter, content, &header)
+        );
+
+        // Whitespace changes and banners which aren't adjacent.
+        let content = "# ----\n\n# License 2020\n#\n# text  \n# ----\n\necho hello\n";
+        assert_eq!(
+            Some("# ----\n\n\necho hello\n".to_string()),
+            Licensure::strip_header(&templ, &commenter, content)
+        );
     }
 
     #[test]
-    fn test_detects_outdated_year_range_when_previous_header_wasnt_a_range() {
-        let l = Licensure::new(Config::default());
-        let templ = Template::new(
-            "License [year]\n\ntext",
-            test_context_with_range("2020", "2024"),
+    fn test_strip_header_trimmed() {
+        let templ = Template::new("License [year]\n\ntext", test_context("2024"));
+        let commenter = LineComment::new("#", None).set_trailing_lines(1);
+        let content = "# License 2020\n#\n# text\necho hello\n";
+        assert_eq!(
+            Some("echo hello\n".to_string()),
+            Licensure::strip_header(&templ, &commenter, content)
         );
-        let commenter = LineComment::new("#", None);
-        let header = commenter.comment(&templ.render());
-        let content = "# License 2020\n#\n# text";
-        let result = l.check_if_outdated(&templ, &commenter, content, &header);
-        assert!(result.is_some());
     }
 
     #[test]
-    fn test_detects_outdated_year_trailing_whitespace() {
-        let l = Licensure::new(Config::default());
+    fn test_strip_header_not_found() {
         let templ = Template::new("License [year]\n\ntext", test_context("2024"));
         let commenter = LineComment::new("#", None);
-        let header = commenter.comment(&templ.render());
-        let content = "# License 2020\n#\n# text\n";
-        let result = l.check_if_outdated(&templ, &commenter, content, &header);
-        assert!(result.is_some());
+        assert_eq!(
+            None,
+            Licensure::strip_header(&templ, &commenter, "echo hello\n")
+        );
+    }
+
+    #[test]
+    fn test_explain_difference() {
+        let header = "# License 2024\n#\n# text\n";
+        assert_eq!(
+            "a.py: header differs from expected:\nLicense [-2020-] {+2024+} text",
+            Licensure::explain_difference("a.py", "# License 2020\n#\n# text\nimport os\n", header)
+        );
+        assert_eq!(
+            "a.py: header text matches but its wrapping or whitespace differs",
+            Licensure::explain_difference("a.py", "# License 2024 text\nimport os\n", header)
+        );
+        assert_eq!(
+            "a.py: no existing license header found",
+            Licensure::explain_difference("a.py", "import os\n", header)
+        );
+    }
+
+    #[test]
+    fn test_find_pragma() {
+        assert_eq!(
+            Some(Pragma::Ignore),
+            find_pragma("#!/bin/sh\n# licensure: ignore\necho\n")
+        );
+        assert_eq!(
+            Some(Pragma::License("Apache-2.0".to_string())),
+            find_pragma("// licensure: license=Apache-2.0\nfn main() {}\n")
+        );
+        // A header added above the pragma doesn't hide it.
+        let header = "# License\n#\n".repeat(20);
+        assert_eq!(
+            Some(Pragma::Ignore),
+            find_pragma(&format!("{}# licensure: ignore\n", header))
+        );
+        assert_eq!(None, find_pragma("fn main() {}\n"));
+        assert_eq!(
+            None,
+            find_pragma(&format!("{}# licensure: ignore\n", "x\n".repeat(20)))
+        );
+    }
+
+    #[test]
+    fn test_find_generated_marker() {
+        let markers = Config::default().skip_if_contains;
+        assert_eq!(
+            Some("DO NOT EDIT"),
+            find_generated_marker(
+                "// Code generated by protoc-gen-go. DO NOT EDIT.\npackage pb\n",
+                &markers
+            )
+        );
+        assert_eq!(
+            Some("@generated"),
+            find_generated_marker(
+                "/*\n * Copyright 2024\n *\n * @generated\n */\nint x;\n",
+                &markers
+            )
+        );
+        let far = format!("fn main() {{}}\n{}// DO NOT EDIT\n", "\n".repeat(20));
+        assert_eq!(None, find_generated_marker(&far, &markers));
+        assert_eq!(None, find_generated_marker("// DO NOT EDIT\n", &[]));
+    }
+
+    #[test]
+    fn test_merge_contributors() {
+        let authors: BTreeSet<String> = vec!["Bob <bob@example.com>", "Alice"]
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        assert_eq!(
+            "Alice\nBob <bob@example.com>\nCarol\n",
+            merge_contributors("Carol\nBob <bob@example.com>\n\n", &authors)
+        );
+        assert_eq!(
+            "Alice\nBob <bob@example.com>\n",
+            merge_contributors("", &authors)
+        );
+    }
+
+    #[test]
+    fn test_render_header() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+excludes: []
+licenses:
+  - files: any
+    ident: MIT
+    authors:
+      - name: Jane
+    end_year: "2024"
+    template: "[filename] Copyright [year] [name of author]."
+comments:
+  - extension: rs
+    columns: 80
+    commenter:
+      type: line
+      comment_char: "//"
+"#,
+        )
+        .unwrap();
+        let l = Licensure::new(config);
+        assert_eq!(
+            "// main.rs Copyright 2024 Jane.\n",
+            l.render_header("src/main.rs", None, None).unwrap()
+        );
+        assert_eq!(
+            "// file.rs Copyright 2024\n// Jane.\n",
+            l.render_header("file.rs", Some("MIT"), Some(24)).unwrap()
+        );
+        assert!(l.render_header("file.rs", Some("GPL"), None).is_err());
+    }
+
+    #[test]
+    fn test_matched_rules() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+excludes: ['.*\.json', 'vendor/.*']
+licenses:
+  - files: 'assets/.*'
+    ident: CC-BY-4.0
+    authors: [{name: Jane}]
+    template: "[year]"
+  - files: any
+    ident: MIT
+    authors: [{name: Jane}]
+    template: "[year]"
+comments:
+  - extension: rs
+    commenter: {type: line, comment_char: "//"}
+"#,
+        )
+        .unwrap();
+        let l = Licensure::new(config);
+
+        let rules = l.matched_rules("assets/main.rs");
+        assert_eq!(
+            Some(LicenseEntry {
+                index: 0,
+                ident: "CC-BY-4.0".to_string()
+            }),
+            rules.license
+        );
+        assert_eq!(
+            "licenses[0] (CC-BY-4.0), comment config comments[0] (rs)",
+            rules.to_string()
+        );
+
+        let rules = l.matched_rules("vendor/lib.py");
+        assert_eq!(Some("vendor/.*".to_string()), rules.exclude);
+        assert_eq!("built in for py", rules.comment);
+    }
+
+    #[test]
+    fn test_replaces_templates() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+excludes: []
+licenses:
+  - files: any
+    ident: MIT
+    authors: [{name: Jane}]
+    end_year: "2024"
+    template: "Copyright [year] [name of author], [ident] licensed."
+    replaces_templates:
+      - ident: Apache-2.0
+      - template: "Copyright [year] Old Corp. All rights reserved."
+  - files: none
+    ident: Apache-2.0
+    authors: [{name: Old Corp}]
+    template: "Licensed under the [ident] license by [name of author], [year]."
+comments:
+  - extension: rs
+    commenter: {type: line, comment_char: "//", trailing_lines: 1}
+"#,
+        )
+        .unwrap();
+        let l = Licensure::new(config);
+        let update = |content: &str| match l
+            .add_license_header(&"main.rs".to_string(), &mut content.to_string(), None)
+            .unwrap()
+        {
+            LicenseStatus::NeedsUpdate(update) => Some(update),
+            _ => None,
+        };
+
+        let expected = "// Copyright 2024 Jane, MIT licensed.\n\nfn main() {}\n";
+        assert_eq!(
+            Some(expected.to_string()),
+            update("// Licensed under the Apache-2.0 license by Old Corp, 2019.\n\nfn main() {}\n")
+        );
+        assert_eq!(
+            Some(expected.to_string()),
+            update("// Copyright 2015, 2020 Old Corp. All rights reserved.\n\nfn main() {}\n")
+        );
+        assert_eq!(
+            None,
+            update("// Copyright 2020 New Corp.\n\nfn main() {}\n")
+        );
+    }
+
+    #[test]
+    fn test_fix_header_format() {
+        let header = "# License 2024 with some\n# text\n\n";
+        assert_eq!(
+            Some("#!/bin/sh\n# License 2024 with some\n# text\n\necho hi\n".to_string()),
+            Licensure::fix_header_format(
+                "#!/bin/sh\n# License 2024\n# with some text   \n\n\n\necho hi\n",
+                header,
+                &Placement::default()
+            )
+        );
+
+        // Text changes aren't formatting fixes.
+        assert_eq!(
+            None,
+            Licensure::fix_header_format(
+                "# License 2020 with some text\necho hi\n",
+                header,
+                &Placement::default()
+            )
+        );
+        assert_eq!(
+            None,
+            Licensure::fix_header_format("echo hi\n", header, &Placement::default())
+        );
     }
 
     #[test]
@@ -248,7 +3312,7 @@ if __name__ == '__main__':
     main()
 "#
         .to_string();
-        let result = l.add_header(header, &mut content);
+        let result = l.add_header(header, &mut content, &Placement::default());
         assert_eq!(
             result,
             r#"# License 2024
@@ -291,13 +3355,198 @@ if __name__ == '__main__':
     main()
 "#;
 
-        let result = l.add_header(header, &mut content);
+        let result = l.add_header(header, &mut content, &Placement::default());
         println!("result: {}", result);
         println!("----------------------");
         println!("expected: {}", expected);
         assert_eq!(result, expected)
     }
 
+    #[test]
+    fn test_add_header_handles_emacs_mode_line() {
+        let l = Licensure::new(Config::default());
+        let templ = Template::new("License [year]", test_context("2024"));
+        let commenter = LineComment::new("#", None);
+        let header = commenter.comment(&templ.render());
+        let mut content =
+            "#!/usr/bin/env python3\n# -*- coding: utf-8 -*-\nprint('hi')\n".to_string();
+        assert_eq!(
+            "#!/usr/bin/env python3\n# -*- coding: utf-8 -*-\n# License 2024\nprint('hi')\n",
+            l.add_header(header, &mut content, &Placement::default())
+        );
+
+        let header = commenter.comment(&templ.render());
+        let mut content = ";; -*- mode: lisp -*-\n(defun f ())\n".to_string();
+        assert_eq!(
+            ";; -*- mode: lisp -*-\n# License 2024\n(defun f ())\n",
+            l.add_header(header, &mut content, &Placement::default())
+        );
+    }
+
+    #[test]
+    fn test_outdated_replacement_ignores_local_variables() {
+        let l = Licensure::new(Config::default());
+        let templ = Template::new("License [year]", test_context("2024"));
+        let commenter = LineComment::new("#", None);
+        let header = commenter.comment(&templ.render());
+        let content = "print('hi')\n# Local Variables:\n# License 2020\n# End:\n";
+        assert_eq!(
+            None,
+            l.check_if_outdated(&templ, &commenter, content, &header)
+        );
+
+        let content = "# License 2020\nprint('hi')\n# Local Variables:\n# License 2020\n# End:\n";
+        assert_eq!(
+            Some(
+                "# License 2024\nprint('hi')\n# Local Variables:\n# License 2020\n# End:\n"
+                    .to_string()
+            ),
+            l.check_if_outdated(&templ, &commenter, content, &header)
+        );
+    }
+
+    #[test]
+    fn test_add_header_handles_magic_comments() {
+        let l = Licensure::new(Config::default());
+        let templ = Template::new("License [year]", test_context("2024"));
+        let commenter = LineComment::new("#", None);
+        let header = commenter.comment(&templ.render());
+        let magic = LinePatterns::from(vec![
+            "^# frozen_string_literal:".to_string(),
+            "^# encoding:".to_string(),
+        ]);
+        let mut content = "#!/usr/bin/env ruby\n# frozen_string_literal: true\n# encoding: utf-8\n# A comment\nputs 'hi'\n".to_string();
+        assert_eq!(
+            "#!/usr/bin/env ruby\n# frozen_string_literal: true\n# encoding: utf-8\n# License 2024\n# A comment\nputs 'hi'\n",
+            l.add_header(
+                header,
+                &mut content,
+                &Placement {
+                    magic_comments: Some(&magic),
+                    ..Default::default()
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_add_header_handles_insert_after() {
+        let l = Licensure::new(Config::default());
+        let templ = Template::new("License [year]", test_context("2024"));
+        let commenter = LineComment::new("//", None);
+        let header = commenter.comment(&templ.render());
+        let insert_after =
+            LinePatterns::from(vec![r"#!\[(?s:.*?)\]$".to_string(), "//!.*".to_string()]);
+        let mut content =
+            "#![deny(\n    missing_docs,\n)]\n\n//! Crate docs\n\nfn main() {}\n#![not_leading]\n"
+                .to_string();
+        assert_eq!(
+            "#![deny(\n    missing_docs,\n)]\n\n//! Crate docs\n// License 2024\n\nfn main() {}\n#![not_leading]\n",
+            l.add_header(
+                header,
+                &mut content,
+                &Placement {
+                    insert_after: Some(&insert_after),
+                    ..Default::default()
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_add_header_after_front_matter() {
+        // The default config's commenter for any file would take
+        // precedence over the builtin one.
+        let config = serde_yaml::from_str("excludes: []\nlicenses: []\ncomments: []\n").unwrap();
+        let l = Licensure::new(config);
+        let templ = Template::new("License [year]\n", test_context("2024"));
+        let header = l
+            .config
+            .comments
+            .get_commenter("docs/index.md")
+            .comment(&templ.render());
+
+        let mut content = "---\ntitle: Home\ntags: [a, b]\n---\n\n# Home\n\n---\n".to_string();
+        assert_eq!(
+            "---\ntitle: Home\ntags: [a, b]\n---\n<!--\nLicense 2024 -->\n\n# Home\n\n---\n",
+            l.add_header(header.clone(), &mut content, &l.placement("docs/index.md"))
+        );
+
+        let mut content = "+++\ntitle = \"Home\"\n+++\n# Home\n".to_string();
+        assert_eq!(
+            "+++\ntitle = \"Home\"\n+++\n<!--\nLicense 2024 -->\n# Home\n",
+            l.add_header(header.clone(), &mut content, &l.placement("docs/index.md"))
+        );
+
+        // A horizontal rule which isn't at the start isn't front matter.
+        let mut content = "# Home\n\n---\n\ntext\n---\n".to_string();
+        assert_eq!(
+            "<!--\nLicense 2024 -->\n# Home\n\n---\n\ntext\n---\n",
+            l.add_header(header, &mut content, &l.placement("docs/index.md"))
+        );
+    }
+
+    #[test]
+    fn test_add_header_spacing() {
+        let l = Licensure::new(Config::default());
+        let header = "# License 2024\n\n".to_string();
+        let placement = Placement {
+            spacing: Spacing {
+                leading_lines: 1,
+                exact_trailing_lines: true,
+            },
+            ..Default::default()
+        };
+        let mut content = "#!/bin/sh\n\n\n\necho hi\n".to_string();
+        assert_eq!(
+            "#!/bin/sh\n\n# License 2024\n\necho hi\n",
+            l.add_header(header.clone(), &mut content, &placement)
+        );
+
+        let mut content = "\n  echo hi\n".to_string();
+        assert_eq!(
+            "# License 2024\n\n  echo hi\n",
+            l.add_header(header, &mut content, &placement)
+        );
+    }
+
+    #[test]
+    fn test_add_header_position() {
+        let l = Licensure::new(Config::default());
+        let header = "# License 2024\n".to_string();
+        let bottom = Placement {
+            position: Position::Bottom,
+            ..Default::default()
+        };
+        let mut content = "#!/bin/sh\necho hi\n\n".to_string();
+        assert_eq!(
+            "#!/bin/sh\necho hi\n\n# License 2024\n",
+            l.add_header(header.clone(), &mut content, &bottom)
+        );
+
+        let mut content = "echo hi\n# Local Variables:\n# mode: sh\n# End:\n".to_string();
+        assert_eq!(
+            "echo hi\n\n# License 2024\n\n# Local Variables:\n# mode: sh\n# End:\n",
+            l.add_header(header.clone(), &mut content, &bottom)
+        );
+
+        let after_line = Placement {
+            position: Position::AfterLine(2),
+            ..Default::default()
+        };
+        let mut content = "msgid \"\"\nmsgstr \"\"\n\"Language: de\"\n".to_string();
+        assert_eq!(
+            "msgid \"\"\nmsgstr \"\"\n# License 2024\n\"Language: de\"\n",
+            l.add_header(header.clone(), &mut content, &after_line)
+        );
+
+        let mut content = "one line".to_string();
+        assert_eq!(
+            "one line\n# License 2024\n",
+            l.add_header(header, &mut content, &after_line)
+        );
+    }
+
     #[test]
     fn test_add_header_ignores_shebang_in_middle_of_file() {
         let l = Licensure::new(Config::default());
@@ -327,7 +3576,7 @@ if __name__ == '__main__':
     main()
 "#;
 
-        let result = l.add_header(header, &mut content);
+        let result = l.add_header(header, &mut content, &Placement::default());
         assert_eq!(result, expected)
     }
 }
@@ -18,28 +18,49 @@ extern crate clap;
 extern crate log;
 extern crate regex;
 extern crate serde;
+extern crate serde_json;
 extern crate serde_yaml;
 extern crate textwrap;
 extern crate ureq;
 
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::prelude::*;
-use std::io::ErrorKind;
-use std::path::Path;
+use std::io::{self, ErrorKind, IsTerminal};
+use std::path::{Path, PathBuf};
 use std::process;
-use std::process::Command;
+use std::thread;
+use std::time::Duration;
 
 use chrono::offset::{Offset, Utc};
-use clap::{App, Arg};
+use chrono::SecondsFormat;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 
 use config::DEFAULT_CONFIG;
 use licensure::Licensure;
+use output::OutputFormat;
+use source::{FileSource, OsSource};
 
+mod clock;
 mod comments;
 mod config;
+mod errors;
+mod exit;
+mod foreign;
+mod import;
+mod journal;
 mod licensure;
+mod metrics;
+mod output;
+mod progress;
+mod release;
+mod relicense;
+mod report;
+mod reuse;
+mod source;
 mod template;
+mod uncommentable;
 mod utils;
+mod walk;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const AUTHORS: &str = env!("CARGO_PKG_AUTHORS");
@@ -47,34 +68,560 @@ const ABOUT: &str = env!("CARGO_PKG_DESCRIPTION");
 const HOMEPAGE: &str = env!("CARGO_PKG_HOMEPAGE");
 
 // FIXME: Possible that we should remove this functionality.
-fn get_project_files() -> Vec<String> {
-    let mut files = git_ls_files(Vec::new());
+fn get_project_files(no_git: bool) -> Vec<PathBuf> {
+    let repo = match utils::git::repo() {
+        Some(repo) if !no_git => repo,
+        _ => {
+            return match OsSource::default().list() {
+                Ok(files) => files,
+                Err(e) => fail!(
+                    errors::LISTING_FILES_FAILED,
+                    "Failed to list project files: {}",
+                    e
+                ),
+            }
+        }
+    };
 
-    let mut new_unstaged_files = git_ls_files(vec!["--others", "--exclude-standard"]);
+    let mut files = git_ls_files(repo, Vec::new());
+
+    let mut new_unstaged_files = git_ls_files(repo, vec!["--others", "--exclude-standard"]);
     files.append(&mut new_unstaged_files);
 
-    files
+    files.into_iter().map(PathBuf::from).collect()
 }
 
-fn git_ls_files(extra_args: Vec<&str>) -> Vec<String> {
-    match Command::new("git")
+/// The files which have changed between git_ref and the working tree,
+/// files which have since been deleted are left out.
+fn get_changed_files(git_ref: &str) -> Vec<PathBuf> {
+    let repo = match utils::git::repo() {
+        Some(repo) => repo,
+        None => fail!(
+            errors::LISTING_FILES_FAILED,
+            "--changed-since can only be used inside a git repository"
+        ),
+    };
+
+    let proc = match repo
+        .command()
+        .args(["diff", "--name-only", "--diff-filter=d"])
+        .arg(git_ref)
+        .arg("--")
+        .arg(repo.root_relative("."))
+        .output()
+    {
+        Ok(proc) => proc,
+        Err(e) => fail!(
+            errors::LISTING_FILES_FAILED,
+            "Failed to run git diff. Make sure you're in a git repo.\n{}",
+            e
+        ),
+    };
+
+    if !proc.status.success() {
+        fail!(
+            errors::LISTING_FILES_FAILED,
+            "Failed to list files changed since {}: {}",
+            git_ref,
+            String::from_utf8_lossy(&proc.stderr).trim()
+        )
+    }
+
+    String::from_utf8(proc.stdout)
+        .expect("git diff output was not UTF-8!")
+        .split('\n')
+        .filter_map(|s| repo.cwd_relative(s))
+        .filter(|s| !s.is_empty() && Path::new(s).exists())
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Run git ls-files from the root of repo for the current directory,
+/// returning paths relative to the current directory.
+fn git_ls_files(repo: &utils::git::Repo, extra_args: Vec<&str>) -> Vec<String> {
+    match repo
+        .command()
         .arg("ls-files")
         .args(extra_args)
+        .arg("--")
+        .arg(repo.root_relative("."))
         .output()
     {
         Ok(proc) => String::from_utf8(proc.stdout)
             .expect("git ls-files output was not UTF-8!")
             .split('\n')
+            .filter_map(|s| repo.cwd_relative(s))
             // git-ls still returns the removed files that are not committed, so we filter those out.
             .filter(|s| !s.is_empty() && Path::new(s).exists())
             .map(str::to_string)
             .collect(),
-        Err(e) => {
-            println!("Failed to run git ls-files. Make sure you're in a git repo.");
-            println!("{}", e);
-            process::exit(1)
+        Err(e) => fail!(
+            errors::LISTING_FILES_FAILED,
+            "Failed to run git ls-files. Make sure you're in a git repo.\n{}",
+            e
+        ),
+    }
+}
+
+fn import_template(matches: &ArgMatches) {
+    let from = matches.value_of("from").expect("--from is required");
+    let content = match std::fs::read_to_string(from) {
+        Ok(c) => c,
+        Err(e) => fail!(errors::IMPORT_FAILED, "Unable to read {}: {}", from, e),
+    };
+
+    let header = match import::extract_leading_comment(&content) {
+        Some(h) if !h.is_empty() => h,
+        _ => fail!(
+            errors::IMPORT_FAILED,
+            "No leading comment block found in {}",
+            from
+        ),
+    };
+
+    let imported = import::infer_template(&header);
+    println!("licenses:");
+    print!(
+        "{}",
+        imported.to_license_config(matches.value_of("ident").unwrap_or("UNKNOWN"))
+    );
+}
+
+/// Split a list of file names read from stdin. Names are NUL delimited
+/// if the input contains a NUL, as output by git diff -z, otherwise they
+/// are newline delimited.
+fn parse_file_list(input: &str) -> Vec<String> {
+    let names: Vec<&str> = if input.contains('\0') {
+        input.split('\0').collect()
+    } else {
+        input.lines().collect()
+    };
+
+    names
+        .into_iter()
+        .filter(|n| !n.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Append line to the file at path, creating it if needed.
+fn append_line(path: &str, line: &str) -> io::Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", line)
+}
+
+fn read_stdin_files() -> Vec<PathBuf> {
+    let mut input = String::new();
+    if let Err(e) = io::stdin().read_to_string(&mut input) {
+        fail!(
+            errors::LISTING_FILES_FAILED,
+            "Unable to read file names from stdin: {}",
+            e
+        );
+    }
+
+    parse_file_list(&input)
+        .into_iter()
+        .map(PathBuf::from)
+        .collect()
+}
+
+fn load_config_or_exit() -> config::Config {
+    match config::load_config() {
+        Ok(c) => c,
+        Err(e) if ErrorKind::NotFound == e.kind() => fail!(
+            errors::CONFIG_NOT_FOUND,
+            "No config file found, generate one with licensure --generate-config"
+        ),
+        Err(e) => fail!(errors::INVALID_CONFIG, "Error loading config file: {}", e),
+    }
+}
+
+fn print_fingerprints() {
+    let config = load_config_or_exit();
+    for (ident, files, fingerprint) in config.licenses.fingerprints(&config.variables) {
+        println!("{}\t{}\t{}", ident, files, fingerprint);
+    }
+}
+
+fn print_config_paths() {
+    let found = config::find_config_file();
+    for path in config::config_search_paths() {
+        let status = if found.as_ref() == Some(&path) {
+            "(in use)"
+        } else if path.exists() {
+            "(found)"
+        } else {
+            "(not found)"
+        };
+
+        println!("{} {}", path.display(), status);
+        if found.as_ref() == Some(&path) {
+            for fragment in config::config_fragments(&path) {
+                println!("  {} (fragment)", fragment.display());
+            }
+        }
+    }
+
+    if found.is_none() {
+        println!("No config file found, generate one with licensure --generate-config");
+    }
+}
+
+/// Warn about every file which matches the files pattern of a license
+/// but is excluded, since conflicting patterns are a common reason for
+/// files not being licensed. Returns the number of such files.
+fn warn_excluded_license_matches(config: &config::Config, files: &[PathBuf]) -> usize {
+    let (files, _) = utils::utf8_paths(files);
+    let matches = config.excluded_license_matches(&files);
+    for (file, ident, pattern) in &matches {
+        warning!(
+            "{} matches the files pattern {} of license {} but is excluded",
+            file,
+            pattern,
+            ident
+        );
+    }
+
+    matches.len()
+}
+
+fn which(matches: &ArgMatches) {
+    let config = load_config_or_exit();
+    let path = matches.value_of("PATH").expect("PATH is required");
+    let license = match config.license_for(path) {
+        Some(license) => license,
+        None => fail!(errors::NO_LICENSE, "No license applies to {}", path),
+    };
+
+    if matches.is_present("json") {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&license).expect("license failed to serialize!")
+        );
+    } else {
+        println!("{}", license.ident);
+    }
+}
+
+fn explain(matches: &ArgMatches) {
+    let code = match matches.value_of("CODE") {
+        Some(code) => code,
+        None => {
+            for error in errors::CATALOG {
+                println!("{}\t{}", error.code, error.summary);
+            }
+            return;
+        }
+    };
+
+    match errors::lookup(code) {
+        Some(error) => println!("{}", error),
+        None => fail!(
+            errors::INVALID_ARGUMENT,
+            "Unknown error code {}, run licensure explain to list every code",
+            code
+        ),
+    }
+}
+
+fn validate_config(matches: &ArgMatches) {
+    let config = load_config_or_exit();
+    let files = get_project_files(matches.is_present("no-git"));
+    let excluded = warn_excluded_license_matches(&config, &files);
+
+    let tests = config.licenses.template_tests().len();
+    let failures = match Licensure::new(config).run_template_tests() {
+        Ok(failures) => failures,
+        Err(e) => fail!(errors::IO_FAILED, "Failed to run template tests: {}", e),
+    };
+    if !failures.is_empty() {
+        for failure in &failures {
+            println!("{}", failure);
+        }
+        fail!(
+            errors::TEMPLATE_TESTS_FAILED,
+            "{} template test failure(s)",
+            failures.len()
+        );
+    }
+
+    println!(
+        "Config is valid, {} template test(s) passed and {} of {} project file(s) match a license but are excluded",
+        tests,
+        excluded,
+        files.len()
+    );
+}
+
+fn serve(matches: &ArgMatches) {
+    let addr = matches
+        .value_of("metrics-addr")
+        .expect("--metrics-addr is required");
+    let interval = match matches.value_of("interval").unwrap_or("60").parse::<u64>() {
+        Ok(secs) => Duration::from_secs(secs),
+        Err(e) => fail!(
+            errors::INVALID_ARGUMENT,
+            "Invalid value for --interval: {}",
+            e
+        ),
+    };
+
+    if let Err(e) = metrics::serve(addr) {
+        fail!(
+            errors::IO_FAILED,
+            "Unable to serve metrics on {}: {}",
+            addr,
+            e
+        );
+    }
+
+    // The config is loaded for every check so changes to it are picked up
+    // without a restart, an invalid config is reported and the last valid
+    // one is used until it's fixed.
+    let mut config = load_config_or_exit();
+    loop {
+        match config::load_config() {
+            Ok(c) => config = c,
+            Err(e) => warning!("Error loading config file, using the last valid one: {}", e),
+        }
+
+        let files = get_project_files(matches.is_present("no-git"));
+        match Licensure::new(config.clone())
+            .with_check_mode(true)
+            .license_files(&files)
+        {
+            Ok(stats) => eprintln!("{}", stats.summary),
+            Err(e) => eprintln!("Failed to check files: {}", e),
+        }
+
+        thread::sleep(interval);
+    }
+}
+
+fn recover(matches: &ArgMatches) {
+    let recovery = if matches.is_present("rollback") {
+        journal::Recovery::Rollback
+    } else {
+        journal::Recovery::Complete
+    };
+
+    match journal::recover(journal::DEFAULT_DIR, recovery) {
+        Ok(None) => println!("No interrupted run to recover"),
+        Ok(Some(changed)) => {
+            for file in &changed {
+                println!("{}", file);
+            }
+            println!("Recovered {} file(s)", changed.len());
+        }
+        Err(e) => fail!(errors::IO_FAILED, "Failed to recover: {}", e),
+    }
+}
+
+fn report(matches: &ArgMatches) {
+    let format: report::ReportFormat = matches
+        .value_of("format")
+        .unwrap_or("json")
+        .parse()
+        .unwrap_or(report::ReportFormat::Json);
+    let files: Vec<PathBuf> = match matches.values_of_os("FILES") {
+        Some(files) => files.map(PathBuf::from).collect(),
+        None => get_project_files(matches.is_present("no-git")),
+    };
+
+    let licensure = Licensure::new(load_config_or_exit());
+    match report::Report::new(&licensure, &files) {
+        Ok(report) => print!("{}", report.render(format)),
+        Err(e) => fail!(errors::IO_FAILED, "Failed to report on files: {}", e),
+    }
+}
+
+fn render_header(matches: &ArgMatches) {
+    let file = match (matches.value_of("path"), matches.value_of("filetype")) {
+        (Some(path), _) => path.to_string(),
+        (None, Some(filetype)) => format!("file.{}", filetype),
+        (None, None) => unreachable!("clap requires --path or --filetype"),
+    };
+
+    let columns = matches.value_of("width").map(|w| match w.parse::<usize>() {
+        Ok(w) => w,
+        Err(_) => fail!(
+            errors::INVALID_ARGUMENT,
+            "--width must be a number, got: {}",
+            w
+        ),
+    });
+
+    let licensure = Licensure::new(load_config_or_exit());
+    match licensure.render_header(&file, matches.value_of("license"), columns) {
+        Ok(header) => print!("{}", header),
+        Err(e) => fail!(errors::RENDER_FAILED, "Unable to render header: {}", e),
+    }
+}
+
+fn relicense(matches: &ArgMatches) {
+    let plan_path = matches.value_of("plan").expect("--plan is required");
+    let config = load_config_or_exit();
+
+    let mut plan = if matches.is_present("apply") {
+        match relicense::Plan::load(plan_path) {
+            Ok(plan) => Some(plan),
+            Err(e) => fail!(
+                errors::IO_FAILED,
+                "Unable to read plan {}: {}",
+                plan_path,
+                e
+            ),
+        }
+    } else {
+        None
+    };
+
+    let (from, to) = match &plan {
+        Some(plan) => (plan.from.clone(), plan.to.clone()),
+        None => (
+            matches
+                .value_of("from")
+                .expect("--from is required")
+                .to_string(),
+            matches
+                .value_of("to")
+                .expect("--to is required")
+                .to_string(),
+        ),
+    };
+    for ident in [&from, &to] {
+        if !config.licenses.has_ident(ident) {
+            fail!(
+                errors::UNKNOWN_IDENT,
+                "No license with ident {} is configured",
+                ident
+            );
         }
     }
+
+    let licensure = Licensure::new(config);
+    if let Some(plan) = &mut plan {
+        match plan.apply(&licensure, plan_path) {
+            Ok(count) => println!("Relicensed {} file(s) from {} to {}", count, from, to),
+            Err(e) => fail!(
+                errors::IO_FAILED,
+                "Failed to relicense files: {}\nRun the same command again to resume from where it stopped",
+                e
+            ),
+        }
+
+        return;
+    }
+
+    let files: Vec<PathBuf> = match matches.values_of_os("FILES") {
+        Some(files) => files.map(PathBuf::from).collect(),
+        None => get_project_files(false),
+    };
+    let plan = match relicense::Plan::new(&licensure, &files, &from, &to) {
+        Ok(plan) => plan,
+        Err(e) => fail!(errors::IO_FAILED, "Failed to plan relicensing: {}", e),
+    };
+    if let Err(e) = plan.save(plan_path) {
+        fail!(
+            errors::IO_FAILED,
+            "Unable to write plan {}: {}",
+            plan_path,
+            e
+        );
+    }
+
+    println!(
+        "Planned relicensing {} file(s) from {} to {}, review {} then run licensure relicense --apply --plan {}",
+        plan.pending(),
+        from,
+        to,
+        plan_path,
+        plan_path
+    );
+}
+
+fn stamp_release(matches: &ArgMatches) {
+    let tag = matches.value_of("tag").expect("--tag is required");
+    let year = match matches.value_of("year") {
+        Some(year) if year.len() == 4 && year.chars().all(|c| c.is_ascii_digit()) => {
+            year.to_string()
+        }
+        Some(year) => fail!(
+            errors::INVALID_ARGUMENT,
+            "--year must be a year, got: {}",
+            year
+        ),
+        None => clock::current().year(),
+    };
+    let files: Vec<PathBuf> = match matches.values_of_os("FILES") {
+        Some(files) => files.map(PathBuf::from).collect(),
+        None => get_project_files(matches.is_present("no-git")),
+    };
+
+    let mut config = load_config_or_exit();
+    config.change_in_place = true;
+    let journal = match journal::Journal::create(journal::DEFAULT_DIR) {
+        Ok(journal) => journal,
+        Err(e) => fail!(errors::IO_FAILED, "Failed to stamp files: {}", e),
+    };
+
+    let release = release::Release::new(tag, &year);
+    match Licensure::new(config)
+        .with_journal(Some(journal))
+        .with_release(Some(release.clone()))
+        .license_files(&files)
+    {
+        Ok(stats) => print!("{}", release.notes(&stats.summary)),
+        Err(e) => fail!(errors::IO_FAILED, "Failed to stamp files: {}", e),
+    }
+}
+
+fn dep5_sync(matches: &ArgMatches) {
+    let config = load_config_or_exit();
+    let path = match matches.value_of("file") {
+        Some(path) => path.to_string(),
+        None => match &config.uncommentable {
+            Some(cfg) => cfg.dep5_file.clone(),
+            None => ".reuse/dep5".to_string(),
+        },
+    };
+
+    let annotations = reuse::annotations(&config);
+    let (current, synced) = match reuse::sync(&path, &annotations) {
+        Ok(result) => result,
+        Err(e) => fail!(errors::IO_FAILED, "Unable to read {}: {}", path, e),
+    };
+
+    if current == synced {
+        println!("{} is up to date", path);
+        return;
+    }
+
+    if matches.is_present("check") {
+        println!(
+            "{} is out of date, run licensure dep5 sync to update it",
+            path
+        );
+        process::exit(exit::NEEDS_UPDATE);
+    }
+
+    let written = Path::new(&path)
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&path, synced));
+    if let Err(e) = written {
+        fail!(errors::IO_FAILED, "Unable to write {}: {}", path, e);
+    }
+
+    println!(
+        "Synced {} license pattern(s) to {}",
+        annotations.len(),
+        path
+    );
 }
 
 fn main() {
@@ -100,34 +647,401 @@ More information is available at: {}",
                 .long("verbose")
                 .multiple(true),
         )
+        .arg(
+            Arg::with_name("quiet")
+                .short("q")
+                .long("quiet")
+                .conflicts_with("verbose")
+                .help("Don't print warnings or progress, only errors and requested output"),
+        )
         .arg(Arg::with_name("in-place").short("i").long("in-place"))
+        .arg(
+            Arg::with_name("backup-ext")
+                .long("backup-ext")
+                .takes_value(true)
+                .value_name("EXT")
+                .help("Keep the original of every file changed in place as the file name with EXT appended, e.g. .bak"),
+        )
+        .arg(
+            Arg::with_name("enforce-directory-consistency")
+                .long("enforce-directory-consistency")
+                .requires("check")
+                .help("Also fail when a directory contains files with different licenses, by their header or matching license"),
+        )
+        .arg(
+            Arg::with_name("chmod-if-needed")
+                .long("chmod-if-needed")
+                .help("Make read-only files, and their directories, writable while changing them in place then restore their mode"),
+        )
+        .arg(
+            Arg::with_name("preserve-mtime")
+                .long("preserve-mtime")
+                .help("Keep the modification time of files changed in place"),
+        )
+        .arg(
+            Arg::with_name("diff")
+                .long("diff")
+                .conflicts_with("in-place")
+                .help("Print a unified diff of the changes instead of the full file contents"),
+        )
+        .arg(
+            Arg::with_name("remove")
+                .long("remove")
+                .conflicts_with("check")
+                .help("Remove the configured license header from files instead of adding it"),
+        )
+        .arg(
+            Arg::with_name("force")
+                .long("force")
+                .help("Add headers even to files which seem to have one already, formatted differently or of another license"),
+        )
         .arg(
             Arg::with_name("check")
                 .long("check")
                 .help("Checks if any file is not licensed with the given config"),
         )
+        .arg(
+            Arg::with_name("fix-years-in-ci")
+                .long("fix-years-in-ci")
+                .requires("check")
+                .conflicts_with("remove")
+                .help("With --check, write the updates of headers which only differ in their years instead of failing on them, other differences still fail"),
+        )
+        .arg(
+            Arg::with_name("jobs")
+                .short("j")
+                .long("jobs")
+                .takes_value(true)
+                .value_name("N")
+                .help("Number of files to process concurrently, 0 uses all available cores"),
+        )
+        .arg(
+            Arg::with_name("fix-format")
+                .long("fix-format")
+                .help("Rewrite license headers whose text is correct but whose wrapping or spacing differs from the config"),
+        )
+        .arg(
+            Arg::with_name("explain-diff")
+                .long("explain-diff")
+                .requires("check")
+                .help("Show a word level diff between the existing and expected header of files needing an update"),
+        )
+        .arg(
+            Arg::with_name("summary")
+                .long("summary")
+                .help("Print a one line count of scanned, excluded, already licensed, added, updated, and skipped files to stderr, followed by where any SPDX templates used came from"),
+        )
+        .arg(
+            Arg::with_name("audit-log")
+                .long("audit-log")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Append a JSON line recording the run, the files it changed, and the URL and SHA-256 of every SPDX template used, to FILE"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(output::FORMATS)
+                .default_value("text")
+                .help("The output format for --check results"),
+        )
         .arg(
             Arg::with_name("exclude")
                 .short("e")
                 .long("exclude")
                 .takes_value(true)
                 .value_name("REGEX")
-                .help("A regex which will be used to determine what files to ignore."),
+                .help("A regex matching the whole paths of files to ignore."),
         )
         .arg(Arg::with_name("project").long("project").short("p").help(
             "When specified will license the current project files as returned by git ls-files",
         ))
+        .arg(
+            Arg::with_name("no-git")
+                .long("no-git")
+                .requires("project")
+                .help("List project files by walking the current directory, honoring .gitignore files, instead of running git"),
+        )
         .arg(
             Arg::with_name("generate-config")
                 .long("generate-config")
                 .help("Generate a default licensure config file"),
         )
+        .arg(
+            Arg::with_name("fix-config")
+                .long("fix-config")
+                .help("Rewrite deprecated keys in the licensure config file to their replacements"),
+        )
+        .arg(
+            Arg::with_name("refresh-spdx-cache")
+                .long("refresh-spdx-cache")
+                .help("Fetch SPDX license templates again even if they are cached"),
+        )
+        .arg(
+            Arg::with_name("changed-since")
+                .long("changed-since")
+                .takes_value(true)
+                .value_name("REF")
+                .conflicts_with_all(&["project", "stdin"])
+                .help("License the files which have changed since the given git ref"),
+        )
+        .arg(
+            Arg::with_name("stdin")
+                .long("stdin")
+                .conflicts_with("project")
+                .help("Read the files to license from stdin, one per line or NUL delimited"),
+        )
         .arg(
             Arg::with_name("FILES")
                 .multiple(true)
                 .help("Files to license, ignored if --project is supplied"),
         )
-        .get_matches();
+        .subcommand(
+            SubCommand::with_name("import-template")
+                .about("Generate a license config entry from the header of an existing file")
+                .arg(
+                    Arg::with_name("from")
+                        .long("from")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .required(true)
+                        .help("A file with a known good license header"),
+                )
+                .arg(
+                    Arg::with_name("ident")
+                        .long("ident")
+                        .takes_value(true)
+                        .value_name("IDENT")
+                        .help("The license identifier to use if the header has no SPDX identifier"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("config")
+                .about("Inspect licensure configuration")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("path")
+                        .about("Print every location searched for a config file and which one is used"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("render")
+                .about("Print the commented header licensure expects for a file type, without needing a file")
+                .arg(
+                    Arg::with_name("filetype")
+                        .long("filetype")
+                        .takes_value(true)
+                        .value_name("FILETYPE")
+                        .required_unless("path")
+                        .help("The file extension to pick the commenter by, e.g. py"),
+                )
+                .arg(
+                    Arg::with_name("path")
+                        .long("path")
+                        .takes_value(true)
+                        .value_name("PATH")
+                        .help("A path, which doesn't need to exist, to pick the license and commenter by and to use for [path], [filename], and [basename]"),
+                )
+                .arg(
+                    Arg::with_name("width")
+                        .long("width")
+                        .takes_value(true)
+                        .value_name("COLUMNS")
+                        .help("Override the configured column width of the comment"),
+                )
+                .arg(
+                    Arg::with_name("license")
+                        .long("license")
+                        .takes_value(true)
+                        .value_name("IDENT")
+                        .help("The ident of the configured license to render instead of matching by path"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("relicense")
+                .about("Plan moving files from one configured license to another, then apply the plan with --apply")
+                .arg(
+                    Arg::with_name("from")
+                        .long("from")
+                        .takes_value(true)
+                        .value_name("IDENT")
+                        .required_unless("apply")
+                        .help("The ident of the license files are currently under"),
+                )
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .takes_value(true)
+                        .value_name("IDENT")
+                        .required_unless("apply")
+                        .help("The ident of the license to move files to"),
+                )
+                .arg(
+                    Arg::with_name("plan")
+                        .long("plan")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .required(true)
+                        .help("The JSON plan file to write, or with --apply to read and update"),
+                )
+                .arg(
+                    Arg::with_name("apply")
+                        .long("apply")
+                        .conflicts_with_all(&["from", "to", "FILES"])
+                        .help("Relicense the pending files of the plan, marking each done as it goes so an interrupted run can be resumed"),
+                )
+                .arg(
+                    Arg::with_name("FILES")
+                        .multiple(true)
+                        .help("The files to plan for, defaults to the files of the project"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("stamp-release")
+                .about("Bump the end years of all headers to the release year and render [version] as the release, then print a summary for the release notes")
+                .arg(
+                    Arg::with_name("tag")
+                        .long("tag")
+                        .takes_value(true)
+                        .value_name("VERSION")
+                        .required(true)
+                        .help("The version being released, rendered for [version] in templates"),
+                )
+                .arg(
+                    Arg::with_name("year")
+                        .long("year")
+                        .takes_value(true)
+                        .value_name("YEAR")
+                        .help("The year of the release, defaults to the current year"),
+                )
+                .arg(
+                    Arg::with_name("no-git")
+                        .long("no-git")
+                        .help("List project files by walking the current directory instead of with git"),
+                )
+                .arg(
+                    Arg::with_name("FILES")
+                        .multiple(true)
+                        .help("The files to stamp, defaults to the files of the project"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("dep5")
+                .about("Maintain a DEP-5 or REUSE.toml file describing the licenses of the project")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("sync")
+                        .about("Write the files pattern, copyright, and license of every configured license to the DEP-5 or REUSE.toml file")
+                        .arg(
+                            Arg::with_name("file")
+                                .long("file")
+                                .takes_value(true)
+                                .value_name("PATH")
+                                .help("The file to sync, treated as REUSE.toml if it's named so, defaults to the uncommentable dep5_file or .reuse/dep5"),
+                        )
+                        .arg(
+                            Arg::with_name("check")
+                                .long("check")
+                                .help("Exit with an error instead of writing the file if it's out of date"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("which")
+                .about("Print the ident of the license which applies to a path, which doesn't need to exist")
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Print the ident, authors, and rendered template as JSON"),
+                )
+                .arg(Arg::with_name("PATH").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("explain")
+                .about("Describe the causes of and fixes for an error code, or list every error code")
+                .arg(Arg::with_name("CODE").help("The error code to explain, such as E004")),
+        )
+        .subcommand(
+            SubCommand::with_name("validate-config")
+                .about("Load the config and report problems with it, such as excludes hiding files which match a license")
+                .arg(
+                    Arg::with_name("no-git")
+                        .long("no-git")
+                        .help("List project files by walking the current directory instead of with git"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("serve")
+                .about("Check the project every interval, serving metrics about the checks in the Prometheus text format")
+                .arg(
+                    Arg::with_name("metrics-addr")
+                        .long("metrics-addr")
+                        .takes_value(true)
+                        .value_name("ADDR")
+                        .required(true)
+                        .help("The address to serve metrics on at /metrics, e.g. 127.0.0.1:9090"),
+                )
+                .arg(
+                    Arg::with_name("interval")
+                        .long("interval")
+                        .takes_value(true)
+                        .value_name("SECS")
+                        .help("Seconds to wait between checks, defaults to 60"),
+                )
+                .arg(
+                    Arg::with_name("no-git")
+                        .long("no-git")
+                        .help("List project files by walking the current directory instead of with git"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("recover")
+                .about("Complete the changes of an in place run which was interrupted, or roll them back")
+                .arg(
+                    Arg::with_name("rollback")
+                        .long("rollback")
+                        .help("Restore the files changed by the interrupted run instead"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("report")
+                .about("Print an inventory of which license matches the files of each directory and which headers they have")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .value_name("FORMAT")
+                        .possible_values(report::FORMATS)
+                        .default_value("json")
+                        .help("The output format of the report"),
+                )
+                .arg(
+                    Arg::with_name("no-git")
+                        .long("no-git")
+                        .help("List project files by walking the current directory instead of with git"),
+                )
+                .arg(
+                    Arg::with_name("FILES")
+                        .multiple(true)
+                        .help("The files to report on, defaults to the files of the project"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("fingerprint")
+                .about("Print the regex used to detect the header of every configured license"),
+        )
+        .get_matches_safe()
+        .unwrap_or_else(|e| match e.kind {
+            clap::ErrorKind::HelpDisplayed | clap::ErrorKind::VersionDisplayed => e.exit(),
+            _ => {
+                eprintln!("{}", e.message);
+                process::exit(exit::USAGE_ERROR);
+            }
+        });
+
+    progress::set_quiet(matches.is_present("quiet"));
 
     match matches.occurrences_of("verbose") {
         0 => (),
@@ -148,45 +1062,153 @@ More information is available at: {}",
         .unwrap(),
     };
 
+    if let Some(sub_matches) = matches.subcommand_matches("import-template") {
+        import_template(sub_matches);
+        process::exit(0);
+    }
+
+    if let Some(sub_matches) = matches.subcommand_matches("config") {
+        if sub_matches.subcommand_matches("path").is_some() {
+            print_config_paths();
+        }
+
+        process::exit(0);
+    }
+
+    if let Some(sub_matches) = matches.subcommand_matches("render") {
+        render_header(sub_matches);
+        process::exit(0);
+    }
+
+    if let Some(sub_matches) = matches.subcommand_matches("relicense") {
+        relicense(sub_matches);
+        process::exit(0);
+    }
+
+    if let Some(sub_matches) = matches.subcommand_matches("stamp-release") {
+        stamp_release(sub_matches);
+        process::exit(0);
+    }
+
+    if let Some(sub_matches) = matches.subcommand_matches("dep5") {
+        if let Some(sync_matches) = sub_matches.subcommand_matches("sync") {
+            dep5_sync(sync_matches);
+        }
+
+        process::exit(0);
+    }
+
+    if let Some(sub_matches) = matches.subcommand_matches("which") {
+        which(sub_matches);
+        process::exit(0);
+    }
+
+    if let Some(sub_matches) = matches.subcommand_matches("explain") {
+        explain(sub_matches);
+        process::exit(0);
+    }
+
+    if let Some(sub_matches) = matches.subcommand_matches("validate-config") {
+        validate_config(sub_matches);
+        process::exit(0);
+    }
+
+    if let Some(sub_matches) = matches.subcommand_matches("serve") {
+        serve(sub_matches);
+    }
+
+    if let Some(sub_matches) = matches.subcommand_matches("recover") {
+        recover(sub_matches);
+        process::exit(0);
+    }
+
+    if let Some(sub_matches) = matches.subcommand_matches("report") {
+        report(sub_matches);
+        process::exit(0);
+    }
+
+    if matches.subcommand_matches("fingerprint").is_some() {
+        print_fingerprints();
+        process::exit(0);
+    }
+
     if matches.is_present("generate-config") {
         let mut f = match File::create(".licensure.yml") {
             Ok(f) => f,
-            Err(e) => {
-                println!("Unable to create .licensure.yml: {}", e);
-                process::exit(1);
-            }
+            Err(e) => fail!(
+                errors::GENERATE_CONFIG_FAILED,
+                "Unable to create .licensure.yml: {}",
+                e
+            ),
         };
 
         if let Err(e) = f.write_all(DEFAULT_CONFIG.as_bytes()) {
-            println!("Unable to write to .licensure.yml: {}", e);
-            process::exit(1);
+            fail!(
+                errors::GENERATE_CONFIG_FAILED,
+                "Unable to write to .licensure.yml: {}",
+                e
+            );
+        }
+
+        process::exit(0);
+    }
+
+    if matches.is_present("fix-config") {
+        match config::fix_config() {
+            Ok((path, 0)) => println!("{} has no deprecated keys", path.display()),
+            Ok((path, count)) => {
+                println!("Updated {} deprecated key(s) in {}", count, path.display())
+            }
+            Err(e) if ErrorKind::NotFound == e.kind() => fail!(
+                errors::CONFIG_NOT_FOUND,
+                "No config file found, generate one with licensure --generate-config"
+            ),
+            Err(e) => fail!(errors::INVALID_CONFIG, "Unable to fix config file: {}", e),
         }
 
         process::exit(0);
     }
 
-    let files: Vec<String> = if matches.is_present("project") {
-        get_project_files()
+    if matches.is_present("refresh-spdx-cache") {
+        config::set_refresh_spdx_cache(true);
+
+        if !matches.is_present("project")
+            && !matches.is_present("stdin")
+            && !matches.is_present("changed-since")
+            && !matches.is_present("FILES")
+        {
+            match config::refresh_cached_spdx_templates() {
+                Ok(count) => info!("refreshed {} cached SPDX template(s)", count),
+                Err(e) => fail!(
+                    errors::CACHE_FAILED,
+                    "Unable to refresh SPDX template cache: {}",
+                    e
+                ),
+            }
+            process::exit(0);
+        }
+    }
+
+    let files: Vec<PathBuf> = if matches.is_present("project") {
+        get_project_files(matches.is_present("no-git"))
+    } else if let Some(git_ref) = matches.value_of("changed-since") {
+        get_changed_files(git_ref)
+    } else if matches.is_present("stdin") {
+        let mut files = read_stdin_files();
+        if let Some(args) = matches.values_of_os("FILES") {
+            files.extend(args.map(PathBuf::from));
+        }
+
+        files
     } else {
         matches
-            .values_of("FILES")
+            .values_of_os("FILES")
             .expect("ERROR: Must provide files to license either as matches or via --project")
-            .map(str::to_string)
+            .map(PathBuf::from)
             .collect()
     };
 
-    let mut config = match config::load_config() {
-        Ok(c) => c,
-        Err(e) => {
-            if ErrorKind::NotFound == e.kind() {
-                println!("No config file found, generate one with licensure --generate-config");
-            } else {
-                println!("Error loading config file: {}", e);
-            }
-
-            process::exit(1);
-        }
-    };
+    let mut config = load_config_or_exit();
 
     if let Some(exclude) = matches.value_of("exclude") {
         config.add_exclude(exclude);
@@ -196,32 +1218,110 @@ More information is available at: {}",
         config.change_in_place = true;
     }
 
-    let licensure = Licensure::new(config).with_check_mode(matches.is_present("check"));
-    match licensure.license_files(&files) {
-        Err(e) => {
-            println!("Failed to license files: {}", e);
-            process::exit(1);
+    if matches.occurrences_of("verbose") > 0 {
+        warn_excluded_license_matches(&config, &files);
+    }
+
+    let jobs = match matches.value_of("jobs").map(str::parse::<usize>) {
+        None => 1,
+        Some(Ok(n)) => n,
+        Some(Err(e)) => fail!(errors::INVALID_ARGUMENT, "Invalid value for --jobs: {}", e),
+    };
+
+    // Log lines would break up the progress line, and it isn't useful
+    // when files are printed instead of changed.
+    let progress = matches.occurrences_of("verbose") == 0
+        && io::stderr().is_terminal()
+        && (config.change_in_place || matches.is_present("check"));
+
+    // Changes are journaled so they can be recovered if the run is
+    // interrupted.
+    let writes = match matches.is_present("check") {
+        true => matches.is_present("fix-years-in-ci"),
+        false => config.change_in_place,
+    };
+    let journal = if writes {
+        match journal::Journal::create(journal::DEFAULT_DIR) {
+            Ok(journal) => Some(journal),
+            Err(e) => fail!(errors::IO_FAILED, "Failed to license files: {}", e),
         }
+    } else {
+        None
+    };
+
+    let licensure = Licensure::new(config)
+        .with_journal(journal)
+        .with_check_mode(matches.is_present("check"))
+        .with_fix_years(matches.is_present("fix-years-in-ci"))
+        .with_diff_mode(matches.is_present("diff"))
+        .with_remove_mode(matches.is_present("remove"))
+        .with_explain_diff(matches.is_present("explain-diff"))
+        .with_fix_format(matches.is_present("fix-format"))
+        .with_force(matches.is_present("force"))
+        .with_backup_ext(matches.value_of("backup-ext").map(str::to_string))
+        .with_preserve_mtime(matches.is_present("preserve-mtime"))
+        .with_chmod_if_needed(matches.is_present("chmod-if-needed"))
+        .with_enforce_directory_consistency(matches.is_present("enforce-directory-consistency"))
+        .with_progress(progress)
+        .with_jobs(jobs);
+    match licensure.license_files(&files) {
+        Err(e) => fail!(errors::IO_FAILED, "Failed to license files: {}", e),
         Ok(stats) => {
-            if matches.is_present("check")
-                && !(stats.files_not_licensed.is_empty()
-                    && stats.files_needing_license_update.is_empty())
-            {
-                if !stats.files_needing_license_update.is_empty() {
-                    eprintln!("The following files' licenses need to be updated");
-                    for file in stats.files_needing_license_update {
-                        eprintln!("{}", file);
+            if matches.is_present("check") {
+                let format: OutputFormat = matches
+                    .value_of("format")
+                    .unwrap_or("text")
+                    .parse()
+                    .unwrap_or(OutputFormat::Text);
+                if let Some(out) = output::render_check_results(format, &stats) {
+                    match format {
+                        OutputFormat::Text => eprintln!("{}", out),
+                        _ => println!("{}", out),
                     }
                 }
+            }
 
-                if !stats.files_not_licensed.is_empty() {
-                    eprintln!("The following files were not licensed with the given config.");
-                    for file in stats.files_not_licensed {
-                        eprintln!("{}", file);
+            if !stats.files_skipped_read_only.is_empty() {
+                warning!(
+                    "{} read-only file(s) were not changed, pass --chmod-if-needed to change them",
+                    stats.files_skipped_read_only.len()
+                );
+            }
+
+            if matches.is_present("summary") {
+                eprintln!("{}", stats.summary);
+                for provenance in &stats.templates {
+                    eprintln!("{}", provenance);
+                }
+            }
+
+            if let Some(path) = matches.value_of("audit-log") {
+                let time = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+                let record =
+                    output::render_audit_record(&time, matches.is_present("check"), &stats);
+                if let Err(e) = append_line(path, &record) {
+                    fail!(
+                        errors::IO_FAILED,
+                        "Failed to write audit log {}: {}",
+                        path,
+                        e
+                    );
+                }
+            }
+
+            if matches.is_present("check") {
+                for (condition, count) in stats.conditions() {
+                    if !stats.fails_on(condition) {
+                        warning!(
+                            "{}, which doesn't fail the check because of check_fail_on",
+                            condition.describe(count)
+                        );
                     }
                 }
 
-                process::exit(1);
+                if let Some(code) = stats.exit_code() {
+                    process::exit(code);
+                }
             }
         }
     }
@@ -231,8 +1331,32 @@ More information is available at: {}",
 mod test {
     use super::*;
 
+    #[test]
+    fn test_parse_file_list() {
+        assert_eq!(
+            vec!["a.rs".to_string(), "b c.rs".to_string()],
+            parse_file_list("a.rs\nb c.rs\n\n")
+        );
+        assert_eq!(
+            vec!["a\nb.rs".to_string(), "c.rs".to_string()],
+            parse_file_list("a\nb.rs\0c.rs\0")
+        );
+    }
+
     #[test]
     fn test_get_project_files() {
-        assert!(get_project_files().len() != 0)
+        assert!(!get_project_files(false).is_empty())
+    }
+
+    #[test]
+    fn test_get_changed_files() {
+        assert!(get_changed_files("HEAD").iter().all(|f| f.exists()));
+    }
+
+    #[test]
+    fn test_get_project_files_no_git() {
+        let files = get_project_files(true);
+        assert!(files.contains(&PathBuf::from("src/main.rs")));
+        assert!(!files.iter().any(|f| f.starts_with("target")));
     }
 }
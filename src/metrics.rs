@@ -0,0 +1,142 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+
+/// A monotonically increasing count of events in this process.
+pub struct Counter {
+    name: &'static str,
+    help: &'static str,
+    value: AtomicU64,
+}
+
+impl Counter {
+    const fn new(name: &'static str, help: &'static str) -> Counter {
+        Counter {
+            name,
+            help,
+            value: AtomicU64::new(0),
+        }
+    }
+
+    pub fn inc(&self) {
+        self.value.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+pub static FILES_PROCESSED: Counter = Counter::new(
+    "licensure_files_processed_total",
+    "Files checked or licensed.",
+);
+pub static ERRORS: Counter = Counter::new(
+    "licensure_errors_total",
+    "Files which failed to be processed.",
+);
+pub static CACHE_HITS: Counter = Counter::new(
+    "licensure_cache_hits_total",
+    "File reads served from the content cache.",
+);
+pub static SPDX_FETCHES: Counter = Counter::new(
+    "licensure_spdx_fetches_total",
+    "License templates downloaded from the SPDX API.",
+);
+
+static REGISTRY: [&Counter; 4] = [&FILES_PROCESSED, &ERRORS, &CACHE_HITS, &SPDX_FETCHES];
+
+/// Every counter in the Prometheus text exposition format.
+pub fn render() -> String {
+    let mut out = String::new();
+    for counter in REGISTRY {
+        let _ = writeln!(out, "# HELP {} {}", counter.name, counter.help);
+        let _ = writeln!(out, "# TYPE {} counter", counter.name);
+        let _ = writeln!(out, "{} {}", counter.name, counter.get());
+    }
+
+    out
+}
+
+/// Serve the metrics at /metrics on addr from a background thread.
+pub fn serve(addr: &str) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if let Err(e) = respond(stream) {
+                debug!("failed to respond to metrics request: {}", e);
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn respond(mut stream: TcpStream) -> io::Result<()> {
+    let mut request = String::new();
+    BufReader::new(&stream).read_line(&mut request)?;
+
+    let (status, body) = match request.split_whitespace().nth(1) {
+        Some("/metrics") => ("200 OK", render()),
+        _ => ("404 Not Found", "not found\n".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_render() {
+        SPDX_FETCHES.inc();
+        let out = render();
+        assert!(out.contains(
+            "# HELP licensure_spdx_fetches_total License templates downloaded from the SPDX API.\n# TYPE licensure_spdx_fetches_total counter\nlicensure_spdx_fetches_total "
+        ));
+        assert_eq!(12, out.lines().count());
+    }
+
+    #[test]
+    fn test_serve() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        drop(listener);
+        serve(&addr).unwrap();
+
+        let get = |path: &str| {
+            let mut stream = TcpStream::connect(&addr).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let response = get("/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("licensure_files_processed_total "));
+        assert!(get("/").starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
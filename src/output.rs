@@ -0,0 +1,433 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use crate::config::CheckCondition;
+use crate::licensure::{Action, LicenseStats};
+
+pub const FORMATS: &[&str] = &["text", "json", "github", "tap"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Github,
+    Tap,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "github" => Ok(OutputFormat::Github),
+            "tap" => Ok(OutputFormat::Tap),
+            _ => Err(format!(
+                "unknown output format {}, expected one of: {}",
+                s,
+                FORMATS.join(", ")
+            )),
+        }
+    }
+}
+
+/// Render the results of a --check run. Returns None if there is
+/// nothing to report.
+pub fn render_check_results(format: OutputFormat, stats: &LicenseStats) -> Option<String> {
+    match format {
+        OutputFormat::Text => render_text(stats),
+        OutputFormat::Json => {
+            Some(serde_json::to_string_pretty(stats).expect("LicenseStats is always valid JSON"))
+        }
+        OutputFormat::Github => render_github(stats),
+        OutputFormat::Tap => Some(render_tap(stats)),
+    }
+}
+
+fn render_text(stats: &LicenseStats) -> Option<String> {
+    if !stats.has_failures() && stats.files_with_years_fixed.is_empty() {
+        return None;
+    }
+
+    let mut out = Vec::new();
+    if !stats.files_with_years_fixed.is_empty() {
+        out.push("The following files' years were updated".to_string());
+        out.extend(stats.files_with_years_fixed.iter().cloned());
+    }
+
+    if !stats.files_needing_license_update.is_empty() {
+        out.push("The following files' licenses need to be updated".to_string());
+        out.extend(stats.files_needing_license_update.iter().cloned());
+    }
+
+    if !stats.files_needing_format_fix.is_empty() {
+        out.push("The following files' license headers need to be reformatted".to_string());
+        out.extend(stats.files_needing_format_fix.iter().cloned());
+    }
+
+    if !stats.files_with_similar_header.is_empty() {
+        out.push(
+            "The following files seem to have a license header which is formatted differently, reformat it or use --force to add another"
+                .to_string(),
+        );
+        out.extend(stats.files_with_similar_header.iter().cloned());
+    }
+
+    if !stats.files_not_licensed.is_empty() {
+        out.push("The following files were not licensed with the given config.".to_string());
+        out.extend(stats.files_not_licensed.iter().cloned());
+    }
+
+    if !stats.mixed_license_directories.is_empty() {
+        out.push("The following directories contain files with different licenses".to_string());
+        for (dir, licenses) in &stats.mixed_license_directories {
+            out.push(format!("{}: {}", dir, describe_licenses(licenses)));
+        }
+    }
+
+    if !stats.files_with_unknown_license.is_empty() {
+        out.push(
+            "The following files have license headers which don't match any configured license"
+                .to_string(),
+        );
+        out.extend(stats.files_with_unknown_license.iter().cloned());
+    }
+
+    Some(out.join("\n"))
+}
+
+// See: https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions#setting-an-error-message
+fn render_github(stats: &LicenseStats) -> Option<String> {
+    if !stats.has_failures() {
+        return None;
+    }
+
+    let mut out = Vec::new();
+    for file in &stats.files_needing_license_update {
+        out.push(format!(
+            "::{} file={},line=1,title=License header outdated::The license header in {} needs to be updated",
+            level(stats, stats.license_condition(file)), escape_property(file), escape_data(file)
+        ));
+    }
+
+    for file in &stats.files_needing_format_fix {
+        out.push(format!(
+            "::{} file={},line=1,title=License header formatting::The license header in {} needs to be reformatted",
+            level(stats, CheckCondition::Format), escape_property(file), escape_data(file)
+        ));
+    }
+
+    for file in &stats.files_with_similar_header {
+        out.push(format!(
+            "::{} file={},line=1,title=License header formatting::{} seems to have a license header which is formatted differently",
+            level(stats, CheckCondition::Format), escape_property(file), escape_data(file)
+        ));
+    }
+
+    for file in &stats.files_not_licensed {
+        out.push(format!(
+            "::{} file={},title=License not configured::{} was not licensed with the given config",
+            level(stats, CheckCondition::Unconfigured),
+            escape_property(file),
+            escape_data(file)
+        ));
+    }
+
+    for (dir, licenses) in &stats.mixed_license_directories {
+        out.push(format!(
+            "::{} title=Mixed licenses::{} contains files with different licenses, {}",
+            level(stats, CheckCondition::MixedLicenses),
+            escape_data(dir),
+            escape_data(&describe_licenses(licenses))
+        ));
+    }
+
+    for file in &stats.files_with_unknown_license {
+        out.push(format!(
+            "::{} file={},line=1,title=Unknown license::The license header in {} doesn't match any configured license",
+            level(stats, CheckCondition::UnknownLicense), escape_property(file), escape_data(file)
+        ));
+    }
+
+    Some(out.join("\n"))
+}
+
+// Escape the message of a workflow command, so file names with newlines
+// can't end the command or start another.
+fn escape_data(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+// Escape a property of a workflow command, like file, where commas and
+// colons would end the value.
+fn escape_property(s: &str) -> String {
+    escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+// GitHub annotations are errors for conditions which fail the check
+// and warnings for the rest.
+fn level(stats: &LicenseStats, condition: CheckCondition) -> &'static str {
+    match stats.fails_on(condition) {
+        true => "error",
+        false => "warning",
+    }
+}
+
+// Conditions which don't fail the check are marked TODO, which TAP
+// harnesses don't count as failures.
+fn todo(stats: &LicenseStats, condition: CheckCondition) -> &'static str {
+    match stats.fails_on(condition) {
+        true => "",
+        false => " # TODO not in check_fail_on",
+    }
+}
+
+// Each license with the files that have it, e.g. MIT (a.rs, b.rs)
+fn describe_licenses(licenses: &BTreeMap<String, Vec<String>>) -> String {
+    licenses
+        .iter()
+        .map(|(ident, files)| format!("{} ({})", ident, files.join(", ")))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// One line of JSON recording a run for --audit-log: when it happened,
+/// what it did, and where the templates it stamped files with came from.
+pub fn render_audit_record(time: &str, check: bool, stats: &LicenseStats) -> String {
+    let changed: Vec<&String> = stats
+        .files_needing_license_update
+        .iter()
+        .chain(&stats.files_needing_format_fix)
+        .collect();
+    serde_json::json!({
+        "time": time,
+        "check": check,
+        "summary": stats.summary,
+        "files": changed,
+        "templates": stats.templates,
+    })
+    .to_string()
+}
+
+// Every checked file is a test point, see https://testanything.org/
+fn render_tap(stats: &LicenseStats) -> String {
+    let mut out = vec![
+        "TAP version 13".to_string(),
+        format!("1..{}", stats.files_checked.len()),
+    ];
+
+    for (idx, file) in stats.files_checked.iter().enumerate() {
+        let num = idx + 1;
+        let line = match stats
+            .files
+            .get(file)
+            .map(|report| (report.action, report.reason))
+        {
+            Some((Action::Added | Action::Updated, None)) => format!(
+                "not ok {} - {}: license header needs to be updated{}",
+                num,
+                file,
+                todo(stats, stats.license_condition(file))
+            ),
+            Some((_, Some("format_fix"))) => format!(
+                "not ok {} - {}: license header needs to be reformatted{}",
+                num,
+                file,
+                todo(stats, CheckCondition::Format)
+            ),
+            Some((_, Some("no_config_matched"))) => format!(
+                "not ok {} - {}: not licensed with the given config{}",
+                num,
+                file,
+                todo(stats, CheckCondition::Unconfigured)
+            ),
+            Some((_, Some("similar_header"))) => format!(
+                "not ok {} - {}: license header seems to be formatted differently{}",
+                num,
+                file,
+                todo(stats, CheckCondition::Format)
+            ),
+            Some((_, Some("unknown_license"))) => format!(
+                "not ok {} - {}: license header doesn't match any configured license{}",
+                num,
+                file,
+                todo(stats, CheckCondition::UnknownLicense)
+            ),
+            Some((_, Some("years_fixed"))) => format!("ok {} - {}: years updated", num, file),
+            Some((_, Some("binary"))) => format!("ok {} - {} # SKIP binary file", num, file),
+            Some((_, Some("generated"))) => format!("ok {} - {} # SKIP generated file", num, file),
+            Some((_, Some("bad_encoding"))) => format!("ok {} - {} # SKIP not UTF-8", num, file),
+            Some((_, Some("bad_path"))) => format!("ok {} - {} # SKIP path not UTF-8", num, file),
+            Some((_, Some("read_only"))) => format!("ok {} - {} # SKIP read-only", num, file),
+            Some((_, Some("too_small"))) => {
+                format!("ok {} - {} # SKIP too small for its header", num, file)
+            }
+            _ => format!("ok {} - {}", num, file),
+        };
+        out.push(line);
+    }
+
+    out.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::licensure::FileReport;
+
+    fn stats() -> LicenseStats {
+        let mut stats = LicenseStats::new();
+        stats.files_not_licensed.push("a.py".to_string());
+        report(
+            &mut stats,
+            "a.py",
+            Action::Skipped,
+            Some("no_config_matched"),
+        );
+        stats.files_needing_license_update.push("b.rs".to_string());
+        report(&mut stats, "b.rs", Action::Updated, None);
+        stats
+    }
+
+    fn report(stats: &mut LicenseStats, file: &str, action: Action, reason: Option<&'static str>) {
+        stats
+            .files
+            .insert(file.to_string(), FileReport::new(action, reason));
+    }
+
+    #[test]
+    fn test_render_json() {
+        let out = render_check_results(OutputFormat::Json, &stats()).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(value["files_not_licensed"][0], "a.py");
+        assert_eq!(value["files_needing_license_update"][0], "b.rs");
+    }
+
+    #[test]
+    fn test_render_audit_record() {
+        let out = render_audit_record("2024-06-01T00:00:00Z", false, &stats());
+        assert!(!out.contains('\n'));
+        let value: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(value["time"], "2024-06-01T00:00:00Z");
+        assert_eq!(value["check"], false);
+        assert_eq!(value["files"][0], "b.rs");
+        assert_eq!(value["summary"]["scanned"], 0);
+        assert_eq!(value["templates"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_render_years_fixed() {
+        let mut stats = LicenseStats::new();
+        stats.files_with_years_fixed.push("c.rs".to_string());
+        stats.files_checked.push("c.rs".to_string());
+        report(&mut stats, "c.rs", Action::Updated, Some("years_fixed"));
+        assert_eq!(
+            Some("The following files' years were updated\nc.rs".to_string()),
+            render_check_results(OutputFormat::Text, &stats)
+        );
+        assert!(render_check_results(OutputFormat::Tap, &stats)
+            .unwrap()
+            .ends_with("ok 1 - c.rs: years updated"));
+        assert_eq!(None, stats.exit_code());
+    }
+
+    #[test]
+    fn test_render_github() {
+        let out = render_check_results(OutputFormat::Github, &stats()).unwrap();
+        assert!(out.starts_with("::error file=b.rs,line=1,"));
+        assert!(out.contains("::error file=a.py,"));
+
+        let mut stats = stats();
+        stats.fail_on = vec![CheckCondition::Missing];
+        let out = render_check_results(OutputFormat::Github, &stats).unwrap();
+        assert!(out.starts_with("::warning file=b.rs,line=1,"));
+        assert!(out.contains("::warning file=a.py,"));
+        stats.files_checked.push("a.py".to_string());
+        let out = render_check_results(OutputFormat::Tap, &stats).unwrap();
+        assert!(out.contains("not ok 1 - a.py: not licensed with the given config # TODO"));
+    }
+
+    #[test]
+    fn test_render_github_escapes_file_names() {
+        let mut stats = LicenseStats::new();
+        stats
+            .files_not_licensed
+            .push("a,b:c%d\nline=2::e.py".to_string());
+        assert_eq!(
+            Some(
+                "::error file=a%2Cb%3Ac%25d%0Aline=2%3A%3Ae.py,title=License not configured::a,b:c%25d%0Aline=2::e.py was not licensed with the given config"
+                    .to_string()
+            ),
+            render_check_results(OutputFormat::Github, &stats)
+        );
+    }
+
+    #[test]
+    fn test_render_mixed_licenses() {
+        let mut stats = LicenseStats::new();
+        let licenses = stats
+            .mixed_license_directories
+            .entry("src".to_string())
+            .or_default();
+        licenses.insert(
+            "MIT".to_string(),
+            vec!["src/a.rs".to_string(), "src/c.rs".to_string()],
+        );
+        licenses.insert("Apache-2.0".to_string(), vec!["src/b.rs".to_string()]);
+
+        assert_eq!(
+            "The following directories contain files with different licenses
+src: Apache-2.0 (src/b.rs), MIT (src/a.rs, src/c.rs)",
+            render_check_results(OutputFormat::Text, &stats).unwrap()
+        );
+        assert_eq!(Some(crate::exit::MIXED_LICENSES), stats.exit_code());
+    }
+
+    #[test]
+    fn test_render_tap() {
+        let mut stats = stats();
+        stats.files_skipped_binary.push("logo.png".to_string());
+        report(&mut stats, "logo.png", Action::Skipped, Some("binary"));
+        stats.files_skipped_generated.push("d.pb.go".to_string());
+        report(&mut stats, "d.pb.go", Action::Skipped, Some("generated"));
+        stats.files_skipped_encoding.push("e.txt".to_string());
+        report(&mut stats, "e.txt", Action::Skipped, Some("bad_encoding"));
+        stats.files_checked = vec!["a.py", "b.rs", "c.rs", "logo.png", "d.pb.go", "e.txt"]
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        assert_eq!(
+            "TAP version 13
+1..6
+not ok 1 - a.py: not licensed with the given config
+not ok 2 - b.rs: license header needs to be updated
+ok 3 - c.rs
+ok 4 - logo.png # SKIP binary file
+ok 5 - d.pb.go # SKIP generated file
+ok 6 - e.txt # SKIP not UTF-8",
+            render_check_results(OutputFormat::Tap, &stats).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_render_nothing_when_no_failures() {
+        assert!(render_check_results(OutputFormat::Text, &LicenseStats::new()).is_none());
+        assert!(render_check_results(OutputFormat::Json, &LicenseStats::new()).is_some());
+    }
+}
@@ -0,0 +1,134 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+// How often the progress line is redrawn at most.
+const DRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+// Serializes writes to stderr of the progress line and warnings so they
+// don't interleave, holds whether the progress line is currently shown.
+static STDERR: Mutex<bool> = Mutex::new(false);
+
+/// Print a warning to stderr unless quiet is set, clearing the progress
+/// line first.
+#[macro_export]
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        $crate::progress::warn(format_args!($($arg)*))
+    };
+}
+
+/// When quiet is set warnings and progress aren't printed.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Use the warning macro instead.
+pub fn warn(args: fmt::Arguments) {
+    if is_quiet() {
+        return;
+    }
+
+    let mut shown = lock();
+    clear(&mut shown);
+    eprintln!("Warning: {}", args);
+}
+
+fn lock() -> MutexGuard<'static, bool> {
+    STDERR.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn clear(shown: &mut bool) {
+    if *shown {
+        eprint!("\r\x1b[K");
+        *shown = false;
+    }
+}
+
+/// A count of processed files drawn on a single line of stderr, which
+/// is cleared when it's dropped. It's safe to tick from many threads.
+pub struct Progress {
+    total: usize,
+    done: AtomicUsize,
+    enabled: bool,
+    last_draw: Mutex<Option<Instant>>,
+}
+
+impl Progress {
+    pub fn new(total: usize, enabled: bool) -> Progress {
+        Progress {
+            total,
+            done: AtomicUsize::new(0),
+            enabled: enabled && !is_quiet(),
+            last_draw: Mutex::new(None),
+        }
+    }
+
+    /// Record that another file was processed.
+    pub fn tick(&self) {
+        let done = self.done.fetch_add(1, Ordering::SeqCst) + 1;
+        if !self.enabled {
+            return;
+        }
+
+        let mut shown = lock();
+        let mut last_draw = self.last_draw.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        if done < self.total && last_draw.is_some_and(|t| now - t < DRAW_INTERVAL) {
+            return;
+        }
+
+        *last_draw = Some(now);
+        eprint!("\r\x1b[K{}/{} files", done, self.total);
+        *shown = true;
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if self.enabled {
+            clear(&mut lock());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_progress_counts_across_threads() {
+        let progress = Progress::new(400, false);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..100 {
+                        progress.tick();
+                    }
+                });
+            }
+        });
+        assert_eq!(400, progress.done.load(Ordering::SeqCst));
+    }
+}
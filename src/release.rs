@@ -0,0 +1,113 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use std::fmt::Write;
+
+use crate::licensure::Summary;
+use crate::template::{Template, YearMode};
+
+/// A release headers are stamped for, its version is rendered for
+/// [version] and the end year of every header is bumped to its year.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Release {
+    pub version: String,
+    pub year: String,
+}
+
+impl Release {
+    pub fn new(version: &str, year: &str) -> Release {
+        Release {
+            version: version.to_string(),
+            year: year.to_string(),
+        }
+    }
+
+    /// The template as it should be rendered for this release. Preserved
+    /// years are extended to the release year so that they're bumped too.
+    pub fn stamp(&self, templ: Template) -> Template {
+        let templ = match templ.year_mode() {
+            YearMode::Preserve => templ.set_year_mode(YearMode::RangeExtend),
+            _ => templ,
+        };
+
+        templ
+            .set_version(self.version.clone())
+            .set_end_year(self.year.clone())
+    }
+
+    /// A summary of the stamped headers for the release notes.
+    pub fn notes(&self, summary: &Summary) -> String {
+        let mut notes = format!("License headers for {}:\n\n", self.version);
+        writeln!(
+            notes,
+            "- {} header(s) updated to {}",
+            summary.updated, self.year
+        )
+        .unwrap();
+        writeln!(notes, "- {} header(s) added", summary.added).unwrap();
+        writeln!(
+            notes,
+            "- {} header(s) already up to date",
+            summary.already_licensed
+        )
+        .unwrap();
+        if summary.skipped > 0 {
+            writeln!(notes, "- {} file(s) skipped", summary.skipped).unwrap();
+        }
+
+        notes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::comments::LineComment;
+    use crate::template::test_context;
+
+    #[test]
+    fn test_stamp() {
+        let templ = Template::new(
+            "Copyright [year]\n\n[if version]Version [version][end]",
+            test_context("2024"),
+        );
+        let commenter = LineComment::new("#", None);
+        let existing = "# Copyright 2020, 2024\n#\n# Version v1.0.0\n";
+        assert_eq!(
+            Some("v1.0.0".to_string()),
+            templ.existing_version(&commenter, existing)
+        );
+
+        let stamped = Release::new("v2.0.0", "2026")
+            .stamp(templ.set_year_mode(YearMode::Preserve))
+            .apply_year_mode("2020, 2024");
+        assert_eq!("Copyright 2020, 2026\n\nVersion v2.0.0", stamped.render());
+    }
+
+    #[test]
+    fn test_notes() {
+        let summary = Summary {
+            scanned: 10,
+            excluded: 1,
+            already_licensed: 4,
+            added: 2,
+            updated: 3,
+            skipped: 0,
+        };
+        assert_eq!(
+            "License headers for v2.0.0:\n\n- 3 header(s) updated to 2026\n- 2 header(s) added\n- 4 header(s) already up to date\n",
+            Release::new("v2.0.0", "2026").notes(&summary)
+        );
+    }
+}
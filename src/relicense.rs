@@ -0,0 +1,154 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::licensure::Licensure;
+use crate::utils::utf8_paths;
+use crate::warning;
+
+/// A reviewable list of the files to move from one license to another.
+/// Each file is marked done as it's relicensed and the plan is saved
+/// after every file so an interrupted run can be resumed.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Plan {
+    pub from: String,
+    pub to: String,
+    pub files: Vec<PlanEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct PlanEntry {
+    pub path: String,
+    pub status: EntryStatus,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryStatus {
+    Pending,
+    Done,
+    // The file no longer had the header of the old license when the plan
+    // was applied.
+    Skipped,
+}
+
+impl Plan {
+    /// Plan to relicense every file in files which currently has the
+    /// header of the license from.
+    pub fn new<P: AsRef<Path>>(
+        licensure: &Licensure,
+        paths: &[P],
+        from: &str,
+        to: &str,
+    ) -> Result<Plan, io::Error> {
+        let (files, not_utf8) = utf8_paths(paths);
+        for file in &not_utf8 {
+            warning!("skipping {} because its path isn't valid UTF-8", file);
+        }
+
+        let mut entries = Vec::new();
+        for file in &files {
+            if licensure.has_license(file, from)? {
+                entries.push(PlanEntry {
+                    path: file.clone(),
+                    status: EntryStatus::Pending,
+                });
+            }
+        }
+
+        Ok(Plan {
+            from: from.to_string(),
+            to: to.to_string(),
+            files: entries,
+        })
+    }
+
+    pub fn load(path: &str) -> Result<Plan, io::Error> {
+        let content = fs::read_to_string(path)?;
+        serde_json::from_str(&content)
+            .map_err(|e| io::Error::other(format!("Invalid plan file {}: {}", path, e)))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), io::Error> {
+        let mut content = serde_json::to_string_pretty(self).expect("Plan is always valid JSON");
+        content.push('\n');
+        fs::write(path, content)
+    }
+
+    /// Relicense every pending file, saving the plan to path after each
+    /// one. Returns the number of files relicensed.
+    pub fn apply(&mut self, licensure: &Licensure, path: &str) -> Result<usize, io::Error> {
+        let mut relicensed = 0;
+        for idx in 0..self.files.len() {
+            if self.files[idx].status != EntryStatus::Pending {
+                continue;
+            }
+
+            let entry = &mut self.files[idx];
+            entry.status = if licensure.relicense_file(&entry.path, &self.from, &self.to)? {
+                relicensed += 1;
+                EntryStatus::Done
+            } else {
+                warning!(
+                    "skipping {} because it no longer has the {} header",
+                    entry.path,
+                    self.from
+                );
+                EntryStatus::Skipped
+            };
+            self.save(path)?;
+        }
+
+        Ok(relicensed)
+    }
+
+    pub fn pending(&self) -> usize {
+        self.files
+            .iter()
+            .filter(|e| e.status == EntryStatus::Pending)
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_round_trips() {
+        let plan = Plan {
+            from: "GPL-3.0".to_string(),
+            to: "Apache-2.0".to_string(),
+            files: vec![
+                PlanEntry {
+                    path: "src/a.rs".to_string(),
+                    status: EntryStatus::Done,
+                },
+                PlanEntry {
+                    path: "src/b.rs".to_string(),
+                    status: EntryStatus::Pending,
+                },
+            ],
+        };
+
+        let json = serde_json::to_string(&plan).unwrap();
+        assert!(json.contains("\"status\":\"pending\""));
+        assert_eq!(plan, serde_json::from_str::<Plan>(&json).unwrap());
+        assert_eq!(1, plan.pending());
+    }
+}
@@ -0,0 +1,263 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::licensure::Licensure;
+use crate::utils::utf8_paths;
+use crate::warning;
+
+/// The licensing inventory of a set of files, summarized per directory.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Report {
+    pub directories: Vec<DirectorySummary>,
+    pub files: Vec<FileEntry>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct FileEntry {
+    pub path: String,
+    /// The ident of the license whose files pattern matches the file.
+    pub license: Option<String>,
+    pub excluded: bool,
+    /// Whether the file has the header of the license matching it.
+    pub has_header: bool,
+    /// The idents of every configured license whose header the file has.
+    pub detected: Vec<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Default)]
+pub struct DirectorySummary {
+    pub directory: String,
+    pub files: usize,
+    pub excluded: usize,
+    pub with_header: usize,
+    pub without_header: usize,
+    /// The number of files matching each license.
+    pub licenses: BTreeMap<String, usize>,
+    /// The number of files with the header of each license.
+    pub detected: BTreeMap<String, usize>,
+}
+
+pub const FORMATS: &[&str] = &["json", "csv"];
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ReportFormat {
+    Json,
+    Csv,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(ReportFormat::Json),
+            "csv" => Ok(ReportFormat::Csv),
+            _ => Err(format!(
+                "{} is not a valid report format, expected one of: {}",
+                s,
+                FORMATS.join(", ")
+            )),
+        }
+    }
+}
+
+impl Report {
+    /// Inventory files, which are read but never changed.
+    pub fn new<P: AsRef<Path>>(licensure: &Licensure, paths: &[P]) -> Result<Report, io::Error> {
+        let (files, not_utf8) = utf8_paths(paths);
+        for file in &not_utf8 {
+            warning!("skipping {} because its path isn't valid UTF-8", file);
+        }
+
+        let mut entries = Vec::new();
+        for file in &files {
+            let excluded = licensure.is_excluded(file);
+            let license = licensure.matching_license(file).map(str::to_string);
+            let detected: Vec<String> = match excluded {
+                true => Vec::new(),
+                false => licensure
+                    .detected_licenses(file)?
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect(),
+            };
+
+            entries.push(FileEntry {
+                path: file.clone(),
+                has_header: license.as_ref().is_some_and(|l| detected.contains(l)),
+                license,
+                excluded,
+                detected,
+            });
+        }
+
+        Ok(Report::from_entries(entries))
+    }
+
+    pub fn from_entries(files: Vec<FileEntry>) -> Report {
+        let mut directories: BTreeMap<String, DirectorySummary> = BTreeMap::new();
+        for entry in &files {
+            let directory = directory_of(&entry.path);
+            let summary =
+                directories
+                    .entry(directory.clone())
+                    .or_insert_with(|| DirectorySummary {
+                        directory,
+                        ..Default::default()
+                    });
+
+            summary.files += 1;
+            if entry.excluded {
+                summary.excluded += 1;
+                continue;
+            }
+
+            if entry.has_header {
+                summary.with_header += 1;
+            } else {
+                summary.without_header += 1;
+            }
+
+            if let Some(license) = &entry.license {
+                *summary.licenses.entry(license.clone()).or_default() += 1;
+            }
+
+            for ident in &entry.detected {
+                *summary.detected.entry(ident.clone()).or_default() += 1;
+            }
+        }
+
+        Report {
+            directories: directories.into_values().collect(),
+            files,
+        }
+    }
+
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Json => {
+                serde_json::to_string_pretty(self).expect("Report is always valid JSON") + "\n"
+            }
+            ReportFormat::Csv => self.render_csv(),
+        }
+    }
+
+    /// One row per directory, the counts per license are written as
+    /// ident=count separated by semicolons.
+    fn render_csv(&self) -> String {
+        let mut out =
+            String::from("directory,files,excluded,with_header,without_header,licenses,detected\n");
+        for dir in &self.directories {
+            out.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                csv_field(&dir.directory),
+                dir.files,
+                dir.excluded,
+                dir.with_header,
+                dir.without_header,
+                csv_field(&join_counts(&dir.licenses)),
+                csv_field(&join_counts(&dir.detected)),
+            ));
+        }
+
+        out
+    }
+}
+
+/// The directory of path, "." for files in the current directory.
+fn directory_of(path: &str) -> String {
+    let path = path.strip_prefix("./").unwrap_or(path);
+    match Path::new(path).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_string_lossy().to_string(),
+        _ => ".".to_string(),
+    }
+}
+
+fn join_counts(counts: &BTreeMap<String, usize>) -> String {
+    counts
+        .iter()
+        .map(|(ident, count)| format!("{}={}", ident, count))
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// Quote field if it contains a character with meaning in CSV.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, license: Option<&str>, excluded: bool, detected: &[&str]) -> FileEntry {
+        let detected: Vec<String> = detected.iter().map(|d| d.to_string()).collect();
+        FileEntry {
+            path: path.to_string(),
+            license: license.map(str::to_string),
+            excluded,
+            has_header: license.is_some_and(|l| detected.iter().any(|d| d == l)),
+            detected,
+        }
+    }
+
+    #[test]
+    fn test_report_by_directory() {
+        let report = Report::from_entries(vec![
+            entry("./README.md", None, true, &[]),
+            entry("src/main.rs", Some("MIT"), false, &["MIT"]),
+            entry("src/lib.rs", Some("MIT"), false, &[]),
+            entry("src/vendor.rs", Some("MIT"), false, &["Apache-2.0"]),
+            entry("docs,old/a.rs", Some("MIT"), false, &["MIT"]),
+        ]);
+
+        assert_eq!(
+            vec![".", "docs,old", "src"],
+            report
+                .directories
+                .iter()
+                .map(|d| d.directory.as_str())
+                .collect::<Vec<_>>()
+        );
+        let src = &report.directories[2];
+        assert_eq!(
+            (3, 0, 1, 2),
+            (src.files, src.excluded, src.with_header, src.without_header)
+        );
+        assert_eq!(Some(&3), src.licenses.get("MIT"));
+        assert_eq!(Some(&1), src.detected.get("Apache-2.0"));
+
+        assert_eq!(
+            "directory,files,excluded,with_header,without_header,licenses,detected
+.,1,1,0,0,,
+\"docs,old\",1,0,1,0,MIT=1,MIT=1
+src,3,0,1,2,MIT=3,Apache-2.0=1;MIT=1
+",
+            report.render(ReportFormat::Csv)
+        );
+        assert!(report
+            .render(ReportFormat::Json)
+            .contains("\"has_header\": true"));
+    }
+}
@@ -0,0 +1,205 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::uncommentable::Dep5;
+use crate::warning;
+
+/// The path glob, copyright, and license of a configured license, as
+/// recorded in a DEP-5 or REUSE.toml file.
+#[derive(Debug, PartialEq)]
+pub struct Annotation {
+    // Uses ** for any sequence of characters, including /.
+    pub glob: String,
+    pub copyright: String,
+    pub license: String,
+}
+
+/// An annotation for every configured license whose files pattern can be
+/// expressed as a glob, in the order they should be written. Both DEP-5
+/// and REUSE.toml apply the last entry matching a file while licensure
+/// uses the first license that matches, so they are reversed.
+pub fn annotations(config: &Config) -> Vec<Annotation> {
+    let mut annotations: Vec<Annotation> = config
+        .licenses
+        .static_templates()
+        .into_iter()
+        .filter_map(|(files, templ)| match regex_to_glob(files) {
+            Some(glob) => Some(Annotation {
+                glob,
+                copyright: templ.copyright(),
+                license: templ.ident().to_string(),
+            }),
+            None => {
+                warning!(
+                    "the files pattern {} of license {} can't be expressed as a path glob, add an entry for it by hand",
+                    files,
+                    templ.ident()
+                );
+                None
+            }
+        })
+        .collect();
+    annotations.reverse();
+    annotations
+}
+
+/// The current and synced content of the DEP-5 or REUSE.toml file at
+/// path, which is treated as REUSE.toml if it's named so.
+pub fn sync(path: &str, annotations: &[Annotation]) -> Result<(String, String), io::Error> {
+    let current = if Path::new(path).exists() {
+        fs::read_to_string(path)?
+    } else {
+        String::new()
+    };
+
+    let is_reuse_toml = Path::new(path)
+        .file_name()
+        .is_some_and(|name| name == "REUSE.toml");
+    let synced = if is_reuse_toml {
+        render_reuse_toml(annotations)
+    } else {
+        let mut dep5 = Dep5::load(path)?;
+        let entries: Vec<(String, String, String)> = annotations
+            .iter()
+            .map(|a| {
+                (
+                    a.glob.replace("**", "*"),
+                    a.copyright.clone(),
+                    a.license.clone(),
+                )
+            })
+            .collect();
+        dep5.sync(&entries);
+        dep5.render()
+    };
+
+    Ok((current, synced))
+}
+
+/// A REUSE.toml file, see https://reuse.software/spec-3.2/#reusetoml,
+/// which licensure owns entirely.
+fn render_reuse_toml(annotations: &[Annotation]) -> String {
+    let mut out = String::from(
+        "# Generated by licensure dep5 sync from the licensure config, don't edit by hand.\nversion = 1\n",
+    );
+    for a in annotations {
+        out.push_str(&format!(
+            "\n[[annotations]]\npath = {}\nSPDX-FileCopyrightText = {}\nSPDX-License-Identifier = {}\n",
+            toml_string(&a.glob),
+            toml_string(&a.copyright),
+            toml_string(&a.license)
+        ));
+    }
+
+    out
+}
+
+fn toml_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Convert a licenses files regex to a glob matching the same paths, or
+/// None if it uses anything other than literal text, escaped punctuation,
+/// .*, and anchors. Like the regex an unanchored pattern can match
+/// anywhere in the path.
+fn regex_to_glob(pattern: &str) -> Option<String> {
+    if pattern == "any" {
+        return Some("**".to_string());
+    }
+
+    let (anchored_start, pattern) = match pattern.strip_prefix('^') {
+        Some(rest) => (true, rest),
+        None => (false, pattern),
+    };
+    let (anchored_end, pattern) = match pattern.strip_suffix('$') {
+        Some(rest) if !rest.ends_with('\\') => (true, rest),
+        _ => (false, pattern),
+    };
+
+    let mut glob = String::new();
+    if !anchored_start {
+        glob.push_str("**");
+    }
+
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' if chars.peek() == Some(&'*') => {
+                chars.next();
+                glob.push_str("**");
+            }
+            // Escaped letters are classes like \d and glob metacharacters
+            // would change meaning.
+            '\\' => match chars.next()? {
+                e if e.is_ascii_alphanumeric() || matches!(e, '*' | '?' | '[' | ']') => {
+                    return None
+                }
+                e => glob.push(e),
+            },
+            c if c.is_alphanumeric() || "/_-~,@=% ".contains(c) => glob.push(c),
+            _ => return None,
+        }
+    }
+
+    if !anchored_end {
+        glob.push_str("**");
+    }
+
+    while glob.contains("****") {
+        glob = glob.replace("****", "**");
+    }
+
+    Some(glob)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_regex_to_glob() {
+        assert_eq!(Some("**".to_string()), regex_to_glob("any"));
+        assert_eq!(Some("**.png".to_string()), regex_to_glob(".*\\.png$"));
+        assert_eq!(Some("**.png".to_string()), regex_to_glob("\\.png$"));
+        assert_eq!(Some("assets/**".to_string()), regex_to_glob("^assets/.*"));
+        assert_eq!(Some("**vendor/**".to_string()), regex_to_glob("vendor/"));
+        assert_eq!(None, regex_to_glob(".*\\.(png|jpg)$"));
+        assert_eq!(None, regex_to_glob("\\d+\\.rs"));
+        assert_eq!(None, regex_to_glob("a.rs"));
+    }
+
+    #[test]
+    fn test_render_reuse_toml() {
+        let annotations = vec![Annotation {
+            glob: "assets/**".to_string(),
+            copyright: "2024 Bob \"Bobby\" Smith".to_string(),
+            license: "CC-BY-4.0".to_string(),
+        }];
+        assert_eq!(
+            "# Generated by licensure dep5 sync from the licensure config, don't edit by hand.
+version = 1
+
+[[annotations]]
+path = \"assets/**\"
+SPDX-FileCopyrightText = \"2024 Bob \\\"Bobby\\\" Smith\"
+SPDX-License-Identifier = \"CC-BY-4.0\"
+",
+            render_reuse_toml(&annotations)
+        );
+    }
+}
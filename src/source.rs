@@ -0,0 +1,165 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[cfg(test)]
+use std::collections::BTreeMap;
+#[cfg(test)]
+use std::sync::Mutex;
+
+use crate::utils::{atomic, cache};
+use crate::walk;
+
+/// Where the files being licensed are listed, read from, and written to.
+/// Files are on disk by default but can be anywhere, e.g. in memory or
+/// fetched from a code review, without licensure touching the disk.
+pub trait FileSource: Send + Sync {
+    /// The files of the project.
+    fn list(&self) -> Result<Vec<PathBuf>, io::Error>;
+
+    /// The content of path, an error of kind NotFound if it doesn't
+    /// exist.
+    fn read(&self, path: &str) -> Result<Arc<Vec<u8>>, io::Error>;
+
+    /// Replace the content of path, creating it if it doesn't exist.
+    fn write(&self, path: &str, content: &[u8]) -> Result<(), io::Error>;
+
+    fn exists(&self, path: &str) -> bool;
+
+    /// Whether path can be written, files which can't are skipped.
+    fn is_writable(&self, _path: &str) -> bool {
+        true
+    }
+}
+
+/// Files on disk relative to the current directory. Reads go through the
+/// content cache and writes are atomic.
+#[derive(Default)]
+pub struct OsSource {
+    pub options: atomic::Options,
+    /// Read-only files and directories are made writable while writing
+    /// to them instead of being skipped.
+    pub chmod_if_needed: bool,
+}
+
+impl FileSource for OsSource {
+    fn list(&self) -> Result<Vec<PathBuf>, io::Error> {
+        let files = walk::walk_project_files(Path::new("."))?;
+        Ok(files.into_iter().map(PathBuf::from).collect())
+    }
+
+    fn read(&self, path: &str) -> Result<Arc<Vec<u8>>, io::Error> {
+        cache::read(path)
+    }
+
+    fn write(&self, path: &str, content: &[u8]) -> Result<(), io::Error> {
+        if let Some(parent) = Path::new(path).parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        if self.chmod_if_needed {
+            atomic::with_writable(path, || atomic::write(path, content, &self.options))?;
+        } else {
+            atomic::write(path, content, &self.options)?;
+        }
+        cache::invalidate(path);
+        Ok(())
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        Path::new(path).exists()
+    }
+
+    fn is_writable(&self, path: &str) -> bool {
+        self.chmod_if_needed || !atomic::is_read_only(path)
+    }
+}
+
+/// A shared source, so that its files can be inspected after licensing.
+#[cfg(test)]
+impl<S: FileSource> FileSource for Arc<S> {
+    fn list(&self) -> Result<Vec<PathBuf>, io::Error> {
+        self.as_ref().list()
+    }
+
+    fn read(&self, path: &str) -> Result<Arc<Vec<u8>>, io::Error> {
+        self.as_ref().read(path)
+    }
+
+    fn write(&self, path: &str, content: &[u8]) -> Result<(), io::Error> {
+        self.as_ref().write(path, content)
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.as_ref().exists(path)
+    }
+
+    fn is_writable(&self, path: &str) -> bool {
+        self.as_ref().is_writable(path)
+    }
+}
+
+/// Files held in memory by path.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemorySource {
+    files: Mutex<BTreeMap<String, Arc<Vec<u8>>>>,
+}
+
+#[cfg(test)]
+impl MemorySource {
+    pub fn new(files: &[(&str, &str)]) -> MemorySource {
+        let source = MemorySource::default();
+        for (path, content) in files {
+            source.write(path, content.as_bytes()).unwrap();
+        }
+        source
+    }
+
+    pub fn content(&self, path: &str) -> Option<String> {
+        self.read(path)
+            .ok()
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, Arc<Vec<u8>>>> {
+        self.files.lock().expect("memory source lock was poisoned!")
+    }
+}
+
+#[cfg(test)]
+impl FileSource for MemorySource {
+    fn list(&self) -> Result<Vec<PathBuf>, io::Error> {
+        Ok(self.lock().keys().map(PathBuf::from).collect())
+    }
+
+    fn read(&self, path: &str) -> Result<Arc<Vec<u8>>, io::Error> {
+        self.lock().get(path).cloned().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("{} doesn't exist", path))
+        })
+    }
+
+    fn write(&self, path: &str, content: &[u8]) -> Result<(), io::Error> {
+        self.lock()
+            .insert(path.to_string(), Arc::new(content.to_vec()));
+        Ok(())
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.lock().contains_key(path)
+    }
+}
@@ -11,30 +11,66 @@
 // You should have received a copy of the GNU General Public License along with
 // this program. If not, see <https://www.gnu.org/licenses/>.
 //
-use std::fmt;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+use std::sync::Arc;
 
-use chrono::prelude::*;
-use regex::Regex;
+use chrono::NaiveDate;
+use regex::{Captures, Regex};
 use serde::Deserialize;
 
-use crate::comments::Comment;
+use crate::clock::Clock;
+#[cfg(test)]
+use crate::clock::FixedClock;
+use crate::comments::{trim_line_ends, Comment};
+use crate::config::LinePatterns;
 use crate::utils::remove_column_wrapping;
 
 #[derive(Clone, Deserialize, Debug)]
 struct CopyrightHolder {
     name: String,
     email: Option<String>,
+    // Optional years this author holds copyright for, when set they
+    // are rendered before the name e.g. "2019-2021 Alice".
+    start_year: Option<String>,
+    end_year: Option<String>,
 }
 
-impl fmt::Display for CopyrightHolder {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut a = self.name.clone();
+impl CopyrightHolder {
+    fn has_years(&self) -> bool {
+        self.start_year.is_some() || self.end_year.is_some()
+    }
+
+    fn years(&self, current_year: &str) -> Option<String> {
+        match (&self.start_year, &self.end_year) {
+            (None, None) => None,
+            (None, Some(end)) => Some(end.clone()),
+            (Some(start), end) => {
+                let end = end.as_deref().unwrap_or(current_year);
+                if start == end {
+                    Some(end.to_string())
+                } else {
+                    Some(format!("{}-{}", start, end))
+                }
+            }
+        }
+    }
+
+    /// The author as it appears in a header, open ended year ranges end
+    /// in current_year.
+    fn render(&self, current_year: &str) -> String {
+        let mut a = match self.years(current_year) {
+            Some(years) => format!("{} ", years),
+            None => String::new(),
+        };
+        a.push_str(&self.name);
 
         if let Some(email) = &self.email {
             a.push_str(&format!(" <{}>", email));
         }
 
-        write!(f, "{}", a)
+        a
     }
 }
 
@@ -44,25 +80,256 @@ pub struct Authors {
     authors: Vec<CopyrightHolder>,
 }
 
+impl Authors {
+    pub fn from_names(names: Vec<(String, Option<String>)>) -> Authors {
+        let authors = names
+            .into_iter()
+            .map(|(name, email)| CopyrightHolder {
+                name,
+                email,
+                start_year: None,
+                end_year: None,
+            })
+            .collect();
+        Authors { authors }
+    }
+
+    /// The comma separated authors as they appear in a header.
+    fn render(&self, current_year: &str) -> String {
+        self.authors
+            .iter()
+            .map(|author| author.render(current_year))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Every author as "Name <email>", or just the name if they have no
+    /// email, without their years.
+    pub fn identities(&self) -> Vec<String> {
+        self.authors
+            .iter()
+            .map(|author| match &author.email {
+                Some(email) => format!("{} <{}>", author.name, email),
+                None => author.name.clone(),
+            })
+            .collect()
+    }
+
+    /// Replace the years of every author which has them with token, used
+    /// to build regexes which match any per-author years.
+    fn with_year_token(&self, token: &str) -> Authors {
+        let authors = self
+            .authors
+            .iter()
+            .map(|author| {
+                let mut author = author.clone();
+                if author.has_years() {
+                    author.start_year = None;
+                    author.end_year = Some(token.to_string());
+                }
+
+                author
+            })
+            .collect();
+        Authors { authors }
+    }
+}
+
 impl From<Vec<CopyrightHolder>> for Authors {
     fn from(authors: Vec<CopyrightHolder>) -> Authors {
         Authors { authors }
     }
 }
 
-impl fmt::Display for Authors {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut a = String::new();
+/// How a range of years is rendered for [year], e.g. "[start]-[end]",
+/// the text before, between, and after the [start] and [end] tokens.
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+#[serde(try_from = "String")]
+pub struct YearRangeFormat {
+    before: String,
+    between: String,
+    after: String,
+}
+
+impl YearRangeFormat {
+    pub fn render(&self, start_year: &str, end_year: &str) -> String {
+        format!(
+            "{}{}{}{}{}",
+            self.before, start_year, self.between, end_year, self.after
+        )
+    }
 
-        for author in &self.authors {
-            if !a.is_empty() {
-                a.push_str(", ");
+    /// A regex matching a single 4-digit year or a range of them in this
+    /// format. Formats with nothing before [start] and after [end] also
+    /// match lists of years and ranges like "2018, 2020-2022, 2024".
+    fn regex(&self) -> String {
+        if self.is_bare() {
+            let mut separators = vec![regex::escape(YEAR_LIST_SEPARATOR), regex::escape("-")];
+            let between = regex::escape(&self.between);
+            if !separators.contains(&between) {
+                separators.push(between);
             }
+            return format!("[0-9]{{4}}(({})[0-9]{{4}})*", separators.join("|"));
+        }
+
+        format!(
+            "({}[0-9]{{4}}{}[0-9]{{4}}{}|[0-9]{{4}})",
+            regex::escape(&self.before),
+            regex::escape(&self.between),
+            regex::escape(&self.after)
+        )
+    }
+
+    fn is_bare(&self) -> bool {
+        self.before.is_empty() && self.after.is_empty()
+    }
+
+    /// The start and, if they're a range, end year of years rendered in
+    /// this format. None if they're neither a year nor a range.
+    fn parse(&self, years: &str) -> Option<(String, Option<String>)> {
+        if is_year(years) {
+            return Some((years.to_string(), None));
+        }
+
+        let (start, end) = years
+            .strip_prefix(&self.before)
+            .and_then(|r| r.strip_suffix(&self.after))
+            .and_then(|r| r.split_once(&self.between))?;
+        if !is_year(start) || !is_year(end) {
+            return None;
+        }
+
+        Some((start.to_string(), Some(end.to_string())))
+    }
+
+    /// The sorted and merged ranges of a list of years and ranges like
+    /// "2018, 2020-2022, 2024", the ranges may be in this format too.
+    fn parse_list(&self, years: &str) -> Option<Vec<(u32, u32)>> {
+        let ranges = years
+            .split(YEAR_LIST_SEPARATOR)
+            .map(|item| {
+                let (start, end) = item
+                    .split_once('-')
+                    .or_else(|| item.split_once(self.between.as_str()))
+                    .unwrap_or((item, item));
+                if !is_year(start) || !is_year(end) {
+                    return None;
+                }
+                Some((start.parse().ok()?, end.parse().ok()?))
+            })
+            .collect::<Option<Vec<(u32, u32)>>>()?;
+        Some(merge_year_ranges(ranges))
+    }
+
+    /// Render ranges as a list of years and ranges, the ranges are in
+    /// this format if it's bare and doesn't use the list separator.
+    fn render_list(&self, ranges: &[(u32, u32)]) -> String {
+        let between = if self.is_bare() && self.between != YEAR_LIST_SEPARATOR {
+            self.between.as_str()
+        } else {
+            "-"
+        };
+        ranges
+            .iter()
+            .map(|(start, end)| match start == end {
+                true => start.to_string(),
+                false => format!("{}{}{}", start, between, end),
+            })
+            .collect::<Vec<_>>()
+            .join(YEAR_LIST_SEPARATOR)
+    }
+}
+
+// Separates the years and ranges of a year list.
+const YEAR_LIST_SEPARATOR: &str = ", ";
+
+fn is_year(s: &str) -> bool {
+    s.len() == 4 && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Sort ranges of years, merging those which overlap or are adjacent.
+fn merge_year_ranges(mut ranges: Vec<(u32, u32)>) -> Vec<(u32, u32)> {
+    ranges
+        .iter_mut()
+        .for_each(|r| *r = (r.0.min(r.1), r.0.max(r.1)));
+    ranges.sort();
+    let mut merged: Vec<(u32, u32)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+
+    merged
+}
+
+/// What is done with files which are too small for their header.
+#[derive(Clone, Copy, Deserialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SmallFiles {
+    /// Give them a header with only an SPDX license identifier tag.
+    #[default]
+    SpdxTag,
+    /// Leave them unlicensed, with a warning.
+    Skip,
+}
+
+/// When a file is too small for its header, either because it has fewer
+/// than min_lines or the header would have more than max_ratio times its
+/// lines.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SmallFileLimits {
+    pub min_lines: Option<usize>,
+    pub max_ratio: Option<f64>,
+    pub action: SmallFiles,
+}
 
-            a.push_str(&author.to_string());
+const SPDX_TAG_TEMPLATE: &str = "SPDX-License-Identifier: [ident]";
+
+/// How the years of existing headers are updated.
+#[derive(Clone, Copy, Deserialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum YearMode {
+    /// Replace them with the years of the rendered header.
+    #[default]
+    Current,
+    /// Never change them.
+    Preserve,
+    /// Keep their start year and extend them to the rendered end year.
+    RangeExtend,
+}
+
+impl Default for YearRangeFormat {
+    fn default() -> YearRangeFormat {
+        YearRangeFormat::try_from(DEFAULT_YEAR_RANGE_FORMAT.to_string()).unwrap()
+    }
+}
+
+impl TryFrom<String> for YearRangeFormat {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<YearRangeFormat, String> {
+        let error = || {
+            format!(
+                "year range format \"{}\" must contain [start] followed by [end]",
+                s
+            )
+        };
+        let (before, rest) = s.split_once("[start]").ok_or_else(error)?;
+        let (between, after) = rest.split_once("[end]").ok_or_else(error)?;
+        if between.is_empty() || between.chars().any(|c| c.is_ascii_digit()) {
+            return Err(format!(
+                "year range format \"{}\" must separate [start] and [end] by something other than digits",
+                s
+            ));
         }
 
-        write!(f, "{}", a)
+        Ok(YearRangeFormat {
+            before: before.to_string(),
+            between: between.to_string(),
+            after: after.to_string(),
+        })
     }
 }
 
@@ -73,21 +340,74 @@ pub struct Context {
     pub end_year: Option<String>,
     pub start_year: Option<String>,
     pub unwrap_text: bool,
+    /// The path of the file being licensed, used for the [path],
+    /// [filename], and [basename] variables.
+    pub path: String,
+    /// User defined variables from the config file, each is substituted
+    /// for [name] in the template.
+    pub variables: BTreeMap<String, String>,
+    /// Provides the current year for headers without an end year.
+    pub clock: Arc<dyn Clock>,
+    /// How [year] is rendered when start_year and end_year differ.
+    pub year_range_format: YearRangeFormat,
+    /// The date rendered for [date], [month], and [date:<format>]
+    /// tokens, today if not set.
+    pub date: Option<NaiveDate>,
+    /// The release rendered for [version], set by stamp-release or kept
+    /// from the existing header. A version variable is used if not set.
+    pub version: Option<String>,
 }
 
 impl Context {
     fn get_authors(&self) -> String {
-        self.authors.to_string()
+        self.authors.render(&self.clock.year())
+    }
+
+    fn get_filename(&self) -> &str {
+        Path::new(&self.path)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default()
+    }
+
+    fn get_basename(&self) -> &str {
+        Path::new(&self.path)
+            .file_stem()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default()
+    }
+
+    /// Whether the variable name has a non-empty value, this decides if
+    /// the [if name]...[end] blocks of a template are rendered.
+    fn is_set(&self, name: &str) -> bool {
+        match name {
+            "path" => !self.path.is_empty(),
+            "filename" => !self.get_filename().is_empty(),
+            "basename" => !self.get_basename().is_empty(),
+            "ident" => !self.ident.is_empty(),
+            "name of author" => !self.get_authors().is_empty(),
+            "version" if self.version.is_some() => self.version.as_deref() != Some(""),
+            _ => self
+                .variables
+                .get(name)
+                .is_some_and(|value| !value.is_empty()),
+        }
+    }
+
+    fn get_date(&self) -> NaiveDate {
+        self.date.unwrap_or_else(|| self.clock.today())
     }
 
     fn get_year(&self) -> String {
         let end_year = match &self.end_year {
             Some(year) => year.clone(),
-            None => format!("{}", Local::now().year()),
+            None => self.clock.year(),
         };
 
         match &self.start_year {
-            Some(start_year) if *start_year != end_year => format!("{}, {}", start_year, end_year),
+            Some(start_year) if *start_year != end_year => {
+                self.year_range_format.render(start_year, &end_year)
+            }
             _ => end_year,
         }
     }
@@ -96,8 +416,15 @@ impl Context {
 #[derive(Clone)]
 pub struct Template {
     spdx_template: bool,
+    auto_strip_comments: bool,
+    strict_bytes: bool,
+    authors_varying: bool,
+    year_mode: YearMode,
+    small_file_limits: Option<SmallFileLimits>,
     content: String,
     context: Context,
+    banners: LinePatterns,
+    replaces: Vec<Template>,
 }
 
 // this token is temporarily used when formatting the template into a comment
@@ -109,15 +436,42 @@ pub struct Template {
 // in the license text.
 const INTERMEDIATE_YEAR_TOKEN: &str = "@YR@";
 
-// Matches any full 4-digit year
-const YEAR_RE: &str = "[0-9]{4}(, [0-9]{4})?";
+// Stands in for the file path when building a fingerprint, it has no
+// extension so that [filename] and [basename] render it unchanged.
+const INTERMEDIATE_PATH_TOKEN: &str = "@PATH@";
+
+// Stands in for the release version, which like the path matches any
+// word.
+const INTERMEDIATE_VERSION_TOKEN: &str = "@VERSION@";
+const VERSION_RE: &str = "\\S+";
+
+// The default year_range_format, matched by the year regex as
+// [0-9]{4}((, |\-)[0-9]{4})*
+const DEFAULT_YEAR_RANGE_FORMAT: &str = "[start], [end]";
+
+// The per-author equivalents of the above, authors render their years as
+// a single year or a range like 2019-2021.
+const INTERMEDIATE_AUTHOR_YEAR_TOKEN: &str = "@AY@";
+const AUTHOR_YEAR_RE: &str = "[0-9]{4}(-[0-9]{4})?";
+
+// Stands in for the authors of templates with varying authors, which
+// are matched by any text on the same line.
+const INTERMEDIATE_AUTHORS_TOKEN: &str = "@AUTHORS@";
+const AUTHORS_RE: &str = "[^\n]+?";
 
 impl Template {
     pub fn new(template: &str, context: Context) -> Template {
         Template {
             spdx_template: false,
+            auto_strip_comments: false,
+            strict_bytes: false,
+            authors_varying: false,
+            small_file_limits: None,
+            year_mode: YearMode::default(),
             content: template.to_string(),
             context,
+            banners: LinePatterns::default(),
+            replaces: Vec::new(),
         }
     }
 
@@ -126,6 +480,190 @@ impl Template {
         self
     }
 
+    pub fn authors(&self) -> &Authors {
+        &self.context.authors
+    }
+
+    pub fn ident(&self) -> &str {
+        &self.context.ident
+    }
+
+    /// The year and authors as they'd appear in a copyright line e.g.
+    /// "2024 Alice <alice@example.com>".
+    pub fn copyright(&self) -> String {
+        format!("{} {}", self.context.get_year(), self.context.get_authors())
+    }
+
+    pub fn set_clock(mut self, clock: Arc<dyn Clock>) -> Template {
+        self.context.clock = clock;
+        self
+    }
+
+    pub fn set_date(mut self, date: NaiveDate) -> Template {
+        self.context.date = Some(date);
+        self
+    }
+
+    pub fn set_version(mut self, version: String) -> Template {
+        self.context.version = Some(version);
+        self
+    }
+
+    pub fn set_end_year(mut self, end_year: String) -> Template {
+        self.context.end_year = Some(end_year);
+        self
+    }
+
+    pub fn set_authors(mut self, authors: Authors) -> Template {
+        self.context.authors = authors;
+        self
+    }
+
+    /// Add the user defined variables of the config, variables the
+    /// license entry already set take precedence.
+    pub fn set_variables(mut self, variables: BTreeMap<String, String>) -> Template {
+        for (name, value) in variables {
+            self.context.variables.entry(name).or_insert(value);
+        }
+        self
+    }
+
+    pub fn set_auto_strip_comments(mut self, yes_or_no: bool) -> Template {
+        self.auto_strip_comments = yes_or_no;
+        self
+    }
+
+    /// Only accept existing headers which are byte for byte identical
+    /// to the rendered header, any difference in whitespace or wrapping
+    /// means the header needs an update.
+    pub fn set_strict_bytes(mut self, yes_or_no: bool) -> Template {
+        self.strict_bytes = yes_or_no;
+        self
+    }
+
+    pub fn strict_bytes(&self) -> bool {
+        self.strict_bytes
+    }
+
+    /// Match existing headers with any authors, not only the configured
+    /// ones, so that headers are updated when the authors change.
+    pub fn set_authors_varying(mut self, yes_or_no: bool) -> Template {
+        self.authors_varying = yes_or_no;
+        self
+    }
+
+    pub fn set_small_file_limits(mut self, limits: Option<SmallFileLimits>) -> Template {
+        self.small_file_limits = limits;
+        self
+    }
+
+    /// What to do instead of adding a header of header_lines to a file
+    /// of content_lines, or None if the file isn't too small for it.
+    pub fn small_file_action(
+        &self,
+        content_lines: usize,
+        header_lines: usize,
+    ) -> Option<SmallFiles> {
+        let limits = self.small_file_limits.as_ref()?;
+        let too_few_lines = limits.min_lines.is_some_and(|min| content_lines < min);
+        let too_large = limits
+            .max_ratio
+            .is_some_and(|max| header_lines as f64 > max * content_lines as f64);
+        (too_few_lines || too_large).then_some(limits.action)
+    }
+
+    /// This template with only an SPDX license identifier tag, used for
+    /// files which are too small for the full header.
+    pub fn spdx_tag_only(&self) -> Template {
+        let mut templ = self.clone();
+        templ.content = SPDX_TAG_TEMPLATE.to_string();
+        templ.spdx_template = false;
+        templ.small_file_limits = None;
+        templ
+    }
+
+    /// This template with other content and ident, e.g. for a template
+    /// it replaces. spdx_template is whether content is an SPDX license
+    /// template.
+    pub fn with_content(&self, content: &str, ident: &str, spdx_template: bool) -> Template {
+        let mut templ = self.clone();
+        templ.content = content.to_string();
+        templ.context.ident = ident.to_string();
+        templ.spdx_template = spdx_template;
+        templ.small_file_limits = None;
+        templ.replaces = Vec::new();
+        templ
+    }
+
+    /// Set the templates whose headers are replaced by this one's.
+    pub fn set_replaces(mut self, replaces: Vec<Template>) -> Template {
+        self.replaces = replaces;
+        self
+    }
+
+    pub fn replaces(&self) -> &[Template] {
+        &self.replaces
+    }
+
+    pub fn set_year_mode(mut self, year_mode: YearMode) -> Template {
+        self.year_mode = year_mode;
+        self
+    }
+
+    pub fn year_mode(&self) -> YearMode {
+        self.year_mode
+    }
+
+    /// Set the patterns of banner lines, such as rows of dashes, which
+    /// are treated as part of an existing header when found directly
+    /// above or below it.
+    pub fn set_banners(mut self, banners: LinePatterns) -> Template {
+        self.banners = banners;
+        self
+    }
+
+    /// Whether line, with the comment marker of commenter removed, is a
+    /// banner line.
+    pub fn is_banner(&self, commenter: &dyn Comment, line: &str) -> bool {
+        let line = line.trim();
+        let line = commenter
+            .line_marker()
+            .and_then(|marker| line.strip_prefix(marker.trim_end()))
+            .unwrap_or(line)
+            .trim();
+        !line.is_empty() && self.banners.is_match(line)
+    }
+
+    /// Check whether the template text is already commented with the
+    /// given line marker, as happens when a header is pasted from a
+    /// source file. If auto_strip_comments is enabled the marker is
+    /// removed, otherwise an error explaining the problem is returned.
+    pub fn strip_comment_markers(mut self, marker: &str) -> Result<Template, String> {
+        let mut lines = self.content.lines().filter(|l| !l.trim().is_empty());
+        if marker.is_empty() || !lines.all(|l| l.trim_start().starts_with(marker)) {
+            return Ok(self);
+        }
+
+        if !self.auto_strip_comments {
+            return Err(format!(
+                "the template for {} is already commented with \"{}\", remove the comment characters from the template or set auto_strip_comments: true",
+                self.context.ident, marker
+            ));
+        }
+
+        self.content = self
+            .content
+            .lines()
+            .map(|l| {
+                let l = l.trim_start();
+                let l = l.strip_prefix(marker).unwrap_or(l);
+                l.strip_prefix(' ').unwrap_or(l)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        Ok(self)
+    }
+
     pub fn outdated_license_pattern(&self, commenter: &dyn Comment) -> Regex {
         self.build_year_varying_regex(commenter, false)
     }
@@ -134,67 +672,340 @@ impl Template {
         self.build_year_varying_regex(commenter, true)
     }
 
+    /// A regex matching the uncommented header with any year and any file
+    /// path. Runs of whitespace are matched by \s+ so it is independent
+    /// of comment style and wrapping, other tools can use it to detect
+    /// headers the same way licensure does after stripping comment
+    /// characters.
+    pub fn fingerprint(&self) -> String {
+        let mut context = self.context.clone();
+        context.end_year = Some(INTERMEDIATE_YEAR_TOKEN.to_string());
+        context.start_year = None;
+        context.path = INTERMEDIATE_PATH_TOKEN.to_string();
+        context.authors = context
+            .authors
+            .with_year_token(INTERMEDIATE_AUTHOR_YEAR_TOKEN);
+
+        let year_re = context.year_range_format.regex();
+        self.interpolate(&context, true)
+            .split_whitespace()
+            .map(|word| {
+                let escaped = escape_with_years(word, &year_re, |fragment| {
+                    fragment
+                        .split(INTERMEDIATE_PATH_TOKEN)
+                        .map(regex::escape)
+                        .collect::<Vec<_>>()
+                        .join("\\S+")
+                });
+                replace_date_tokens(&escaped, false)
+            })
+            .collect::<Vec<_>>()
+            .join("\\s+")
+    }
+
     pub fn render(&self) -> String {
-        self.interpolate(&self.context)
+        self.interpolate(&self.context, false)
     }
 
-    fn interpolate(&self, context: &Context) -> String {
+    /// The date of the header of this template in content, which is
+    /// kept when the header is updated so that dates don't change every
+    /// day. None if content has no such header or its date tokens don't
+    /// make up a full date.
+    pub fn existing_date(&self, commenter: &dyn Comment, content: &str) -> Option<NaiveDate> {
+        if date_formats(&self.content).is_empty() {
+            return None;
+        }
+
+        let context = self.year_varying_context();
+        let header = self.interpolate(&context, true);
+        let pattern = self.header_pattern(commenter, &header, true, true);
+        let captures = Regex::new(&pattern).ok()?.captures(content)?;
+
+        // Each date token is captured word by word, in order.
+        let resolved = resolve_conditionals(&self.content, &|name| context.is_set(name));
+        let substituted = self.substitute(&resolved, &context);
+        let formats = date_formats(&substituted);
+        let mut words: Vec<Vec<&str>> = vec![Vec::new(); formats.len()];
+        let commented = commenter.comment(&header);
+        for (group, index) in date_token_runs(&commented).into_iter().enumerate() {
+            words[index].push(captures.name(&date_group(group))?.as_str());
+        }
+
+        formats
+            .iter()
+            .zip(words)
+            .find_map(|(format, words)| NaiveDate::parse_from_str(&words.join(" "), format).ok())
+    }
+
+    /// The years of the header of this template in content, as the first
+    /// [year] of it is rendered, or None if content has no such header.
+    pub fn existing_years(&self, commenter: &dyn Comment, content: &str) -> Option<String> {
+        let (year_repl, _, _) = self.replacement_tokens();
+        if !self.content.contains(year_repl) {
+            return None;
+        }
+
+        let captures = self.capture_header(&self.year_varying_context(), commenter, content)?;
+        Some(captures.name("year")?.as_str().to_string())
+    }
+
+    /// The release of the header of this template in content, as its
+    /// first [version] is rendered, or None if content has no such header.
+    pub fn existing_version(&self, commenter: &dyn Comment, content: &str) -> Option<String> {
+        if !self.content.contains(VERSION_TOKEN) {
+            return None;
+        }
+
+        let mut context = self.year_varying_context();
+        context.version = Some(INTERMEDIATE_VERSION_TOKEN.to_string());
+        let captures = self.capture_header(&context, commenter, content)?;
+        Some(captures.name("version")?.as_str().to_string())
+    }
+
+    /// The captures of the header of this template in content, rendered
+    /// with context or as it was before the variables of its conditional
+    /// blocks were set.
+    fn capture_header<'c>(
+        &self,
+        context: &Context,
+        commenter: &dyn Comment,
+        content: &'c str,
+    ) -> Option<Captures<'c>> {
+        [
+            self.interpolate(context, true),
+            self.interpolate_unset(context, true),
+        ]
+        .iter()
+        .find_map(|header| {
+            let pattern = self.header_pattern(commenter, header, true, true);
+            Regex::new(&pattern).ok()?.captures(content)
+        })
+    }
+
+    /// Change the years of the header according to the year mode given
+    /// the years of the existing header.
+    pub fn apply_year_mode(mut self, existing_years: &str) -> Template {
+        let format = &self.context.year_range_format;
+        if let Some((start_year, end_year)) = format.parse(existing_years) {
+            match self.year_mode {
+                YearMode::Current => {}
+                YearMode::Preserve => {
+                    self.context.end_year = Some(end_year.unwrap_or(start_year.clone()));
+                    self.context.start_year = Some(start_year);
+                }
+                YearMode::RangeExtend => self.context.start_year = Some(start_year),
+            }
+            return self;
+        }
+
+        // Lists of years are normalized, and extended with the end year
+        // as another range.
+        let mut ranges = match format.parse_list(existing_years) {
+            Some(ranges) => ranges,
+            None => return self,
+        };
+        match self.year_mode {
+            YearMode::Current => return self,
+            YearMode::Preserve => {}
+            YearMode::RangeExtend => {
+                let end_year = self.context.end_year.clone();
+                let end_year = end_year.unwrap_or_else(|| self.context.clock.year());
+                if let Ok(year) = end_year.parse() {
+                    ranges = merge_year_ranges([ranges, vec![(year, year)]].concat());
+                }
+            }
+        }
+        self.context.end_year = Some(format.render_list(&ranges));
+        self.context.start_year = None;
+        self
+    }
+
+    /// Interpolate the template, with date tokens replaced by
+    /// intermediate tokens if intermediate is true.
+    fn interpolate(&self, context: &Context, intermediate: bool) -> String {
+        let content = resolve_conditionals(&self.content, &|name| context.is_set(name));
+        let content = self.vary_authors(content, intermediate);
+        render_dates(
+            &self.substitute(&content, context),
+            context.get_date(),
+            intermediate,
+        )
+    }
+
+    /// Interpolate the template as if none of its conditional blocks
+    /// were set, this is how the header looked before an optional
+    /// variable was added.
+    fn interpolate_unset(&self, context: &Context, intermediate: bool) -> String {
+        let content = resolve_conditionals(&self.content, &|_| false);
+        let content = self.vary_authors(content, intermediate);
+        render_dates(
+            &self.substitute(&content, context),
+            context.get_date(),
+            intermediate,
+        )
+    }
+
+    /// Replace the authors of content with the intermediate authors
+    /// token when building regexes for a template with varying authors.
+    fn vary_authors(&self, content: String, intermediate: bool) -> String {
+        if !intermediate || !self.authors_varying {
+            return content;
+        }
+
+        let (_, author_repl, _) = self.replacement_tokens();
+        content.replace(author_repl, INTERMEDIATE_AUTHORS_TOKEN)
+    }
+
+    fn substitute(&self, content: &str, context: &Context) -> String {
         let (year_repl, author_repl, ident_repl) = self.replacement_tokens();
         let templ = if self.context.unwrap_text {
             // Some license headers come pre-textwrapped. This regex
             // replacement removes their wrapping while preserving
             // intentional line breaks / empty lines.
-            remove_column_wrapping(&self.content.clone())
+            remove_column_wrapping(content)
         } else {
-            self.content.clone()
+            content.to_string()
         };
 
         // Perform our substitutions
-        templ
+        let mut rendered = templ
             .replace(year_repl, &context.get_year())
             .replace(author_repl, &context.get_authors())
             .replace(ident_repl, &context.ident)
+            .replace("[path]", &context.path)
+            .replace("[filename]", context.get_filename())
+            .replace("[basename]", context.get_basename());
+        if let Some(version) = &context.version {
+            rendered = rendered.replace(VERSION_TOKEN, version);
+        }
+
+        for (name, value) in &context.variables {
+            rendered = rendered.replace(&format!("[{}]", name), value);
+        }
+
+        rendered
     }
 
-    fn build_year_varying_regex(&self, commenter: &dyn Comment, trim_trailing: bool) -> Regex {
+    /// The context with intermediate tokens for the years, see
+    /// build_year_varying_regex.
+    fn year_varying_context(&self) -> Context {
         let mut context = self.context.clone();
 
         // interpolate the header with the intermediate year token
         context.end_year = Some(INTERMEDIATE_YEAR_TOKEN.to_string());
         // The year regex accounts for ranges so we don't need to worry about start_year here.
         context.start_year = None;
+        context.authors = context
+            .authors
+            .with_year_token(INTERMEDIATE_AUTHOR_YEAR_TOKEN);
+        // Any release matches, so headers aren't outdated by a release.
+        if context.version.is_some() && self.content.contains(VERSION_TOKEN) {
+            context.version = Some(INTERMEDIATE_VERSION_TOKEN.to_string());
+        }
+        context
+    }
 
-        let interpolated_header = self.interpolate(&context);
-        let mut rendered = commenter.comment(&interpolated_header);
+    fn build_year_varying_regex(&self, commenter: &dyn Comment, trim_trailing: bool) -> Regex {
+        Regex::new(
+            &self
+                .year_varying_patterns(commenter, trim_trailing)
+                .join("|"),
+        )
+        .unwrap()
+    }
+
+    /// A regex matching the header with any years however it's wrapped,
+    /// e.g. at the columns of a previous comment config.
+    pub fn rewrapped_license_pattern(&self, commenter: &dyn Comment) -> Regex {
+        let marker = commenter.line_marker();
+        let case_insensitive = commenter.case_insensitive_marker().is_some();
+        let patterns: Vec<String> = self
+            .year_varying_patterns(commenter, true)
+            .iter()
+            .map(|pattern| loosen_wrapping(pattern, marker, case_insensitive))
+            .collect();
+        Regex::new(&patterns.join("|")).unwrap()
+    }
+
+    fn year_varying_patterns(&self, commenter: &dyn Comment, trim_trailing: bool) -> Vec<String> {
+        let context = self.year_varying_context();
+
+        // A header written before the variables of its conditional
+        // blocks were set is outdated too, so match it as well.
+        let mut headers = vec![self.interpolate(&context, true)];
+        let unset = self.interpolate_unset(&context, true);
+        if unset != headers[0] {
+            headers.push(unset);
+        }
+
+        headers
+            .iter()
+            .map(|header| self.header_pattern(commenter, header, trim_trailing, false))
+            .collect()
+    }
+
+    /// The regex matching header commented by commenter, with any years
+    /// and dates. If capture is true every word of a date is captured, see
+    /// date_group, and so is the first [year] in the group named year.
+    fn header_pattern(
+        &self,
+        commenter: &dyn Comment,
+        header: &str,
+        trim_trailing: bool,
+        capture: bool,
+    ) -> String {
+        // Lines match with or without trailing whitespace, see
+        // LINE_END_RE, so that neither form of a header is churned.
+        let mut rendered = trim_line_ends(&commenter.comment(header));
 
         if trim_trailing {
             rendered = rendered.trim_end().to_string();
         }
 
-        // let's now replace the intermediate year token with a proper
-        // regex for a 4-digit year (see const `YEAR_RE`)
-        let escaped = rendered
-            // split removes all instances of the token, yielding all text fragments
-            // around the locations where tokens were excised
-            .split(INTERMEDIATE_YEAR_TOKEN)
-            // convert to iterable for functional-style chaining
-            .collect::<Vec<_>>()
-            .into_iter()
-            // regex-escape each text fragment so we can match the literal
-            // text via regex
-            .map(regex::escape)
-            // yields a list containing all of the text fragments we want
-            // to match as literals via regex
-            .collect::<Vec<_>>()
-            // joining the fragments with the year-matching regex pattern
-            // effectively inserts itself into all the locations where the
-            // intermediate token existed. We now have a regex that matches
-            // the exact license header text, but with any 4-digit year.
-            //
-            // And we only care about 4-digit years in our lifetime ;).
-            .join(YEAR_RE);
+        // let's now replace the intermediate year tokens with a proper
+        // regex for a 4-digit year or range in the year range format,
+        // this results in a regex that matches the exact license header
+        // text, but with any 4-digit year.
+        //
+        // And we only care about 4-digit years in our lifetime ;).
+        let year_re = self.context.year_range_format.regex();
+        let escape = |text: &str| regex::escape(text).replace('\n', LINE_END_RE);
+        let escaped = if capture {
+            escape_with_years(&rendered, INTERMEDIATE_YEAR_CHAR, escape)
+                .replacen(INTERMEDIATE_YEAR_CHAR, &format!("(?P<year>{})", year_re), 1)
+                .replace(INTERMEDIATE_YEAR_CHAR, &year_re)
+        } else {
+            escape_with_years(&rendered, &year_re, escape)
+        };
+        let escaped = replace_date_tokens(&escaped, capture);
+        let escaped = if capture {
+            escaped
+                .replacen(
+                    INTERMEDIATE_VERSION_TOKEN,
+                    &format!("(?P<version>{})", VERSION_RE),
+                    1,
+                )
+                .replace(INTERMEDIATE_VERSION_TOKEN, VERSION_RE)
+        } else {
+            escaped.replace(INTERMEDIATE_VERSION_TOKEN, VERSION_RE)
+        };
 
-        Regex::new(&escaped).unwrap()
+        // Allow any casing of case insensitive comment markers at the
+        // start of each line so that e.g. "rem" headers are found when
+        // the configured marker is "REM".
+        match commenter.case_insensitive_marker() {
+            Some(marker) if !marker.is_empty() => {
+                let marker = regex::escape(marker);
+                let insensitive = format!("(?i:{})", marker);
+                let escaped =
+                    escaped.replace(&format!("\n{}", marker), &format!("\n{}", insensitive));
+                match escaped.strip_prefix(&marker) {
+                    Some(rest) => format!("{}{}", insensitive, rest),
+                    None => escaped,
+                }
+            }
+            _ => escaped,
+        }
     }
 
     fn replacement_tokens(&self) -> (&'static str, &'static str, &'static str) {
@@ -222,6 +1033,298 @@ impl Template {
     }
 }
 
+const VERSION_TOKEN: &str = "[version]";
+
+/// The end of a line of a header, with or without trailing whitespace.
+/// Written without literal whitespace so that loosen_wrapping leaves it
+/// be.
+const LINE_END_RE: &str = "[\\t\\x20]*\n";
+
+const CONDITIONAL_START: &str = "[if ";
+const CONDITIONAL_END: &str = "[end]";
+
+/// Render the [if name]...[end] blocks of text, keeping the contents of
+/// those for which is_set returns true. Blocks can be nested, an [if
+/// name] without a matching [end] is left as is.
+fn resolve_conditionals(text: &str, is_set: &dyn Fn(&str) -> bool) -> String {
+    let mut resolved = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(CONDITIONAL_START) {
+        resolved.push_str(&rest[..start]);
+        let after = &rest[start + CONDITIONAL_START.len()..];
+        let block = after.find(']').and_then(|close| {
+            let body = &after[close + 1..];
+            matching_end(body).map(|end| (after[..close].trim(), body, end))
+        });
+
+        match block {
+            Some((name, body, end)) => {
+                if is_set(name) {
+                    resolved.push_str(&resolve_conditionals(&body[..end], is_set));
+                }
+                rest = &body[end + CONDITIONAL_END.len()..];
+            }
+            None => {
+                resolved.push_str(CONDITIONAL_START);
+                rest = after;
+            }
+        }
+    }
+
+    resolved.push_str(rest);
+    resolved
+}
+
+/// The index of the [end] closing a conditional block whose body starts
+/// text, skipping over nested blocks.
+fn matching_end(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut offset = 0;
+    loop {
+        let rest = &text[offset..];
+        let end = rest.find(CONDITIONAL_END)?;
+        match rest.find(CONDITIONAL_START) {
+            Some(start) if start < end => {
+                depth += 1;
+                offset += start + CONDITIONAL_START.len();
+            }
+            _ if depth == 0 => return Some(offset + end),
+            _ => {
+                depth -= 1;
+                offset += end + CONDITIONAL_END.len();
+            }
+        }
+    }
+}
+
+// The formats of the [date] and [month] tokens, other formats are given
+// as [date:<format>] e.g. [date:%d.%m.%Y].
+const DATE_TOKENS: [(&str, &str); 2] = [("[date]", "%Y-%m-%d"), ("[month]", "%B")];
+const DATE_FORMAT_TOKEN_START: &str = "[date:";
+
+// The date tokens of a header are replaced by runs of a character from
+// the private use area, one per token, when building its regexes. Like
+// the intermediate year tokens they keep the width of the rendered date
+// so that it is wrapped the same way.
+const INTERMEDIATE_DATE_CHAR: u32 = 0xE000;
+const INTERMEDIATE_DATE_CHARS: u32 = 0x100;
+
+// Stands in for the year regex when the first [year] is captured.
+const INTERMEDIATE_YEAR_CHAR: &str = "\u{E100}";
+
+/// The date token at the start of text, as its length and format.
+fn date_token(text: &str) -> Option<(usize, &str)> {
+    if let Some((token, format)) = DATE_TOKENS.iter().find(|(t, _)| text.starts_with(t)) {
+        return Some((token.len(), format));
+    }
+
+    let format = text.strip_prefix(DATE_FORMAT_TOKEN_START)?;
+    let end = format.find(']')?;
+    let format = &format[..end];
+    // Formatting fails for invalid formats and those with times.
+    let mut example = String::new();
+    if format.is_empty() || write!(example, "{}", NaiveDate::MIN.format(format)).is_err() {
+        return None;
+    }
+
+    Some((DATE_FORMAT_TOKEN_START.len() + end + 1, format))
+}
+
+/// Replace the date tokens of text with date in their format, or with
+/// intermediate tokens if intermediate is true. Tokens with an invalid
+/// format are left as is.
+fn render_dates(text: &str, date: NaiveDate, intermediate: bool) -> String {
+    let mut rendered = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut index = 0;
+    while let Some(start) = rest.find('[') {
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match date_token(rest) {
+            Some((len, format)) => {
+                let date = date.format(format).to_string();
+                if intermediate {
+                    let token =
+                        char::from_u32(INTERMEDIATE_DATE_CHAR + index % INTERMEDIATE_DATE_CHARS)
+                            .expect("private use area characters are valid");
+                    rendered.extend(
+                        date.chars()
+                            .map(|c| if c.is_whitespace() { c } else { token }),
+                    );
+                    index += 1;
+                } else {
+                    rendered.push_str(&date);
+                }
+                rest = &rest[len..];
+            }
+            None => {
+                rendered.push('[');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    rendered.push_str(rest);
+    rendered
+}
+
+/// The formats of the date tokens of text, in order.
+fn date_formats(text: &str) -> Vec<&str> {
+    let mut formats = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('[') {
+        rest = &rest[start..];
+        match date_token(rest) {
+            Some((len, format)) => {
+                formats.push(format);
+                rest = &rest[len..];
+            }
+            None => rest = &rest[1..],
+        }
+    }
+
+    formats
+}
+
+/// The index of the date token of each run of intermediate date
+/// characters in text.
+fn date_token_runs(text: &str) -> Vec<usize> {
+    let mut runs = Vec::new();
+    let mut previous = None;
+    for c in text.chars() {
+        let index = intermediate_date_index(c);
+        if index.is_some() && index != previous {
+            runs.push(index.unwrap_or_default());
+        }
+        previous = index;
+    }
+
+    runs
+}
+
+fn intermediate_date_index(c: char) -> Option<usize> {
+    let offset = (c as u32).checked_sub(INTERMEDIATE_DATE_CHAR)?;
+    (offset < INTERMEDIATE_DATE_CHARS).then_some(offset as usize)
+}
+
+/// The name of the capture group of the nth run of intermediate date
+/// characters.
+fn date_group(n: usize) -> String {
+    format!("date{}", n)
+}
+
+/// Replace every run of intermediate date characters in escaped with a
+/// regex matching a word, captured in the groups named by date_group if
+/// capture is true.
+fn replace_date_tokens(escaped: &str, capture: bool) -> String {
+    let mut replaced = String::with_capacity(escaped.len());
+    let mut previous = None;
+    let mut runs = 0;
+    for c in escaped.chars() {
+        let index = intermediate_date_index(c);
+        match index {
+            Some(_) if index == previous => {}
+            Some(_) if capture => {
+                replaced.push_str(&format!("(?P<{}>\\S+)", date_group(runs)));
+                runs += 1;
+            }
+            Some(_) => replaced.push_str("\\S+"),
+            None => replaced.push(c),
+        }
+        previous = index;
+    }
+
+    replaced
+}
+
+/// Regex escape text with the escape function, except for the
+/// intermediate year and authors tokens which are replaced with their
+/// regexes, year_re being the regex of [year].
+fn escape_with_years<F>(text: &str, year_re: &str, escape: F) -> String
+where
+    F: Fn(&str) -> String,
+{
+    let tokens = [
+        (INTERMEDIATE_YEAR_TOKEN, year_re),
+        (INTERMEDIATE_AUTHOR_YEAR_TOKEN, AUTHOR_YEAR_RE),
+        (INTERMEDIATE_AUTHORS_TOKEN, AUTHORS_RE),
+    ];
+    escape_tokens(text, &tokens, &escape)
+}
+
+fn escape_tokens(text: &str, tokens: &[(&str, &str)], escape: &dyn Fn(&str) -> String) -> String {
+    match tokens.split_first() {
+        Some(((token, re), rest)) => text
+            .split(token)
+            .map(|fragment| escape_tokens(fragment, rest, escape))
+            .collect::<Vec<_>>()
+            .join(re),
+        None => escape(text),
+    }
+}
+
+/// Let every run of spaces and line breaks in pattern, an escaped
+/// commented header, match any other such run, with the comment
+/// continued by marker after each line break. The header is then matched
+/// no matter the width it was wrapped at, including with its URLs and
+/// hyphenated words broken across lines or not.
+fn loosen_wrapping(pattern: &str, marker: Option<&str>, case_insensitive: bool) -> String {
+    // Runs of whitespace match trailing whitespace anyway.
+    let pattern = &pattern.replace(LINE_END_RE, "\n");
+    let marker = match marker.map(str::trim).filter(|m| !m.is_empty()) {
+        Some(marker) => regex::escape(marker),
+        None => {
+            let loosened = Regex::new(r"[ \t\n]+")
+                .unwrap()
+                .replace_all(pattern, r"\s+")
+                .to_string();
+            return allow_word_breaks(&loosened, r"\s*", r"\s+");
+        }
+    };
+
+    // Case insensitive markers appear as (?i:marker) in pattern.
+    let (in_pattern, line_break) = match case_insensitive {
+        true => (
+            regex::escape(&format!("(?i:{})", marker)),
+            format!(r"[ \t]*\n[ \t]*(?i:{})", marker),
+        ),
+        false => (regex::escape(&marker), format!(r"[ \t]*\n[ \t]*{}", marker)),
+    };
+    let separator = format!(r"(?:{}|[ \t]+)+", line_break);
+    let runs = Regex::new(&format!(r"(?:[ \t]*\n[ \t]*(?:{})?|[ \t]+)+", in_pattern)).unwrap();
+    let loosened = runs
+        .replace_all(pattern, regex::NoExpand(&separator))
+        .to_string();
+    allow_word_breaks(&loosened, &format!(r"{}[ \t]*", line_break), &separator)
+}
+
+/// Let the words of pattern be broken at line_break after a / or -, as
+/// textwrap breaks URLs and hyphenated words unless preserve_urls is set,
+/// and words broken there in pattern, followed by separator, be whole.
+fn allow_word_breaks(pattern: &str, line_break: &str, separator: &str) -> String {
+    let mut pattern = pattern.to_string();
+    for after in ["/", "\\-"] {
+        pattern = pattern.replace(
+            &format!("{}{}", after, separator),
+            &format!("{}(?:{})?", after, separator),
+        );
+    }
+
+    Regex::new(r"(/|\\-)([[:alnum:]])")
+        .unwrap()
+        .replace_all(&pattern, |caps: &Captures| {
+            format!("{}(?:{})?{}", &caps[1], line_break, &caps[2])
+        })
+        .to_string()
+}
+
+// A clock fixed in the middle of 2024 so tests don't depend on the date
+// they're run on.
+#[cfg(test)]
+pub fn test_clock() -> Arc<dyn Clock> {
+    Arc::new(FixedClock(NaiveDate::from_ymd_opt(2024, 6, 1).unwrap()))
+}
+
 #[cfg(test)]
 pub fn test_context(year: &str) -> Context {
     Context {
@@ -230,6 +1333,12 @@ pub fn test_context(year: &str) -> Context {
         end_year: Some(String::from(year)),
         start_year: None,
         unwrap_text: true,
+        path: String::new(),
+        variables: BTreeMap::new(),
+        clock: test_clock(),
+        year_range_format: YearRangeFormat::default(),
+        date: None,
+        version: None,
     }
 }
 
@@ -241,10 +1350,17 @@ pub fn test_context_with_range(start_year: &str, end_year: &str) -> Context {
         end_year: Some(String::from(end_year)),
         start_year: Some(String::from(start_year)),
         unwrap_text: true,
+        path: String::new(),
+        variables: BTreeMap::new(),
+        clock: test_clock(),
+        year_range_format: YearRangeFormat::default(),
+        date: None,
+        version: None,
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use crate::comments::LineComment;
 
@@ -256,7 +1372,9 @@ mod tests {
         let template = Template::new("License [year]\n\ntext", context);
         let commenter = LineComment::new("#", None);
         let rgx = template.outdated_license_pattern(&commenter);
-        let expected = Regex::new("\\# License [0-9]{4}(, [0-9]{4})?\n\\#\n\\# text\n")
+        let expected = Regex::new(
+            "\\# License [0-9]{4}((, |\\-)[0-9]{4})*[\\t\\x20]*\n\\#[\\t\\x20]*\n\\# text[\\t\\x20]*\n",
+        )
             .expect("This should have compiled?");
 
         assert_eq!(rgx.to_string(), expected.to_string());
@@ -266,6 +1384,7 @@ mod tests {
 # text
 "#
         ));
+        assert!(rgx.is_match("# License 2020  \n# \n# text\n"));
     }
 
     #[test]
@@ -274,8 +1393,10 @@ mod tests {
         let template = Template::new("License [year]\n\ntext", context);
         let commenter = LineComment::new("#", None);
         let rgx = template.outdated_license_trimmed_pattern(&commenter);
-        let expected = Regex::new("\\# License [0-9]{4}(, [0-9]{4})?\n\\#\n\\# text")
-            .expect("This should have compiled?");
+        let expected = Regex::new(
+            "\\# License [0-9]{4}((, |\\-)[0-9]{4})*[\\t\\x20]*\n\\#[\\t\\x20]*\n\\# text",
+        )
+        .expect("This should have compiled?");
 
         assert_eq!(rgx.to_string(), expected.to_string());
         assert!(rgx.is_match(
@@ -285,6 +1406,469 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_strip_comment_markers() {
+        let template = Template::new("# License [year]\n#\n# text", test_context("2020"))
+            .set_auto_strip_comments(true)
+            .strip_comment_markers("#")
+            .unwrap();
+        assert_eq!("License 2020\n\ntext", template.render());
+    }
+
+    #[test]
+    fn test_strip_comment_markers_disabled() {
+        let template = Template::new("# License [year]\n#\n# text", test_context("2020"));
+        assert!(template.strip_comment_markers("#").is_err());
+    }
+
+    #[test]
+    fn test_strip_comment_markers_not_commented() {
+        let template = Template::new("License [year]\n# text", test_context("2020"))
+            .strip_comment_markers("#")
+            .unwrap();
+        assert_eq!("License 2020 # text", template.render());
+    }
+
+    #[test]
+    fn test_rewrapped_license_pattern_broken_urls() {
+        let template = Template::new(
+            "See https://www.gnu.org/licenses/gpl-3.0-standalone.html for details.",
+            test_context("2020"),
+        );
+        let broken =
+            "// See https://www.gnu.org/\n// licenses/gpl-3.0-\n// standalone.html for details.\n";
+        let whole =
+            "// See\n// https://www.gnu.org/licenses/gpl-3.0-standalone.html\n// for details.\n";
+        for cols in [20, 80] {
+            let rgx = template.rewrapped_license_pattern(&LineComment::new("//", Some(cols)));
+            assert!(rgx.is_match(broken));
+            assert!(rgx.is_match(whole));
+        }
+    }
+
+    #[test]
+    fn test_year_varying_regex_case_insensitive_marker() {
+        let template = Template::new("License [year]\n\ntext", test_context("2020"));
+        let commenter = LineComment::new("REM", None).set_case_insensitive(true);
+        let rgx = template.outdated_license_pattern(&commenter);
+        assert!(rgx.is_match("rem License 2020\nRem\nREM text\n"));
+        assert!(!rgx.is_match("rem license 2020\nrem\nrem text\n"));
+
+        let commenter = LineComment::new("REM", None);
+        let rgx = template.outdated_license_pattern(&commenter);
+        assert!(!rgx.is_match("rem License 2020\nrem\nrem text\n"));
+    }
+
+    fn test_authors_with_years() -> Authors {
+        Authors::from(vec![
+            CopyrightHolder {
+                name: "Alice".to_string(),
+                email: None,
+                start_year: Some("2019".to_string()),
+                end_year: Some("2021".to_string()),
+            },
+            CopyrightHolder {
+                name: "Bob".to_string(),
+                email: Some("bob@example.com".to_string()),
+                start_year: None,
+                end_year: Some("2024".to_string()),
+            },
+            CopyrightHolder {
+                name: "Carol".to_string(),
+                email: None,
+                start_year: None,
+                end_year: None,
+            },
+        ])
+    }
+
+    #[test]
+    fn test_current_year_comes_from_clock() {
+        let mut context = test_context("2020");
+        context.end_year = None;
+        context.start_year = Some("2019".to_string());
+        context.authors = Authors::from(vec![CopyrightHolder {
+            name: "Alice".to_string(),
+            email: None,
+            start_year: Some("2022".to_string()),
+            end_year: None,
+        }]);
+        let template = Template::new("Copyright [year] [name of author]", context);
+        assert_eq!("Copyright 2019, 2024 2022-2024 Alice", template.render());
+    }
+
+    #[test]
+    fn test_substitutions_per_author_years() {
+        let mut context = test_context("2020");
+        context.authors = test_authors_with_years();
+        let template = Template::new("Copyright [name of author]", context);
+        assert_eq!(
+            "Copyright 2019-2021 Alice, 2024 Bob <bob@example.com>, Carol",
+            template.render()
+        );
+    }
+
+    #[test]
+    fn test_year_varying_regex_per_author_years() {
+        let mut context = test_context("2020");
+        context.authors = test_authors_with_years();
+        let template = Template::new("Copyright [name of author]", context);
+        let rgx = template.outdated_license_pattern(&LineComment::new("#", None));
+        assert!(
+            rgx.is_match("# Copyright 2019-2021 Alice, 2022-2024 Bob <bob@example.com>, Carol\n")
+        );
+        assert!(rgx.is_match("# Copyright 2019 Alice, 2020 Bob <bob@example.com>, Carol\n"));
+        assert!(!rgx.is_match("# Copyright 2019 Alice, 2020 Bob, Carol\n"));
+    }
+
+    #[test]
+    fn test_substitutions_file_variables() {
+        let mut context = test_context("2020");
+        context.path = "src/config/mod.rs".to_string();
+        let template = Template::new(
+            "[filename] ([basename]) at [path] is part of Project X",
+            context,
+        );
+        assert_eq!(
+            "mod.rs (mod) at src/config/mod.rs is part of Project X",
+            template.render()
+        );
+    }
+
+    #[test]
+    fn test_substitutions_user_variables() {
+        let mut variables = BTreeMap::new();
+        variables.insert("project".to_string(), "MyApp".to_string());
+        variables.insert("homepage".to_string(), "https://example.com".to_string());
+        let template = Template::new(
+            "This file is part of [project], see [homepage]. [unknown]",
+            test_context("2020"),
+        )
+        .set_variables(variables);
+        assert_eq!(
+            "This file is part of MyApp, see https://example.com. [unknown]",
+            template.render()
+        );
+    }
+
+    #[test]
+    fn test_year_range_format() {
+        let dash = YearRangeFormat::try_from("[start]-[end]".to_string()).unwrap();
+        assert_eq!("2019-2025", dash.render("2019", "2025"));
+        assert_eq!("[0-9]{4}((, |\\-)[0-9]{4})*", dash.regex());
+
+        let years = YearRangeFormat::try_from("from [start] to [end]".to_string()).unwrap();
+        assert_eq!("from 2019 to 2025", years.render("2019", "2025"));
+        let rgx = Regex::new(&format!("^{}$", years.regex())).unwrap();
+        assert!(rgx.is_match("from 2019 to 2025"));
+        assert!(rgx.is_match("2025"));
+        assert!(!rgx.is_match("2019, 2025"));
+
+        assert!(YearRangeFormat::try_from("[start]".to_string()).is_err());
+        assert!(YearRangeFormat::try_from("[end]-[start]".to_string()).is_err());
+        assert!(YearRangeFormat::try_from("[start][end]".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_year_varying_regex_year_range_format() {
+        let mut context = test_context_with_range("2019", "2024");
+        context.year_range_format =
+            YearRangeFormat::try_from("[start]\u{2013}[end]".to_string()).unwrap();
+        let template = Template::new("Copyright [year] Alice", context);
+        assert_eq!("Copyright 2019\u{2013}2024 Alice", template.render());
+
+        let commenter = LineComment::new("#", None);
+        let rgx = template.outdated_license_pattern(&commenter);
+        assert!(rgx.is_match("# Copyright 2019\u{2013}2023 Alice\n"));
+        assert!(rgx.is_match("# Copyright 2023 Alice\n"));
+        assert!(rgx.is_match("# Copyright 2019, 2023 Alice\n"));
+        assert!(!rgx.is_match("# Copyright 2019 to 2023 Alice\n"));
+    }
+
+    #[test]
+    fn test_substitutions_dates() {
+        let template = Template::new(
+            "Created [date] in [month] [date:%Y], [date:%d.%m.%y] [date:%H] [date:]",
+            test_context("2020"),
+        );
+        assert_eq!(
+            "Created 2024-06-01 in June 2024, 01.06.24 [date:%H] [date:]",
+            template.render()
+        );
+        let date = NaiveDate::from_ymd_opt(2021, 12, 24).unwrap();
+        assert_eq!(
+            "Created 2021-12-24 in December 2021, 24.12.21 [date:%H] [date:]",
+            template.set_date(date).render()
+        );
+    }
+
+    #[test]
+    fn test_year_varying_regex_dates() {
+        let template = Template::new(
+            "Copyright [year] Alice, created [date:%B %-d, %Y]. All rights reserved.",
+            test_context("2024"),
+        );
+        let commenter = LineComment::new("#", None);
+        let rgx = template.outdated_license_pattern(&commenter);
+        assert!(rgx
+            .is_match("# Copyright 2020 Alice, created December 24, 2019. All rights reserved.\n"));
+        assert!(!rgx.is_match("# Copyright 2020 Alice, created December. All rights reserved.\n"));
+
+        let fingerprint = Regex::new(&template.fingerprint()).unwrap();
+        assert!(fingerprint
+            .is_match("Copyright 2020 Alice, created May 1, 2019. All\nrights reserved."));
+    }
+
+    #[test]
+    fn test_existing_date() {
+        let commenter = LineComment::new("#", None);
+        let template = Template::new(
+            "Copyright [year] Alice, created [date]",
+            test_context("2024"),
+        );
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2019, 12, 24),
+            template.existing_date(
+                &commenter,
+                "#!/bin/sh\n# Copyright 2020 Alice, created 2019-12-24\n"
+            )
+        );
+        assert_eq!(
+            None,
+            template.existing_date(&commenter, "# Copyright 2020 Bob, created 2019-12-24\n")
+        );
+
+        // A month alone isn't a date.
+        let template = Template::new(
+            "Copyright [year] Alice, created in [month]",
+            test_context("2024"),
+        );
+        assert_eq!(
+            None,
+            template.existing_date(&commenter, "# Copyright 2020 Alice, created in May\n")
+        );
+    }
+
+    #[test]
+    fn test_existing_years() {
+        let commenter = LineComment::new("#", None);
+        let template = Template::new("Copyright [year] Alice, since [year]", test_context("2024"));
+        assert_eq!(
+            Some("2019, 2021".to_string()),
+            template.existing_years(&commenter, "# Copyright 2019, 2021 Alice, since 2019\n")
+        );
+        assert_eq!(
+            Some("2020".to_string()),
+            template.existing_years(&commenter, "# Copyright 2020 Alice, since 2020\n")
+        );
+        assert_eq!(
+            None,
+            template.existing_years(&commenter, "# Copyright 2020 Bob\n")
+        );
+    }
+
+    #[test]
+    fn test_apply_year_mode() {
+        let template = |year_mode| {
+            Template::new("Copyright [year]", test_context("2024")).set_year_mode(year_mode)
+        };
+        for (years, current, preserve, range_extend) in [
+            ("2019", "2024", "2019", "2019, 2024"),
+            ("2019, 2021", "2024", "2019, 2021", "2019, 2024"),
+            ("2024", "2024", "2024", "2024"),
+        ] {
+            let render = |year_mode| template(year_mode).apply_year_mode(years).render();
+            assert_eq!(format!("Copyright {}", current), render(YearMode::Current));
+            assert_eq!(
+                format!("Copyright {}", preserve),
+                render(YearMode::Preserve)
+            );
+            assert_eq!(
+                format!("Copyright {}", range_extend),
+                render(YearMode::RangeExtend)
+            );
+        }
+    }
+
+    #[test]
+    fn test_year_lists() {
+        let format = YearRangeFormat::default();
+        assert_eq!(None, format.parse("2018, 2020-2022"));
+        assert_eq!(
+            Some(vec![(2018, 2018), (2020, 2022), (2024, 2024)]),
+            format.parse_list("2018, 2020-2022, 2024")
+        );
+        assert_eq!(
+            Some(vec![(2018, 2022)]),
+            format.parse_list("2021, 2018-2019, 2020, 2019-2022")
+        );
+        assert_eq!(None, format.parse_list("2018, twenty"));
+        assert_eq!(
+            "2018, 2020-2022, 2024",
+            format.render_list(&[(2018, 2018), (2020, 2022), (2024, 2024)])
+        );
+
+        let en_dash = YearRangeFormat::try_from("[start]\u{2013}[end]".to_string()).unwrap();
+        assert_eq!(
+            Some(vec![(2018, 2022)]),
+            en_dash.parse_list("2018\u{2013}2022")
+        );
+        assert_eq!(
+            "2018, 2020\u{2013}2022",
+            en_dash.render_list(&[(2018, 2018), (2020, 2022)])
+        );
+
+        let template = Template::new("Copyright [year] Alice", test_context("2024"));
+        let commenter = LineComment::new("#", None);
+        let rgx = template.outdated_license_pattern(&commenter);
+        assert!(rgx.is_match("# Copyright 2018, 2020-2022, 2024 Alice\n"));
+    }
+
+    #[test]
+    fn test_apply_year_mode_lists() {
+        let render = |year_mode| {
+            Template::new("Copyright [year]", test_context("2025"))
+                .set_year_mode(year_mode)
+                .apply_year_mode("2020-2022, 2018, 2024")
+                .render()
+        };
+        assert_eq!("Copyright 2025", render(YearMode::Current));
+        assert_eq!(
+            "Copyright 2018, 2020-2022, 2024",
+            render(YearMode::Preserve)
+        );
+        assert_eq!(
+            "Copyright 2018, 2020-2022, 2024-2025",
+            render(YearMode::RangeExtend)
+        );
+    }
+
+    #[test]
+    fn test_year_varying_regex_authors_varying() {
+        let mut context = test_context("2024");
+        context.authors = Authors::from_names(vec![("Acme Corp".to_string(), None)]);
+        let template = Template::new(
+            "Copyright [year] [name of author]. All rights reserved.",
+            context,
+        );
+        let commenter = LineComment::new("#", None);
+        let old_holder = "# Copyright 2020 Acme Inc <legal@acme.com>. All rights reserved.\n";
+        assert!(!template
+            .outdated_license_pattern(&commenter)
+            .is_match(old_holder));
+
+        let template = template.set_authors_varying(true);
+        let rgx = template.outdated_license_pattern(&commenter);
+        assert!(rgx.is_match(old_holder));
+        assert!(rgx.is_match("# Copyright 2020 Acme Corp. All rights reserved.\n"));
+        assert!(!rgx.is_match("# Copyright 2020. All rights reserved.\n"));
+        assert!(!rgx.is_match("# Copyright 2020 Acme\n# Inc. All rights reserved.\n"));
+        assert_eq!(
+            "Copyright 2024 Acme Corp. All rights reserved.",
+            template.render()
+        );
+    }
+
+    #[test]
+    fn test_small_file_action() {
+        let template = Template::new("[ident] license", test_context("2024"));
+        assert_eq!(None, template.small_file_action(0, 30));
+
+        let template = template.set_small_file_limits(Some(SmallFileLimits {
+            min_lines: Some(10),
+            max_ratio: Some(2.0),
+            action: SmallFiles::Skip,
+        }));
+        assert_eq!(Some(SmallFiles::Skip), template.small_file_action(5, 2));
+        assert_eq!(Some(SmallFiles::Skip), template.small_file_action(12, 30));
+        assert_eq!(None, template.small_file_action(15, 30));
+        assert_eq!(
+            "SPDX-License-Identifier: test",
+            template.spdx_tag_only().render()
+        );
+        assert_eq!(None, template.spdx_tag_only().small_file_action(0, 1));
+    }
+
+    #[test]
+    fn test_resolve_conditionals() {
+        let is_set = |name: &str| name == "email" || name == "url";
+        assert_eq!(
+            "Alice <alice@example.com>",
+            resolve_conditionals("Alice[if email] <[email]>[end]", &is_set)
+                .replace("[email]", "alice@example.com")
+        );
+        assert_eq!(
+            "Alice",
+            resolve_conditionals("Alice[if phone] ([phone])[end]", &is_set)
+        );
+        assert_eq!(
+            "a  c",
+            resolve_conditionals("a [if phone]b [if url]x[end] [end] c", &is_set)
+        );
+        assert_eq!(
+            "a b x c",
+            resolve_conditionals("a [if email]b [if url]x[end] [end]c", &is_set)
+        );
+        assert_eq!(
+            "a [if email] b",
+            resolve_conditionals("a [if email] b", &is_set)
+        );
+    }
+
+    #[test]
+    fn test_substitutions_conditionals() {
+        let template = "Copyright [year] Alice[if email] <[email]>[end][if path], see [path][end].";
+        let mut context = test_context("2020");
+        context.path = "src/main.rs".to_string();
+        assert_eq!(
+            "Copyright 2020 Alice, see src/main.rs.",
+            Template::new(template, context.clone()).render()
+        );
+
+        let mut variables = BTreeMap::new();
+        variables.insert("email".to_string(), "alice@example.com".to_string());
+        assert_eq!(
+            "Copyright 2020 Alice <alice@example.com>, see src/main.rs.",
+            Template::new(template, context)
+                .set_variables(variables)
+                .render()
+        );
+    }
+
+    #[test]
+    fn test_year_varying_regex_conditionals() {
+        let mut variables = BTreeMap::new();
+        variables.insert("email".to_string(), "alice@example.com".to_string());
+        let template = Template::new(
+            "License [year] Alice[if email] <[email]>[end]",
+            test_context("2020"),
+        )
+        .set_variables(variables);
+        let commenter = LineComment::new("#", None);
+        let rgx = template.outdated_license_pattern(&commenter);
+        assert!(rgx.is_match("# License 2019 Alice <alice@example.com>\n"));
+        // Headers written before the email was set are outdated too.
+        assert!(rgx.is_match("# License 2019 Alice\n"));
+        assert!(!rgx.is_match("# License 2019 Bob\n"));
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let template = Template::new(
+            "Copyright (C) [year] [name of author]\n\n[filename] is licensed\nunder [ident].",
+            test_context("2020"),
+        );
+        let fingerprint = template.fingerprint();
+        assert_eq!(
+            "Copyright\\s+\\(C\\)\\s+[0-9]{4}((, |\\-)[0-9]{4})*\\s+\\S+\\s+is\\s+licensed\\s+under\\s+test\\.",
+            fingerprint
+        );
+
+        let rgx = Regex::new(&fingerprint).unwrap();
+        assert!(rgx.is_match("Copyright (C) 2024  lib.rs is licensed under test."));
+        assert!(rgx.is_match("Copyright (C) 2019\n\nmain.rs is\nlicensed under test."));
+    }
+
     #[test]
     fn test_substitution_at_end_of_line() {
         let context = test_context("2020");
@@ -301,10 +1885,18 @@ mod tests {
             authors: Authors::from(vec![CopyrightHolder {
                 name: "Mathew Robinson".to_string(),
                 email: Some("chasinglogic@gmail.com".to_string()),
+                start_year: None,
+                end_year: None,
             }]),
             end_year: Some(String::from("2020")),
             start_year: None,
             unwrap_text: true,
+            path: String::new(),
+            variables: BTreeMap::new(),
+            clock: test_clock(),
+            year_range_format: YearRangeFormat::default(),
+            date: None,
+            version: None,
         };
         let template = Template::new("Copyright (C) [year] [name of author] This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General Public License as published by the Free Software Foundation, version 3. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more details. You should have received a copy of the GNU Affero General Public License along with this program. If not, see <https://www.gnu.org/licenses/>", context);
         let expected = String::from("Copyright (C) 2020 Mathew Robinson <chasinglogic@gmail.com> This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General Public License as published by the Free Software Foundation, version 3. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more details. You should have received a copy of the GNU Affero General Public License along with this program. If not, see <https://www.gnu.org/licenses/>");
@@ -318,10 +1910,18 @@ mod tests {
             authors: Authors::from(vec![CopyrightHolder {
                 name: "Mathew Robinson".to_string(),
                 email: Some("chasinglogic@gmail.com".to_string()),
+                start_year: None,
+                end_year: None,
             }]),
             end_year: Some(String::from("2022")),
             start_year: None,
             unwrap_text: true,
+            path: String::new(),
+            variables: BTreeMap::new(),
+            clock: test_clock(),
+            year_range_format: YearRangeFormat::default(),
+            date: None,
+            version: None,
         };
         let template = Template::new(
             "Copyright (C) [year] [name of author] This program is free software.",
@@ -339,10 +1939,18 @@ mod tests {
             authors: Authors::from(vec![CopyrightHolder {
                 name: "Mathew Robinson".to_string(),
                 email: Some("chasinglogic@gmail.com".to_string()),
+                start_year: None,
+                end_year: None,
             }]),
             end_year: Some(String::from("2022")),
             start_year: None,
             unwrap_text: true,
+            path: String::new(),
+            variables: BTreeMap::new(),
+            clock: test_clock(),
+            year_range_format: YearRangeFormat::default(),
+            date: None,
+            version: None,
         };
         let template = Template::new(
             "Copyright (C) [year] [name of author] This program is free software.",
@@ -365,10 +1973,18 @@ mod tests {
             authors: Authors::from(vec![CopyrightHolder {
                 name: "Mathew Robinson".to_string(),
                 email: Some("chasinglogic@gmail.com".to_string()),
+                start_year: None,
+                end_year: None,
             }]),
             end_year: Some(String::from("2020")),
             start_year: None,
             unwrap_text: true,
+            path: String::new(),
+            variables: BTreeMap::new(),
+            clock: test_clock(),
+            year_range_format: YearRangeFormat::default(),
+            date: None,
+            version: None,
         };
         let template = Template::new(
             "Copyright (C) [year] [name of author] This
@@ -394,10 +2010,18 @@ this program. If not, see <https://www.gnu.org/licenses/>",
             authors: Authors::from(vec![CopyrightHolder {
                 name: "Mathew Robinson".to_string(),
                 email: Some("chasinglogic@gmail.com".to_string()),
+                start_year: None,
+                end_year: None,
             }]),
             end_year: Some(String::from("2020")),
             start_year: None,
             unwrap_text: false,
+            path: String::new(),
+            variables: BTreeMap::new(),
+            clock: test_clock(),
+            year_range_format: YearRangeFormat::default(),
+            date: None,
+            version: None,
         };
         let template = Template::new(
             "Copyright (c) [name of author]
@@ -418,10 +2042,18 @@ this program. If not, see <https://www.gnu.org/licenses/>",
             authors: Authors::from(vec![CopyrightHolder {
                 name: "Mathew Robinson".to_string(),
                 email: Some("chasinglogic@gmail.com".to_string()),
+                start_year: None,
+                end_year: None,
             }]),
             end_year: Some(String::from("2020")),
             start_year: None,
             unwrap_text: true,
+            path: String::new(),
+            variables: BTreeMap::new(),
+            clock: test_clock(),
+            year_range_format: YearRangeFormat::default(),
+            date: None,
+            version: None,
         };
         let template = Template::new(
             "Copyright (C) [year] [name of author] This
@@ -449,10 +2081,18 @@ Free Software Foundation, version 3. This program is distributed in the hope tha
             authors: Authors::from(vec![CopyrightHolder {
                 name: "Mathew Robinson".to_string(),
                 email: Some("chasinglogic@gmail.com".to_string()),
+                start_year: None,
+                end_year: None,
             }]),
             end_year: Some(String::from("2024")),
             start_year: Some(String::from("2020")),
             unwrap_text: true,
+            path: String::new(),
+            variables: BTreeMap::new(),
+            clock: test_clock(),
+            year_range_format: YearRangeFormat::default(),
+            date: None,
+            version: None,
         };
         let template = Template::new("Copyright (C) [year] [name of author] This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General Public License as published by the Free Software Foundation, version 3. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more details. You should have received a copy of the GNU Affero General Public License along with this program. If not, see <https://www.gnu.org/licenses/>", context);
         let expected = String::from("Copyright (C) 2020, 2024 Mathew Robinson <chasinglogic@gmail.com> This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General Public License as published by the Free Software Foundation, version 3. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more details. You should have received a copy of the GNU Affero General Public License along with this program. If not, see <https://www.gnu.org/licenses/>");
@@ -0,0 +1,434 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use regex::Regex;
+use serde::Deserialize;
+
+// Marks the paragraphs written by licensure dep5 sync so they can be
+// replaced on the next sync without touching any others.
+const MANAGED_COMMENT: &str = "Generated by licensure dep5 sync from the licensure config.";
+
+// The Format field of a newly created DEP-5 file.
+const DEP5_FORMAT: &str = "https://www.debian.org/doc/packaging-manuals/copyright-format/1.0/";
+
+fn default_dep5_file() -> String {
+    ".reuse/dep5".to_string()
+}
+
+/// What to do with files that can't contain a comment, those with a
+/// sidecar commenter and binary files like images.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Policy {
+    /// Write the header to a <file>.license file next to it.
+    Sidecar,
+    /// Record the copyright and license of the file in a DEP-5
+    /// machine-readable copyright file.
+    Dep5,
+    /// Leave the file alone.
+    Skip,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Config {
+    pub policy: Policy,
+    /// The DEP-5 file maintained by the dep5 policy, usually
+    /// .reuse/dep5 or debian/copyright.
+    #[serde(default = "default_dep5_file")]
+    pub dep5_file: String,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Paragraph {
+    // Field names and their raw values, everything after the colon
+    // including any continuation lines.
+    fields: Vec<(String, String)>,
+}
+
+impl Paragraph {
+    fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn set_field(&mut self, name: &str, value: String) {
+        match self
+            .fields
+            .iter_mut()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        {
+            Some((_, v)) => *v = value,
+            None => self.fields.push((name.to_string(), value)),
+        }
+    }
+
+    fn files(&self) -> Vec<&str> {
+        self.field("Files")
+            .map(|files| files.split_whitespace().collect())
+            .unwrap_or_default()
+    }
+
+    fn set_files<S: AsRef<str>>(&mut self, files: &[S]) {
+        let value = files
+            .iter()
+            .map(|f| format!(" {}", f.as_ref()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.set_field("Files", value);
+    }
+
+    fn matches(&self, file: &str) -> bool {
+        self.files()
+            .iter()
+            .any(|pattern| glob_matches(pattern, file))
+    }
+
+    fn copyright(&self) -> String {
+        normalize(self.field("Copyright").unwrap_or_default())
+    }
+
+    // Only the first line of License is the license name, any following
+    // lines are its text.
+    fn license(&self) -> &str {
+        self.field("License")
+            .unwrap_or_default()
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim()
+    }
+}
+
+/// A DEP-5 machine-readable copyright file, see
+/// https://www.debian.org/doc/packaging-manuals/copyright-format/1.0/
+#[derive(Clone, Debug)]
+pub struct Dep5 {
+    paragraphs: Vec<Paragraph>,
+}
+
+impl Dep5 {
+    /// Load the DEP-5 file at path, a missing file is treated as one with
+    /// only a header paragraph.
+    pub fn load(path: &str) -> Result<Dep5, io::Error> {
+        if !Path::new(path).exists() {
+            return Ok(Dep5::new());
+        }
+
+        Ok(Dep5::parse(&fs::read_to_string(path)?))
+    }
+
+    fn new() -> Dep5 {
+        Dep5 {
+            paragraphs: vec![Paragraph {
+                fields: vec![("Format".to_string(), format!(" {}", DEP5_FORMAT))],
+            }],
+        }
+    }
+
+    fn parse(content: &str) -> Dep5 {
+        let mut paragraphs = Vec::new();
+        let mut current = Paragraph::default();
+
+        for line in content.lines() {
+            if line.trim().is_empty() {
+                if !current.fields.is_empty() {
+                    paragraphs.push(std::mem::take(&mut current));
+                }
+            } else if line.starts_with([' ', '\t']) {
+                if let Some((_, value)) = current.fields.last_mut() {
+                    value.push('\n');
+                    value.push_str(line);
+                }
+            } else if let Some((key, value)) = line.split_once(':') {
+                current.fields.push((key.to_string(), value.to_string()));
+            }
+        }
+
+        if !current.fields.is_empty() {
+            paragraphs.push(current);
+        }
+
+        Dep5 { paragraphs }
+    }
+
+    pub fn render(&self) -> String {
+        self.paragraphs
+            .iter()
+            .map(|p| {
+                p.fields
+                    .iter()
+                    .map(|(key, value)| format!("{}:{}\n", key, value))
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Whether any paragraph lists file explicitly, rather than through
+    /// a pattern.
+    pub fn lists(&self, file: &str) -> bool {
+        self.paragraphs.iter().any(|p| p.files().contains(&file))
+    }
+
+    /// Whether file is covered with the given copyright and license. As
+    /// in DEP-5 the last paragraph matching file applies.
+    pub fn contains(&self, file: &str, copyright: &str, license: &str) -> bool {
+        self.paragraphs
+            .iter()
+            .rev()
+            .find(|p| p.matches(file))
+            .is_some_and(|p| p.copyright() == normalize(copyright) && p.license() == license)
+    }
+
+    /// Record that file has copyright and license, removing it from any
+    /// paragraph which lists it explicitly.
+    pub fn add(&mut self, file: &str, copyright: &str, license: &str) {
+        self.remove(file);
+
+        let copyright = normalize(copyright);
+        // A paragraph can only be reused if no later paragraph also
+        // matches the file, since that would take precedence.
+        let last_match = self.paragraphs.iter().rposition(|p| p.matches(file));
+        let reusable = self.paragraphs.iter().enumerate().rposition(|(idx, p)| {
+            p.field("Files").is_some()
+                && p.copyright() == copyright
+                && p.license() == license
+                && last_match.is_none_or(|last| idx >= last)
+        });
+
+        match reusable {
+            Some(idx) => {
+                let paragraph = &mut self.paragraphs[idx];
+                let mut files: Vec<String> =
+                    paragraph.files().into_iter().map(str::to_string).collect();
+                files.push(file.to_string());
+                paragraph.set_files(&files);
+            }
+            None => {
+                let mut paragraph = Paragraph::default();
+                paragraph.set_files(&[file]);
+                paragraph.set_field(
+                    "Copyright",
+                    copyright
+                        .lines()
+                        .map(|l| format!(" {}", l))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                );
+                paragraph.set_field("License", format!(" {}", license));
+                self.paragraphs.push(paragraph);
+            }
+        }
+    }
+
+    /// Replace the paragraphs written by a previous sync with one per
+    /// files pattern, copyright, and license given. They go directly
+    /// after the header paragraph so that paragraphs for single files
+    /// still take precedence over them.
+    pub fn sync(&mut self, entries: &[(String, String, String)]) {
+        self.paragraphs
+            .retain(|p| p.field("Comment").map(normalize).as_deref() != Some(MANAGED_COMMENT));
+
+        let at = match self.paragraphs.first() {
+            Some(p) if p.field("Files").is_none() => 1,
+            _ => 0,
+        };
+        let managed = entries.iter().map(|(files, copyright, license)| {
+            let mut paragraph = Paragraph::default();
+            paragraph.set_files(&[files]);
+            paragraph.set_field("Copyright", format!(" {}", copyright));
+            paragraph.set_field("License", format!(" {}", license));
+            paragraph.set_field("Comment", format!(" {}", MANAGED_COMMENT));
+            paragraph
+        });
+        self.paragraphs.splice(at..at, managed.collect::<Vec<_>>());
+    }
+
+    /// Remove file from every paragraph which lists it explicitly,
+    /// dropping paragraphs left with no files. Returns whether any
+    /// paragraph listed it.
+    pub fn remove(&mut self, file: &str) -> bool {
+        let mut removed = false;
+        self.paragraphs.retain_mut(|p| {
+            let files = p.files();
+            if !files.contains(&file) {
+                return true;
+            }
+
+            removed = true;
+            let remaining: Vec<String> = files
+                .into_iter()
+                .filter(|f| *f != file)
+                .map(str::to_string)
+                .collect();
+            if remaining.is_empty() {
+                return false;
+            }
+
+            p.set_files(&remaining);
+            true
+        });
+
+        removed
+    }
+}
+
+// Collapse a multi-line field value to its trimmed lines so formatting
+// differences don't matter when comparing.
+fn normalize(value: &str) -> String {
+    value
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// DEP-5 Files patterns only support * and ?, and * matches / too.
+fn glob_matches(pattern: &str, file: &str) -> bool {
+    let re = regex::escape(pattern)
+        .replace("\\*", ".*")
+        .replace("\\?", ".");
+    Regex::new(&format!("^{}$", re)).is_ok_and(|re| re.is_match(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str =
+        "Format: https://www.debian.org/doc/packaging-manuals/copyright-format/1.0/
+Upstream-Name: example
+
+Files: *
+Copyright: 2020 Alice
+License: GPL-3.0
+ Some license text.
+
+Files: images/logo.png
+ images/icon.png
+Copyright: 2024 Bob
+License: MIT
+";
+
+    #[test]
+    fn test_parse_and_render() {
+        let dep5 = Dep5::parse(EXAMPLE);
+        assert_eq!(3, dep5.paragraphs.len());
+        assert_eq!(
+            vec!["images/logo.png", "images/icon.png"],
+            dep5.paragraphs[2].files()
+        );
+        assert_eq!("GPL-3.0", dep5.paragraphs[1].license());
+        assert_eq!(EXAMPLE, dep5.render());
+    }
+
+    #[test]
+    fn test_contains() {
+        let dep5 = Dep5::parse(EXAMPLE);
+        assert!(dep5.contains("images/logo.png", "2024 Bob", "MIT"));
+        assert!(!dep5.contains("images/logo.png", "2024 Bob", "GPL-3.0"));
+        assert!(dep5.contains("src/main.rs", "2020 Alice", "GPL-3.0"));
+        assert!(!Dep5::new().contains("a.png", "2024 Bob", "MIT"));
+    }
+
+    #[test]
+    fn test_add() {
+        let mut dep5 = Dep5::parse(EXAMPLE);
+        dep5.add("images/new.png", "2024 Bob", "MIT");
+        assert_eq!(
+            vec!["images/logo.png", "images/icon.png", "images/new.png"],
+            dep5.paragraphs[2].files()
+        );
+
+        dep5.add("images/icon.png", "2024 Carol", "MIT");
+        assert_eq!(4, dep5.paragraphs.len());
+        assert_eq!(
+            vec!["images/logo.png", "images/new.png"],
+            dep5.paragraphs[2].files()
+        );
+        assert!(dep5.contains("images/icon.png", "2024 Carol", "MIT"));
+        assert!(dep5
+            .render()
+            .ends_with("\nFiles: images/icon.png\nCopyright: 2024 Carol\nLicense: MIT\n"));
+
+        // Files matched by a glob are still listed explicitly.
+        let mut dep5 = Dep5::parse(EXAMPLE);
+        dep5.add("data.bin", "2020 Alice", "GPL-3.0");
+        assert_eq!(vec!["*", "data.bin"], dep5.paragraphs[1].files());
+
+        let mut dep5 = Dep5::new();
+        dep5.add("a.png", "2024 Bob", "MIT");
+        assert_eq!(
+            format!(
+                "Format: {}\n\nFiles: a.png\nCopyright: 2024 Bob\nLicense: MIT\n",
+                DEP5_FORMAT
+            ),
+            dep5.render()
+        );
+    }
+
+    #[test]
+    fn test_sync() {
+        let mut dep5 = Dep5::parse(EXAMPLE);
+        let entries = vec![(
+            "*.png".to_string(),
+            "2024 Carol".to_string(),
+            "CC-BY-4.0".to_string(),
+        )];
+        dep5.sync(&entries);
+        assert_eq!(4, dep5.paragraphs.len());
+        assert_eq!(vec!["*.png"], dep5.paragraphs[1].files());
+        // Paragraphs which were already there take precedence.
+        assert!(dep5.contains("b.png", "2020 Alice", "GPL-3.0"));
+        assert!(dep5.contains("images/logo.png", "2024 Bob", "MIT"));
+
+        let synced = dep5.render();
+        let mut dep5 = Dep5::parse(&synced);
+        dep5.sync(&entries);
+        assert_eq!(synced, dep5.render());
+
+        dep5.sync(&[]);
+        assert_eq!(EXAMPLE, dep5.render());
+
+        let mut dep5 = Dep5::new();
+        dep5.sync(&entries);
+        assert!(dep5.contains("b.png", "2024 Carol", "CC-BY-4.0"));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut dep5 = Dep5::parse(EXAMPLE);
+        assert!(dep5.lists("images/logo.png"));
+        assert!(!dep5.lists("src/main.rs"));
+        assert!(dep5.remove("images/logo.png"));
+        assert!(dep5.remove("images/icon.png"));
+        assert!(!dep5.remove("src/main.rs"));
+        assert!(!dep5.lists("images/logo.png"));
+        assert_eq!(2, dep5.paragraphs.len());
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("*", "a/b.png"));
+        assert!(glob_matches("images/*.png", "images/a.png"));
+        assert!(glob_matches("a?.png", "ab.png"));
+        assert!(!glob_matches("a.png", "b/a.png"));
+    }
+}
@@ -0,0 +1,372 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+#[cfg(test)]
+use std::cell::Cell;
+
+// Held while a file is made writable by with_writable, so that threads
+// writing to files in the same directory don't restore its mode while
+// another still needs it writable.
+static WRITABLE: Mutex<()> = Mutex::new(());
+
+#[cfg(test)]
+thread_local! {
+    // When set write stops as if the process was killed after writing
+    // the temporary file, but before renaming it.
+    static CRASH_BEFORE_RENAME: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Simulate being killed in the middle of write on this thread.
+#[cfg(test)]
+pub fn crash_before_rename(crash: bool) {
+    CRASH_BEFORE_RENAME.with(|c| c.set(crash));
+}
+
+#[cfg(test)]
+fn crashed() -> bool {
+    CRASH_BEFORE_RENAME.with(Cell::get)
+}
+
+#[cfg(not(test))]
+fn crashed() -> bool {
+    false
+}
+
+/// What write keeps of the file it replaces. Permissions, including
+/// the executable bit, are always kept.
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    /// When set the original file is first copied to its path with this
+    /// appended.
+    pub backup_ext: Option<String>,
+    /// Keep the modification time of the original file, so build
+    /// systems don't consider it changed.
+    pub preserve_mtime: bool,
+}
+
+/// Replace the content of path without ever leaving it partially
+/// written. The content is written to a temporary file in the same
+/// directory, which is then renamed over path. When path is a symlink
+/// the file it points to is replaced and the link is kept.
+pub fn write(path: impl AsRef<Path>, content: &[u8], opts: &Options) -> io::Result<()> {
+    let link = path.as_ref();
+    let existing = fs::metadata(link).ok();
+
+    if let (Some(ext), Some(_)) = (&opts.backup_ext, &existing) {
+        let mut backup = link.as_os_str().to_owned();
+        backup.push(ext);
+        fs::copy(link, backup)?;
+    }
+
+    let path = &resolve_symlinks(link)?;
+    let tmp = temp_path(path);
+    let written = (|| {
+        let mut f = File::create(&tmp)?;
+        f.write_all(content)?;
+        if let Some(metadata) = &existing {
+            if opts.preserve_mtime {
+                f.set_modified(metadata.modified()?)?;
+            }
+            f.set_permissions(metadata.permissions())?;
+        }
+        f.sync_all()?;
+
+        if crashed() {
+            return Err(io::Error::other("simulated crash"));
+        }
+        fs::rename(&tmp, path)
+    })();
+    if written.is_err() && !crashed() {
+        let _ = fs::remove_file(&tmp);
+    }
+
+    written
+}
+
+/// Whether path, or the directory it would be written to, is read-only
+/// so that write would fail.
+pub fn is_read_only(path: impl AsRef<Path>) -> bool {
+    let path = &resolve_symlinks(path.as_ref()).unwrap_or_else(|_| path.as_ref().to_path_buf());
+    let file = fs::metadata(path)
+        .map(|m| m.permissions().readonly())
+        .unwrap_or(false);
+    file || fs::metadata(parent(path))
+        .map(|m| m.permissions().readonly())
+        .unwrap_or(false)
+}
+
+/// Run f with path and its directory made writable by their owner,
+/// restoring their modes afterwards.
+pub fn with_writable<T>(
+    path: impl AsRef<Path>,
+    f: impl FnOnce() -> io::Result<T>,
+) -> io::Result<T> {
+    let path = &resolve_symlinks(path.as_ref())?;
+    let _guard = WRITABLE.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut restore = Vec::new();
+    for p in [parent(path), path] {
+        if let Ok(metadata) = fs::metadata(p) {
+            let original = metadata.permissions();
+            if original.readonly() {
+                fs::set_permissions(p, owner_writable(original.clone()))?;
+                restore.push((p, original));
+            }
+        }
+    }
+
+    let result = f();
+    for (p, original) in restore {
+        fs::set_permissions(p, original)?;
+    }
+
+    result
+}
+
+// Follow path through any symlinks to the file they point to, which
+// doesn't need to exist yet.
+fn resolve_symlinks(path: &Path) -> io::Result<PathBuf> {
+    // The same limit as Linux's MAXSYMLINKS.
+    const MAX_LINKS: usize = 40;
+
+    let mut path = path.to_path_buf();
+    for _ in 0..MAX_LINKS {
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                let target = fs::read_link(&path)?;
+                path = parent(&path).join(target);
+            }
+            _ => return Ok(path),
+        }
+    }
+
+    Err(io::Error::other(format!(
+        "too many levels of symbolic links resolving {}",
+        path.display()
+    )))
+}
+
+fn parent(path: &Path) -> &Path {
+    match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    }
+}
+
+#[cfg(unix)]
+fn owner_writable(perms: fs::Permissions) -> fs::Permissions {
+    use std::os::unix::fs::PermissionsExt;
+    fs::Permissions::from_mode(perms.mode() | 0o200)
+}
+
+#[cfg(not(unix))]
+fn owner_writable(mut perms: fs::Permissions) -> fs::Permissions {
+    perms.set_readonly(false);
+    perms
+}
+
+/// The temporary files left next to path by writes which were killed
+/// before they finished.
+pub fn stale_temp_files(path: impl AsRef<Path>) -> io::Result<Vec<PathBuf>> {
+    let path = path.as_ref();
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let prefix = format!(".{}.licensure-", name);
+    let dir = parent(path);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut stale = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if file_name.starts_with(&prefix) && file_name.ends_with(".tmp") {
+            stale.push(entry.path());
+        }
+    }
+
+    Ok(stale)
+}
+
+// A hidden file next to path, unique to this process so that concurrent
+// runs don't clobber each other's temporary files.
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.licensure-{}.tmp", name, std::process::id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_write() {
+        let dir = env::temp_dir().join(format!("licensure-atomic-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("a.rs");
+
+        let backup = Options {
+            backup_ext: Some(".bak".to_string()),
+            ..Options::default()
+        };
+        write(&file, b"new file\n", &backup).unwrap();
+        assert_eq!("new file\n", fs::read_to_string(&file).unwrap());
+        // There was nothing to back up.
+        assert!(!dir.join("a.rs.bak").exists());
+
+        write(&file, b"changed\n", &backup).unwrap();
+        assert_eq!("changed\n", fs::read_to_string(&file).unwrap());
+        assert_eq!(
+            "new file\n",
+            fs::read_to_string(dir.join("a.rs.bak")).unwrap()
+        );
+
+        write(&file, b"again\n", &Options::default()).unwrap();
+        assert_eq!(
+            "new file\n",
+            fs::read_to_string(dir.join("a.rs.bak")).unwrap()
+        );
+        assert_eq!(2, fs::read_dir(&dir).unwrap().count());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_through_symlink() {
+        use std::os::unix::fs::symlink;
+
+        let dir = env::temp_dir().join(format!("licensure-atomic-link-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("real")).unwrap();
+        fs::write(dir.join("real/a.rs"), "fn main() {}\n").unwrap();
+        symlink("real/a.rs", dir.join("link.rs")).unwrap();
+        symlink("link.rs", dir.join("link2.rs")).unwrap();
+
+        write(dir.join("link2.rs"), b"changed\n", &Options::default()).unwrap();
+        assert!(fs::symlink_metadata(dir.join("link.rs"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert!(fs::symlink_metadata(dir.join("link2.rs"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(
+            "changed\n",
+            fs::read_to_string(dir.join("real/a.rs")).unwrap()
+        );
+        assert_eq!(1, fs::read_dir(dir.join("real")).unwrap().count());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = env::temp_dir().join(format!("licensure-atomic-perms-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("run.sh");
+        fs::write(&file, "echo hi\n").unwrap();
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o755)).unwrap();
+
+        write(&file, b"# License\necho hi\n", &Options::default()).unwrap();
+        assert_eq!(
+            0o755,
+            fs::metadata(&file).unwrap().permissions().mode() & 0o777
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_preserve_mtime() {
+        use std::time::{Duration, SystemTime};
+
+        let dir = env::temp_dir().join(format!("licensure-atomic-mtime-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("a.rs");
+        fs::write(&file, "fn main() {}\n").unwrap();
+        let mtime = SystemTime::now() - Duration::from_secs(24 * 60 * 60);
+        File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+
+        let opts = Options {
+            preserve_mtime: true,
+            ..Options::default()
+        };
+        write(&file, b"// License\nfn main() {}\n", &opts).unwrap();
+        assert_eq!(mtime, fs::metadata(&file).unwrap().modified().unwrap());
+
+        write(&file, b"fn main() {}\n", &Options::default()).unwrap();
+        assert!(fs::metadata(&file).unwrap().modified().unwrap() > mtime);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_with_writable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = env::temp_dir().join(format!("licensure-atomic-ro-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("a.rs");
+        fs::write(&file, "fn main() {}\n").unwrap();
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o444)).unwrap();
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o555)).unwrap();
+        assert!(is_read_only(&file));
+
+        with_writable(&file, || {
+            write(&file, b"// License\nfn main() {}\n", &Options::default())
+        })
+        .unwrap();
+        assert_eq!(
+            "// License\nfn main() {}\n",
+            fs::read_to_string(&file).unwrap()
+        );
+        let mode = |p: &Path| fs::metadata(p).unwrap().permissions().mode() & 0o777;
+        assert_eq!(0o444, mode(&file));
+        assert_eq!(0o555, mode(&dir));
+
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(is_read_only(&file));
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(!is_read_only(&file));
+        assert!(!is_read_only(dir.join("new.rs")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,204 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+use crate::metrics;
+
+// The most bytes of file content kept in memory at once.
+const CAPACITY: usize = 64 * 1024 * 1024;
+
+// Files larger than this are read but never cached so that a single huge
+// file can't evict everything else.
+const MAX_ENTRY: usize = CAPACITY / 16;
+
+static CACHE: OnceLock<ContentCache> = OnceLock::new();
+
+/// Read the content of path through the process wide content cache, so
+/// that detecting, checking, and diffing a file only reads it from disk
+/// once. The cached content is only used while the file's modification
+/// time and size are unchanged.
+pub fn read(path: impl AsRef<Path>) -> Result<Arc<Vec<u8>>, io::Error> {
+    CACHE
+        .get_or_init(|| ContentCache::new(CAPACITY, MAX_ENTRY))
+        .read(path.as_ref())
+}
+
+/// Forget the cached content of path, this must be called after writing
+/// to a file.
+pub fn invalidate(path: impl AsRef<Path>) {
+    if let Some(cache) = CACHE.get() {
+        cache.invalidate(path.as_ref());
+    }
+}
+
+struct Entry {
+    modified: Option<SystemTime>,
+    len: u64,
+    content: Arc<Vec<u8>>,
+    // When this entry was last used, the key of its slot in by_use.
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct Entries {
+    by_path: HashMap<PathBuf, Entry>,
+    // Paths ordered from least to most recently used.
+    by_use: BTreeMap<u64, PathBuf>,
+    size: usize,
+    clock: u64,
+}
+
+impl Entries {
+    fn remove(&mut self, path: &Path) {
+        if let Some(entry) = self.by_path.remove(path) {
+            self.by_use.remove(&entry.last_used);
+            self.size -= entry.content.len();
+        }
+    }
+
+    fn touch(&mut self, path: &Path) {
+        self.clock += 1;
+        let clock = self.clock;
+        if let Some(entry) = self.by_path.get_mut(path) {
+            self.by_use.remove(&entry.last_used);
+            entry.last_used = clock;
+            self.by_use.insert(clock, path.to_path_buf());
+        }
+    }
+}
+
+/// A least recently used cache of file content bounded by total size.
+struct ContentCache {
+    capacity: usize,
+    max_entry: usize,
+    entries: Mutex<Entries>,
+}
+
+impl ContentCache {
+    fn new(capacity: usize, max_entry: usize) -> ContentCache {
+        ContentCache {
+            capacity,
+            max_entry,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    fn read(&self, path: &Path) -> Result<Arc<Vec<u8>>, io::Error> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata.modified().ok();
+        let len = metadata.len();
+
+        {
+            let mut entries = self.lock();
+            match entries.by_path.get(path) {
+                Some(entry) if entry.modified == modified && entry.len == len => {
+                    let content = entry.content.clone();
+                    entries.touch(path);
+                    metrics::CACHE_HITS.inc();
+                    return Ok(content);
+                }
+                Some(_) => entries.remove(path),
+                None => (),
+            }
+        }
+
+        // Read without holding the lock so other threads aren't blocked
+        // on IO.
+        let content = Arc::new(fs::read(path)?);
+        if content.len() > self.max_entry {
+            return Ok(content);
+        }
+
+        let mut entries = self.lock();
+        entries.remove(path);
+        entries.size += content.len();
+        entries.by_path.insert(
+            path.to_path_buf(),
+            Entry {
+                modified,
+                len,
+                content: content.clone(),
+                last_used: 0,
+            },
+        );
+        entries.touch(path);
+
+        while entries.size > self.capacity {
+            let oldest = match entries.by_use.first_key_value() {
+                Some((_, oldest)) => oldest.clone(),
+                None => break,
+            };
+            entries.remove(&oldest);
+        }
+
+        Ok(content)
+    }
+
+    fn invalidate(&self, path: &Path) {
+        self.lock().remove(path);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Entries> {
+        self.entries
+            .lock()
+            .expect("content cache lock was poisoned!")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_content_cache() {
+        let dir = env::temp_dir().join(format!("licensure-cache-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let (a, b, c) = (dir.join("a"), dir.join("b"), dir.join("c"));
+        fs::write(&a, "aaaa").unwrap();
+        fs::write(&b, "bbbb").unwrap();
+        fs::write(&c, "cccccccccccc").unwrap();
+
+        let cache = ContentCache::new(10, 8);
+        assert_eq!(b"aaaa".to_vec(), *cache.read(&a).unwrap());
+        assert_eq!(b"bbbb".to_vec(), *cache.read(&b).unwrap());
+        assert_eq!(8, cache.lock().size);
+
+        // Too large to be cached.
+        assert_eq!(12, cache.read(&c).unwrap().len());
+        assert!(!cache.lock().by_path.contains_key(&c));
+
+        // Using a makes b the least recently used, so it's evicted.
+        cache.read(&a).unwrap();
+        fs::write(&c, "cccc").unwrap();
+        cache.read(&c).unwrap();
+        assert_eq!(2, cache.lock().by_use.len());
+        assert!(!cache.lock().by_path.contains_key(&b));
+
+        // Changes in size are noticed even within the mtime resolution.
+        fs::write(&a, "aaaaaa").unwrap();
+        assert_eq!(b"aaaaaa".to_vec(), *cache.read(&a).unwrap());
+
+        cache.invalidate(&a);
+        assert!(!cache.lock().by_path.contains_key(&a));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
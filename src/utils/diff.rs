@@ -0,0 +1,324 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+
+// Above this many cells the LCS table is not computed and the changed
+// region is reported as a whole replacement instead.
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Compute a minimal edit script turning old into new. Common leading
+/// and trailing items are stripped before running the LCS since license
+/// header changes are almost always confined to the top of a file.
+pub fn diff<'a, T: PartialEq>(old: &'a [T], new: &'a [T]) -> Vec<(Op, &'a T)> {
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    let mut ops: Vec<(Op, &T)> = old[..prefix].iter().map(|t| (Op::Equal, t)).collect();
+
+    if old_mid.len() * new_mid.len() > MAX_LCS_CELLS {
+        ops.extend(old_mid.iter().map(|t| (Op::Delete, t)));
+        ops.extend(new_mid.iter().map(|t| (Op::Insert, t)));
+    } else {
+        ops.append(&mut lcs_diff(old_mid, new_mid));
+    }
+
+    ops.extend(old[old.len() - suffix..].iter().map(|t| (Op::Equal, t)));
+    ops
+}
+
+fn lcs_diff<'a, T: PartialEq>(old: &'a [T], new: &'a [T]) -> Vec<(Op, &'a T)> {
+    let (n, m) = (old.len(), new.len());
+    // table[i][j] is the length of the LCS of old[i..] and new[j..]
+    let mut table = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            table[i][j] = if old[i] == new[j] {
+                table[i + 1][j + 1] + 1
+            } else {
+                table[i + 1][j].max(table[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::with_capacity(n + m);
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if old[i] == new[j] {
+            ops.push((Op::Equal, &old[i]));
+            i += 1;
+            j += 1;
+        } else if table[i + 1][j] >= table[i][j + 1] {
+            ops.push((Op::Delete, &old[i]));
+            i += 1;
+        } else {
+            ops.push((Op::Insert, &new[j]));
+            j += 1;
+        }
+    }
+
+    ops.extend(old[i..].iter().map(|t| (Op::Delete, t)));
+    ops.extend(new[j..].iter().map(|t| (Op::Insert, t)));
+    ops
+}
+
+/// Render a unified diff between old and new with the given number of
+/// context lines. Returns an empty string if the contents are equal.
+/// Lines are compared with their line endings, and a last line without
+/// one is marked like diff does, so changes to only the end of the file
+/// are shown.
+pub fn unified_diff(
+    old: &str,
+    new: &str,
+    old_name: &str,
+    new_name: &str,
+    context: usize,
+) -> String {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let ops = diff(&old_lines, &new_lines);
+
+    let changes: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, (op, _))| *op != Op::Equal)
+        .map(|(idx, _)| idx)
+        .collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    // Group changes into hunks whose context would overlap.
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for idx in changes {
+        let start = idx.saturating_sub(context);
+        let end = (idx + context + 1).min(ops.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", old_name, new_name);
+    for (start, end) in hunks {
+        let count =
+            |range: &[(Op, &&str)], skip: Op| range.iter().filter(|(op, _)| *op != skip).count();
+        let old_before = count(&ops[..start], Op::Insert);
+        let new_before = count(&ops[..start], Op::Delete);
+        let old_len = count(&ops[start..end], Op::Insert);
+        let new_len = count(&ops[start..end], Op::Delete);
+
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            hunk_start(old_before, old_len),
+            old_len,
+            hunk_start(new_before, new_len),
+            new_len,
+        ));
+
+        for (op, line) in &ops[start..end] {
+            let marker = match op {
+                Op::Equal => ' ',
+                Op::Delete => '-',
+                Op::Insert => '+',
+            };
+            out.push(marker);
+            out.push_str(line);
+            if !line.ends_with('\n') {
+                out.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+
+    out
+}
+
+/// Render a word level diff between old and new in the style of git's
+/// --word-diff=plain, removed words are wrapped in [-...-] and added words
+/// in {+...+}. Whitespace differences are ignored.
+pub fn word_diff(old: &str, new: &str) -> String {
+    let old_words: Vec<&str> = old.split_whitespace().collect();
+    let new_words: Vec<&str> = new.split_whitespace().collect();
+
+    let mut out: Vec<String> = Vec::new();
+    let mut run: Option<(Op, Vec<&str>)> = None;
+    for (op, word) in diff(&old_words, &new_words) {
+        match run {
+            Some((run_op, ref mut words)) if run_op == op => words.push(word),
+            _ => {
+                if let Some((run_op, words)) = run.take() {
+                    out.push(render_word_run(run_op, &words));
+                }
+                run = Some((op, vec![word]));
+            }
+        }
+    }
+
+    if let Some((run_op, words)) = run {
+        out.push(render_word_run(run_op, &words));
+    }
+
+    out.join(" ")
+}
+
+/// The lines of old and new between their common leading and trailing
+/// lines, i.e. the region which changed, each line ending in a newline.
+pub fn changed_lines(old: &str, new: &str) -> (String, String) {
+    let old: Vec<&str> = old.split_inclusive('\n').collect();
+    let new: Vec<&str> = new.split_inclusive('\n').collect();
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    (
+        old[prefix..old.len() - suffix].concat(),
+        new[prefix..new.len() - suffix].concat(),
+    )
+}
+
+fn render_word_run(op: Op, words: &[&str]) -> String {
+    let text = words.join(" ");
+    match op {
+        Op::Equal => text,
+        Op::Delete => format!("[-{}-]", text),
+        Op::Insert => format!("{{+{}+}}", text),
+    }
+}
+
+// Hunk ranges are 1-indexed, except empty ranges which refer to the
+// line before the hunk.
+fn hunk_start(lines_before: usize, len: usize) -> usize {
+    if len == 0 {
+        lines_before
+    } else {
+        lines_before + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff_added_header() {
+        let old = "fn main() {\n    println!(\"hi\");\n}\n";
+        let new = "// License 2024\n\nfn main() {\n    println!(\"hi\");\n}\n";
+        assert_eq!(
+            "--- a/main.rs
++++ b/main.rs
+@@ -1,2 +1,4 @@
++// License 2024
++
+ fn main() {
+     println!(\"hi\");
+",
+            unified_diff(old, new, "a/main.rs", "b/main.rs", 2)
+        );
+    }
+
+    #[test]
+    fn test_unified_diff_changed_year() {
+        let old = "# License 2020\n#\n# text\na\nb\nc\nd\n";
+        let new = "# License 2024\n#\n# text\na\nb\nc\nd\n";
+        assert_eq!(
+            "--- old
++++ new
+@@ -1,2 +1,2 @@
+-# License 2020
++# License 2024
+ #
+",
+            unified_diff(old, new, "old", "new", 1)
+        );
+    }
+
+    #[test]
+    fn test_word_diff() {
+        assert_eq!(
+            "Copyright [-2020-] {+2024+} Jane Doe. [-All rights reserved.-]",
+            word_diff(
+                "Copyright 2020 Jane Doe.\nAll rights reserved.",
+                "Copyright 2024\n  Jane Doe."
+            )
+        );
+        assert_eq!("a b", word_diff("a\nb", "a b"));
+    }
+
+    #[test]
+    fn test_changed_lines() {
+        assert_eq!(
+            ("# 2020\n".to_string(), "# 2024\n".to_string()),
+            changed_lines("#!/bin/sh\n# 2020\necho\n", "#!/bin/sh\n# 2024\necho\n")
+        );
+        assert_eq!(
+            (String::new(), "# 2024\n\n".to_string()),
+            changed_lines("echo\n", "# 2024\n\necho\n")
+        );
+    }
+
+    #[test]
+    fn test_unified_diff_equal() {
+        assert_eq!("", unified_diff("a\nb\n", "a\nb\n", "old", "new", 3));
+    }
+
+    #[test]
+    fn test_unified_diff_no_newline_at_end() {
+        assert_eq!(
+            "--- old
++++ new
+@@ -1,2 +1,2 @@
+ a
+-b
+\\ No newline at end of file
++b
+",
+            unified_diff("a\nb", "a\nb\n", "old", "new", 3)
+        );
+        assert_eq!(
+            "--- old
++++ new
+@@ -1,1 +1,2 @@
++# License 2024
+ a
+\\ No newline at end of file
+",
+            unified_diff("a", "# License 2024\na", "old", "new", 3)
+        );
+    }
+}
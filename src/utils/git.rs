@@ -0,0 +1,260 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use std::collections::HashSet;
+use std::env;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use crate::warning;
+
+// Environment variables holding paths which git resolves relative to
+// its working directory, they are made absolute so that running git
+// with -C doesn't change what they refer to.
+const PATH_ENV_VARS: &[&str] = &[
+    "GIT_DIR",
+    "GIT_WORK_TREE",
+    "GIT_INDEX_FILE",
+    "GIT_COMMON_DIR",
+];
+
+static REPO: OnceLock<Option<Repo>> = OnceLock::new();
+
+/// The git work tree licensure is running in. This is resolved once and
+/// a single warning is printed if there isn't one, so that git based
+/// features can fall back quietly instead of failing part way through a
+/// run.
+pub fn repo() -> Option<&'static Repo> {
+    REPO.get_or_init(|| {
+        let repo = Repo::discover(Path::new("."));
+        if repo.is_none() {
+            warning!(
+                "not inside a git repository, project files will be found by walking \
+                 the directory, dynamic year ranges will use the current year, and dynamic \
+                 authors will use the configured authors"
+            );
+        }
+
+        repo
+    })
+    .as_ref()
+}
+
+/// A git work tree, which may be a linked worktree or one given by
+/// GIT_DIR and GIT_WORK_TREE.
+#[derive(Debug, PartialEq)]
+pub struct Repo {
+    root: PathBuf,
+    // The directory licensure was run from relative to root, either
+    // empty or ending with a /.
+    prefix: String,
+}
+
+impl Repo {
+    /// The work tree containing dir, if it's in one.
+    pub fn discover(dir: &Path) -> Option<Repo> {
+        let proc = command_in(dir)
+            .args([
+                "rev-parse",
+                "--is-inside-work-tree",
+                "--show-toplevel",
+                "--show-prefix",
+            ])
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        if !proc.status.success() {
+            return None;
+        }
+
+        // Inside the .git directory this succeeds but prints false.
+        let out = String::from_utf8(proc.stdout).ok()?;
+        let mut lines = out.lines();
+        if lines.next()? != "true" {
+            return None;
+        }
+
+        Some(Repo {
+            root: PathBuf::from(lines.next()?),
+            prefix: lines.next().unwrap_or_default().to_string(),
+        })
+    }
+
+    /// A git command run from the root of the work tree.
+    pub fn command(&self) -> Command {
+        command_in(&self.root)
+    }
+
+    /// Convert path, relative to the directory licensure was run from,
+    /// to be relative to the root of the work tree.
+    pub fn root_relative(&self, path: &str) -> String {
+        format!("{}{}", self.prefix, path)
+    }
+
+    /// Convert path, relative to the root of the work tree, to be
+    /// relative to the directory licensure was run from. Paths outside
+    /// of that directory return None.
+    pub fn cwd_relative<'a>(&self, path: &'a str) -> Option<&'a str> {
+        path.strip_prefix(self.prefix.as_str())
+    }
+
+    /// The files which .gitattributes marks as generated or vendored
+    /// with linguist-generated or linguist-vendored.
+    pub fn linguist_excluded(&self, files: &[String]) -> Result<HashSet<String>, io::Error> {
+        let mut child = self
+            .command()
+            .args([
+                "check-attr",
+                "-z",
+                "--stdin",
+                "linguist-generated",
+                "linguist-vendored",
+            ])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let input: String = files
+            .iter()
+            .map(|f| format!("{}\0", self.root_relative(f)))
+            .collect();
+        // Write from another thread so that git can't block on a full
+        // stdout pipe while we're still writing.
+        let mut stdin = child
+            .stdin
+            .take()
+            .expect("git check-attr stdin was not piped!");
+        let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+        let output = child.wait_with_output()?;
+        writer.join().expect("git check-attr writer panicked!")?;
+        if !output.status.success() {
+            return Err(io::Error::other("git check-attr failed"));
+        }
+
+        // Output is path, attribute, and value triples.
+        let output = String::from_utf8_lossy(&output.stdout);
+        let fields: Vec<&str> = output.split('\0').collect();
+        Ok(fields
+            .chunks_exact(3)
+            .filter(|triple| matches!(triple[2], "set" | "true"))
+            .filter_map(|triple| self.cwd_relative(triple[0]))
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+fn command_in(dir: &Path) -> Command {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C").arg(dir);
+    if let Ok(cwd) = env::current_dir() {
+        cmd.envs(absolute_path_vars(&cwd, |var| {
+            env::var_os(var).map(PathBuf::from)
+        }));
+    }
+
+    cmd
+}
+
+fn absolute_path_vars(
+    cwd: &Path,
+    var: impl Fn(&str) -> Option<PathBuf>,
+) -> Vec<(&'static str, PathBuf)> {
+    PATH_ENV_VARS
+        .iter()
+        .filter_map(|name| var(name).map(|path| (*name, cwd.join(path))))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    #[test]
+    fn test_discover() {
+        assert!(Repo::discover(Path::new(".")).is_some());
+        assert!(Repo::discover(Path::new("/")).is_none());
+
+        let tmp = env::temp_dir().join(format!("licensure-git-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&tmp);
+        let main = tmp.join("main");
+        fs::create_dir_all(main.join("sub")).unwrap();
+        fs::write(main.join("sub/a.rs"), "fn main() {}\n").unwrap();
+        git(&main, &["init", "-q"]);
+        git(&main, &["add", "."]);
+        git(&main, &["commit", "-q", "-m", "initial"]);
+        git(&main, &["worktree", "add", "-q", "../linked"]);
+
+        let main = main.canonicalize().unwrap();
+        let repo = Repo::discover(&main.join("sub")).unwrap();
+        assert_eq!(main, repo.root.canonicalize().unwrap());
+        assert_eq!("sub/a.rs", repo.root_relative("a.rs"));
+        assert_eq!(Some("a.rs"), repo.cwd_relative("sub/a.rs"));
+        assert_eq!(None, repo.cwd_relative("b.rs"));
+
+        fs::write(
+            main.join(".gitattributes"),
+            "gen/* linguist-generated\nvendor/** linguist-vendored=true\nsub/keep.rs -linguist-generated\n",
+        )
+        .unwrap();
+        let files: Vec<String> = ["gen/a.rs", "vendor/x/b.c", "a.rs", "keep.rs"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        let repo = Repo::discover(&main).unwrap();
+        let excluded = repo.linguist_excluded(&files).unwrap();
+        assert_eq!(
+            HashSet::from(["gen/a.rs".to_string(), "vendor/x/b.c".to_string()]),
+            excluded
+        );
+
+        let linked = tmp.join("linked").canonicalize().unwrap();
+        let repo = Repo::discover(&linked.join("sub")).unwrap();
+        assert_eq!(linked, repo.root.canonicalize().unwrap());
+        assert_eq!("sub/", repo.prefix);
+
+        fs::remove_dir_all(&tmp).unwrap();
+    }
+
+    #[test]
+    fn test_absolute_path_vars() {
+        let vars = absolute_path_vars(Path::new("/work"), |var| match var {
+            "GIT_DIR" => Some(PathBuf::from("repo.git")),
+            "GIT_WORK_TREE" => Some(PathBuf::from("/checkout")),
+            _ => None,
+        });
+        assert_eq!(
+            vec![
+                ("GIT_DIR", PathBuf::from("/work/repo.git")),
+                ("GIT_WORK_TREE", PathBuf::from("/checkout")),
+            ],
+            vars
+        );
+    }
+}
@@ -11,8 +11,16 @@
 // You should have received a copy of the GNU General Public License along with
 // this program. If not, see <https://www.gnu.org/licenses/>.
 //
+use std::path::Path;
+
 use regex::Regex;
 
+pub mod atomic;
+pub mod cache;
+pub mod diff;
+pub mod git;
+pub mod sha256;
+
 pub fn remove_column_wrapping(string: &str) -> String {
     // Some license headers come pre-wrapped to a column width.
     // This regex replacement undoes the column-width wrapping
@@ -21,9 +29,27 @@ pub fn remove_column_wrapping(string: &str) -> String {
     re.replace_all(string, "$char ").replace(" \n", "\n\n")
 }
 
+/// Split paths into the UTF-8 strings paths are matched and reported
+/// as, and the lossy names of the paths which aren't valid UTF-8 so they
+/// can be reported as skipped.
+pub fn utf8_paths<P: AsRef<Path>>(paths: &[P]) -> (Vec<String>, Vec<String>) {
+    let mut valid = Vec::with_capacity(paths.len());
+    let mut invalid = Vec::new();
+    for path in paths {
+        let path = path.as_ref();
+        match path.to_str() {
+            Some(s) => valid.push(s.to_string()),
+            None => invalid.push(path.to_string_lossy().into_owned()),
+        }
+    }
+
+    (valid, invalid)
+}
+
 #[cfg(test)]
+#[allow(clippy::needless_borrow)]
 mod tests {
-    use crate::utils::remove_column_wrapping;
+    use crate::utils::{remove_column_wrapping, utf8_paths};
 
     #[test]
     fn test_remove_column_wrapping() {
@@ -41,4 +67,24 @@ So is this.";
         is an intentional line break.\n\nSo is this.";
         assert_eq!(expected, remove_column_wrapping(&content))
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_utf8_paths() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        use std::path::PathBuf;
+
+        let paths = vec![
+            PathBuf::from("src/main.rs"),
+            PathBuf::from(OsStr::from_bytes(b"src/\xFF.rs")),
+        ];
+        assert_eq!(
+            (
+                vec!["src/main.rs".to_string()],
+                vec!["src/\u{FFFD}.rs".to_string()]
+            ),
+            utf8_paths(&paths)
+        );
+    }
 }
@@ -0,0 +1,286 @@
+// Copyright (C) 2024 Mathew Robinson <chasinglogic@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program. If not, see <https://www.gnu.org/licenses/>.
+//
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use regex::Regex;
+
+// Directories which are never walked regardless of ignore files.
+const SKIPPED_DIRS: &[&str] = &[".git", ".hg", ".svn"];
+
+struct IgnoreRule {
+    regex: Regex,
+    negate: bool,
+    dir_only: bool,
+}
+
+/// The rules of a single .gitignore file. Paths are matched relative to
+/// the directory containing it.
+pub struct IgnoreFile {
+    base: String,
+    rules: Vec<IgnoreRule>,
+}
+
+impl IgnoreFile {
+    pub fn parse(base: &str, content: &str) -> IgnoreFile {
+        let rules = content.lines().filter_map(parse_rule).collect();
+        IgnoreFile {
+            base: base.to_string(),
+            rules,
+        }
+    }
+
+    /// Returns Some(true) if the path is ignored, Some(false) if it is
+    /// explicitly un-ignored, and None if no rule matched.
+    fn matches(&self, path: &str, is_dir: bool) -> Option<bool> {
+        let rel = if self.base.is_empty() {
+            path
+        } else {
+            path.strip_prefix(&self.base)?.strip_prefix('/')?
+        };
+
+        // The last matching rule wins.
+        self.rules
+            .iter()
+            .rev()
+            .find(|r| (is_dir || !r.dir_only) && r.regex.is_match(rel))
+            .map(|r| !r.negate)
+    }
+}
+
+fn parse_rule(line: &str) -> Option<IgnoreRule> {
+    let line = line.trim_end();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let (negate, pattern) = match line.strip_prefix('!') {
+        Some(p) => (true, p),
+        None => (false, line.strip_prefix('\\').unwrap_or(line)),
+    };
+
+    let (dir_only, pattern) = match pattern.strip_suffix('/') {
+        Some(p) => (true, p),
+        None => (false, pattern),
+    };
+
+    // A pattern containing a slash anywhere but the end is relative to
+    // the directory of the ignore file, otherwise it can match at any
+    // depth.
+    let anchored = pattern.contains('/');
+    let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+    let prefix = if anchored { "^" } else { "^(?:.*/)?" };
+    let regex = Regex::new(&format!("{}{}$", prefix, glob_to_regex(pattern))).ok()?;
+
+    Some(IgnoreRule {
+        regex,
+        negate,
+        dir_only,
+    })
+}
+
+/// Convert a gitignore style glob into an (unanchored) regex.
+pub fn glob_to_regex(glob: &str) -> String {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut re = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let at_start = i == 0 || chars[i - 1] == '/';
+                let next = chars.get(i + 2);
+                if at_start && next == Some(&'/') {
+                    // "**/" matches zero or more directories
+                    re.push_str("(?:.*/)?");
+                    i += 3;
+                    continue;
+                } else if at_start && next.is_none() {
+                    // A trailing "/**" matches everything inside
+                    re.push_str(".*");
+                } else {
+                    re.push_str("[^/]*");
+                }
+                i += 2;
+                continue;
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            '[' => match chars[i..].iter().position(|c| *c == ']') {
+                Some(end) if end > 1 => {
+                    re.push('[');
+                    let class: String = chars[i + 1..i + end].iter().collect();
+                    match class.strip_prefix('!') {
+                        Some(rest) => {
+                            re.push('^');
+                            re.push_str(&rest.replace('\\', "\\\\"));
+                        }
+                        None => re.push_str(&class.replace('\\', "\\\\")),
+                    }
+                    re.push(']');
+                    i += end + 1;
+                    continue;
+                }
+                _ => re.push_str("\\["),
+            },
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                re.push_str(&regex::escape(&chars[i].to_string()));
+            }
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+
+        i += 1;
+    }
+
+    re
+}
+
+/// Recursively list all files under root, relative to root, honoring
+/// any .gitignore files found along the way. This is used for projects
+/// which aren't git repositories.
+pub fn walk_project_files(root: &Path) -> Result<Vec<String>, io::Error> {
+    let mut files = Vec::new();
+    let mut ignores = Vec::new();
+
+    if let Ok(content) = fs::read_to_string(root.join(".git").join("info").join("exclude")) {
+        ignores.push(IgnoreFile::parse("", &content));
+    }
+
+    walk_dir(root, "", &mut ignores, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+fn is_ignored(ignores: &[IgnoreFile], path: &str, is_dir: bool) -> bool {
+    ignores
+        .iter()
+        .rev()
+        .find_map(|i| i.matches(path, is_dir))
+        .unwrap_or(false)
+}
+
+fn walk_dir(
+    root: &Path,
+    rel_dir: &str,
+    ignores: &mut Vec<IgnoreFile>,
+    files: &mut Vec<String>,
+) -> Result<(), io::Error> {
+    let dir = root.join(rel_dir);
+    let pushed = match fs::read_to_string(dir.join(".gitignore")) {
+        Ok(content) => {
+            ignores.push(IgnoreFile::parse(rel_dir, &content));
+            true
+        }
+        Err(_) => false,
+    };
+
+    let mut entries = fs::read_dir(&dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        let name = match entry.file_name().into_string() {
+            Ok(n) => n,
+            Err(_) => continue,
+        };
+
+        let rel = if rel_dir.is_empty() {
+            name.clone()
+        } else {
+            format!("{}/{}", rel_dir, name)
+        };
+
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if SKIPPED_DIRS.contains(&name.as_str()) || is_ignored(ignores, &rel, true) {
+                continue;
+            }
+
+            walk_dir(root, &rel, ignores, files)?;
+        } else if file_type.is_file() && !is_ignored(ignores, &rel, false) {
+            files.push(rel);
+        }
+    }
+
+    if pushed {
+        ignores.pop();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_to_regex() {
+        assert_eq!("[^/]*\\.rs", glob_to_regex("*.rs"));
+        assert_eq!("(?:.*/)?foo", glob_to_regex("**/foo"));
+        assert_eq!("foo/.*", glob_to_regex("foo/**"));
+        assert_eq!("a/(?:.*/)?b", glob_to_regex("a/**/b"));
+        assert_eq!("[^abc]", glob_to_regex("[!abc]"));
+    }
+
+    #[test]
+    fn test_ignore_file_matches() {
+        let ignore = IgnoreFile::parse(
+            "",
+            "# comment\ntarget/\n*.log\n!keep.log\n/Cargo.lock\ndocs/*.html\n",
+        );
+        assert_eq!(Some(true), ignore.matches("target", true));
+        assert_eq!(None, ignore.matches("target", false));
+        assert_eq!(Some(true), ignore.matches("a/b/debug.log", false));
+        assert_eq!(Some(false), ignore.matches("a/keep.log", false));
+        assert_eq!(Some(true), ignore.matches("Cargo.lock", false));
+        assert_eq!(None, ignore.matches("sub/Cargo.lock", false));
+        assert_eq!(Some(true), ignore.matches("docs/index.html", false));
+        assert_eq!(None, ignore.matches("docs/api/index.html", false));
+        assert_eq!(None, ignore.matches("src/main.rs", false));
+    }
+
+    #[test]
+    fn test_nested_ignore_file() {
+        let ignores = vec![
+            IgnoreFile::parse("", "*.gen\n"),
+            IgnoreFile::parse("sub", "!special.gen\n/local.txt\n"),
+        ];
+        assert!(is_ignored(&ignores, "a.gen", false));
+        assert!(is_ignored(&ignores, "sub/other.gen", false));
+        assert!(!is_ignored(&ignores, "sub/special.gen", false));
+        assert!(is_ignored(&ignores, "sub/local.txt", false));
+        assert!(!is_ignored(&ignores, "local.txt", false));
+    }
+
+    #[test]
+    fn test_walk_project_files() {
+        let root = std::env::temp_dir().join(format!("licensure-walk-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\n*.tmp\n").unwrap();
+        fs::write(root.join("src/main.rs"), "").unwrap();
+        fs::write(root.join("src/scratch.tmp"), "").unwrap();
+        fs::write(root.join("target/out.rs"), "").unwrap();
+
+        let files = walk_project_files(&root).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            vec![".gitignore".to_string(), "src/main.rs".to_string()],
+            files
+        );
+    }
+}